@@ -0,0 +1,57 @@
+//! Feeds arbitrary bytes to every way of reading a class, none of which may panic
+
+#![no_main]
+
+use classfile::{
+    ClassFile,
+    borrowed::ClassFileRef,
+    cfg::ControlFlowGraph,
+    descriptor::{FieldType, MethodDescriptor},
+    diagnostics::ReadOptions,
+    lazy::LazyClassFile,
+    names::InnerClasses,
+    reader::ClassReader,
+    signature::{ClassSignature, FieldSignature, MethodSignature},
+    visitor::ClassWriter,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(class) = ClassFile::read(data) {
+        let idx_map = class.const_idx_map().unwrap();
+        let _ = class.validate_constants();
+        for method in &class.methods {
+            if let Ok(Some(code)) = method.code(&class.constants, &idx_map) {
+                let _ = ControlFlowGraph::new(&code);
+            }
+        }
+        let inner_classes = InnerClasses::read(&class).unwrap_or_default();
+        for utf8 in class.constants.iter().filter_map(|item| item.as_utf8()) {
+            let string = &utf8.string;
+            let _ = FieldType::parse(string);
+            let _ = MethodDescriptor::parse(string);
+            let _ = ClassSignature::parse(string);
+            let _ = MethodSignature::parse(string);
+            let _ = FieldSignature::parse(string);
+            let _ = inner_classes.canonical_name(&string.to_str());
+        }
+        // Bytes following the class are ignored
+        assert!(data.starts_with(&class.write()));
+    }
+
+    let _ = ClassFile::read_with_diagnostics(data, &ReadOptions::default());
+    if let Ok(class) = ClassFileRef::read(data) {
+        let _ = class.into_owned();
+    }
+    if let Ok(class) = LazyClassFile::read(data) {
+        let _ = class.to_class_file();
+    }
+    if let Ok(reader) = ClassReader::new(data) {
+        let _ = reader.header();
+        if let Ok(mut writer) = ClassWriter::from_reader(&reader)
+            && reader.accept(&mut writer).is_ok()
+        {
+            let _ = writer.build();
+        }
+    }
+});
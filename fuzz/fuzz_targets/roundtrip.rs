@@ -0,0 +1,36 @@
+//! Writes generated classes and reads them back with every reader, which must all give back
+//! the class that was written
+
+#![no_main]
+
+use classfile::{
+    ClassFile, borrowed::ClassFileRef, cfg::ControlFlowGraph, diagnostics::ReadOptions,
+    lazy::LazyClassFile, reader::ClassReader, visitor::ClassWriter,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|class: ClassFile| {
+    assert_eq!(Ok(()), class.validate_constants());
+    let bytes = class.write();
+
+    let read = ClassFile::read(&bytes).unwrap();
+    assert_eq!(bytes, read.write());
+    let idx_map = read.const_idx_map().unwrap();
+    for method in &read.methods {
+        if let Some(code) = method.code(&read.constants, &idx_map).unwrap() {
+            let _ = ControlFlowGraph::new(&code);
+        }
+    }
+
+    let (read, diagnostics) = ClassFile::read_with_diagnostics(&bytes, &ReadOptions::default())
+        .unwrap();
+    assert!(diagnostics.is_empty());
+    assert_eq!(bytes, read.write());
+    assert_eq!(bytes, ClassFileRef::read(&bytes).unwrap().into_owned().write());
+    assert_eq!(bytes, LazyClassFile::read(&bytes).unwrap().to_class_file().unwrap().write());
+
+    let reader = ClassReader::new(&bytes).unwrap();
+    let mut writer = ClassWriter::from_reader(&reader).unwrap();
+    reader.accept(&mut writer).unwrap();
+    ClassFile::read(&writer.build().unwrap().write()).unwrap();
+});
@@ -0,0 +1,506 @@
+//! Structure-aware generation of classes with [arbitrary](https://docs.rs/arbitrary), behind
+//! the `arbitrary` feature.
+//!
+//! Unlike classes read from random bytes, generated classes are internally consistent: their
+//! constant pool is valid, every index points to a constant of the expected kind, names and
+//! descriptors are well-formed and methods have code that the JVM would accept. This makes them
+//! suitable for property tests, e.g. that writing a class and reading it back is the identity,
+//! and for fuzzing what lies beyond the parser.
+
+use std::collections::HashSet;
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    ClassAccessFlags, ClassFile,
+    builder::{ClassBuilder, CodeBuilder, ConstantValue},
+    code::Instruction,
+    descriptor::{BaseType, FieldType, MethodDescriptor},
+    field::FieldAccessFlags,
+    method::MethodAccessFlags,
+    version::ClassFileVersion,
+};
+
+/// Classes referenced by generated descriptors, besides generated names
+const KNOWN_CLASSES: &[&str] = &[
+    "java/lang/Object",
+    "java/lang/String",
+    "java/lang/Runnable",
+    "java/io/Serializable",
+    "java/lang/Exception",
+];
+
+/// Known classes that a class can extend
+const KNOWN_SUPER_CLASSES: &[&str] = &["java/lang/Object", "java/lang/Exception"];
+
+/// Known interfaces that a class can implement
+const KNOWN_INTERFACES: &[&str] = &[
+    "java/lang/Runnable",
+    "java/io/Serializable",
+    "java/lang/Comparable",
+];
+
+/// Characters starting generated names, which are Java identifiers as older class file versions
+/// require
+const NAME_START_CHARS: &[char] = &['a', 'b', 'x', 'z', 'A', 'B', 'Z', '_', '$', 'é'];
+
+const NAME_PART_CHARS: &[char] = &['a', 'b', 'z', 'A', 'Z', '0', '1', '9', '_', '$', 'é', '€'];
+
+/// Characters of generated strings, including some that need two or more bytes in modified UTF-8
+const STRING_CHARS: &[char] = &['a', 'Z', '0', ' ', '.', ';', '/', '\0', 'é', '€', '😀'];
+
+const ALL_VERSIONS: &[ClassFileVersion] = &[
+    ClassFileVersion::Jdk1_1,
+    ClassFileVersion::Jdk1_2,
+    ClassFileVersion::Jdk1_3,
+    ClassFileVersion::Jdk1_4,
+    ClassFileVersion::Jdk1_5,
+    ClassFileVersion::Jdk6,
+    ClassFileVersion::Jdk7,
+    ClassFileVersion::Jdk8,
+    ClassFileVersion::Jdk9,
+    ClassFileVersion::Jdk10,
+    ClassFileVersion::Jdk11,
+    ClassFileVersion::Jdk12,
+    ClassFileVersion::Jdk13,
+    ClassFileVersion::Jdk14,
+    ClassFileVersion::Jdk15,
+    ClassFileVersion::Jdk16,
+    ClassFileVersion::Jdk17,
+    ClassFileVersion::Jdk18,
+    ClassFileVersion::Jdk19,
+    ClassFileVersion::Jdk20,
+    ClassFileVersion::Jdk21,
+    ClassFileVersion::Jdk22,
+];
+
+const MAX_MEMBERS: usize = 8;
+
+impl<'a> Arbitrary<'a> for ClassFileVersion {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(ALL_VERSIONS).cloned()
+    }
+}
+
+impl<'a> Arbitrary<'a> for BaseType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[
+            BaseType::Byte,
+            BaseType::Char,
+            BaseType::Double,
+            BaseType::Float,
+            BaseType::Int,
+            BaseType::Long,
+            BaseType::Short,
+            BaseType::Boolean,
+        ])
+        .copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for FieldType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let element = if u.arbitrary()? {
+            FieldType::Base(u.arbitrary()?)
+        } else {
+            FieldType::Object(class_name(u)?.into())
+        };
+        Ok(match u.int_in_range(0..=7)? {
+            dimensions @ 1..=3 => FieldType::Array(dimensions, Box::new(element)),
+            _ => element,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MethodDescriptor {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let count = u.int_in_range(0..=4)?;
+        let parameters = (0..count)
+            .map(|_| FieldType::arbitrary(u))
+            .collect::<Result<_>>()?;
+        Ok(MethodDescriptor {
+            parameters,
+            return_type: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ClassFile {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let name = new_class_name(u)?;
+        let mut builder = ClassBuilder::new(&name);
+        builder.version(u.arbitrary()?, 0);
+
+        let interface = u.ratio(1, 4)?;
+        let mut access_flags = if interface {
+            let mut flags = ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT;
+            if u.ratio(1, 4)? {
+                flags |= ClassAccessFlags::ANNOTATION;
+            }
+            flags
+        } else {
+            let mut flags = ClassAccessFlags::SUPER;
+            flags |= u
+                .choose(&[
+                    ClassAccessFlags::empty(),
+                    ClassAccessFlags::FINAL,
+                    ClassAccessFlags::ABSTRACT,
+                    ClassAccessFlags::ENUM | ClassAccessFlags::FINAL,
+                ])?
+                .clone();
+            flags
+        };
+        if u.arbitrary()? {
+            access_flags |= ClassAccessFlags::PUBLIC;
+        }
+        if u.ratio(1, 8)? {
+            access_flags |= ClassAccessFlags::SYNTHETIC;
+        }
+        builder.access_flags(access_flags.clone());
+
+        let super_class = if !interface && u.ratio(1, 4)? {
+            let super_class = known_or_new(u, KNOWN_SUPER_CLASSES)?;
+            builder.super_class(Some(&super_class));
+            super_class
+        } else {
+            "java/lang/Object".to_string()
+        };
+        let mut interfaces = HashSet::new();
+        for _ in 0..u.int_in_range(0..=3)? {
+            let name = known_or_new(u, KNOWN_INTERFACES)?;
+            if interfaces.insert(name.clone()) {
+                builder.interface(&name);
+            }
+        }
+
+        let mut fields = HashSet::new();
+        for _ in 0..u.int_in_range(0..=MAX_MEMBERS)? {
+            let name = unqualified_name(u)?;
+            let descriptor: FieldType = u.arbitrary()?;
+            if fields.insert((name.clone(), descriptor.clone())) {
+                add_field(u, &mut builder, interface, &name, &descriptor)?;
+            }
+        }
+
+        let mut methods = HashSet::new();
+        if !interface && u.arbitrary()? {
+            let descriptor = MethodDescriptor {
+                return_type: None,
+                ..u.arbitrary()?
+            };
+            methods.insert(("<init>".to_string(), descriptor.clone()));
+            add_constructor(u, &mut builder, &super_class, &descriptor)?;
+        }
+        for _ in 0..u.int_in_range(0..=MAX_MEMBERS)? {
+            let name = unqualified_name(u)?;
+            let descriptor: MethodDescriptor = u.arbitrary()?;
+            if methods.insert((name.clone(), descriptor.clone())) {
+                add_method(u, &mut builder, &access_flags, &name, &descriptor)?;
+            }
+        }
+
+        if u.arbitrary()? {
+            builder.source_file(format!("{}.java", unqualified_name(u)?));
+        }
+        if u.ratio(1, 8)? {
+            builder.attribute("Deprecated", Vec::new());
+        }
+        builder
+            .build()
+            .map_err(|_| ::arbitrary::Error::IncorrectFormat)
+    }
+}
+
+/// An unqualified name (JVMS 4.2.2), which is also a valid field or method name
+fn unqualified_name(u: &mut Unstructured) -> Result<String> {
+    let mut name = u.choose(NAME_START_CHARS)?.to_string();
+    for _ in 0..u.int_in_range(0..=5)? {
+        name.push(*u.choose(NAME_PART_CHARS)?);
+    }
+    Ok(name)
+}
+
+/// The value of a string constant
+fn string(u: &mut Unstructured) -> Result<String> {
+    let len = u.int_in_range(0..=8)?;
+    (0..len).map(|_| u.choose(STRING_CHARS).copied()).collect()
+}
+
+/// An internal class name, e.g. `java/lang/Object`
+fn class_name(u: &mut Unstructured) -> Result<String> {
+    known_or_new(u, KNOWN_CLASSES)
+}
+
+/// One of the `known` classes, or a generated name
+fn known_or_new(u: &mut Unstructured, known: &[&str]) -> Result<String> {
+    if u.ratio(1, 3)? {
+        return Ok(u.choose(known)?.to_string());
+    }
+    new_class_name(u)
+}
+
+/// A class name in a package of its own, which is never one of the known classes
+fn new_class_name(u: &mut Unstructured) -> Result<String> {
+    let segments = (0..u.int_in_range(1..=3)?)
+        .map(|_| unqualified_name(u))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(segments.join("/"))
+}
+
+/// Bits of one of the public, private and protected flags, or of none of them
+fn visibility(u: &mut Unstructured) -> Result<u16> {
+    u.choose(&[0x0000, 0x0001, 0x0002, 0x0004]).copied()
+}
+
+fn add_field(
+    u: &mut Unstructured,
+    builder: &mut ClassBuilder,
+    interface: bool,
+    name: &str,
+    descriptor: &FieldType,
+) -> Result<()> {
+    let access_flags = if interface {
+        FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL
+    } else {
+        let mut flags = FieldAccessFlags::from_bits_truncate(visibility(u)?);
+        flags |= u
+            .choose(&[
+                FieldAccessFlags::empty(),
+                FieldAccessFlags::STATIC,
+                FieldAccessFlags::FINAL,
+                FieldAccessFlags::STATIC | FieldAccessFlags::FINAL,
+                FieldAccessFlags::VOLATILE,
+                FieldAccessFlags::TRANSIENT,
+            ])?
+            .clone();
+        flags
+    };
+    let constant_value = if access_flags.contains(FieldAccessFlags::STATIC) && u.arbitrary()? {
+        constant_value(u, descriptor)?
+    } else {
+        None
+    };
+    let descriptor = descriptor.to_string();
+    builder.field(name, &descriptor, access_flags, |field| {
+        if let Some(value) = constant_value {
+            field.constant_value(value);
+        }
+    });
+    Ok(())
+}
+
+/// An initial value of the type of a field, if the type allows one
+fn constant_value(u: &mut Unstructured, descriptor: &FieldType) -> Result<Option<ConstantValue>> {
+    Ok(Some(match descriptor {
+        FieldType::Base(BaseType::Boolean) => ConstantValue::Int(u.int_in_range(0..=1)?),
+        FieldType::Base(BaseType::Byte) => ConstantValue::Int(u.arbitrary::<i8>()?.into()),
+        FieldType::Base(BaseType::Char) => ConstantValue::Int(u.arbitrary::<u16>()?.into()),
+        FieldType::Base(BaseType::Short) => ConstantValue::Int(u.arbitrary::<i16>()?.into()),
+        FieldType::Base(BaseType::Int) => ConstantValue::Int(u.arbitrary()?),
+        FieldType::Base(BaseType::Long) => ConstantValue::Long(u.arbitrary()?),
+        FieldType::Base(BaseType::Float) => ConstantValue::Float(u.arbitrary()?),
+        FieldType::Base(BaseType::Double) => ConstantValue::Double(u.arbitrary()?),
+        FieldType::Object(name) if name == "java/lang/String" => {
+            ConstantValue::String(string(u)?.into())
+        }
+        _ => return Ok(None),
+    }))
+}
+
+fn add_constructor(
+    u: &mut Unstructured,
+    builder: &mut ClassBuilder,
+    super_class: &str,
+    descriptor: &MethodDescriptor,
+) -> Result<()> {
+    let access_flags = MethodAccessFlags::from_bits_truncate(visibility(u)?);
+    let statements = statements(u)?;
+    builder.method("<init>", &descriptor.to_string(), access_flags, |method| {
+        method.code(|code| {
+            code.instruction(Instruction::Aload0)
+                .invoke_special(super_class, "<init>", "()V");
+            push_statements(code, &statements);
+            code.instruction(Instruction::Return);
+        });
+    });
+    Ok(())
+}
+
+fn add_method(
+    u: &mut Unstructured,
+    builder: &mut ClassBuilder,
+    class_flags: &ClassAccessFlags,
+    name: &str,
+    descriptor: &MethodDescriptor,
+) -> Result<()> {
+    let access_flags = if class_flags.contains(ClassAccessFlags::INTERFACE) {
+        MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT
+    } else {
+        let mut flags = MethodAccessFlags::from_bits_truncate(visibility(u)?);
+        flags |= u
+            .choose(&[
+                MethodAccessFlags::empty(),
+                MethodAccessFlags::STATIC,
+                MethodAccessFlags::FINAL,
+                MethodAccessFlags::SYNCHRONIZED,
+                MethodAccessFlags::STATIC | MethodAccessFlags::NATIVE,
+                MethodAccessFlags::NATIVE,
+            ])?
+            .clone();
+        if class_flags.contains(ClassAccessFlags::ABSTRACT)
+            && !flags.contains(MethodAccessFlags::PRIVATE)
+            && u.ratio(1, 4)?
+        {
+            // Abstract methods admit no other flag than their visibility
+            flags &= MethodAccessFlags::PUBLIC | MethodAccessFlags::PROTECTED;
+            flags |= MethodAccessFlags::ABSTRACT;
+        }
+        flags
+    };
+    let has_code =
+        !access_flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::NATIVE);
+    let statements = if has_code { statements(u)? } else { Vec::new() };
+    let exceptions = (0..u.int_in_range(0..=2)?)
+        .map(|_| class_name(u))
+        .collect::<Result<Vec<_>>>()?;
+    builder.method(name, &descriptor.to_string(), access_flags, |method| {
+        if !exceptions.is_empty() {
+            method.exceptions(exceptions);
+        }
+        if has_code {
+            method.code(|code| {
+                push_statements(code, &statements);
+                push_return(code, descriptor.return_type.as_ref());
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Code leaving the operand stack as it found it
+#[derive(Debug)]
+enum Statement {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    NewObject,
+}
+
+fn statements(u: &mut Unstructured) -> Result<Vec<Statement>> {
+    (0..u.int_in_range(0..=6)?)
+        .map(|_| {
+            Ok(match u.int_in_range(0..=5)? {
+                0 => Statement::Int(u.arbitrary()?),
+                1 => Statement::Long(u.arbitrary()?),
+                2 => Statement::Float(u.arbitrary()?),
+                3 => Statement::Double(u.arbitrary()?),
+                4 => Statement::String(string(u)?),
+                _ => Statement::NewObject,
+            })
+        })
+        .collect()
+}
+
+fn push_statements(code: &mut CodeBuilder, statements: &[Statement]) {
+    for statement in statements {
+        match statement {
+            Statement::Int(value) => {
+                code.load_int(*value).instruction(Instruction::Pop);
+            }
+            Statement::Long(value) => {
+                let idx = code.pool().long(*value);
+                code.ldc(idx).instruction(Instruction::Pop2);
+            }
+            Statement::Float(value) => {
+                let idx = code.pool().float(*value);
+                code.ldc(idx).instruction(Instruction::Pop);
+            }
+            Statement::Double(value) => {
+                let idx = code.pool().double(*value);
+                code.ldc(idx).instruction(Instruction::Pop2);
+            }
+            Statement::String(value) => {
+                code.load_string(value).instruction(Instruction::Pop);
+            }
+            Statement::NewObject => {
+                code.new_instance("java/lang/Object")
+                    .instruction(Instruction::Dup)
+                    .invoke_special("java/lang/Object", "<init>", "()V")
+                    .instruction(Instruction::Pop);
+            }
+        }
+    }
+}
+
+/// Returns the default value of `return_type`
+fn push_return(code: &mut CodeBuilder, return_type: Option<&FieldType>) {
+    let (value, instruction) = match return_type {
+        None => (None, Instruction::Return),
+        Some(FieldType::Base(BaseType::Long)) => (Some(Instruction::Lconst0), Instruction::Lreturn),
+        Some(FieldType::Base(BaseType::Float)) => {
+            (Some(Instruction::Fconst0), Instruction::Freturn)
+        }
+        Some(FieldType::Base(BaseType::Double)) => {
+            (Some(Instruction::Dconst0), Instruction::Dreturn)
+        }
+        Some(FieldType::Base(_)) => (Some(Instruction::Iconst0), Instruction::Ireturn),
+        Some(_) => (Some(Instruction::AconstNull), Instruction::Areturn),
+    };
+    if let Some(value) = value {
+        code.instruction(value);
+    }
+    code.instruction(instruction);
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arbitrary::{Arbitrary, Unstructured};
+
+    use crate::{ClassFile, descriptor::MethodDescriptor, java_str::JavaString, verifier::Classes};
+
+    /// Deterministic pseudo-random bytes to drive the generator
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generated_classes_are_consistent() {
+        for seed in 0..500 {
+            let bytes = random_bytes(seed, 4096);
+            let class = ClassFile::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            assert_eq!(Ok(()), class.check_format());
+            assert_eq!(Ok(()), class.verify(&Classes::new()));
+
+            let written = class.write();
+            let read = ClassFile::read(&written).unwrap();
+            assert_eq!(written, read.write());
+
+            let idx_map = read.const_idx_map().unwrap();
+            for method in &read.methods {
+                let descriptor = read.constants[method.descriptor_index].as_utf8().unwrap();
+                assert!(MethodDescriptor::parse(&descriptor.string).is_ok());
+                method.code(&read.constants, &idx_map).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn descriptors_are_well_formed() {
+        for seed in 0..500 {
+            let bytes = random_bytes(seed, 256);
+            let descriptor = MethodDescriptor::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            let parsed = MethodDescriptor::parse(&JavaString::from(descriptor.to_string()));
+            assert_eq!(Ok(descriptor), parsed);
+        }
+    }
+}
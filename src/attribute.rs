@@ -1,4 +1,10 @@
-use crate::{Read, Result, buffer::Buffer, constants::ConstItemIdx};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+    constants::{ConstIdxMap, ConstItemIdx, Constants},
+    error::ClassReaderError,
+    java_str::JavaStr,
+};
 
 // TODO
 pub enum AttributeKind {
@@ -25,17 +31,49 @@ pub enum AttributeKind {
     Other(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attribute {
     pub attribute_name_index: ConstItemIdx,
     pub info: Vec<u8>,
 }
 
-impl Read for Attribute {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
-        let attribute_name_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+impl Attribute {
+    /// Name of the attribute, if its name index points to an Utf8 constant
+    pub fn name<'a>(&self, constants: &'a Constants) -> Option<&'a JavaStr> {
+        constants
+            .get(self.attribute_name_index)
+            .and_then(|item| item.as_utf8())
+            .map(|utf8| &*utf8.string)
+    }
+
+    /// Finds the first attribute named `name`
+    pub fn find<'a>(
+        attributes: &'a [Attribute],
+        constants: &Constants,
+        name: &str,
+    ) -> Option<&'a Attribute> {
+        attributes
+            .iter()
+            .find(|attribute| attribute.name(constants).is_some_and(|n| n == name))
+    }
+
+    /// Reads the length of an attribute, checking it against
+    /// [Limits::max_attribute_length](crate::limits::Limits::max_attribute_length)
+    pub(crate) fn read_length(buf: &mut Buffer) -> Result<usize> {
         let len = buf.read_u32()?;
-        let bytes = buf.read_bytes(len as usize)?;
+        if len > buf.limits().max_attribute_length {
+            return Err(ClassReaderError::LimitExceeded("max_attribute_length"));
+        }
+        Ok(len as usize)
+    }
+}
+
+impl Read for Attribute {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let attribute_name_index = ConstItemIdx::read(buf, idx_map)?;
+        let len = Self::read_length(buf)?;
+        let bytes = buf.read_bytes(len)?;
+        buf.allocate(len)?;
 
         Ok(Self {
             attribute_name_index,
@@ -43,3 +81,12 @@ impl Read for Attribute {
         })
     }
 }
+
+impl Write for Attribute {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.attribute_name_index.write(buf, idx_map);
+        let len = u32::try_from(self.info.len()).expect("attribute too large for a class file");
+        buf.write_u32(len);
+        buf.write_bytes(&self.info);
+    }
+}
@@ -0,0 +1,304 @@
+//! A view of a class that borrows from the bytes it was read from.
+//!
+//! [ClassFileRef] has the same layout as [ClassFile], but Utf8 constants and attribute bodies
+//! are slices of the input instead of owned copies, so reading a class only allocates its
+//! tables. [ClassFileRef::into_owned] turns it into a [ClassFile] when it has to outlive the
+//! input.
+
+use index_vec::IndexVec;
+
+use crate::{
+    ClassAccessFlags, ClassFile, Read, Result,
+    attribute::Attribute,
+    buffer::Buffer,
+    constants::{ConstIdxMap, ConstItem, ConstItemIdx, ConstUtf8},
+    field::{Field, FieldAccessFlags},
+    java_str::JavaStr,
+    method::{Method, MethodAccessFlags},
+    version::ClassFileVersion,
+};
+
+/// A constant of a [ClassFileRef]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstItemRef<'a> {
+    Utf8(&'a JavaStr),
+    /// Any other constant, which never holds a [ConstItem::Utf8]
+    Other(ConstItem),
+}
+
+impl<'a> ConstItemRef<'a> {
+    pub fn as_utf8(&self) -> Option<&'a JavaStr> {
+        match self {
+            Self::Utf8(string) => Some(string),
+            Self::Other(_) => None,
+        }
+    }
+
+    fn read(buf: &mut Buffer<'a>, idx_map: &ConstIdxMap) -> Result<Self> {
+        let start = buf.get_pos();
+        if buf.read_u8()? == 1 {
+            let len = buf.read_u16()?;
+            return Ok(Self::Utf8(buf.read_java_str(len as usize)?));
+        }
+        buf.set_pos(start);
+        ConstItem::read(buf, idx_map).map(Self::Other)
+    }
+
+    pub fn to_const_item(&self) -> ConstItem {
+        match self {
+            Self::Utf8(string) => ConstItem::Utf8(ConstUtf8 {
+                string: string.to_java_string(),
+            }),
+            Self::Other(item) => item.clone(),
+        }
+    }
+}
+
+pub type ConstantsRef<'a> = IndexVec<ConstItemIdx, ConstItemRef<'a>>;
+
+/// An [Attribute] whose body is borrowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeRef<'a> {
+    pub attribute_name_index: ConstItemIdx,
+    pub info: &'a [u8],
+}
+
+impl<'a> AttributeRef<'a> {
+    /// Name of the attribute, if its name index points to an Utf8 constant
+    pub fn name(&self, constants: &ConstantsRef<'a>) -> Option<&'a JavaStr> {
+        constants
+            .get(self.attribute_name_index)
+            .and_then(ConstItemRef::as_utf8)
+    }
+
+    /// Finds the first attribute named `name`
+    pub fn find<'b>(
+        attributes: &'b [AttributeRef<'a>],
+        constants: &ConstantsRef<'a>,
+        name: &str,
+    ) -> Option<&'b AttributeRef<'a>> {
+        attributes
+            .iter()
+            .find(|attribute| attribute.name(constants).is_some_and(|n| n == name))
+    }
+
+    fn read(buf: &mut Buffer<'a>, idx_map: &ConstIdxMap) -> Result<Self> {
+        let attribute_name_index = ConstItemIdx::read(buf, idx_map)?;
+        let len = Attribute::read_length(buf)?;
+        let info = buf.read_bytes(len)?;
+
+        Ok(Self {
+            attribute_name_index,
+            info,
+        })
+    }
+
+    fn read_all(buf: &mut Buffer<'a>, idx_map: &ConstIdxMap) -> Result<Vec<Self>> {
+        let count = buf.read_u16()?;
+        (0..count).map(|_| Self::read(buf, idx_map)).collect()
+    }
+
+    pub fn to_attribute(&self) -> Attribute {
+        Attribute {
+            attribute_name_index: self.attribute_name_index,
+            info: self.info.to_vec(),
+        }
+    }
+}
+
+/// A [Field] whose attributes are borrowed
+#[derive(Debug, Clone)]
+pub struct FieldInfoRef<'a> {
+    pub access_flag: FieldAccessFlags,
+    pub name_index: ConstItemIdx,
+    pub descriptor_index: ConstItemIdx,
+    pub attributes: Vec<AttributeRef<'a>>,
+}
+
+impl<'a> FieldInfoRef<'a> {
+    fn read(buf: &mut Buffer<'a>, idx_map: &ConstIdxMap) -> Result<Self> {
+        Ok(Self {
+            access_flag: FieldAccessFlags::read(buf)?,
+            name_index: ConstItemIdx::read(buf, idx_map)?,
+            descriptor_index: ConstItemIdx::read(buf, idx_map)?,
+            attributes: AttributeRef::read_all(buf, idx_map)?,
+        })
+    }
+
+    pub fn to_field(&self) -> Field {
+        Field {
+            access_flag: self.access_flag.clone(),
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: self
+                .attributes
+                .iter()
+                .map(AttributeRef::to_attribute)
+                .collect(),
+        }
+    }
+}
+
+/// A [Method] whose attributes are borrowed
+#[derive(Debug, Clone)]
+pub struct MethodInfoRef<'a> {
+    pub access_flags: MethodAccessFlags,
+    pub name_index: ConstItemIdx,
+    pub descriptor_index: ConstItemIdx,
+    pub attributes: Vec<AttributeRef<'a>>,
+}
+
+impl<'a> MethodInfoRef<'a> {
+    fn read(buf: &mut Buffer<'a>, idx_map: &ConstIdxMap) -> Result<Self> {
+        Ok(Self {
+            access_flags: MethodAccessFlags::read(buf)?,
+            name_index: ConstItemIdx::read(buf, idx_map)?,
+            descriptor_index: ConstItemIdx::read(buf, idx_map)?,
+            attributes: AttributeRef::read_all(buf, idx_map)?,
+        })
+    }
+
+    pub fn to_method(&self) -> Method {
+        Method {
+            access_flags: self.access_flags.clone(),
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: self
+                .attributes
+                .iter()
+                .map(AttributeRef::to_attribute)
+                .collect(),
+        }
+    }
+}
+
+/// A [ClassFile] borrowing its Utf8 constants and attribute bodies from the class bytes
+#[derive(Debug, Clone)]
+pub struct ClassFileRef<'a> {
+    pub version: ClassFileVersion,
+    pub minor_version: u16,
+    pub constants: ConstantsRef<'a>,
+    pub access_flag: ClassAccessFlags,
+    pub this_class: ConstItemIdx,
+    pub super_class: Option<ConstItemIdx>,
+    pub interfaces: Vec<ConstItemIdx>,
+    pub fields: Vec<FieldInfoRef<'a>>,
+    pub methods: Vec<MethodInfoRef<'a>>,
+    pub attributes: Vec<AttributeRef<'a>>,
+}
+
+impl<'a> ClassFileRef<'a> {
+    pub fn read(buf: &'a [u8]) -> Result<Self> {
+        let mut buf = Buffer::new(buf);
+        ClassFile::check_magic_number(&mut buf)?;
+        let (version, minor_version) = ClassFile::read_version(&mut buf)?;
+        let (constants, idx_map) = Self::read_constants(&mut buf)?;
+        let access_flag = ClassFile::read_access_flags(&mut buf)?;
+        let this_class = ConstItemIdx::read(&mut buf, &idx_map)?;
+        let super_class = Option::<ConstItemIdx>::read(&mut buf, &idx_map)?;
+        let interfaces = Vec::read(&mut buf, &idx_map)?;
+        let fields_count = buf.read_u16()?;
+        let fields = (0..fields_count)
+            .map(|_| FieldInfoRef::read(&mut buf, &idx_map))
+            .collect::<Result<_>>()?;
+        let methods_count = buf.read_u16()?;
+        let methods = (0..methods_count)
+            .map(|_| MethodInfoRef::read(&mut buf, &idx_map))
+            .collect::<Result<_>>()?;
+        let attributes = AttributeRef::read_all(&mut buf, &idx_map)?;
+
+        Ok(Self {
+            version,
+            minor_version,
+            constants,
+            access_flag,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+        })
+    }
+
+    fn read_constants(buf: &mut Buffer<'a>) -> Result<(ConstantsRef<'a>, ConstIdxMap)> {
+        let mut consts = ConstantsRef::new();
+        let (idx_map, _) =
+            ClassFile::read_constant_pool(buf, size_of::<ConstItemRef>(), |buf, idx_map| {
+                let item = ConstItemRef::read(buf, idx_map)?;
+                let wide = matches!(&item, ConstItemRef::Other(item) if item.is_8bit());
+                consts.push(item);
+                Ok(wide)
+            })?;
+        for item in consts.iter_mut() {
+            if let ConstItemRef::Other(item) = item {
+                item.resolve_indices(&idx_map)?;
+            }
+        }
+        Ok((consts, idx_map))
+    }
+
+    /// The Utf8 constant at `idx`, borrowed from the class bytes
+    pub fn utf8(&self, idx: ConstItemIdx) -> Option<&'a JavaStr> {
+        self.constants.get(idx).and_then(ConstItemRef::as_utf8)
+    }
+
+    /// Copies the borrowed parts, giving the same class as [ClassFile::read]
+    pub fn into_owned(self) -> ClassFile {
+        ClassFile {
+            version: self.version,
+            minor_version: self.minor_version,
+            constants: self
+                .constants
+                .iter()
+                .map(ConstItemRef::to_const_item)
+                .collect(),
+            access_flag: self.access_flag,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields.iter().map(FieldInfoRef::to_field).collect(),
+            methods: self.methods.iter().map(MethodInfoRef::to_method).collect(),
+            attributes: self
+                .attributes
+                .iter()
+                .map(AttributeRef::to_attribute)
+                .collect(),
+        }
+    }
+}
+
+impl From<ClassFileRef<'_>> for ClassFile {
+    fn from(class: ClassFileRef<'_>) -> Self {
+        class.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClassFileRef;
+    use crate::{ClassFile, constants::ConstItem};
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn strings_and_attributes_are_borrowed() {
+        let class = ClassFileRef::read(SAMPLE).unwrap();
+        let ConstItem::Class(this_class) = class.constants[class.this_class].to_const_item() else {
+            panic!("this_class is not a Class constant");
+        };
+        let name = class.utf8(this_class.name_index).unwrap();
+        assert_eq!(name, "Sample");
+
+        let input = SAMPLE.as_ptr_range();
+        assert!(input.contains(&name.as_bytes().as_ptr()));
+        assert!(input.contains(&class.methods[0].attributes[0].info.as_ptr()));
+    }
+
+    #[test]
+    fn owned_classes_are_the_same_as_read_ones() {
+        let class = ClassFileRef::read(SAMPLE).unwrap().into_owned();
+        assert_eq!(ClassFile::read(SAMPLE).unwrap().write(), class.write());
+        assert_eq!(SAMPLE, class.write());
+    }
+}
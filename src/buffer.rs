@@ -1,10 +1,14 @@
-use cesu8::from_java_cesu8;
 use thiserror::Error;
 
+use crate::{java_str::JavaStr, limits::Limits};
+
 /// A buffer reader, used to marshall data from a generic byte array
 pub struct Buffer<'a> {
     buffer: &'a [u8],
     position: usize,
+    limits: Limits,
+    /// Bytes allocated so far to hold what was read, see [Buffer::allocate]
+    allocated: usize,
 }
 
 /// Errors related to reading from a [Buffer]
@@ -15,16 +19,39 @@ pub enum BufferError {
 
     #[error("invalid cesu8 string")]
     InvalidCesu8String,
+
+    #[error("the {0} limit is exceeded")]
+    LimitExceeded(&'static str),
 }
 
 type Result<T> = std::result::Result<T, BufferError>;
 
 impl<'a> Buffer<'a> {
     pub fn new(data: &'a [u8]) -> Self {
+        Self::with_limits(data, Limits::default())
+    }
+
+    pub fn with_limits(data: &'a [u8], limits: Limits) -> Self {
         Buffer {
             buffer: data,
             position: 0,
+            limits,
+            allocated: 0,
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Accounts for `size` bytes allocated to hold data read from the buffer, failing once
+    /// the total exceeds [Limits::max_allocation]
+    pub fn allocate(&mut self, size: usize) -> Result<()> {
+        self.allocated = self.allocated.saturating_add(size);
+        if self.allocated > self.limits.max_allocation {
+            return Err(BufferError::LimitExceeded("max_allocation"));
         }
+        Ok(())
     }
 
     fn advance(&mut self, size: usize) -> Result<&'a [u8]> {
@@ -62,6 +89,11 @@ impl<'a> Buffer<'a> {
             .map(|bytes| i64::from_be_bytes(bytes.try_into().unwrap()))
     }
 
+    pub fn read_u64(&mut self) -> Result<u64> {
+        self.advance(std::mem::size_of::<u64>())
+            .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
     pub fn read_f32(&mut self) -> Result<f32> {
         self.advance(std::mem::size_of::<f32>())
             .map(|bytes| f32::from_be_bytes(bytes.try_into().unwrap()))
@@ -72,17 +104,14 @@ impl<'a> Buffer<'a> {
             .map(|bytes| f64::from_be_bytes(bytes.try_into().unwrap()))
     }
 
-    pub fn read_utf8(&mut self, len: usize) -> Result<String> {
-        self.advance(len)
-            .and_then(|bytes| from_java_cesu8(bytes).map_err(|_| BufferError::InvalidCesu8String))
-            .map(|cow_string| cow_string.into_owned())
+    pub fn read_java_str(&mut self, len: usize) -> Result<&'a JavaStr> {
+        self.advance(len).and_then(JavaStr::from_bytes)
     }
 
     pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
         self.advance(len)
     }
 
-    #[allow(dead_code)]
     pub fn has_more_data(&self) -> bool {
         self.position < self.buffer.len()
     }
@@ -91,14 +120,83 @@ impl<'a> Buffer<'a> {
         self.position
     }
 
+    /// Number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.position
+    }
+
     pub fn set_pos(&mut self, pos: usize) {
         self.position = pos;
     }
 }
 
+/// A buffer writer, the counterpart of [Buffer], used to marshall data into a byte array
+#[derive(Debug, Default)]
+pub struct BufferWriter {
+    buffer: Vec<u8>,
+}
+
+impl BufferWriter {
+    pub fn new() -> Self {
+        BufferWriter { buffer: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Writes the length of a table whose size is stored on 2 bytes.
+    ///
+    /// Panics if the table is too large to be represented in a class file.
+    pub fn write_len_u16(&mut self, len: usize) {
+        let len = u16::try_from(len).expect("table too large for a class file");
+        self.write_u16(len);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn get_pos(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::buffer::Buffer;
+    use crate::buffer::{Buffer, BufferWriter};
 
     #[test]
     fn buffer_works() {
@@ -111,4 +209,18 @@ mod tests {
 
         assert!(buffer.read_u32().is_err());
     }
+
+    #[test]
+    fn buffer_writer_works() {
+        let mut writer = BufferWriter::new();
+        writer.write_u8(0x01);
+        writer.write_u16(0x0203);
+        writer.write_i32(-1);
+
+        assert_eq!(7, writer.get_pos());
+        assert_eq!(
+            vec![0x01, 0x02, 0x03, 0xFF, 0xFF, 0xFF, 0xFF],
+            writer.into_bytes()
+        );
+    }
 }
@@ -0,0 +1,369 @@
+use std::mem;
+
+use crate::{
+    Result,
+    attribute::Attribute,
+    code::{Code, ExceptionHandler, Instruction, Label, LineNumber, LocalVariable, write_table},
+    constants::{ConstItem, ConstItemIdx, ConstPoolBuilder},
+    descriptor::MethodDescriptor,
+    error::ClassReaderError,
+    java_str::JavaString,
+    transform::CodeElement,
+};
+
+#[derive(Debug)]
+enum CodeItem {
+    Instruction(Instruction),
+    Label(Label),
+    LineNumber(u16),
+}
+
+/// Builds a [Code] attribute from a list of instructions, see
+/// [MethodBuilder::code](super::MethodBuilder::code).
+///
+/// Branch targets are labels created with [CodeBuilder::new_label] and placed with
+/// [CodeBuilder::place_label]. Unless they are set explicitly, `max_stack` and `max_locals` are
+/// computed from the instructions.
+#[derive(Debug)]
+pub struct CodeBuilder<'a> {
+    pool: &'a mut ConstPoolBuilder,
+    items: Vec<CodeItem>,
+    labels: u32,
+    exception_table: Vec<ExceptionHandler>,
+    local_variables: Vec<LocalVariable>,
+    local_variable_types: Vec<LocalVariable>,
+    attributes: Vec<Attribute>,
+    max_stack: Option<u16>,
+    max_locals: Option<u16>,
+    /// Elements collected instead of being applied, see [CodeBuilder::record]
+    recorded: Option<Vec<CodeElement>>,
+}
+
+impl<'a> CodeBuilder<'a> {
+    /// Starts an empty body, whose new labels are numbered from `first_label`
+    pub(crate) fn new(pool: &'a mut ConstPoolBuilder, first_label: u32) -> Self {
+        Self {
+            pool,
+            items: Vec::new(),
+            labels: first_label,
+            exception_table: Vec::new(),
+            local_variables: Vec::new(),
+            local_variable_types: Vec::new(),
+            attributes: Vec::new(),
+            max_stack: None,
+            max_locals: None,
+            recorded: None,
+        }
+    }
+
+    /// Runs `build` and returns the elements it produced, without applying them
+    pub(crate) fn record(&mut self, build: impl FnOnce(&mut Self)) -> Vec<CodeElement> {
+        let previous = self.recorded.replace(Vec::new());
+        build(self);
+        mem::replace(&mut self.recorded, previous).unwrap_or_default()
+    }
+
+    pub fn pool(&mut self) -> &mut ConstPoolBuilder {
+        self.pool
+    }
+
+    /// Adds an element to the code, every other method of the builder going through this one
+    pub fn with(&mut self, element: CodeElement) -> &mut Self {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(element);
+            return self;
+        }
+        match element {
+            CodeElement::Instruction(instruction) => {
+                self.items.push(CodeItem::Instruction(instruction))
+            }
+            CodeElement::Label(label) => self.items.push(CodeItem::Label(label)),
+            CodeElement::LineNumber(line) => self.items.push(CodeItem::LineNumber(line)),
+            CodeElement::ExceptionHandler(handler) => self.exception_table.push(handler),
+            CodeElement::LocalVariable(variable) => self.local_variables.push(variable),
+            CodeElement::LocalVariableType(variable) => self.local_variable_types.push(variable),
+            CodeElement::Attribute(attribute) => self.attributes.push(attribute),
+        }
+        self
+    }
+
+    /// Creates a label, to be placed later with [CodeBuilder::place_label]
+    pub fn new_label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    /// Binds `label` to the position of the next instruction
+    pub fn place_label(&mut self, label: Label) -> &mut Self {
+        self.with(CodeElement::Label(label))
+    }
+
+    pub fn instruction(&mut self, instruction: Instruction) -> &mut Self {
+        self.with(CodeElement::Instruction(instruction))
+    }
+
+    /// Marks the next instruction as the start of `line` in the source file
+    pub fn line_number(&mut self, line: u16) -> &mut Self {
+        self.with(CodeElement::LineNumber(line))
+    }
+
+    /// Declares the local variable `name` of type `descriptor`, stored in slot `index`
+    /// between `start` and `end`
+    pub fn local_variable(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        index: u16,
+        start: Label,
+        end: Label,
+    ) -> &mut Self {
+        let name_index = self.pool.utf8(name);
+        let descriptor_index = self.pool.utf8(descriptor);
+        self.with(CodeElement::LocalVariable(LocalVariable {
+            start,
+            end,
+            name_index,
+            descriptor_index,
+            index,
+        }))
+    }
+
+    /// Registers a handler for exceptions of class `catch_type` (or any exception if `None`)
+    /// thrown between `start` and `end`
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) -> &mut Self {
+        let catch_type = catch_type.map(|name| self.pool.class(name));
+        self.with(CodeElement::ExceptionHandler(ExceptionHandler {
+            start,
+            end,
+            handler,
+            catch_type,
+        }))
+    }
+
+    pub fn max_stack(&mut self, max_stack: u16) -> &mut Self {
+        self.max_stack = Some(max_stack);
+        self
+    }
+
+    pub fn max_locals(&mut self, max_locals: u16) -> &mut Self {
+        self.max_locals = Some(max_locals);
+        self
+    }
+
+    /// Adds an attribute, whose content must refer to constants by their class file index
+    pub fn attribute(&mut self, name: impl Into<JavaString>, info: Vec<u8>) -> &mut Self {
+        let attribute_name_index = self.pool.utf8(name);
+        self.with(CodeElement::Attribute(Attribute {
+            attribute_name_index,
+            info,
+        }))
+    }
+
+    /// Pushes an int, using the shortest instruction
+    pub fn load_int(&mut self, value: i32) -> &mut Self {
+        let instruction = match value {
+            -1 => Instruction::IconstM1,
+            0 => Instruction::Iconst0,
+            1 => Instruction::Iconst1,
+            2 => Instruction::Iconst2,
+            3 => Instruction::Iconst3,
+            4 => Instruction::Iconst4,
+            5 => Instruction::Iconst5,
+            _ if i8::try_from(value).is_ok() => Instruction::Bipush(value as i8),
+            _ if i16::try_from(value).is_ok() => Instruction::Sipush(value as i16),
+            _ => {
+                let idx = self.pool.integer(value);
+                return self.ldc(idx);
+            }
+        };
+        self.instruction(instruction)
+    }
+
+    /// Pushes a string constant
+    pub fn load_string(&mut self, value: &str) -> &mut Self {
+        let idx = self.pool.string(value);
+        self.ldc(idx)
+    }
+
+    /// Pushes a loadable constant, choosing between `ldc`, `ldc_w` and `ldc2_w`
+    pub fn ldc(&mut self, idx: ConstItemIdx) -> &mut Self {
+        let wide = self
+            .pool
+            .constants()
+            .get(idx)
+            .is_some_and(ConstItem::is_8bit);
+        let instruction = if wide {
+            Instruction::Ldc2W(idx)
+        } else if self.pool.idx_map().raw_past_end(idx) <= u8::MAX as u16 {
+            Instruction::Ldc(idx)
+        } else {
+            Instruction::LdcW(idx)
+        };
+        self.instruction(instruction)
+    }
+
+    pub fn get_static(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.field_ref(owner, name, descriptor);
+        self.instruction(Instruction::Getstatic(idx))
+    }
+
+    pub fn put_static(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.field_ref(owner, name, descriptor);
+        self.instruction(Instruction::Putstatic(idx))
+    }
+
+    pub fn get_field(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.field_ref(owner, name, descriptor);
+        self.instruction(Instruction::Getfield(idx))
+    }
+
+    pub fn put_field(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.field_ref(owner, name, descriptor);
+        self.instruction(Instruction::Putfield(idx))
+    }
+
+    pub fn invoke_virtual(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.method_ref(owner, name, descriptor);
+        self.instruction(Instruction::Invokevirtual(idx))
+    }
+
+    pub fn invoke_special(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.method_ref(owner, name, descriptor);
+        self.instruction(Instruction::Invokespecial(idx))
+    }
+
+    pub fn invoke_static(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.method_ref(owner, name, descriptor);
+        self.instruction(Instruction::Invokestatic(idx))
+    }
+
+    pub fn invoke_interface(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let idx = self.pool.interface_method_ref(owner, name, descriptor);
+        let count = descriptor
+            .parse::<MethodDescriptor>()
+            .map_or(1, |descriptor| descriptor.parameter_slots() + 1);
+        self.instruction(Instruction::Invokeinterface(idx, count as u8))
+    }
+
+    /// Creates an uninitialized instance of the class `name`
+    pub fn new_instance(&mut self, name: &str) -> &mut Self {
+        let idx = self.pool.class(name);
+        self.instruction(Instruction::New(idx))
+    }
+
+    pub fn checkcast(&mut self, name: &str) -> &mut Self {
+        let idx = self.pool.class(name);
+        self.instruction(Instruction::Checkcast(idx))
+    }
+
+    pub fn instanceof(&mut self, name: &str) -> &mut Self {
+        let idx = self.pool.class(name);
+        self.instruction(Instruction::Instanceof(idx))
+    }
+
+    /// Assigns a pc to every instruction and resolves the labels, generating the debug tables
+    pub(crate) fn into_code(mut self, argument_slots: u16) -> Result<Code> {
+        let mut label_pcs = vec![None; self.labels as usize];
+        let mut instructions = Vec::with_capacity(self.items.len());
+        let mut line_numbers = Vec::new();
+        let mut pc = 0u32;
+        for item in self.items {
+            match item {
+                CodeItem::Label(label) => {
+                    // Labels may also come from decoded code, where they are pcs
+                    let label = label.0 as usize;
+                    if label >= label_pcs.len() && label <= u16::MAX as usize {
+                        label_pcs.resize(label + 1, None);
+                    }
+                    if let Some(label_pc) = label_pcs.get_mut(label) {
+                        *label_pc = Some(pc);
+                    }
+                }
+                CodeItem::LineNumber(line) => line_numbers.push(LineNumber {
+                    start: Label(pc),
+                    line,
+                }),
+                CodeItem::Instruction(instruction) => {
+                    let size = instruction.size(pc);
+                    instructions.push((pc, instruction));
+                    pc += size;
+                }
+            }
+        }
+
+        let mut undefined = None;
+        let mut resolve = |label: Label| match label_pcs.get(label.0 as usize).copied().flatten() {
+            Some(pc) => Label(pc),
+            None => {
+                undefined.get_or_insert(label.0);
+                label
+            }
+        };
+        for (_, instruction) in &mut instructions {
+            instruction.map_labels(&mut resolve);
+        }
+        let mut exception_table = self.exception_table;
+        for handler in &mut exception_table {
+            handler.start = resolve(handler.start);
+            handler.end = resolve(handler.end);
+            handler.handler = resolve(handler.handler);
+        }
+        for variable in self
+            .local_variables
+            .iter_mut()
+            .chain(&mut self.local_variable_types)
+        {
+            variable.start = resolve(variable.start);
+            variable.end = resolve(variable.end);
+        }
+        if let Some(label) = undefined {
+            return Err(ClassReaderError::UndefinedLabel(label));
+        }
+
+        let idx_map = self.pool.idx_map();
+        let tables = [
+            ("LineNumberTable", write_table(&line_numbers, idx_map)),
+            (
+                "LocalVariableTable",
+                write_table(&self.local_variables, idx_map),
+            ),
+            (
+                "LocalVariableTypeTable",
+                write_table(&self.local_variable_types, idx_map),
+            ),
+        ];
+        for (name, info) in tables {
+            // An empty table is only its u16 length
+            if info.len() > 2 {
+                let attribute_name_index = self.pool.utf8(name);
+                self.attributes.push(Attribute {
+                    attribute_name_index,
+                    info,
+                });
+            }
+        }
+
+        let mut code = Code {
+            max_stack: 0,
+            max_locals: 0,
+            instructions,
+            exception_table,
+            attributes: self.attributes,
+        };
+        code.max_stack = match self.max_stack {
+            Some(max_stack) => max_stack,
+            None => code.compute_max_stack(self.pool.constants()),
+        };
+        code.max_locals = match self.max_locals {
+            Some(max_locals) => max_locals,
+            None => code.compute_max_locals(argument_slots),
+        };
+        Ok(code)
+    }
+}
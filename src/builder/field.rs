@@ -0,0 +1,81 @@
+use crate::{
+    attribute::Attribute,
+    constants::ConstPoolBuilder,
+    field::{Field, FieldAccessFlags},
+    java_str::JavaString,
+};
+
+/// Initial value of a static field, stored in its `ConstantValue` attribute
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(JavaString),
+}
+
+/// Builds a [Field], see [ClassBuilder::field](super::ClassBuilder::field)
+#[derive(Debug)]
+pub struct FieldBuilder<'a> {
+    pool: &'a mut ConstPoolBuilder,
+    field: Field,
+}
+
+impl<'a> FieldBuilder<'a> {
+    pub(crate) fn new(
+        pool: &'a mut ConstPoolBuilder,
+        name: impl Into<JavaString>,
+        descriptor: impl Into<JavaString>,
+        access_flag: FieldAccessFlags,
+    ) -> Self {
+        let name_index = pool.utf8(name);
+        let descriptor_index = pool.utf8(descriptor);
+        Self {
+            pool,
+            field: Field {
+                access_flag,
+                name_index,
+                descriptor_index,
+                attributes: Vec::new(),
+            },
+        }
+    }
+
+    pub fn pool(&mut self) -> &mut ConstPoolBuilder {
+        self.pool
+    }
+
+    pub fn constant_value(&mut self, value: ConstantValue) -> &mut Self {
+        let idx = match value {
+            ConstantValue::Int(value) => self.pool.integer(value),
+            ConstantValue::Long(value) => self.pool.long(value),
+            ConstantValue::Float(value) => self.pool.float(value),
+            ConstantValue::Double(value) => self.pool.double(value),
+            ConstantValue::String(value) => self.pool.string(value),
+        };
+        let info = self.pool.idx_map().raw_past_end(idx).to_be_bytes().to_vec();
+        self.attribute("ConstantValue", info)
+    }
+
+    /// Sets the generic signature of the field
+    pub fn signature(&mut self, signature: impl Into<JavaString>) -> &mut Self {
+        let idx = self.pool.utf8(signature);
+        let info = self.pool.idx_map().raw_past_end(idx).to_be_bytes().to_vec();
+        self.attribute("Signature", info)
+    }
+
+    /// Adds an attribute, whose content must refer to constants by their class file index
+    pub fn attribute(&mut self, name: impl Into<JavaString>, info: Vec<u8>) -> &mut Self {
+        let attribute_name_index = self.pool.utf8(name);
+        self.field.attributes.push(Attribute {
+            attribute_name_index,
+            info,
+        });
+        self
+    }
+
+    pub(crate) fn into_field(self) -> Field {
+        self.field
+    }
+}
@@ -0,0 +1,276 @@
+use std::mem;
+
+use crate::{
+    Result,
+    attribute::Attribute,
+    buffer::BufferWriter,
+    code::Code,
+    constants::{ConstPoolBuilder, Constants},
+    descriptor::MethodDescriptor,
+    error::ClassReaderError,
+    java_str::JavaString,
+    method::{Method, MethodAccessFlags},
+    transform::{CodeTransform, MethodElement},
+};
+
+use super::CodeBuilder;
+
+/// Builds a [Method], see [ClassBuilder::method](super::ClassBuilder::method)
+#[derive(Debug)]
+pub struct MethodBuilder<'a> {
+    pool: &'a mut ConstPoolBuilder,
+    method: Method,
+    /// Slots taken by the arguments, excluding `this`
+    descriptor_slots: Option<u16>,
+    error: Option<ClassReaderError>,
+    /// Elements collected instead of being applied, see [MethodBuilder::record]
+    recorded: Option<Vec<MethodElement>>,
+}
+
+impl<'a> MethodBuilder<'a> {
+    pub(crate) fn new(
+        pool: &'a mut ConstPoolBuilder,
+        name: impl Into<JavaString>,
+        descriptor: impl Into<JavaString>,
+        access_flags: MethodAccessFlags,
+    ) -> Self {
+        let descriptor = descriptor.into();
+        let descriptor_slots = MethodDescriptor::parse(&descriptor)
+            .ok()
+            .map(|descriptor| descriptor.parameter_slots());
+        let name_index = pool.utf8(name);
+        let descriptor_index = pool.utf8(descriptor);
+        Self {
+            pool,
+            descriptor_slots,
+            method: Method {
+                access_flags,
+                name_index,
+                descriptor_index,
+                attributes: Vec::new(),
+            },
+            error: None,
+            recorded: None,
+        }
+    }
+
+    /// Starts from the name, descriptor and flags of `method`, without its attributes, which
+    /// are then expected as elements
+    pub(crate) fn from_method(pool: &'a mut ConstPoolBuilder, method: &Method) -> Self {
+        let descriptor_slots = pool
+            .constants()
+            .get(method.descriptor_index)
+            .and_then(|item| item.as_utf8())
+            .and_then(|descriptor| MethodDescriptor::parse(&descriptor.string).ok())
+            .map(|descriptor| descriptor.parameter_slots());
+        Self {
+            pool,
+            descriptor_slots,
+            method: Method {
+                attributes: Vec::new(),
+                ..method.clone()
+            },
+            error: None,
+            recorded: None,
+        }
+    }
+
+    /// Runs `build` and returns the elements it produced, without applying them
+    pub(crate) fn record(&mut self, build: impl FnOnce(&mut Self)) -> Vec<MethodElement> {
+        let previous = self.recorded.replace(Vec::new());
+        build(self);
+        mem::replace(&mut self.recorded, previous).unwrap_or_default()
+    }
+
+    pub fn pool(&mut self) -> &mut ConstPoolBuilder {
+        self.pool
+    }
+
+    /// Adds an element to the method, every other method of the builder going through this one
+    pub fn with(&mut self, element: MethodElement) -> &mut Self {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(element);
+            return self;
+        }
+        match element {
+            MethodElement::AccessFlags(access_flags) => self.method.access_flags = access_flags,
+            MethodElement::Code(attribute) | MethodElement::Attribute(attribute) => {
+                self.method.attributes.push(attribute)
+            }
+        }
+        self
+    }
+
+    pub fn access_flags(&mut self, access_flags: MethodAccessFlags) -> &mut Self {
+        self.with(MethodElement::AccessFlags(access_flags))
+    }
+
+    /// Sets the body of the method.
+    ///
+    /// Frames are not computed, so in classes of version 50 or later, code that needs them,
+    /// having branches or exception handlers, must come with a `StackMapTable` attribute for
+    /// [ClassBuilder::build](super::ClassBuilder::build) to succeed.
+    pub fn code(&mut self, build: impl FnOnce(&mut CodeBuilder)) -> &mut Self {
+        let argument_slots = self.argument_slots();
+        let mut builder = CodeBuilder::new(self.pool, 0);
+        build(&mut builder);
+        let code = builder.into_code(argument_slots);
+        self.add_code(code)
+    }
+
+    /// Rebuilds the `Code` attribute `code` by passing its elements through `transform`.
+    ///
+    /// The `StackMapTable` of the original attribute is kept as long as every instruction keeps
+    /// its pc and branch targets and the exception table is unchanged, otherwise it is dropped as
+    /// its frames would be stale, see [MethodBuilder::code]. The original maxs are kept when
+    /// the instructions are unchanged, and computed otherwise.
+    pub fn transform_code(
+        &mut self,
+        code: &Attribute,
+        transform: &mut impl CodeTransform,
+    ) -> &mut Self {
+        let code = self.transformed_code(code, transform);
+        self.add_code(code)
+    }
+
+    fn transformed_code(
+        &mut self,
+        attribute: &Attribute,
+        transform: &mut impl CodeTransform,
+    ) -> Result<Code> {
+        let original = Code::read(&attribute.info, self.pool.idx_map())?;
+        let elements = original.elements(self.pool.constants(), self.pool.idx_map())?;
+        let argument_slots = self.argument_slots();
+        let mut builder = CodeBuilder::new(self.pool, original.code_length() + 1);
+        for element in elements {
+            transform.accept(&mut builder, element);
+        }
+        transform.at_end(&mut builder);
+        let mut code = builder.into_code(argument_slots)?;
+
+        let handlers_unchanged = code.exception_table == original.exception_table;
+        if handlers_unchanged && code.instructions == original.instructions {
+            code.max_stack = original.max_stack;
+            code.max_locals = original.max_locals;
+        }
+        let same_layout = handlers_unchanged
+            && code.instructions.len() == original.instructions.len()
+            && code.instructions.iter().zip(&original.instructions).all(
+                |((pc, instruction), (original_pc, original_instruction))| {
+                    pc == original_pc && instruction.targets() == original_instruction.targets()
+                },
+            );
+        code.attributes = merge_attributes(
+            original.attributes,
+            code.attributes,
+            same_layout,
+            self.pool.constants(),
+        );
+        Ok(code)
+    }
+
+    fn add_code(&mut self, code: Result<Code>) -> &mut Self {
+        match code.and_then(|code| code.write(self.pool.idx_map())) {
+            Ok(info) => {
+                let attribute_name_index = self.pool.utf8("Code");
+                self.with(MethodElement::Code(Attribute {
+                    attribute_name_index,
+                    info,
+                }))
+            }
+            Err(err) => {
+                self.error.get_or_insert(err);
+                self
+            }
+        }
+    }
+
+    /// Slots taken by the arguments, including `this`
+    fn argument_slots(&self) -> u16 {
+        let this_slots = if self.method.access_flags.contains(MethodAccessFlags::STATIC) {
+            0
+        } else {
+            1
+        };
+        self.descriptor_slots.unwrap_or(0) + this_slots
+    }
+
+    /// Declares the checked exceptions thrown by the method, by their internal names
+    pub fn exceptions<S: Into<JavaString>>(
+        &mut self,
+        exceptions: impl IntoIterator<Item = S>,
+    ) -> &mut Self {
+        let exceptions: Vec<_> = exceptions
+            .into_iter()
+            .map(|exception| self.pool.class(exception))
+            .collect();
+        let mut buf = BufferWriter::new();
+        buf.write_len_u16(exceptions.len());
+        for idx in exceptions {
+            buf.write_u16(self.pool.idx_map().raw_past_end(idx));
+        }
+        self.attribute("Exceptions", buf.into_bytes())
+    }
+
+    /// Sets the generic signature of the method
+    pub fn signature(&mut self, signature: impl Into<JavaString>) -> &mut Self {
+        let idx = self.pool.utf8(signature);
+        let info = self.pool.idx_map().raw_past_end(idx).to_be_bytes().to_vec();
+        self.attribute("Signature", info)
+    }
+
+    /// Adds an attribute, whose content must refer to constants by their class file index
+    pub fn attribute(&mut self, name: impl Into<JavaString>, info: Vec<u8>) -> &mut Self {
+        let attribute_name_index = self.pool.utf8(name);
+        self.with(MethodElement::Attribute(Attribute {
+            attribute_name_index,
+            info,
+        }))
+    }
+
+    pub(crate) fn into_method(self) -> Result<Method> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.method),
+        }
+    }
+}
+
+/// Whether the verifier needs frames for `code`: at branch targets, exception handlers and
+/// instructions following an unconditional branch
+pub(super) fn needs_frames(code: &Code) -> bool {
+    !code.exception_table.is_empty()
+        || code
+            .instructions
+            .iter()
+            .enumerate()
+            .any(|(position, (_, instruction))| {
+                !instruction.targets().is_empty()
+                    || (!instruction.falls_through() && position + 1 < code.instructions.len())
+            })
+}
+
+/// Lays out the attributes of a rebuilt `Code` attribute in the order of the original ones,
+/// appending the new ones. The original `StackMapTable` is only kept with `keep_stack_map`,
+/// unless a new one replaces it.
+fn merge_attributes(
+    original: Vec<Attribute>,
+    mut rebuilt: Vec<Attribute>,
+    keep_stack_map: bool,
+    constants: &Constants,
+) -> Vec<Attribute> {
+    let mut attributes = Vec::with_capacity(rebuilt.len() + 1);
+    for attribute in original {
+        let name = attribute.name(constants).and_then(|name| name.as_str());
+        if let Some(position) = rebuilt
+            .iter()
+            .position(|rebuilt| rebuilt.attribute_name_index == attribute.attribute_name_index)
+        {
+            attributes.push(rebuilt.remove(position));
+        } else if name == Some("StackMapTable") && keep_stack_map {
+            attributes.push(attribute);
+        }
+    }
+    attributes.extend(rebuilt);
+    attributes
+}
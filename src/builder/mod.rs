@@ -0,0 +1,376 @@
+mod code;
+pub use code::*;
+mod field;
+pub use field::*;
+mod method;
+pub use method::*;
+
+use std::mem;
+
+use crate::{
+    ClassAccessFlags, ClassFile, Result,
+    attribute::Attribute,
+    code::Code,
+    constants::{ConstItemIdx, ConstPoolBuilder},
+    error::ClassReaderError,
+    field::{Field, FieldAccessFlags},
+    java_str::JavaString,
+    method::{Method, MethodAccessFlags},
+    transform::{ClassElement, MethodTransform},
+    version::ClassFileVersion,
+};
+
+/// Builds a [ClassFile] from plain names and descriptors, managing its constant pool.
+///
+/// ```
+/// use classfile::{ClassAccessFlags, builder::ClassBuilder, code::Instruction, method::MethodAccessFlags};
+///
+/// let mut builder = ClassBuilder::new("com/example/Hello");
+/// builder.method("<init>", "()V", MethodAccessFlags::PUBLIC, |method| {
+///     method.code(|code| {
+///         code.instruction(Instruction::Aload0)
+///             .invoke_special("java/lang/Object", "<init>", "()V")
+///             .instruction(Instruction::Return);
+///     });
+/// });
+/// let bytes = builder.build().unwrap().write();
+/// ```
+#[derive(Debug)]
+pub struct ClassBuilder {
+    pool: ConstPoolBuilder,
+    version: ClassFileVersion,
+    minor_version: u16,
+    access_flags: ClassAccessFlags,
+    this_class: ConstItemIdx,
+    super_class: Option<ConstItemIdx>,
+    interfaces: Vec<ConstItemIdx>,
+    fields: Vec<Field>,
+    methods: Vec<Method>,
+    attributes: Vec<Attribute>,
+    error: Option<ClassReaderError>,
+    /// Elements collected instead of being applied, see [ClassBuilder::record]
+    recorded: Option<Vec<ClassElement>>,
+}
+
+impl ClassBuilder {
+    /// Starts a public class extending `java/lang/Object`, `name` being an internal name
+    pub fn new(name: &str) -> Self {
+        Self::with_pool(name, ConstPoolBuilder::new())
+    }
+
+    /// Starts a class whose constant pool begins with the entries of `pool`
+    pub fn with_pool(name: &str, mut pool: ConstPoolBuilder) -> Self {
+        let this_class = pool.class(name);
+        let super_class = pool.class("java/lang/Object");
+        let mut builder = Self::from_pool(pool, this_class);
+        builder.super_class = Some(super_class);
+        builder
+    }
+
+    /// Starts a class without a super class, whose name is already in `pool`
+    pub(crate) fn from_pool(pool: ConstPoolBuilder, this_class: ConstItemIdx) -> Self {
+        Self {
+            pool,
+            version: ClassFileVersion::default(),
+            minor_version: 0,
+            access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+            this_class,
+            super_class: None,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            error: None,
+            recorded: None,
+        }
+    }
+
+    /// Starts from the constant pool and header of `class`, without its members and
+    /// attributes, which are then expected as elements
+    pub(crate) fn from_class(class: &ClassFile) -> Result<Self> {
+        Ok(Self {
+            pool: ConstPoolBuilder::from_constants(class.constants.clone())?,
+            version: class.version.clone(),
+            minor_version: class.minor_version,
+            access_flags: class.access_flag.clone(),
+            this_class: class.this_class,
+            super_class: class.super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            error: None,
+            recorded: None,
+        })
+    }
+
+    /// Runs `build` and returns the elements it produced, without applying them
+    pub(crate) fn record(&mut self, build: impl FnOnce(&mut Self)) -> Vec<ClassElement> {
+        let previous = self.recorded.replace(Vec::new());
+        build(self);
+        mem::replace(&mut self.recorded, previous).unwrap_or_default()
+    }
+
+    pub fn pool(&mut self) -> &mut ConstPoolBuilder {
+        &mut self.pool
+    }
+
+    /// Adds an element to the class, every other method of the builder going through this one
+    pub fn with(&mut self, element: ClassElement) -> &mut Self {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(element);
+            return self;
+        }
+        match element {
+            ClassElement::Version(version, minor_version) => {
+                self.version = version;
+                self.minor_version = minor_version;
+            }
+            ClassElement::AccessFlags(access_flags) => self.access_flags = access_flags,
+            ClassElement::Superclass(super_class) => self.super_class = super_class,
+            ClassElement::Interface(interface) => self.interfaces.push(interface),
+            ClassElement::Field(field) => self.fields.push(field),
+            ClassElement::Method(method) => self.methods.push(method),
+            ClassElement::Attribute(attribute) => self.attributes.push(attribute),
+        }
+        self
+    }
+
+    pub fn version(&mut self, version: ClassFileVersion, minor_version: u16) -> &mut Self {
+        self.with(ClassElement::Version(version, minor_version))
+    }
+
+    pub fn access_flags(&mut self, access_flags: ClassAccessFlags) -> &mut Self {
+        self.with(ClassElement::AccessFlags(access_flags))
+    }
+
+    /// Sets the super class, `None` being only valid for `java/lang/Object`
+    pub fn super_class(&mut self, name: Option<&str>) -> &mut Self {
+        let super_class = name.map(|name| self.pool.class(name));
+        self.with(ClassElement::Superclass(super_class))
+    }
+
+    pub fn interface(&mut self, name: &str) -> &mut Self {
+        let idx = self.pool.class(name);
+        self.with(ClassElement::Interface(idx))
+    }
+
+    pub fn field(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        access_flags: FieldAccessFlags,
+        build: impl FnOnce(&mut FieldBuilder),
+    ) -> &mut Self {
+        let mut builder = FieldBuilder::new(&mut self.pool, name, descriptor, access_flags);
+        build(&mut builder);
+        let field = builder.into_field();
+        self.with(ClassElement::Field(field))
+    }
+
+    pub fn method(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        access_flags: MethodAccessFlags,
+        build: impl FnOnce(&mut MethodBuilder),
+    ) -> &mut Self {
+        let mut builder = MethodBuilder::new(&mut self.pool, name, descriptor, access_flags);
+        build(&mut builder);
+        let method = builder.into_method();
+        self.add_method(method)
+    }
+
+    /// Rebuilds `method` by passing its elements through `transform`
+    pub fn transform_method(
+        &mut self,
+        method: &Method,
+        transform: &mut impl MethodTransform,
+    ) -> &mut Self {
+        let elements = method.elements(self.pool.constants());
+        let mut builder = MethodBuilder::from_method(&mut self.pool, method);
+        for element in elements {
+            transform.accept(&mut builder, element);
+        }
+        transform.at_end(&mut builder);
+        let method = builder.into_method();
+        self.add_method(method)
+    }
+
+    fn add_method(&mut self, method: Result<Method>) -> &mut Self {
+        match method {
+            Ok(method) => self.with(ClassElement::Method(method)),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                self
+            }
+        }
+    }
+
+    pub fn source_file(&mut self, name: impl Into<JavaString>) -> &mut Self {
+        let idx = self.pool.utf8(name);
+        let info = self.pool.idx_map().raw_past_end(idx).to_be_bytes().to_vec();
+        self.attribute("SourceFile", info)
+    }
+
+    /// Adds an attribute, whose content must refer to constants by their class file index
+    pub fn attribute(&mut self, name: impl Into<JavaString>, info: Vec<u8>) -> &mut Self {
+        let attribute_name_index = self.pool.utf8(name);
+        self.with(ClassElement::Attribute(Attribute {
+            attribute_name_index,
+            info,
+        }))
+    }
+
+    /// Fails if the code of `method` needs frames but has no `StackMapTable`
+    fn check_frames(&self, method: &Method) -> Result<()> {
+        let constants = self.pool.constants();
+        let Some(attribute) = method.find_attribute(constants, "Code") else {
+            return Ok(());
+        };
+        let code = Code::read(&attribute.info, self.pool.idx_map())?;
+        if needs_frames(&code)
+            && Attribute::find(&code.attributes, constants, "StackMapTable").is_none()
+        {
+            return Err(ClassReaderError::StackMapTableRequired(
+                self.version.major(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Builds the class, failing if the code of a method could not be assembled, if the
+    /// constant pool is too large, or if the version requires frames that code needs but has
+    /// no `StackMapTable` for, see [MethodBuilder::code]
+    pub fn build(self) -> Result<ClassFile> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.pool.check_size()?;
+        if self.version.major() >= 50 {
+            for method in &self.methods {
+                self.check_frames(method)?;
+            }
+        }
+        Ok(ClassFile {
+            version: self.version,
+            minor_version: self.minor_version,
+            constants: self.pool.into_constants(),
+            access_flag: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        builder::{ClassBuilder, ConstantValue},
+        code::Instruction,
+        error::ClassReaderError,
+        field::FieldAccessFlags,
+        method::MethodAccessFlags,
+        verifier::Classes,
+        version::ClassFileVersion,
+    };
+
+    #[test]
+    fn built_classes_can_be_read_back() {
+        let mut builder = ClassBuilder::new("com/example/Adder");
+        builder
+            .interface("java/lang/Runnable")
+            .source_file("Adder.java")
+            .field(
+                "BIG",
+                "J",
+                FieldAccessFlags::STATIC | FieldAccessFlags::FINAL,
+                |field| {
+                    field.constant_value(ConstantValue::Long(1 << 40));
+                },
+            )
+            .method("add", "(JJ)J", MethodAccessFlags::STATIC, |method| {
+                method.code(|code| {
+                    code.instruction(Instruction::Lload0)
+                        .instruction(Instruction::Lload2)
+                        .instruction(Instruction::Ladd)
+                        .instruction(Instruction::Lreturn);
+                });
+            });
+        let bytes = builder.build().unwrap().write();
+
+        let class = ClassFile::read(&bytes).unwrap();
+        assert_eq!(Ok(()), class.validate_constants());
+        assert_eq!(bytes, class.write());
+        let code = class.methods[0]
+            .code(&class.constants, &class.const_idx_map().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(4, code.max_stack);
+        assert_eq!(4, code.max_locals);
+    }
+
+    #[test]
+    fn branches_are_resolved() {
+        let mut builder = ClassBuilder::new("Abs");
+        builder.version(ClassFileVersion::Jdk1_4, 0);
+        builder.method("abs", "(I)I", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let positive = code.new_label();
+                code.instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ifge(positive))
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ineg)
+                    .instruction(Instruction::Ireturn)
+                    .place_label(positive)
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ireturn);
+            });
+        });
+        let class = builder.build().unwrap();
+        let code = class.methods[0]
+            .code(&class.constants, &class.const_idx_map().unwrap())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            (1, Instruction::Ifge(crate::code::Label(7))),
+            code.instructions[1]
+        );
+        assert_eq!(Ok(()), class.verify(&Classes::new()));
+    }
+
+    #[test]
+    fn branches_need_frames_from_version_50() {
+        let mut builder = ClassBuilder::new("Loop");
+        builder.method("spin", "()V", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let start = code.new_label();
+                code.place_label(start)
+                    .instruction(Instruction::Goto(start));
+            });
+        });
+
+        assert_eq!(
+            &ClassReaderError::StackMapTableRequired(52),
+            builder.build().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn undefined_labels_are_reported() {
+        let mut builder = ClassBuilder::new("Broken");
+        builder.method("loop", "()V", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let label = code.new_label();
+                code.instruction(Instruction::Goto(label));
+            });
+        });
+        assert!(builder.build().is_err());
+    }
+}
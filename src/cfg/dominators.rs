@@ -0,0 +1,276 @@
+use index_vec::IndexVec;
+
+use super::{BlockIdx, ControlFlowGraph, reverse_post_order};
+
+/// The dominator or post-dominator tree of a [ControlFlowGraph].
+///
+/// A block dominates another when every path from the entry to the latter goes through it,
+/// and post-dominates it when every path from it to an exit of the method goes through it.
+/// Blocks that cannot be reached from the entry, or for post-dominators that cannot reach an
+/// exit such as the ones of an infinite loop, are left out of the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominatorTree {
+    immediate: IndexVec<BlockIdx, Option<BlockIdx>>,
+    children: IndexVec<BlockIdx, Vec<BlockIdx>>,
+    roots: Vec<BlockIdx>,
+    /// Pre-order and post-order numbers of the blocks in the tree
+    numbers: IndexVec<BlockIdx, Option<(u32, u32)>>,
+}
+
+impl DominatorTree {
+    /// Builds the tree from the immediate dominator of each node of a graph, the roots being
+    /// their own immediate dominators and the nodes past the blocks a virtual root
+    fn new(blocks: usize, idom: &[Option<usize>]) -> Self {
+        let mut immediate = IndexVec::from_vec(vec![None; blocks]);
+        let mut children = IndexVec::from_vec(vec![Vec::new(); blocks]);
+        let mut roots = Vec::new();
+        for (block, idom) in idom.iter().enumerate().take(blocks) {
+            match *idom {
+                Some(idom) if idom == block || idom >= blocks => roots.push(BlockIdx::new(block)),
+                Some(idom) => {
+                    immediate[block] = Some(BlockIdx::new(idom));
+                    children[idom].push(BlockIdx::new(block));
+                }
+                None => {}
+            }
+        }
+
+        let mut numbers = IndexVec::from_vec(vec![None; blocks]);
+        let (mut pre, mut post) = (0, 0);
+        for &root in &roots {
+            // Blocks being visited, with the number of their children already visited
+            let mut stack = vec![(root, 0)];
+            numbers[root] = Some((pre, 0));
+            pre += 1;
+            while let Some((block, visited)) = stack.last_mut() {
+                let block = *block;
+                match children[block].get(*visited) {
+                    Some(&child) => {
+                        *visited += 1;
+                        numbers[child] = Some((pre, 0));
+                        pre += 1;
+                        stack.push((child, 0));
+                    }
+                    None => {
+                        if let Some((_, number)) = &mut numbers[block] {
+                            *number = post;
+                        }
+                        post += 1;
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        Self {
+            immediate,
+            children,
+            roots,
+            numbers,
+        }
+    }
+
+    /// The entry for dominators, or the exits of the method for post-dominators
+    pub fn roots(&self) -> &[BlockIdx] {
+        &self.roots
+    }
+
+    /// Whether `block` is in the tree
+    pub fn contains(&self, block: BlockIdx) -> bool {
+        self.numbers[block].is_some()
+    }
+
+    /// The parent of `block` in the tree, or `None` for the roots and the blocks left out
+    pub fn immediate_dominator(&self, block: BlockIdx) -> Option<BlockIdx> {
+        self.immediate[block]
+    }
+
+    /// The blocks `block` immediately dominates, ordered by index
+    pub fn children(&self, block: BlockIdx) -> &[BlockIdx] {
+        &self.children[block]
+    }
+
+    /// Whether `a` dominates `b`, which includes `a` being `b`
+    pub fn dominates(&self, a: BlockIdx, b: BlockIdx) -> bool {
+        match (self.numbers[a], self.numbers[b]) {
+            (Some((a_pre, a_post)), Some((b_pre, b_post))) => a_pre <= b_pre && b_post <= a_post,
+            _ => false,
+        }
+    }
+
+    /// Whether `a` dominates `b` and is not `b`
+    pub fn strictly_dominates(&self, a: BlockIdx, b: BlockIdx) -> bool {
+        a != b && self.dominates(a, b)
+    }
+}
+
+impl ControlFlowGraph {
+    /// Computes the dominator tree, rooted at the entry
+    pub fn dominators(&self) -> DominatorTree {
+        let order: Vec<_> = self.reverse_post_order.iter().map(|b| b.index()).collect();
+        let predecessors: Vec<Vec<_>> = self
+            .predecessors
+            .iter()
+            .map(|edges| edges.iter().map(|edge| edge.from.index()).collect())
+            .collect();
+        let idom = immediate_dominators(&order, &predecessors);
+        DominatorTree::new(self.len(), &idom)
+    }
+
+    /// Computes the post-dominator tree, whose roots are the exits of the method: the blocks
+    /// without successors, which return, throw an uncaught exception or return from a
+    /// subroutine
+    pub fn post_dominators(&self) -> DominatorTree {
+        // The graph is reversed, with a virtual root after the blocks leading to the exits
+        let exit = self.len();
+        let mut successors: Vec<Vec<_>> = self
+            .predecessors
+            .iter()
+            .map(|edges| edges.iter().map(|edge| edge.from.index()).collect())
+            .collect();
+        let mut predecessors: Vec<Vec<_>> = self
+            .successors
+            .iter()
+            .map(|edges| edges.iter().map(|edge| edge.to.index()).collect())
+            .collect();
+        let exits: Vec<_> = (0..exit)
+            .filter(|&block| predecessors[block].is_empty())
+            .collect();
+        for &block in &exits {
+            predecessors[block].push(exit);
+        }
+        successors.push(exits);
+        predecessors.push(Vec::new());
+        let order = reverse_post_order(exit, &successors);
+        let idom = immediate_dominators(&order, &predecessors);
+        DominatorTree::new(self.len(), &idom)
+    }
+
+    /// Computes the dominance frontier of each block: the blocks where its dominance ends,
+    /// which it does not strictly dominate but dominates a predecessor of, ordered by index.
+    ///
+    /// `dominators` must be the tree computed by [ControlFlowGraph::dominators].
+    pub fn dominance_frontiers(
+        &self,
+        dominators: &DominatorTree,
+    ) -> IndexVec<BlockIdx, Vec<BlockIdx>> {
+        let mut frontiers = IndexVec::from_vec(vec![Vec::new(); self.len()]);
+        for &block in &self.reverse_post_order {
+            let idom = dominators.immediate_dominator(block);
+            for edge in self.predecessors(block) {
+                if !dominators.contains(edge.from) {
+                    continue;
+                }
+                let mut runner = Some(edge.from);
+                while let Some(current) = runner
+                    && runner != idom
+                {
+                    let frontier: &mut Vec<BlockIdx> = &mut frontiers[current];
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    runner = dominators.immediate_dominator(current);
+                }
+            }
+        }
+        for frontier in &mut frontiers {
+            frontier.sort();
+        }
+        frontiers
+    }
+}
+
+/// Immediate dominator of each node of a graph, computed with the algorithm of Cooper, Harvey
+/// and Kennedy from `order`, a reverse post-order of the nodes reached from the root.
+///
+/// The root is its own immediate dominator, and the nodes not reached have none.
+fn immediate_dominators(order: &[usize], predecessors: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let mut numbers = vec![usize::MAX; predecessors.len()];
+    for (number, &node) in order.iter().enumerate() {
+        numbers[node] = number;
+    }
+    let mut idom = vec![None; predecessors.len()];
+    idom[order[0]] = Some(order[0]);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in &order[1..] {
+            let mut new = None;
+            for &predecessor in &predecessors[node] {
+                if idom[predecessor].is_none() {
+                    continue;
+                }
+                new = Some(match new {
+                    None => predecessor,
+                    Some(other) => common_dominator(predecessor, other, &idom, &numbers),
+                });
+            }
+            if new != idom[node] {
+                idom[node] = new;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+/// Nearest common dominator of `a` and `b`, walking up the dominators found so far
+fn common_dominator(
+    mut a: usize,
+    mut b: usize,
+    idom: &[Option<usize>],
+    numbers: &[usize],
+) -> usize {
+    while a != b {
+        while numbers[a] > numbers[b] {
+            a = idom[a].unwrap();
+        }
+        while numbers[b] > numbers[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::{BlockIdx, ControlFlowGraph, tests::loop_nest};
+
+    #[test]
+    fn dominators_and_frontiers() {
+        let code = loop_nest();
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        let block = BlockIdx::new;
+        let blocks = |indices: &[usize]| indices.iter().copied().map(block).collect::<Vec<_>>();
+
+        let dominators = cfg.dominators();
+        let idoms: Vec<_> = (0..5)
+            .map(|b| dominators.immediate_dominator(block(b)).map(|b| b.index()))
+            .collect();
+        assert_eq!(vec![None, Some(0), Some(1), Some(2), Some(3)], idoms);
+        assert!(dominators.dominates(block(1), block(3)));
+        assert!(!dominators.strictly_dominates(block(3), block(3)));
+
+        let post_dominators = cfg.post_dominators();
+        assert_eq!(blocks(&[4]), post_dominators.roots());
+        let ipdoms: Vec<_> = (0..5)
+            .map(|b| {
+                post_dominators
+                    .immediate_dominator(block(b))
+                    .map(|b| b.index())
+            })
+            .collect();
+        assert_eq!(vec![Some(1), Some(2), Some(3), Some(4), None], ipdoms);
+
+        let frontiers = cfg.dominance_frontiers(&dominators);
+        assert_eq!(
+            vec![
+                blocks(&[]),
+                blocks(&[1]),
+                blocks(&[1, 2]),
+                blocks(&[1]),
+                blocks(&[])
+            ],
+            frontiers.raw
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use index_vec::IndexVec;
+
+use super::{BlockIdx, ControlFlowGraph, DominatorTree, Edge};
+
+/// A natural loop: the blocks that can reach a back edge to a header, which dominates them,
+/// without going through the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    /// The only block of the loop entered from outside of it
+    pub header: BlockIdx,
+    /// Blocks with a back edge to the header, ordered by index
+    pub latches: Vec<BlockIdx>,
+    /// Blocks of the loop, including the header, ordered by index
+    pub body: Vec<BlockIdx>,
+    /// Edges leaving the loop
+    pub exits: Vec<Edge>,
+    /// Innermost loop containing this one, as an index in [Loops::loops]
+    pub parent: Option<usize>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockIdx) -> bool {
+        self.body.binary_search(&block).is_ok()
+    }
+}
+
+/// A cycle that can be entered at more than one block, and so is not a natural loop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrreducibleRegion {
+    /// Blocks of the region entered from outside of it, ordered by index
+    pub entries: Vec<BlockIdx>,
+    /// Blocks of the region, ordered by index
+    pub body: Vec<BlockIdx>,
+}
+
+/// The natural loops and irreducible regions of a [ControlFlowGraph], see
+/// [ControlFlowGraph::loops]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loops {
+    loops: Vec<Loop>,
+    irreducible: Vec<IrreducibleRegion>,
+    innermost: IndexVec<BlockIdx, Option<usize>>,
+}
+
+impl Loops {
+    /// The natural loops, each one before the loops it contains, with a single loop for the
+    /// back edges to the same header
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    /// The irreducible regions, each one before the regions it contains
+    pub fn irreducible(&self) -> &[IrreducibleRegion] {
+        &self.irreducible
+    }
+
+    /// Whether every cycle of the graph is part of a natural loop
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+
+    /// Innermost natural loop containing `block`, as an index in [Loops::loops]
+    pub fn innermost(&self, block: BlockIdx) -> Option<usize> {
+        self.innermost[block]
+    }
+
+    /// Number of natural loops containing `block`
+    pub fn depth(&self, block: BlockIdx) -> usize {
+        std::iter::successors(self.innermost(block), |&index| self.loops[index].parent).count()
+    }
+}
+
+impl ControlFlowGraph {
+    /// Finds the natural loops, formed by the back edges going to a block that dominates
+    /// their source, and the cycles that are not part of one.
+    ///
+    /// `dominators` must be the tree computed by [ControlFlowGraph::dominators]. Blocks that
+    /// cannot be reached from the entry are not part of any loop or region.
+    pub fn loops(&self, dominators: &DominatorTree) -> Loops {
+        let mut loops: Vec<Loop> = Vec::new();
+        // Headers come before the blocks they dominate, so outer loops before inner ones
+        for &header in &self.reverse_post_order {
+            let latches: Vec<_> = self
+                .predecessors(header)
+                .iter()
+                .map(|edge| edge.from)
+                .filter(|&from| dominators.dominates(header, from))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            let mut in_body = vec![false; self.len()];
+            in_body[header.index()] = true;
+            let mut pending = latches.clone();
+            while let Some(block) = pending.pop() {
+                if std::mem::replace(&mut in_body[block.index()], true) {
+                    continue;
+                }
+                let predecessors = self.predecessors(block).iter().map(|edge| edge.from);
+                pending.extend(predecessors.filter(|&from| dominators.contains(from)));
+            }
+            let body: Vec<_> = (0..self.len())
+                .filter(|&block| in_body[block])
+                .map(BlockIdx::new)
+                .collect();
+            let exits = body
+                .iter()
+                .flat_map(|&block| self.successors(block))
+                .filter(|edge| !in_body[edge.to.index()])
+                .copied()
+                .collect();
+            let parent = loops.iter().rposition(|outer| outer.contains(header));
+            let mut latches = latches;
+            latches.sort();
+            latches.dedup();
+            loops.push(Loop {
+                header,
+                latches,
+                body,
+                exits,
+                parent,
+            });
+        }
+
+        let mut innermost = IndexVec::from_vec(vec![None; self.len()]);
+        for (index, natural_loop) in loops.iter().enumerate() {
+            for &block in &natural_loop.body {
+                innermost[block] = Some(index);
+            }
+        }
+        Loops {
+            loops,
+            irreducible: self.irreducible_regions(),
+            innermost,
+        }
+    }
+
+    /// Finds the cycles entered at more than one block, looking at the strongly connected
+    /// components of the reachable blocks, then again inside each one without its entries
+    fn irreducible_regions(&self) -> Vec<IrreducibleRegion> {
+        let mut regions = Vec::new();
+        let mut reachable = vec![false; self.len()];
+        for block in &self.reverse_post_order {
+            reachable[block.index()] = true;
+        }
+        let mut blocks = self.reverse_post_order.clone();
+        blocks.sort();
+        let mut pending = vec![blocks];
+        while let Some(blocks) = pending.pop() {
+            for component in self.cycles(&blocks) {
+                let entries: Vec<_> = component
+                    .iter()
+                    .copied()
+                    .filter(|&block| {
+                        block == self.entry()
+                            || self.predecessors(block).iter().any(|edge| {
+                                reachable[edge.from.index()]
+                                    && component.binary_search(&edge.from).is_err()
+                            })
+                    })
+                    .collect();
+                let inner = component
+                    .iter()
+                    .copied()
+                    .filter(|block| !entries.contains(block))
+                    .collect();
+                if entries.len() > 1 {
+                    regions.push(IrreducibleRegion {
+                        entries,
+                        body: component,
+                    });
+                }
+                pending.push(inner);
+            }
+        }
+        regions
+    }
+
+    /// Strongly connected components of the graph made of `blocks`, ordered by index, that
+    /// have a cycle, found with Tarjan's algorithm
+    fn cycles(&self, blocks: &[BlockIdx]) -> Vec<Vec<BlockIdx>> {
+        let mut inside = vec![false; self.len()];
+        for block in blocks {
+            inside[block.index()] = true;
+        }
+        // Discovery number and lowest number reachable of each visited block
+        let mut numbers: Vec<Option<(usize, usize)>> = vec![None; self.len()];
+        let mut on_stack = vec![false; self.len()];
+        let mut stack = Vec::new();
+        let mut components = Vec::new();
+        let mut next = 0;
+        for &root in blocks {
+            if numbers[root.index()].is_some() {
+                continue;
+            }
+            numbers[root.index()] = Some((next, next));
+            next += 1;
+            stack.push(root);
+            on_stack[root.index()] = true;
+            // Blocks being visited, with the number of their successors already followed
+            let mut visits = vec![(root, 0)];
+            while let Some(&(block, followed)) = visits.last() {
+                if let Some(edge) = self.successors(block).get(followed) {
+                    visits.last_mut().unwrap().1 += 1;
+                    let to = edge.to.index();
+                    if !inside[to] {
+                        continue;
+                    }
+                    match numbers[to] {
+                        None => {
+                            numbers[to] = Some((next, next));
+                            next += 1;
+                            stack.push(edge.to);
+                            on_stack[to] = true;
+                            visits.push((edge.to, 0));
+                        }
+                        Some((number, _)) if on_stack[to] => {
+                            let (_, low) = numbers[block.index()].as_mut().unwrap();
+                            *low = (*low).min(number);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+                visits.pop();
+                let (number, low) = numbers[block.index()].unwrap();
+                if let Some(&(parent, _)) = visits.last() {
+                    let (_, parent_low) = numbers[parent.index()].as_mut().unwrap();
+                    *parent_low = (*parent_low).min(low);
+                }
+                if number != low {
+                    continue;
+                }
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member.index()] = false;
+                    component.push(member);
+                    if member == block {
+                        break;
+                    }
+                }
+                let self_loop = self.successors(block).iter().any(|edge| edge.to == block);
+                if component.len() > 1 || self_loop {
+                    component.sort();
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        cfg::{
+            BlockIdx, ControlFlowGraph, Edge, EdgeKind, IrreducibleRegion, Loop, tests::loop_nest,
+        },
+        code::{Code, Instruction, Label},
+    };
+
+    fn blocks(indices: &[usize]) -> Vec<BlockIdx> {
+        indices.iter().copied().map(BlockIdx::new).collect()
+    }
+
+    #[test]
+    fn nested_loops() {
+        let code = loop_nest();
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        let loops = cfg.loops(&cfg.dominators());
+
+        let exit = |from, to| Edge {
+            from: BlockIdx::new(from),
+            to: BlockIdx::new(to),
+            kind: EdgeKind::Conditional(false),
+        };
+        assert_eq!(
+            &[
+                Loop {
+                    header: BlockIdx::new(1),
+                    latches: blocks(&[3]),
+                    body: blocks(&[1, 2, 3]),
+                    exits: vec![exit(3, 4)],
+                    parent: None,
+                },
+                Loop {
+                    header: BlockIdx::new(2),
+                    latches: blocks(&[2]),
+                    body: blocks(&[2]),
+                    exits: vec![exit(2, 3)],
+                    parent: Some(0),
+                },
+            ],
+            loops.loops()
+        );
+        assert!(loops.is_reducible());
+        assert_eq!(Some(1), loops.innermost(BlockIdx::new(2)));
+        assert_eq!(2, loops.depth(BlockIdx::new(2)));
+        assert_eq!(0, loops.depth(BlockIdx::new(4)));
+    }
+
+    #[test]
+    fn irreducible_regions() {
+        // A cycle between the two branches of a conditional
+        let code = Code {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                (0, Instruction::Iload0),
+                (1, Instruction::Ifeq(Label(8))),
+                (4, Instruction::Nop),
+                (5, Instruction::Goto(Label(8))),
+                (8, Instruction::Iload0),
+                (9, Instruction::Ifne(Label(4))),
+                (12, Instruction::Return),
+            ],
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        };
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        let loops = cfg.loops(&cfg.dominators());
+
+        assert!(loops.loops().is_empty());
+        assert_eq!(
+            &[IrreducibleRegion {
+                entries: blocks(&[1, 2]),
+                body: blocks(&[1, 2]),
+            }],
+            loops.irreducible()
+        );
+    }
+
+    #[test]
+    fn compiled_loops_are_reducible() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+        let method = class
+            .methods
+            .iter()
+            .find(|method| {
+                class.constants[method.name_index].as_utf8().unwrap().string == "compute"
+            })
+            .unwrap();
+        let code = method.code(&class.constants, &idx_map).unwrap().unwrap();
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        let loops = cfg.loops(&cfg.dominators());
+        // The for loop, and the catch-all handler of the finally block, which covers its own
+        // first instructions
+        let headers: Vec<_> = loops
+            .loops()
+            .iter()
+            .map(|natural_loop| cfg.block(natural_loop.header).start)
+            .collect();
+        assert_eq!(2, headers.len());
+        assert_eq!(144, headers[1]);
+        assert!(loops.is_reducible());
+    }
+}
@@ -0,0 +1,491 @@
+//! Control-flow graphs of decoded code, see [ControlFlowGraph]
+
+mod dominators;
+pub use dominators::*;
+mod loops;
+pub use loops::*;
+
+use std::ops::Range;
+
+use index_vec::{IndexVec, define_index_type};
+
+use crate::{
+    code::{Code, Instruction, Label},
+    constants::ConstItemIdx,
+    verifier::StructuralViolation,
+};
+
+define_index_type! {
+    /// Index of a block in a [ControlFlowGraph]
+    pub struct BlockIdx = u32;
+}
+
+/// A maximal sequence of instructions that is only entered at its first instruction and
+/// only left after its last one, exceptions aside
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BasicBlock {
+    /// pc of the first instruction
+    pub start: u32,
+    /// pc following the last instruction
+    pub end: u32,
+    /// Positions of the instructions in [Code::instructions]
+    pub instructions: Range<usize>,
+}
+
+impl BasicBlock {
+    /// The instructions of the block, taken from the code the graph was built from
+    pub fn instructions<'a>(&self, code: &'a Code) -> &'a [(u32, Instruction)] {
+        &code.instructions[self.instructions.clone()]
+    }
+}
+
+/// How control goes from a block to one of its successors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction, or jumps with a `goto`
+    Normal,
+    /// A conditional branch, `true` when its condition holds and `false` when it falls through
+    Conditional(bool),
+    /// A case of a switch with its key, or `None` for the default one
+    Switch(Option<i32>),
+    /// A `jsr` calling a subroutine, which then returns through the [EdgeKind::Normal] edge
+    /// going to the instruction following the `jsr`
+    Subroutine,
+    /// An exception handler covering the block, with the class it catches or `None` for all
+    Exception(Option<ConstItemIdx>),
+}
+
+/// An edge of a [ControlFlowGraph]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: BlockIdx,
+    pub to: BlockIdx,
+    pub kind: EdgeKind,
+}
+
+/// The basic blocks of the code of a method and the edges between them.
+///
+/// Blocks are split at branch targets, after branches, and at the boundaries of the ranges
+/// covered by exception handlers, so a handler covers either all or none of a block. Each
+/// block covered by a handler has an [EdgeKind::Exception] edge to it. Code falling off its
+/// end, which the verifier rejects, leaves its last block without an edge for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    blocks: IndexVec<BlockIdx, BasicBlock>,
+    successors: IndexVec<BlockIdx, Vec<Edge>>,
+    predecessors: IndexVec<BlockIdx, Vec<Edge>>,
+    reverse_post_order: Vec<BlockIdx>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of `code`, which must have instructions, branch targets and exception
+    /// table entries at instruction boundaries
+    pub fn new(code: &Code) -> Result<Self, StructuralViolation> {
+        let instructions = &code.instructions;
+        if instructions.is_empty() {
+            return Err(StructuralViolation::CodeLength(0));
+        }
+        let code_length = code.code_length();
+        let position = |pc: u32| code.position(pc);
+
+        // Whether a block starts at each position, plus one for the end of the code, which
+        // closes the last block even when its last instruction falls through
+        let mut starts = vec![false; instructions.len() + 1];
+        starts[0] = true;
+        starts[instructions.len()] = true;
+        for (index, (pc, instruction)) in instructions.iter().enumerate() {
+            let targets = instruction.targets();
+            for target in &targets {
+                let target =
+                    position(target.0).ok_or(StructuralViolation::InvalidBranchTarget {
+                        pc: *pc,
+                        target: target.0,
+                    })?;
+                starts[target] = true;
+            }
+            if !targets.is_empty() || !instruction.falls_through() {
+                starts[index + 1] = true;
+            }
+        }
+        for (index, handler) in code.exception_table.iter().enumerate() {
+            let (start, end) = (handler.start.0, handler.end.0);
+            let end_position = match end == code_length {
+                true => Some(instructions.len()),
+                false => position(end),
+            };
+            match (position(start), end_position) {
+                (Some(start), Some(end)) if start < end => {
+                    starts[start] = true;
+                    starts[end] = true;
+                }
+                _ => return Err(StructuralViolation::InvalidExceptionRange { index, start, end }),
+            }
+            let handler = handler.handler.0;
+            let handler =
+                position(handler).ok_or(StructuralViolation::InvalidHandler { index, handler })?;
+            starts[handler] = true;
+        }
+
+        let mut blocks = IndexVec::new();
+        let mut block_of = Vec::with_capacity(instructions.len());
+        let mut first = 0;
+        for (index, &start) in starts.iter().enumerate().skip(1) {
+            if !start {
+                continue;
+            }
+            let end = instructions.get(index).map_or(code_length, |(pc, _)| *pc);
+            let block = blocks.push(BasicBlock {
+                start: instructions[first].0,
+                end,
+                instructions: first..index,
+            });
+            block_of.resize(index, block);
+            first = index;
+        }
+
+        let mut successors: IndexVec<BlockIdx, Vec<Edge>> = IndexVec::new();
+        for (from, block) in blocks.iter_enumerated() {
+            let last = block.instructions.end - 1;
+            let next = block_of.get(last + 1).copied();
+            let to = |label: Label| block_of[position(label.0).unwrap()];
+            let mut edges = Vec::new();
+            let mut edge = |to, kind| edges.push(Edge { from, to, kind });
+            match &instructions[last].1 {
+                Instruction::Tableswitch(switch) => {
+                    edge(to(switch.default), EdgeKind::Switch(None));
+                    for (offset, target) in switch.targets.iter().enumerate() {
+                        let key = switch.low.wrapping_add(offset as i32);
+                        edge(to(*target), EdgeKind::Switch(Some(key)));
+                    }
+                }
+                Instruction::Lookupswitch(switch) => {
+                    edge(to(switch.default), EdgeKind::Switch(None));
+                    for (key, target) in &switch.pairs {
+                        edge(to(*target), EdgeKind::Switch(Some(*key)));
+                    }
+                }
+                Instruction::Goto(target) | Instruction::GotoW(target) => {
+                    edge(to(*target), EdgeKind::Normal);
+                }
+                Instruction::Jsr(target) | Instruction::JsrW(target) => {
+                    edge(to(*target), EdgeKind::Subroutine);
+                    if let Some(next) = next {
+                        edge(next, EdgeKind::Normal);
+                    }
+                }
+                instruction => match instruction.targets().first() {
+                    Some(target) => {
+                        edge(to(*target), EdgeKind::Conditional(true));
+                        if let Some(next) = next {
+                            edge(next, EdgeKind::Conditional(false));
+                        }
+                    }
+                    None if instruction.falls_through() => {
+                        if let Some(next) = next {
+                            edge(next, EdgeKind::Normal);
+                        }
+                    }
+                    None => {}
+                },
+            }
+            for handler in &code.exception_table {
+                if (handler.start.0..handler.end.0).contains(&block.start) {
+                    edge(to(handler.handler), EdgeKind::Exception(handler.catch_type));
+                }
+            }
+            successors.push(edges);
+        }
+
+        let mut predecessors: IndexVec<BlockIdx, Vec<Edge>> =
+            IndexVec::from_vec(vec![Vec::new(); blocks.len()]);
+        for edge in successors.iter().flatten() {
+            predecessors[edge.to].push(*edge);
+        }
+        let adjacency: Vec<Vec<usize>> = successors
+            .iter()
+            .map(|edges| edges.iter().map(|edge| edge.to.index()).collect())
+            .collect();
+        let reverse_post_order = reverse_post_order(0, &adjacency)
+            .into_iter()
+            .map(BlockIdx::new)
+            .collect();
+        Ok(Self {
+            blocks,
+            successors,
+            predecessors,
+            reverse_post_order,
+        })
+    }
+
+    /// The block starting at pc 0
+    pub fn entry(&self) -> BlockIdx {
+        BlockIdx::new(0)
+    }
+
+    /// Number of blocks, reachable or not
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Always `false`, as code has at least one instruction
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The blocks, ordered by pc
+    pub fn blocks(&self) -> &IndexVec<BlockIdx, BasicBlock> {
+        &self.blocks
+    }
+
+    pub fn block(&self, block: BlockIdx) -> &BasicBlock {
+        &self.blocks[block]
+    }
+
+    /// The block containing the instruction at `pc`
+    pub fn block_at(&self, pc: u32) -> Option<BlockIdx> {
+        let index = self
+            .blocks
+            .as_raw_slice()
+            .partition_point(|block| block.start <= pc);
+        let block = BlockIdx::new(index.checked_sub(1)?);
+        (pc < self.blocks[block].end).then_some(block)
+    }
+
+    /// Edges leaving `block`: the ones of its last instruction, in the order of its targets,
+    /// then the ones to its exception handlers, in the order of the exception table
+    pub fn successors(&self, block: BlockIdx) -> &[Edge] {
+        &self.successors[block]
+    }
+
+    /// Edges entering `block`
+    pub fn predecessors(&self, block: BlockIdx) -> &[Edge] {
+        &self.predecessors[block]
+    }
+
+    /// Blocks reachable from the entry, each one before its successors unless it is reached
+    /// through a back edge
+    pub fn reverse_post_order(&self) -> &[BlockIdx] {
+        &self.reverse_post_order
+    }
+}
+
+/// Depth-first traversal of a graph from `root`, following the successors of each node in
+/// order, which returns the nodes reached in reverse post-order
+fn reverse_post_order(root: usize, successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(successors.len());
+    let mut visited = vec![false; successors.len()];
+    visited[root] = true;
+    // Nodes being visited, with the number of their successors already followed
+    let mut stack = vec![(root, 0)];
+    while let Some((node, followed)) = stack.last_mut() {
+        let node = *node;
+        match successors[node].get(*followed) {
+            Some(&next) => {
+                *followed += 1;
+                if !std::mem::replace(&mut visited[next], true) {
+                    stack.push((next, 0));
+                }
+            }
+            None => {
+                order.push(node);
+                stack.pop();
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        cfg::{BlockIdx, ControlFlowGraph, Edge, EdgeKind},
+        code::{Code, ExceptionHandler, Instruction, Label, LookupSwitch, TableSwitch},
+        constants::ConstItemIdx,
+    };
+
+    /// `for (;;) { while (x != 0); if (x == 0) return; }`, with one block per statement
+    pub(super) fn loop_nest() -> Code {
+        Code {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                (0, Instruction::Nop),
+                (1, Instruction::Nop),
+                (2, Instruction::Iload0),
+                (3, Instruction::Ifne(Label(2))),
+                (6, Instruction::Iload0),
+                (7, Instruction::Ifne(Label(1))),
+                (10, Instruction::Return),
+            ],
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let catch_type = Some(ConstItemIdx::new(3));
+        let code = Code {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                (0, Instruction::Iload0),
+                (1, Instruction::Ifeq(Label(7))),
+                (4, Instruction::Iconst1),
+                (5, Instruction::Istore0),
+                (6, Instruction::Iload0),
+                (
+                    7,
+                    Instruction::Lookupswitch(LookupSwitch {
+                        padding: [0; 3],
+                        default: Label(32),
+                        pairs: vec![(1, Label(33)), (2, Label(32))],
+                    }),
+                ),
+                (32, Instruction::Return),
+                (33, Instruction::Athrow),
+                (34, Instruction::Pop),
+                (35, Instruction::Goto(Label(0))),
+            ],
+            exception_table: vec![ExceptionHandler {
+                start: Label(5),
+                end: Label(32),
+                handler: Label(34),
+                catch_type,
+            }],
+            attributes: Vec::new(),
+        };
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+
+        let blocks: Vec<_> = cfg
+            .blocks()
+            .iter()
+            .map(|block| block.start..block.end)
+            .collect();
+        assert_eq!(
+            vec![0..4, 4..5, 5..7, 7..32, 32..33, 33..34, 34..38],
+            blocks
+        );
+        let edge = |from: usize, to: usize, kind| Edge {
+            from: BlockIdx::new(from),
+            to: BlockIdx::new(to),
+            kind,
+        };
+        assert_eq!(
+            &[
+                edge(0, 3, EdgeKind::Conditional(true)),
+                edge(0, 1, EdgeKind::Conditional(false)),
+            ],
+            cfg.successors(BlockIdx::new(0))
+        );
+        assert_eq!(
+            &[
+                edge(3, 4, EdgeKind::Switch(None)),
+                edge(3, 5, EdgeKind::Switch(Some(1))),
+                edge(3, 4, EdgeKind::Switch(Some(2))),
+                edge(3, 6, EdgeKind::Exception(catch_type)),
+            ],
+            cfg.successors(BlockIdx::new(3))
+        );
+        assert_eq!(
+            &[
+                edge(2, 6, EdgeKind::Exception(catch_type)),
+                edge(3, 6, EdgeKind::Exception(catch_type)),
+            ],
+            cfg.predecessors(BlockIdx::new(6))
+        );
+        assert_eq!(
+            &[edge(6, 0, EdgeKind::Normal)],
+            cfg.successors(BlockIdx::new(6))
+        );
+        assert_eq!(Some(BlockIdx::new(3)), cfg.block_at(20));
+        assert_eq!(None, cfg.block_at(38));
+
+        let order: Vec<_> = cfg.reverse_post_order().iter().map(|b| b.index()).collect();
+        assert_eq!(vec![0, 1, 2, 3, 6, 5, 4], order);
+    }
+
+    #[test]
+    fn code_falling_off_the_end() {
+        let code = |instructions| Code {
+            max_stack: 0,
+            max_locals: 0,
+            instructions,
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        };
+
+        let cfg = ControlFlowGraph::new(&code(vec![(0, Instruction::Nop)])).unwrap();
+        assert_eq!(1, cfg.len());
+        assert_eq!(0..1, cfg.block(BlockIdx::new(0)).instructions);
+        assert!(cfg.successors(BlockIdx::new(0)).is_empty());
+
+        let instructions = vec![(0, Instruction::Goto(Label(3))), (3, Instruction::Nop)];
+        let cfg = ControlFlowGraph::new(&code(instructions)).unwrap();
+        let blocks: Vec<_> = cfg
+            .blocks()
+            .iter()
+            .map(|block| block.start..block.end)
+            .collect();
+        assert_eq!(vec![0..3, 3..4], blocks);
+        assert!(cfg.successors(BlockIdx::new(1)).is_empty());
+        assert_eq!(
+            &[BlockIdx::new(0), BlockIdx::new(1)],
+            cfg.reverse_post_order()
+        );
+    }
+
+    #[test]
+    fn switch_keys_reach_i32_max() {
+        let code = Code {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                (0, Instruction::Iload0),
+                (
+                    1,
+                    Instruction::Tableswitch(TableSwitch {
+                        padding: [0; 3],
+                        default: Label(20),
+                        low: i32::MAX,
+                        targets: vec![Label(20)],
+                    }),
+                ),
+                (20, Instruction::Return),
+            ],
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        };
+
+        let cfg = ControlFlowGraph::new(&code).unwrap();
+        let kinds: Vec<_> = cfg
+            .successors(BlockIdx::new(0))
+            .iter()
+            .map(|edge| edge.kind)
+            .collect();
+        assert_eq!(
+            vec![EdgeKind::Switch(None), EdgeKind::Switch(Some(i32::MAX))],
+            kinds
+        );
+    }
+
+    #[test]
+    fn compiled_code_is_covered_by_its_blocks() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+        for method in &class.methods {
+            let code = method.code(&class.constants, &idx_map).unwrap().unwrap();
+            let cfg = ControlFlowGraph::new(&code).unwrap();
+            let covered: usize = cfg.blocks().iter().map(|b| b.instructions.len()).sum();
+            assert_eq!(code.instructions.len(), covered);
+            assert_eq!(cfg.len(), cfg.reverse_post_order().len());
+            for (block, _) in cfg.blocks().iter_enumerated() {
+                for edge in cfg.successors(block) {
+                    assert!(cfg.predecessors(edge.to).contains(edge));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+    constants::{ConstIdxMap, ConstItemIdx},
+};
+
+use super::Label;
+
+/// An entry of the `LineNumberTable` attribute of a [Code](super::Code) attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineNumber {
+    pub start: Label,
+    pub line: u16,
+}
+
+impl Read for LineNumber {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
+        let start = Label(buf.read_u16()? as u32);
+        let line = buf.read_u16()?;
+
+        Ok(Self { start, line })
+    }
+}
+
+impl Write for LineNumber {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_u16(self.start.0 as u16);
+        buf.write_u16(self.line);
+    }
+}
+
+/// An entry of the `LocalVariableTable` or `LocalVariableTypeTable` attributes of a
+/// [Code](super::Code) attribute, the latter storing signatures instead of descriptors
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LocalVariable {
+    pub start: Label,
+    /// End of the scope of the variable, exclusive
+    pub end: Label,
+    pub name_index: ConstItemIdx,
+    pub descriptor_index: ConstItemIdx,
+    /// Slot of the variable
+    pub index: u16,
+}
+
+impl Read for LocalVariable {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let start_pc = buf.read_u16()? as u32;
+        let length = buf.read_u16()? as u32;
+        let name_index = ConstItemIdx::read(buf, idx_map)?;
+        let descriptor_index = ConstItemIdx::read(buf, idx_map)?;
+        let index = buf.read_u16()?;
+
+        Ok(Self {
+            start: Label(start_pc),
+            end: Label(start_pc + length),
+            name_index,
+            descriptor_index,
+            index,
+        })
+    }
+}
+
+impl Write for LocalVariable {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u16(self.start.0 as u16);
+        buf.write_u16(self.end.0.wrapping_sub(self.start.0) as u16);
+        self.name_index.write(buf, idx_map);
+        self.descriptor_index.write(buf, idx_map);
+        buf.write_u16(self.index);
+    }
+}
+
+/// Decodes a table of entries prefixed by its length, such as the content of a
+/// `LineNumberTable` attribute
+pub fn read_table<T: Read>(info: &[u8], idx_map: &ConstIdxMap) -> Result<Vec<T>> {
+    Vec::read(&mut Buffer::new(info), idx_map)
+}
+
+/// Encodes a table of entries prefixed by its length, the counterpart of [read_table]
+pub fn write_table<T: Write>(table: &Vec<T>, idx_map: &ConstIdxMap) -> Vec<u8> {
+    let mut buf = BufferWriter::new();
+    table.write(&mut buf, idx_map);
+    buf.into_bytes()
+}
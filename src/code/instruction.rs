@@ -0,0 +1,1598 @@
+use crate::{
+    Read, Result,
+    buffer::{Buffer, BufferWriter},
+    constants::{ConstIdxMap, ConstItemIdx},
+    error::ClassReaderError,
+};
+
+use super::Label;
+
+/// Element type of the array created by a `newarray` instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArrayType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+}
+
+impl TryFrom<u8> for ArrayType {
+    type Error = ();
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            4 => Ok(Self::Boolean),
+            5 => Ok(Self::Char),
+            6 => Ok(Self::Float),
+            7 => Ok(Self::Double),
+            8 => Ok(Self::Byte),
+            9 => Ok(Self::Short),
+            10 => Ok(Self::Int),
+            11 => Ok(Self::Long),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Instructions that can follow the `wide` opcode, with their widened operands
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WideInstruction {
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Ret(u16),
+    Iinc(u16, i16),
+}
+
+impl WideInstruction {
+    fn read(buf: &mut Buffer) -> Result<Self> {
+        let opcode = buf.read_u8()?;
+        Ok(match opcode {
+            0x15 => Self::Iload(buf.read_u16()?),
+            0x16 => Self::Lload(buf.read_u16()?),
+            0x17 => Self::Fload(buf.read_u16()?),
+            0x18 => Self::Dload(buf.read_u16()?),
+            0x19 => Self::Aload(buf.read_u16()?),
+            0x36 => Self::Istore(buf.read_u16()?),
+            0x37 => Self::Lstore(buf.read_u16()?),
+            0x38 => Self::Fstore(buf.read_u16()?),
+            0x39 => Self::Dstore(buf.read_u16()?),
+            0x3a => Self::Astore(buf.read_u16()?),
+            0xa9 => Self::Ret(buf.read_u16()?),
+            0x84 => Self::Iinc(buf.read_u16()?, buf.read_u16()? as i16),
+            opcode => return Err(ClassReaderError::InvalidOpcode(opcode)),
+        })
+    }
+
+    fn write(&self, buf: &mut BufferWriter) {
+        let (opcode, local) = match self {
+            Self::Iload(local) => (0x15, local),
+            Self::Lload(local) => (0x16, local),
+            Self::Fload(local) => (0x17, local),
+            Self::Dload(local) => (0x18, local),
+            Self::Aload(local) => (0x19, local),
+            Self::Istore(local) => (0x36, local),
+            Self::Lstore(local) => (0x37, local),
+            Self::Fstore(local) => (0x38, local),
+            Self::Dstore(local) => (0x39, local),
+            Self::Astore(local) => (0x3a, local),
+            Self::Ret(local) => (0xa9, local),
+            Self::Iinc(local, _) => (0x84, local),
+        };
+        buf.write_u8(opcode);
+        buf.write_u16(*local);
+        if let Self::Iinc(_, value) = self {
+            buf.write_u16(*value as u16);
+        }
+    }
+}
+
+/// Number of padding bytes between a switch opcode at `pc` and its 4-byte aligned operands
+fn switch_padding(pc: u32) -> u32 {
+    3 - pc % 4
+}
+
+fn read_switch_padding(buf: &mut Buffer, pc: u32) -> Result<[u8; 3]> {
+    let mut padding = [0; 3];
+    let len = switch_padding(pc) as usize;
+    padding[..len].copy_from_slice(buf.read_bytes(len)?);
+    Ok(padding)
+}
+
+fn write_switch_padding(buf: &mut BufferWriter, padding: &[u8; 3], pc: u32) {
+    buf.write_bytes(&padding[..switch_padding(pc) as usize]);
+}
+
+/// Operands of a `tableswitch` instruction, jumping to `targets[key - low]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableSwitch {
+    /// Bytes aligning the operands, of which as many as needed at the pc of the instruction
+    /// are written. They are zeros in valid classes of versions below 51.
+    pub padding: [u8; 3],
+    pub default: Label,
+    pub low: i32,
+    pub targets: Vec<Label>,
+}
+
+impl TableSwitch {
+    fn read(buf: &mut Buffer, pc: u32) -> Result<Self> {
+        let padding = read_switch_padding(buf, pc)?;
+        let default = Label(pc.wrapping_add_signed(buf.read_i32()?));
+        let low = buf.read_i32()?;
+        let high = buf.read_i32()?;
+        if high < low {
+            return Err(ClassReaderError::InvalidSwitch(pc));
+        }
+        let count = (high as i64 - low as i64 + 1) as usize;
+        if count * 4 > buf.remaining() {
+            return Err(ClassReaderError::UnexpectedEndOfData);
+        }
+        let mut targets = Vec::with_capacity(count);
+        for _ in 0..count {
+            targets.push(Label(pc.wrapping_add_signed(buf.read_i32()?)));
+        }
+        Ok(Self {
+            padding,
+            default,
+            low,
+            targets,
+        })
+    }
+
+    fn write(&self, buf: &mut BufferWriter, pc: u32) -> Result<()> {
+        let high = i32::try_from(self.low as i64 + self.targets.len() as i64 - 1)
+            .ok()
+            .filter(|&high| high >= self.low)
+            .ok_or(ClassReaderError::InvalidSwitch(pc))?;
+        write_switch_padding(buf, &self.padding, pc);
+        buf.write_i32(self.default.0.wrapping_sub(pc) as i32);
+        buf.write_i32(self.low);
+        buf.write_i32(high);
+        for target in &self.targets {
+            buf.write_i32(target.0.wrapping_sub(pc) as i32);
+        }
+        Ok(())
+    }
+}
+
+/// Operands of a `lookupswitch` instruction, pairs being sorted by key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LookupSwitch {
+    /// Bytes aligning the operands, see [TableSwitch::padding]
+    pub padding: [u8; 3],
+    pub default: Label,
+    pub pairs: Vec<(i32, Label)>,
+}
+
+impl LookupSwitch {
+    fn read(buf: &mut Buffer, pc: u32) -> Result<Self> {
+        let padding = read_switch_padding(buf, pc)?;
+        let default = Label(pc.wrapping_add_signed(buf.read_i32()?));
+        let count = buf.read_i32()?;
+        if count < 0 {
+            return Err(ClassReaderError::InvalidSwitch(pc));
+        }
+        if count as usize * 8 > buf.remaining() {
+            return Err(ClassReaderError::UnexpectedEndOfData);
+        }
+        let mut pairs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let key = buf.read_i32()?;
+            pairs.push((key, Label(pc.wrapping_add_signed(buf.read_i32()?))));
+        }
+        Ok(Self {
+            padding,
+            default,
+            pairs,
+        })
+    }
+
+    fn write(&self, buf: &mut BufferWriter, pc: u32) {
+        write_switch_padding(buf, &self.padding, pc);
+        buf.write_i32(self.default.0.wrapping_sub(pc) as i32);
+        buf.write_i32(self.pairs.len() as i32);
+        for (key, target) in &self.pairs {
+            buf.write_i32(*key);
+            buf.write_i32(target.0.wrapping_sub(pc) as i32);
+        }
+    }
+}
+
+/// A JVM instruction (JVMS 6.5).
+///
+/// Constant pool operands are [ConstItemIdx] values and branch targets are [Label]s, which hold
+/// the pc of their target in decoded code.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    Bipush(i8),
+    Sipush(i16),
+    Ldc(ConstItemIdx),
+    LdcW(ConstItemIdx),
+    Ldc2W(ConstItemIdx),
+    Iload(u8),
+    Lload(u8),
+    Fload(u8),
+    Dload(u8),
+    Aload(u8),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u8),
+    Lstore(u8),
+    Fstore(u8),
+    Dstore(u8),
+    Astore(u8),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc(u8, i8),
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    Ifeq(Label),
+    Ifne(Label),
+    Iflt(Label),
+    Ifge(Label),
+    Ifgt(Label),
+    Ifle(Label),
+    IfIcmpeq(Label),
+    IfIcmpne(Label),
+    IfIcmplt(Label),
+    IfIcmpge(Label),
+    IfIcmpgt(Label),
+    IfIcmple(Label),
+    IfAcmpeq(Label),
+    IfAcmpne(Label),
+    Goto(Label),
+    Jsr(Label),
+    Ret(u8),
+    Tableswitch(TableSwitch),
+    Lookupswitch(LookupSwitch),
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    Getstatic(ConstItemIdx),
+    Putstatic(ConstItemIdx),
+    Getfield(ConstItemIdx),
+    Putfield(ConstItemIdx),
+    Invokevirtual(ConstItemIdx),
+    Invokespecial(ConstItemIdx),
+    Invokestatic(ConstItemIdx),
+    Invokeinterface(ConstItemIdx, u8),
+    Invokedynamic(ConstItemIdx),
+    New(ConstItemIdx),
+    Newarray(ArrayType),
+    Anewarray(ConstItemIdx),
+    Arraylength,
+    Athrow,
+    Checkcast(ConstItemIdx),
+    Instanceof(ConstItemIdx),
+    Monitorenter,
+    Monitorexit,
+    Wide(WideInstruction),
+    Multianewarray(ConstItemIdx, u8),
+    Ifnull(Label),
+    Ifnonnull(Label),
+    GotoW(Label),
+    JsrW(Label),
+}
+
+impl Instruction {
+    /// Decodes the instruction at `pc`, `buf` being positioned on its opcode
+    pub fn read(buf: &mut Buffer, pc: u32, idx_map: &ConstIdxMap) -> Result<Self> {
+        let opcode = buf.read_u8()?;
+        let branch = |offset: i32| Label(pc.wrapping_add_signed(offset));
+        Ok(match opcode {
+            0x00 => Self::Nop,
+            0x01 => Self::AconstNull,
+            0x02 => Self::IconstM1,
+            0x03 => Self::Iconst0,
+            0x04 => Self::Iconst1,
+            0x05 => Self::Iconst2,
+            0x06 => Self::Iconst3,
+            0x07 => Self::Iconst4,
+            0x08 => Self::Iconst5,
+            0x09 => Self::Lconst0,
+            0x0a => Self::Lconst1,
+            0x0b => Self::Fconst0,
+            0x0c => Self::Fconst1,
+            0x0d => Self::Fconst2,
+            0x0e => Self::Dconst0,
+            0x0f => Self::Dconst1,
+            0x10 => Self::Bipush(buf.read_u8()? as i8),
+            0x11 => Self::Sipush(buf.read_u16()? as i16),
+            0x12 => Self::Ldc(idx_map.resolve(buf.read_u8()? as u16)?),
+            0x13 => Self::LdcW(ConstItemIdx::read(buf, idx_map)?),
+            0x14 => Self::Ldc2W(ConstItemIdx::read(buf, idx_map)?),
+            0x15 => Self::Iload(buf.read_u8()?),
+            0x16 => Self::Lload(buf.read_u8()?),
+            0x17 => Self::Fload(buf.read_u8()?),
+            0x18 => Self::Dload(buf.read_u8()?),
+            0x19 => Self::Aload(buf.read_u8()?),
+            0x1a => Self::Iload0,
+            0x1b => Self::Iload1,
+            0x1c => Self::Iload2,
+            0x1d => Self::Iload3,
+            0x1e => Self::Lload0,
+            0x1f => Self::Lload1,
+            0x20 => Self::Lload2,
+            0x21 => Self::Lload3,
+            0x22 => Self::Fload0,
+            0x23 => Self::Fload1,
+            0x24 => Self::Fload2,
+            0x25 => Self::Fload3,
+            0x26 => Self::Dload0,
+            0x27 => Self::Dload1,
+            0x28 => Self::Dload2,
+            0x29 => Self::Dload3,
+            0x2a => Self::Aload0,
+            0x2b => Self::Aload1,
+            0x2c => Self::Aload2,
+            0x2d => Self::Aload3,
+            0x2e => Self::Iaload,
+            0x2f => Self::Laload,
+            0x30 => Self::Faload,
+            0x31 => Self::Daload,
+            0x32 => Self::Aaload,
+            0x33 => Self::Baload,
+            0x34 => Self::Caload,
+            0x35 => Self::Saload,
+            0x36 => Self::Istore(buf.read_u8()?),
+            0x37 => Self::Lstore(buf.read_u8()?),
+            0x38 => Self::Fstore(buf.read_u8()?),
+            0x39 => Self::Dstore(buf.read_u8()?),
+            0x3a => Self::Astore(buf.read_u8()?),
+            0x3b => Self::Istore0,
+            0x3c => Self::Istore1,
+            0x3d => Self::Istore2,
+            0x3e => Self::Istore3,
+            0x3f => Self::Lstore0,
+            0x40 => Self::Lstore1,
+            0x41 => Self::Lstore2,
+            0x42 => Self::Lstore3,
+            0x43 => Self::Fstore0,
+            0x44 => Self::Fstore1,
+            0x45 => Self::Fstore2,
+            0x46 => Self::Fstore3,
+            0x47 => Self::Dstore0,
+            0x48 => Self::Dstore1,
+            0x49 => Self::Dstore2,
+            0x4a => Self::Dstore3,
+            0x4b => Self::Astore0,
+            0x4c => Self::Astore1,
+            0x4d => Self::Astore2,
+            0x4e => Self::Astore3,
+            0x4f => Self::Iastore,
+            0x50 => Self::Lastore,
+            0x51 => Self::Fastore,
+            0x52 => Self::Dastore,
+            0x53 => Self::Aastore,
+            0x54 => Self::Bastore,
+            0x55 => Self::Castore,
+            0x56 => Self::Sastore,
+            0x57 => Self::Pop,
+            0x58 => Self::Pop2,
+            0x59 => Self::Dup,
+            0x5a => Self::DupX1,
+            0x5b => Self::DupX2,
+            0x5c => Self::Dup2,
+            0x5d => Self::Dup2X1,
+            0x5e => Self::Dup2X2,
+            0x5f => Self::Swap,
+            0x60 => Self::Iadd,
+            0x61 => Self::Ladd,
+            0x62 => Self::Fadd,
+            0x63 => Self::Dadd,
+            0x64 => Self::Isub,
+            0x65 => Self::Lsub,
+            0x66 => Self::Fsub,
+            0x67 => Self::Dsub,
+            0x68 => Self::Imul,
+            0x69 => Self::Lmul,
+            0x6a => Self::Fmul,
+            0x6b => Self::Dmul,
+            0x6c => Self::Idiv,
+            0x6d => Self::Ldiv,
+            0x6e => Self::Fdiv,
+            0x6f => Self::Ddiv,
+            0x70 => Self::Irem,
+            0x71 => Self::Lrem,
+            0x72 => Self::Frem,
+            0x73 => Self::Drem,
+            0x74 => Self::Ineg,
+            0x75 => Self::Lneg,
+            0x76 => Self::Fneg,
+            0x77 => Self::Dneg,
+            0x78 => Self::Ishl,
+            0x79 => Self::Lshl,
+            0x7a => Self::Ishr,
+            0x7b => Self::Lshr,
+            0x7c => Self::Iushr,
+            0x7d => Self::Lushr,
+            0x7e => Self::Iand,
+            0x7f => Self::Land,
+            0x80 => Self::Ior,
+            0x81 => Self::Lor,
+            0x82 => Self::Ixor,
+            0x83 => Self::Lxor,
+            0x84 => Self::Iinc(buf.read_u8()?, buf.read_u8()? as i8),
+            0x85 => Self::I2l,
+            0x86 => Self::I2f,
+            0x87 => Self::I2d,
+            0x88 => Self::L2i,
+            0x89 => Self::L2f,
+            0x8a => Self::L2d,
+            0x8b => Self::F2i,
+            0x8c => Self::F2l,
+            0x8d => Self::F2d,
+            0x8e => Self::D2i,
+            0x8f => Self::D2l,
+            0x90 => Self::D2f,
+            0x91 => Self::I2b,
+            0x92 => Self::I2c,
+            0x93 => Self::I2s,
+            0x94 => Self::Lcmp,
+            0x95 => Self::Fcmpl,
+            0x96 => Self::Fcmpg,
+            0x97 => Self::Dcmpl,
+            0x98 => Self::Dcmpg,
+            0x99 => Self::Ifeq(branch(buf.read_u16()? as i16 as i32)),
+            0x9a => Self::Ifne(branch(buf.read_u16()? as i16 as i32)),
+            0x9b => Self::Iflt(branch(buf.read_u16()? as i16 as i32)),
+            0x9c => Self::Ifge(branch(buf.read_u16()? as i16 as i32)),
+            0x9d => Self::Ifgt(branch(buf.read_u16()? as i16 as i32)),
+            0x9e => Self::Ifle(branch(buf.read_u16()? as i16 as i32)),
+            0x9f => Self::IfIcmpeq(branch(buf.read_u16()? as i16 as i32)),
+            0xa0 => Self::IfIcmpne(branch(buf.read_u16()? as i16 as i32)),
+            0xa1 => Self::IfIcmplt(branch(buf.read_u16()? as i16 as i32)),
+            0xa2 => Self::IfIcmpge(branch(buf.read_u16()? as i16 as i32)),
+            0xa3 => Self::IfIcmpgt(branch(buf.read_u16()? as i16 as i32)),
+            0xa4 => Self::IfIcmple(branch(buf.read_u16()? as i16 as i32)),
+            0xa5 => Self::IfAcmpeq(branch(buf.read_u16()? as i16 as i32)),
+            0xa6 => Self::IfAcmpne(branch(buf.read_u16()? as i16 as i32)),
+            0xa7 => Self::Goto(branch(buf.read_u16()? as i16 as i32)),
+            0xa8 => Self::Jsr(branch(buf.read_u16()? as i16 as i32)),
+            0xa9 => Self::Ret(buf.read_u8()?),
+            0xaa => Self::Tableswitch(TableSwitch::read(buf, pc)?),
+            0xab => Self::Lookupswitch(LookupSwitch::read(buf, pc)?),
+            0xac => Self::Ireturn,
+            0xad => Self::Lreturn,
+            0xae => Self::Freturn,
+            0xaf => Self::Dreturn,
+            0xb0 => Self::Areturn,
+            0xb1 => Self::Return,
+            0xb2 => Self::Getstatic(ConstItemIdx::read(buf, idx_map)?),
+            0xb3 => Self::Putstatic(ConstItemIdx::read(buf, idx_map)?),
+            0xb4 => Self::Getfield(ConstItemIdx::read(buf, idx_map)?),
+            0xb5 => Self::Putfield(ConstItemIdx::read(buf, idx_map)?),
+            0xb6 => Self::Invokevirtual(ConstItemIdx::read(buf, idx_map)?),
+            0xb7 => Self::Invokespecial(ConstItemIdx::read(buf, idx_map)?),
+            0xb8 => Self::Invokestatic(ConstItemIdx::read(buf, idx_map)?),
+            0xb9 => {
+                let method = ConstItemIdx::read(buf, idx_map)?;
+                let count = buf.read_u8()?;
+                buf.read_u8()?;
+                Self::Invokeinterface(method, count)
+            }
+            0xba => {
+                let call_site = ConstItemIdx::read(buf, idx_map)?;
+                buf.read_u16()?;
+                Self::Invokedynamic(call_site)
+            }
+            0xbb => Self::New(ConstItemIdx::read(buf, idx_map)?),
+            0xbc => {
+                let atype = buf.read_u8()?;
+                Self::Newarray(
+                    ArrayType::try_from(atype)
+                        .map_err(|_| ClassReaderError::InvalidArrayType(atype))?,
+                )
+            }
+            0xbd => Self::Anewarray(ConstItemIdx::read(buf, idx_map)?),
+            0xbe => Self::Arraylength,
+            0xbf => Self::Athrow,
+            0xc0 => Self::Checkcast(ConstItemIdx::read(buf, idx_map)?),
+            0xc1 => Self::Instanceof(ConstItemIdx::read(buf, idx_map)?),
+            0xc2 => Self::Monitorenter,
+            0xc3 => Self::Monitorexit,
+            0xc4 => Self::Wide(WideInstruction::read(buf)?),
+            0xc5 => Self::Multianewarray(ConstItemIdx::read(buf, idx_map)?, buf.read_u8()?),
+            0xc6 => Self::Ifnull(branch(buf.read_u16()? as i16 as i32)),
+            0xc7 => Self::Ifnonnull(branch(buf.read_u16()? as i16 as i32)),
+            0xc8 => Self::GotoW(branch(buf.read_i32()?)),
+            0xc9 => Self::JsrW(branch(buf.read_i32()?)),
+            opcode => return Err(ClassReaderError::InvalidOpcode(opcode)),
+        })
+    }
+
+    /// Encodes the instruction, located at `pc`
+    pub fn write(&self, buf: &mut BufferWriter, pc: u32, idx_map: &ConstIdxMap) -> Result<()> {
+        let out_of_range = || ClassReaderError::OperandOutOfRange(pc);
+        let constant = |idx: &ConstItemIdx| {
+            idx_map
+                .raw(*idx)
+                .ok_or_else(|| ClassReaderError::InvalidConstantPoolIdx(idx_map.raw_past_end(*idx)))
+        };
+        let branch16 = |label: &Label| {
+            i16::try_from(label.0.wrapping_sub(pc) as i32).map_err(|_| out_of_range())
+        };
+        buf.write_u8(self.opcode());
+        match self {
+            Self::Nop
+            | Self::AconstNull
+            | Self::IconstM1
+            | Self::Iconst0
+            | Self::Iconst1
+            | Self::Iconst2
+            | Self::Iconst3
+            | Self::Iconst4
+            | Self::Iconst5
+            | Self::Lconst0
+            | Self::Lconst1
+            | Self::Fconst0
+            | Self::Fconst1
+            | Self::Fconst2
+            | Self::Dconst0
+            | Self::Dconst1
+            | Self::Iload0
+            | Self::Iload1
+            | Self::Iload2
+            | Self::Iload3
+            | Self::Lload0
+            | Self::Lload1
+            | Self::Lload2
+            | Self::Lload3
+            | Self::Fload0
+            | Self::Fload1
+            | Self::Fload2
+            | Self::Fload3
+            | Self::Dload0
+            | Self::Dload1
+            | Self::Dload2
+            | Self::Dload3
+            | Self::Aload0
+            | Self::Aload1
+            | Self::Aload2
+            | Self::Aload3
+            | Self::Iaload
+            | Self::Laload
+            | Self::Faload
+            | Self::Daload
+            | Self::Aaload
+            | Self::Baload
+            | Self::Caload
+            | Self::Saload
+            | Self::Istore0
+            | Self::Istore1
+            | Self::Istore2
+            | Self::Istore3
+            | Self::Lstore0
+            | Self::Lstore1
+            | Self::Lstore2
+            | Self::Lstore3
+            | Self::Fstore0
+            | Self::Fstore1
+            | Self::Fstore2
+            | Self::Fstore3
+            | Self::Dstore0
+            | Self::Dstore1
+            | Self::Dstore2
+            | Self::Dstore3
+            | Self::Astore0
+            | Self::Astore1
+            | Self::Astore2
+            | Self::Astore3
+            | Self::Iastore
+            | Self::Lastore
+            | Self::Fastore
+            | Self::Dastore
+            | Self::Aastore
+            | Self::Bastore
+            | Self::Castore
+            | Self::Sastore
+            | Self::Pop
+            | Self::Pop2
+            | Self::Dup
+            | Self::DupX1
+            | Self::DupX2
+            | Self::Dup2
+            | Self::Dup2X1
+            | Self::Dup2X2
+            | Self::Swap
+            | Self::Iadd
+            | Self::Ladd
+            | Self::Fadd
+            | Self::Dadd
+            | Self::Isub
+            | Self::Lsub
+            | Self::Fsub
+            | Self::Dsub
+            | Self::Imul
+            | Self::Lmul
+            | Self::Fmul
+            | Self::Dmul
+            | Self::Idiv
+            | Self::Ldiv
+            | Self::Fdiv
+            | Self::Ddiv
+            | Self::Irem
+            | Self::Lrem
+            | Self::Frem
+            | Self::Drem
+            | Self::Ineg
+            | Self::Lneg
+            | Self::Fneg
+            | Self::Dneg
+            | Self::Ishl
+            | Self::Lshl
+            | Self::Ishr
+            | Self::Lshr
+            | Self::Iushr
+            | Self::Lushr
+            | Self::Iand
+            | Self::Land
+            | Self::Ior
+            | Self::Lor
+            | Self::Ixor
+            | Self::Lxor
+            | Self::I2l
+            | Self::I2f
+            | Self::I2d
+            | Self::L2i
+            | Self::L2f
+            | Self::L2d
+            | Self::F2i
+            | Self::F2l
+            | Self::F2d
+            | Self::D2i
+            | Self::D2l
+            | Self::D2f
+            | Self::I2b
+            | Self::I2c
+            | Self::I2s
+            | Self::Lcmp
+            | Self::Fcmpl
+            | Self::Fcmpg
+            | Self::Dcmpl
+            | Self::Dcmpg
+            | Self::Ireturn
+            | Self::Lreturn
+            | Self::Freturn
+            | Self::Dreturn
+            | Self::Areturn
+            | Self::Return
+            | Self::Arraylength
+            | Self::Athrow
+            | Self::Monitorenter
+            | Self::Monitorexit => {}
+            Self::Bipush(value) => buf.write_u8(*value as u8),
+            Self::Sipush(value) => buf.write_u16(*value as u16),
+            Self::Ldc(idx) => {
+                let idx = u8::try_from(constant(idx)?).map_err(|_| out_of_range())?;
+                buf.write_u8(idx);
+            }
+            Self::LdcW(idx)
+            | Self::Ldc2W(idx)
+            | Self::Getstatic(idx)
+            | Self::Putstatic(idx)
+            | Self::Getfield(idx)
+            | Self::Putfield(idx)
+            | Self::Invokevirtual(idx)
+            | Self::Invokespecial(idx)
+            | Self::Invokestatic(idx)
+            | Self::New(idx)
+            | Self::Anewarray(idx)
+            | Self::Checkcast(idx)
+            | Self::Instanceof(idx) => buf.write_u16(constant(idx)?),
+            Self::Iload(local)
+            | Self::Lload(local)
+            | Self::Fload(local)
+            | Self::Dload(local)
+            | Self::Aload(local)
+            | Self::Istore(local)
+            | Self::Lstore(local)
+            | Self::Fstore(local)
+            | Self::Dstore(local)
+            | Self::Astore(local)
+            | Self::Ret(local) => buf.write_u8(*local),
+            Self::Iinc(local, value) => {
+                buf.write_u8(*local);
+                buf.write_u8(*value as u8);
+            }
+            Self::Ifeq(label)
+            | Self::Ifne(label)
+            | Self::Iflt(label)
+            | Self::Ifge(label)
+            | Self::Ifgt(label)
+            | Self::Ifle(label)
+            | Self::IfIcmpeq(label)
+            | Self::IfIcmpne(label)
+            | Self::IfIcmplt(label)
+            | Self::IfIcmpge(label)
+            | Self::IfIcmpgt(label)
+            | Self::IfIcmple(label)
+            | Self::IfAcmpeq(label)
+            | Self::IfAcmpne(label)
+            | Self::Goto(label)
+            | Self::Jsr(label)
+            | Self::Ifnull(label)
+            | Self::Ifnonnull(label) => buf.write_u16(branch16(label)? as u16),
+            Self::GotoW(label) | Self::JsrW(label) => {
+                buf.write_i32(label.0.wrapping_sub(pc) as i32)
+            }
+            Self::Tableswitch(switch) => switch.write(buf, pc)?,
+            Self::Lookupswitch(switch) => switch.write(buf, pc),
+            Self::Invokeinterface(idx, count) => {
+                buf.write_u16(constant(idx)?);
+                buf.write_u8(*count);
+                buf.write_u8(0);
+            }
+            Self::Invokedynamic(idx) => {
+                buf.write_u16(constant(idx)?);
+                buf.write_u16(0);
+            }
+            Self::Newarray(atype) => buf.write_u8(*atype as u8),
+            Self::Wide(instruction) => instruction.write(buf),
+            Self::Multianewarray(idx, dimensions) => {
+                buf.write_u16(constant(idx)?);
+                buf.write_u8(*dimensions);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Self::Nop => 0x00,
+            Self::AconstNull => 0x01,
+            Self::IconstM1 => 0x02,
+            Self::Iconst0 => 0x03,
+            Self::Iconst1 => 0x04,
+            Self::Iconst2 => 0x05,
+            Self::Iconst3 => 0x06,
+            Self::Iconst4 => 0x07,
+            Self::Iconst5 => 0x08,
+            Self::Lconst0 => 0x09,
+            Self::Lconst1 => 0x0a,
+            Self::Fconst0 => 0x0b,
+            Self::Fconst1 => 0x0c,
+            Self::Fconst2 => 0x0d,
+            Self::Dconst0 => 0x0e,
+            Self::Dconst1 => 0x0f,
+            Self::Bipush(..) => 0x10,
+            Self::Sipush(..) => 0x11,
+            Self::Ldc(..) => 0x12,
+            Self::LdcW(..) => 0x13,
+            Self::Ldc2W(..) => 0x14,
+            Self::Iload(..) => 0x15,
+            Self::Lload(..) => 0x16,
+            Self::Fload(..) => 0x17,
+            Self::Dload(..) => 0x18,
+            Self::Aload(..) => 0x19,
+            Self::Iload0 => 0x1a,
+            Self::Iload1 => 0x1b,
+            Self::Iload2 => 0x1c,
+            Self::Iload3 => 0x1d,
+            Self::Lload0 => 0x1e,
+            Self::Lload1 => 0x1f,
+            Self::Lload2 => 0x20,
+            Self::Lload3 => 0x21,
+            Self::Fload0 => 0x22,
+            Self::Fload1 => 0x23,
+            Self::Fload2 => 0x24,
+            Self::Fload3 => 0x25,
+            Self::Dload0 => 0x26,
+            Self::Dload1 => 0x27,
+            Self::Dload2 => 0x28,
+            Self::Dload3 => 0x29,
+            Self::Aload0 => 0x2a,
+            Self::Aload1 => 0x2b,
+            Self::Aload2 => 0x2c,
+            Self::Aload3 => 0x2d,
+            Self::Iaload => 0x2e,
+            Self::Laload => 0x2f,
+            Self::Faload => 0x30,
+            Self::Daload => 0x31,
+            Self::Aaload => 0x32,
+            Self::Baload => 0x33,
+            Self::Caload => 0x34,
+            Self::Saload => 0x35,
+            Self::Istore(..) => 0x36,
+            Self::Lstore(..) => 0x37,
+            Self::Fstore(..) => 0x38,
+            Self::Dstore(..) => 0x39,
+            Self::Astore(..) => 0x3a,
+            Self::Istore0 => 0x3b,
+            Self::Istore1 => 0x3c,
+            Self::Istore2 => 0x3d,
+            Self::Istore3 => 0x3e,
+            Self::Lstore0 => 0x3f,
+            Self::Lstore1 => 0x40,
+            Self::Lstore2 => 0x41,
+            Self::Lstore3 => 0x42,
+            Self::Fstore0 => 0x43,
+            Self::Fstore1 => 0x44,
+            Self::Fstore2 => 0x45,
+            Self::Fstore3 => 0x46,
+            Self::Dstore0 => 0x47,
+            Self::Dstore1 => 0x48,
+            Self::Dstore2 => 0x49,
+            Self::Dstore3 => 0x4a,
+            Self::Astore0 => 0x4b,
+            Self::Astore1 => 0x4c,
+            Self::Astore2 => 0x4d,
+            Self::Astore3 => 0x4e,
+            Self::Iastore => 0x4f,
+            Self::Lastore => 0x50,
+            Self::Fastore => 0x51,
+            Self::Dastore => 0x52,
+            Self::Aastore => 0x53,
+            Self::Bastore => 0x54,
+            Self::Castore => 0x55,
+            Self::Sastore => 0x56,
+            Self::Pop => 0x57,
+            Self::Pop2 => 0x58,
+            Self::Dup => 0x59,
+            Self::DupX1 => 0x5a,
+            Self::DupX2 => 0x5b,
+            Self::Dup2 => 0x5c,
+            Self::Dup2X1 => 0x5d,
+            Self::Dup2X2 => 0x5e,
+            Self::Swap => 0x5f,
+            Self::Iadd => 0x60,
+            Self::Ladd => 0x61,
+            Self::Fadd => 0x62,
+            Self::Dadd => 0x63,
+            Self::Isub => 0x64,
+            Self::Lsub => 0x65,
+            Self::Fsub => 0x66,
+            Self::Dsub => 0x67,
+            Self::Imul => 0x68,
+            Self::Lmul => 0x69,
+            Self::Fmul => 0x6a,
+            Self::Dmul => 0x6b,
+            Self::Idiv => 0x6c,
+            Self::Ldiv => 0x6d,
+            Self::Fdiv => 0x6e,
+            Self::Ddiv => 0x6f,
+            Self::Irem => 0x70,
+            Self::Lrem => 0x71,
+            Self::Frem => 0x72,
+            Self::Drem => 0x73,
+            Self::Ineg => 0x74,
+            Self::Lneg => 0x75,
+            Self::Fneg => 0x76,
+            Self::Dneg => 0x77,
+            Self::Ishl => 0x78,
+            Self::Lshl => 0x79,
+            Self::Ishr => 0x7a,
+            Self::Lshr => 0x7b,
+            Self::Iushr => 0x7c,
+            Self::Lushr => 0x7d,
+            Self::Iand => 0x7e,
+            Self::Land => 0x7f,
+            Self::Ior => 0x80,
+            Self::Lor => 0x81,
+            Self::Ixor => 0x82,
+            Self::Lxor => 0x83,
+            Self::Iinc(..) => 0x84,
+            Self::I2l => 0x85,
+            Self::I2f => 0x86,
+            Self::I2d => 0x87,
+            Self::L2i => 0x88,
+            Self::L2f => 0x89,
+            Self::L2d => 0x8a,
+            Self::F2i => 0x8b,
+            Self::F2l => 0x8c,
+            Self::F2d => 0x8d,
+            Self::D2i => 0x8e,
+            Self::D2l => 0x8f,
+            Self::D2f => 0x90,
+            Self::I2b => 0x91,
+            Self::I2c => 0x92,
+            Self::I2s => 0x93,
+            Self::Lcmp => 0x94,
+            Self::Fcmpl => 0x95,
+            Self::Fcmpg => 0x96,
+            Self::Dcmpl => 0x97,
+            Self::Dcmpg => 0x98,
+            Self::Ifeq(..) => 0x99,
+            Self::Ifne(..) => 0x9a,
+            Self::Iflt(..) => 0x9b,
+            Self::Ifge(..) => 0x9c,
+            Self::Ifgt(..) => 0x9d,
+            Self::Ifle(..) => 0x9e,
+            Self::IfIcmpeq(..) => 0x9f,
+            Self::IfIcmpne(..) => 0xa0,
+            Self::IfIcmplt(..) => 0xa1,
+            Self::IfIcmpge(..) => 0xa2,
+            Self::IfIcmpgt(..) => 0xa3,
+            Self::IfIcmple(..) => 0xa4,
+            Self::IfAcmpeq(..) => 0xa5,
+            Self::IfAcmpne(..) => 0xa6,
+            Self::Goto(..) => 0xa7,
+            Self::Jsr(..) => 0xa8,
+            Self::Ret(..) => 0xa9,
+            Self::Tableswitch(..) => 0xaa,
+            Self::Lookupswitch(..) => 0xab,
+            Self::Ireturn => 0xac,
+            Self::Lreturn => 0xad,
+            Self::Freturn => 0xae,
+            Self::Dreturn => 0xaf,
+            Self::Areturn => 0xb0,
+            Self::Return => 0xb1,
+            Self::Getstatic(..) => 0xb2,
+            Self::Putstatic(..) => 0xb3,
+            Self::Getfield(..) => 0xb4,
+            Self::Putfield(..) => 0xb5,
+            Self::Invokevirtual(..) => 0xb6,
+            Self::Invokespecial(..) => 0xb7,
+            Self::Invokestatic(..) => 0xb8,
+            Self::Invokeinterface(..) => 0xb9,
+            Self::Invokedynamic(..) => 0xba,
+            Self::New(..) => 0xbb,
+            Self::Newarray(..) => 0xbc,
+            Self::Anewarray(..) => 0xbd,
+            Self::Arraylength => 0xbe,
+            Self::Athrow => 0xbf,
+            Self::Checkcast(..) => 0xc0,
+            Self::Instanceof(..) => 0xc1,
+            Self::Monitorenter => 0xc2,
+            Self::Monitorexit => 0xc3,
+            Self::Wide(..) => 0xc4,
+            Self::Multianewarray(..) => 0xc5,
+            Self::Ifnull(..) => 0xc6,
+            Self::Ifnonnull(..) => 0xc7,
+            Self::GotoW(..) => 0xc8,
+            Self::JsrW(..) => 0xc9,
+        }
+    }
+
+    /// Name of the instruction, as written in the JVMS
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Nop => "nop",
+            Self::AconstNull => "aconst_null",
+            Self::IconstM1 => "iconst_m1",
+            Self::Iconst0 => "iconst_0",
+            Self::Iconst1 => "iconst_1",
+            Self::Iconst2 => "iconst_2",
+            Self::Iconst3 => "iconst_3",
+            Self::Iconst4 => "iconst_4",
+            Self::Iconst5 => "iconst_5",
+            Self::Lconst0 => "lconst_0",
+            Self::Lconst1 => "lconst_1",
+            Self::Fconst0 => "fconst_0",
+            Self::Fconst1 => "fconst_1",
+            Self::Fconst2 => "fconst_2",
+            Self::Dconst0 => "dconst_0",
+            Self::Dconst1 => "dconst_1",
+            Self::Bipush(..) => "bipush",
+            Self::Sipush(..) => "sipush",
+            Self::Ldc(..) => "ldc",
+            Self::LdcW(..) => "ldc_w",
+            Self::Ldc2W(..) => "ldc2_w",
+            Self::Iload(..) => "iload",
+            Self::Lload(..) => "lload",
+            Self::Fload(..) => "fload",
+            Self::Dload(..) => "dload",
+            Self::Aload(..) => "aload",
+            Self::Iload0 => "iload_0",
+            Self::Iload1 => "iload_1",
+            Self::Iload2 => "iload_2",
+            Self::Iload3 => "iload_3",
+            Self::Lload0 => "lload_0",
+            Self::Lload1 => "lload_1",
+            Self::Lload2 => "lload_2",
+            Self::Lload3 => "lload_3",
+            Self::Fload0 => "fload_0",
+            Self::Fload1 => "fload_1",
+            Self::Fload2 => "fload_2",
+            Self::Fload3 => "fload_3",
+            Self::Dload0 => "dload_0",
+            Self::Dload1 => "dload_1",
+            Self::Dload2 => "dload_2",
+            Self::Dload3 => "dload_3",
+            Self::Aload0 => "aload_0",
+            Self::Aload1 => "aload_1",
+            Self::Aload2 => "aload_2",
+            Self::Aload3 => "aload_3",
+            Self::Iaload => "iaload",
+            Self::Laload => "laload",
+            Self::Faload => "faload",
+            Self::Daload => "daload",
+            Self::Aaload => "aaload",
+            Self::Baload => "baload",
+            Self::Caload => "caload",
+            Self::Saload => "saload",
+            Self::Istore(..) => "istore",
+            Self::Lstore(..) => "lstore",
+            Self::Fstore(..) => "fstore",
+            Self::Dstore(..) => "dstore",
+            Self::Astore(..) => "astore",
+            Self::Istore0 => "istore_0",
+            Self::Istore1 => "istore_1",
+            Self::Istore2 => "istore_2",
+            Self::Istore3 => "istore_3",
+            Self::Lstore0 => "lstore_0",
+            Self::Lstore1 => "lstore_1",
+            Self::Lstore2 => "lstore_2",
+            Self::Lstore3 => "lstore_3",
+            Self::Fstore0 => "fstore_0",
+            Self::Fstore1 => "fstore_1",
+            Self::Fstore2 => "fstore_2",
+            Self::Fstore3 => "fstore_3",
+            Self::Dstore0 => "dstore_0",
+            Self::Dstore1 => "dstore_1",
+            Self::Dstore2 => "dstore_2",
+            Self::Dstore3 => "dstore_3",
+            Self::Astore0 => "astore_0",
+            Self::Astore1 => "astore_1",
+            Self::Astore2 => "astore_2",
+            Self::Astore3 => "astore_3",
+            Self::Iastore => "iastore",
+            Self::Lastore => "lastore",
+            Self::Fastore => "fastore",
+            Self::Dastore => "dastore",
+            Self::Aastore => "aastore",
+            Self::Bastore => "bastore",
+            Self::Castore => "castore",
+            Self::Sastore => "sastore",
+            Self::Pop => "pop",
+            Self::Pop2 => "pop2",
+            Self::Dup => "dup",
+            Self::DupX1 => "dup_x1",
+            Self::DupX2 => "dup_x2",
+            Self::Dup2 => "dup2",
+            Self::Dup2X1 => "dup2_x1",
+            Self::Dup2X2 => "dup2_x2",
+            Self::Swap => "swap",
+            Self::Iadd => "iadd",
+            Self::Ladd => "ladd",
+            Self::Fadd => "fadd",
+            Self::Dadd => "dadd",
+            Self::Isub => "isub",
+            Self::Lsub => "lsub",
+            Self::Fsub => "fsub",
+            Self::Dsub => "dsub",
+            Self::Imul => "imul",
+            Self::Lmul => "lmul",
+            Self::Fmul => "fmul",
+            Self::Dmul => "dmul",
+            Self::Idiv => "idiv",
+            Self::Ldiv => "ldiv",
+            Self::Fdiv => "fdiv",
+            Self::Ddiv => "ddiv",
+            Self::Irem => "irem",
+            Self::Lrem => "lrem",
+            Self::Frem => "frem",
+            Self::Drem => "drem",
+            Self::Ineg => "ineg",
+            Self::Lneg => "lneg",
+            Self::Fneg => "fneg",
+            Self::Dneg => "dneg",
+            Self::Ishl => "ishl",
+            Self::Lshl => "lshl",
+            Self::Ishr => "ishr",
+            Self::Lshr => "lshr",
+            Self::Iushr => "iushr",
+            Self::Lushr => "lushr",
+            Self::Iand => "iand",
+            Self::Land => "land",
+            Self::Ior => "ior",
+            Self::Lor => "lor",
+            Self::Ixor => "ixor",
+            Self::Lxor => "lxor",
+            Self::Iinc(..) => "iinc",
+            Self::I2l => "i2l",
+            Self::I2f => "i2f",
+            Self::I2d => "i2d",
+            Self::L2i => "l2i",
+            Self::L2f => "l2f",
+            Self::L2d => "l2d",
+            Self::F2i => "f2i",
+            Self::F2l => "f2l",
+            Self::F2d => "f2d",
+            Self::D2i => "d2i",
+            Self::D2l => "d2l",
+            Self::D2f => "d2f",
+            Self::I2b => "i2b",
+            Self::I2c => "i2c",
+            Self::I2s => "i2s",
+            Self::Lcmp => "lcmp",
+            Self::Fcmpl => "fcmpl",
+            Self::Fcmpg => "fcmpg",
+            Self::Dcmpl => "dcmpl",
+            Self::Dcmpg => "dcmpg",
+            Self::Ifeq(..) => "ifeq",
+            Self::Ifne(..) => "ifne",
+            Self::Iflt(..) => "iflt",
+            Self::Ifge(..) => "ifge",
+            Self::Ifgt(..) => "ifgt",
+            Self::Ifle(..) => "ifle",
+            Self::IfIcmpeq(..) => "if_icmpeq",
+            Self::IfIcmpne(..) => "if_icmpne",
+            Self::IfIcmplt(..) => "if_icmplt",
+            Self::IfIcmpge(..) => "if_icmpge",
+            Self::IfIcmpgt(..) => "if_icmpgt",
+            Self::IfIcmple(..) => "if_icmple",
+            Self::IfAcmpeq(..) => "if_acmpeq",
+            Self::IfAcmpne(..) => "if_acmpne",
+            Self::Goto(..) => "goto",
+            Self::Jsr(..) => "jsr",
+            Self::Ret(..) => "ret",
+            Self::Tableswitch(..) => "tableswitch",
+            Self::Lookupswitch(..) => "lookupswitch",
+            Self::Ireturn => "ireturn",
+            Self::Lreturn => "lreturn",
+            Self::Freturn => "freturn",
+            Self::Dreturn => "dreturn",
+            Self::Areturn => "areturn",
+            Self::Return => "return",
+            Self::Getstatic(..) => "getstatic",
+            Self::Putstatic(..) => "putstatic",
+            Self::Getfield(..) => "getfield",
+            Self::Putfield(..) => "putfield",
+            Self::Invokevirtual(..) => "invokevirtual",
+            Self::Invokespecial(..) => "invokespecial",
+            Self::Invokestatic(..) => "invokestatic",
+            Self::Invokeinterface(..) => "invokeinterface",
+            Self::Invokedynamic(..) => "invokedynamic",
+            Self::New(..) => "new",
+            Self::Newarray(..) => "newarray",
+            Self::Anewarray(..) => "anewarray",
+            Self::Arraylength => "arraylength",
+            Self::Athrow => "athrow",
+            Self::Checkcast(..) => "checkcast",
+            Self::Instanceof(..) => "instanceof",
+            Self::Monitorenter => "monitorenter",
+            Self::Monitorexit => "monitorexit",
+            Self::Wide(..) => "wide",
+            Self::Multianewarray(..) => "multianewarray",
+            Self::Ifnull(..) => "ifnull",
+            Self::Ifnonnull(..) => "ifnonnull",
+            Self::GotoW(..) => "goto_w",
+            Self::JsrW(..) => "jsr_w",
+        }
+    }
+
+    /// Size of the encoded instruction, located at `pc`
+    pub fn size(&self, pc: u32) -> u32 {
+        match self {
+            Self::Nop
+            | Self::AconstNull
+            | Self::IconstM1
+            | Self::Iconst0
+            | Self::Iconst1
+            | Self::Iconst2
+            | Self::Iconst3
+            | Self::Iconst4
+            | Self::Iconst5
+            | Self::Lconst0
+            | Self::Lconst1
+            | Self::Fconst0
+            | Self::Fconst1
+            | Self::Fconst2
+            | Self::Dconst0
+            | Self::Dconst1
+            | Self::Iload0
+            | Self::Iload1
+            | Self::Iload2
+            | Self::Iload3
+            | Self::Lload0
+            | Self::Lload1
+            | Self::Lload2
+            | Self::Lload3
+            | Self::Fload0
+            | Self::Fload1
+            | Self::Fload2
+            | Self::Fload3
+            | Self::Dload0
+            | Self::Dload1
+            | Self::Dload2
+            | Self::Dload3
+            | Self::Aload0
+            | Self::Aload1
+            | Self::Aload2
+            | Self::Aload3
+            | Self::Iaload
+            | Self::Laload
+            | Self::Faload
+            | Self::Daload
+            | Self::Aaload
+            | Self::Baload
+            | Self::Caload
+            | Self::Saload
+            | Self::Istore0
+            | Self::Istore1
+            | Self::Istore2
+            | Self::Istore3
+            | Self::Lstore0
+            | Self::Lstore1
+            | Self::Lstore2
+            | Self::Lstore3
+            | Self::Fstore0
+            | Self::Fstore1
+            | Self::Fstore2
+            | Self::Fstore3
+            | Self::Dstore0
+            | Self::Dstore1
+            | Self::Dstore2
+            | Self::Dstore3
+            | Self::Astore0
+            | Self::Astore1
+            | Self::Astore2
+            | Self::Astore3
+            | Self::Iastore
+            | Self::Lastore
+            | Self::Fastore
+            | Self::Dastore
+            | Self::Aastore
+            | Self::Bastore
+            | Self::Castore
+            | Self::Sastore
+            | Self::Pop
+            | Self::Pop2
+            | Self::Dup
+            | Self::DupX1
+            | Self::DupX2
+            | Self::Dup2
+            | Self::Dup2X1
+            | Self::Dup2X2
+            | Self::Swap
+            | Self::Iadd
+            | Self::Ladd
+            | Self::Fadd
+            | Self::Dadd
+            | Self::Isub
+            | Self::Lsub
+            | Self::Fsub
+            | Self::Dsub
+            | Self::Imul
+            | Self::Lmul
+            | Self::Fmul
+            | Self::Dmul
+            | Self::Idiv
+            | Self::Ldiv
+            | Self::Fdiv
+            | Self::Ddiv
+            | Self::Irem
+            | Self::Lrem
+            | Self::Frem
+            | Self::Drem
+            | Self::Ineg
+            | Self::Lneg
+            | Self::Fneg
+            | Self::Dneg
+            | Self::Ishl
+            | Self::Lshl
+            | Self::Ishr
+            | Self::Lshr
+            | Self::Iushr
+            | Self::Lushr
+            | Self::Iand
+            | Self::Land
+            | Self::Ior
+            | Self::Lor
+            | Self::Ixor
+            | Self::Lxor
+            | Self::I2l
+            | Self::I2f
+            | Self::I2d
+            | Self::L2i
+            | Self::L2f
+            | Self::L2d
+            | Self::F2i
+            | Self::F2l
+            | Self::F2d
+            | Self::D2i
+            | Self::D2l
+            | Self::D2f
+            | Self::I2b
+            | Self::I2c
+            | Self::I2s
+            | Self::Lcmp
+            | Self::Fcmpl
+            | Self::Fcmpg
+            | Self::Dcmpl
+            | Self::Dcmpg
+            | Self::Ireturn
+            | Self::Lreturn
+            | Self::Freturn
+            | Self::Dreturn
+            | Self::Areturn
+            | Self::Return
+            | Self::Arraylength
+            | Self::Athrow
+            | Self::Monitorenter
+            | Self::Monitorexit => 1,
+            Self::Bipush(..)
+            | Self::Ldc(..)
+            | Self::Iload(..)
+            | Self::Lload(..)
+            | Self::Fload(..)
+            | Self::Dload(..)
+            | Self::Aload(..)
+            | Self::Istore(..)
+            | Self::Lstore(..)
+            | Self::Fstore(..)
+            | Self::Dstore(..)
+            | Self::Astore(..)
+            | Self::Ret(..)
+            | Self::Newarray(..) => 2,
+            Self::Sipush(..)
+            | Self::LdcW(..)
+            | Self::Ldc2W(..)
+            | Self::Iinc(..)
+            | Self::Ifeq(..)
+            | Self::Ifne(..)
+            | Self::Iflt(..)
+            | Self::Ifge(..)
+            | Self::Ifgt(..)
+            | Self::Ifle(..)
+            | Self::IfIcmpeq(..)
+            | Self::IfIcmpne(..)
+            | Self::IfIcmplt(..)
+            | Self::IfIcmpge(..)
+            | Self::IfIcmpgt(..)
+            | Self::IfIcmple(..)
+            | Self::IfAcmpeq(..)
+            | Self::IfAcmpne(..)
+            | Self::Goto(..)
+            | Self::Jsr(..)
+            | Self::Getstatic(..)
+            | Self::Putstatic(..)
+            | Self::Getfield(..)
+            | Self::Putfield(..)
+            | Self::Invokevirtual(..)
+            | Self::Invokespecial(..)
+            | Self::Invokestatic(..)
+            | Self::New(..)
+            | Self::Anewarray(..)
+            | Self::Checkcast(..)
+            | Self::Instanceof(..)
+            | Self::Ifnull(..)
+            | Self::Ifnonnull(..) => 3,
+            Self::Multianewarray(..) => 4,
+            Self::Invokeinterface(..)
+            | Self::Invokedynamic(..)
+            | Self::GotoW(..)
+            | Self::JsrW(..) => 5,
+            Self::Tableswitch(switch) => {
+                1 + switch_padding(pc) + 12 + 4 * switch.targets.len() as u32
+            }
+            Self::Lookupswitch(switch) => {
+                1 + switch_padding(pc) + 8 + 8 * switch.pairs.len() as u32
+            }
+            Self::Wide(WideInstruction::Iinc(..)) => 6,
+            Self::Wide(_) => 4,
+        }
+    }
+}
+
+impl Instruction {
+    /// Whether this is a switch whose padding, as written at `pc`, holds non-zero bytes
+    pub(crate) fn has_nonzero_padding(&self, pc: u32) -> bool {
+        let padding = match self {
+            Self::Tableswitch(switch) => &switch.padding,
+            Self::Lookupswitch(switch) => &switch.padding,
+            _ => return false,
+        };
+        padding[..switch_padding(pc) as usize]
+            .iter()
+            .any(|&byte| byte != 0)
+    }
+
+    /// Labels this instruction may jump to, besides the next instruction
+    pub fn targets(&self) -> Vec<Label> {
+        match self {
+            Self::Ifeq(label)
+            | Self::Ifne(label)
+            | Self::Iflt(label)
+            | Self::Ifge(label)
+            | Self::Ifgt(label)
+            | Self::Ifle(label)
+            | Self::IfIcmpeq(label)
+            | Self::IfIcmpne(label)
+            | Self::IfIcmplt(label)
+            | Self::IfIcmpge(label)
+            | Self::IfIcmpgt(label)
+            | Self::IfIcmple(label)
+            | Self::IfAcmpeq(label)
+            | Self::IfAcmpne(label)
+            | Self::Goto(label)
+            | Self::Jsr(label)
+            | Self::Ifnull(label)
+            | Self::Ifnonnull(label)
+            | Self::GotoW(label)
+            | Self::JsrW(label) => vec![*label],
+            Self::Tableswitch(switch) => std::iter::once(switch.default)
+                .chain(switch.targets.iter().copied())
+                .collect(),
+            Self::Lookupswitch(switch) => std::iter::once(switch.default)
+                .chain(switch.pairs.iter().map(|(_, label)| *label))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Replaces every label of this instruction
+    pub fn map_labels(&mut self, mut f: impl FnMut(Label) -> Label) {
+        match self {
+            Self::Ifeq(label)
+            | Self::Ifne(label)
+            | Self::Iflt(label)
+            | Self::Ifge(label)
+            | Self::Ifgt(label)
+            | Self::Ifle(label)
+            | Self::IfIcmpeq(label)
+            | Self::IfIcmpne(label)
+            | Self::IfIcmplt(label)
+            | Self::IfIcmpge(label)
+            | Self::IfIcmpgt(label)
+            | Self::IfIcmple(label)
+            | Self::IfAcmpeq(label)
+            | Self::IfAcmpne(label)
+            | Self::Goto(label)
+            | Self::Jsr(label)
+            | Self::Ifnull(label)
+            | Self::Ifnonnull(label)
+            | Self::GotoW(label)
+            | Self::JsrW(label) => *label = f(*label),
+            Self::Tableswitch(switch) => {
+                switch.default = f(switch.default);
+                for target in &mut switch.targets {
+                    *target = f(*target);
+                }
+            }
+            Self::Lookupswitch(switch) => {
+                switch.default = f(switch.default);
+                for (_, target) in &mut switch.pairs {
+                    *target = f(*target);
+                }
+            }
+            _ => {}
+        }
+    }
+}
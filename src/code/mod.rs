@@ -0,0 +1,253 @@
+mod debug;
+pub use debug::*;
+mod instruction;
+pub use instruction::*;
+mod sizes;
+
+use crate::{
+    Read, Result, Write,
+    attribute::Attribute,
+    buffer::{Buffer, BufferWriter},
+    constants::{ConstIdxMap, ConstItemIdx},
+    error::ClassReaderError,
+};
+
+/// A position in the code of a method.
+///
+/// In decoded code, a label is the pc of the instruction it points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub u32);
+
+/// An entry of the exception table of a [Code] attribute
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExceptionHandler {
+    /// First instruction covered by the handler
+    pub start: Label,
+    /// End of the covered range, exclusive
+    pub end: Label,
+    pub handler: Label,
+    /// Class of the caught exceptions, or `None` to catch everything
+    pub catch_type: Option<ConstItemIdx>,
+}
+
+impl Read for ExceptionHandler {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let start = Label(buf.read_u16()? as u32);
+        let end = Label(buf.read_u16()? as u32);
+        let handler = Label(buf.read_u16()? as u32);
+        let catch_type = Option::<ConstItemIdx>::read(buf, idx_map)?;
+
+        Ok(Self {
+            start,
+            end,
+            handler,
+            catch_type,
+        })
+    }
+}
+
+/// Labels must fit in a u16, see [Code::write]
+impl Write for ExceptionHandler {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u16(self.start.0 as u16);
+        buf.write_u16(self.end.0 as u16);
+        buf.write_u16(self.handler.0 as u16);
+        self.catch_type.write(buf, idx_map);
+    }
+}
+
+/// The `Code` attribute of a method (JVMS 4.7.3), with its instructions decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Code {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    /// Instructions, along with their pc
+    pub instructions: Vec<(u32, Instruction)>,
+    pub exception_table: Vec<ExceptionHandler>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Code {
+    /// Decodes the `info` of a `Code` attribute.
+    ///
+    /// The offsets of errors are relative to the start of `info`.
+    pub fn read(info: &[u8], idx_map: &ConstIdxMap) -> Result<Self> {
+        let mut buf = Buffer::new(info);
+        let result = Self::read_info(&mut buf, idx_map);
+        result.map_err(|error| error.at(buf.get_pos()))
+    }
+
+    fn read_info(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let max_stack = buf.read_u16()?;
+        let max_locals = buf.read_u16()?;
+        let code_length = buf.read_u32()?;
+        let code_start = buf.get_pos();
+        let code = buf.read_bytes(code_length as usize)?;
+        let instructions = Self::decode(code, idx_map)
+            .map_err(|error| error.relative_to(Some(code_start)).within("code"))?;
+        let exception_table =
+            Vec::read(buf, idx_map).map_err(|error| error.within("exception_table"))?;
+        let attributes = Vec::read(buf, idx_map).map_err(|error| error.within("attributes"))?;
+
+        Ok(Self {
+            max_stack,
+            max_locals,
+            instructions,
+            exception_table,
+            attributes,
+        })
+    }
+
+    /// Decodes the bytecode, errors being located at the pc of the faulty instruction
+    fn decode(code: &[u8], idx_map: &ConstIdxMap) -> Result<Vec<(u32, Instruction)>> {
+        let mut buf = Buffer::new(code);
+        let mut instructions = Vec::new();
+        while buf.has_more_data() {
+            let pc = buf.get_pos() as u32;
+            let instruction = Instruction::read(&mut buf, pc, idx_map);
+            instructions.push((pc, instruction.map_err(|error| error.at(pc as usize))?));
+        }
+        Ok(instructions)
+    }
+
+    /// Encodes the `info` of a `Code` attribute, failing if the code or the labels of its
+    /// exception table go beyond 65535 bytes
+    pub fn write(&self, idx_map: &ConstIdxMap) -> Result<Vec<u8>> {
+        let mut code = BufferWriter::new();
+        for (pc, instruction) in &self.instructions {
+            if code.get_pos() as u32 != *pc {
+                return Err(ClassReaderError::InvalidInstructionOffset(*pc));
+            }
+            instruction.write(&mut code, *pc, idx_map)?;
+        }
+        let code = code.into_bytes();
+        let labels = self
+            .exception_table
+            .iter()
+            .flat_map(|handler| [handler.start, handler.end, handler.handler]);
+        if let Some(pc) = std::iter::once(code.len() as u32)
+            .chain(labels.map(|label| label.0))
+            .find(|&pc| pc > u16::MAX as u32)
+        {
+            return Err(ClassReaderError::CodeTooLarge(pc));
+        }
+
+        let mut buf = BufferWriter::new();
+        buf.write_u16(self.max_stack);
+        buf.write_u16(self.max_locals);
+        buf.write_u32(code.len() as u32);
+        buf.write_bytes(&code);
+        self.exception_table.write(&mut buf, idx_map);
+        self.attributes.write(&mut buf, idx_map);
+        Ok(buf.into_bytes())
+    }
+
+    /// Length of the bytecode, i.e. the pc following the last instruction
+    pub fn code_length(&self) -> u32 {
+        self.instructions
+            .last()
+            .map_or(0, |(pc, instruction)| pc + instruction.size(*pc))
+    }
+
+    /// Position of the instruction at `pc` in [Code::instructions]
+    pub fn position(&self, pc: u32) -> Option<usize> {
+        self.instructions
+            .binary_search_by_key(&pc, |(pc, _)| *pc)
+            .ok()
+    }
+
+    pub fn instruction_at(&self, pc: u32) -> Option<&Instruction> {
+        self.position(pc).map(|idx| &self.instructions[idx].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        code::{Code, ExceptionHandler, Instruction, Label},
+        constants::{ConstIdxMap, ConstItemIdx, Constants},
+        error::ClassReaderError,
+        verifier::{StructuralViolation, check_structure},
+        version::ClassFileVersion,
+    };
+
+    #[test]
+    fn code_attributes_round_trip() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+        for method in &class.methods {
+            let attribute = method.find_attribute(&class.constants, "Code").unwrap();
+            let code = Code::read(&attribute.info, &idx_map).unwrap();
+            assert_eq!(attribute.info, code.write(&idx_map).unwrap());
+        }
+    }
+
+    #[test]
+    fn switch_padding_is_kept() {
+        let info = |padding: u8| {
+            let mut info = vec![0, 1, 0, 1, 0, 0, 0, 13];
+            // iconst_0; lookupswitch { default: return }; return
+            info.extend([0x03, 0xab, 0, padding, 0, 0, 0, 11, 0, 0, 0, 0, 0xb1]);
+            info.extend([0, 0, 0, 0]);
+            info
+        };
+        let idx_map = ConstIdxMap::new();
+        let check = |code: &Code, version| check_structure(code, &Constants::new(), &version);
+        let code = Code::read(&info(0), &idx_map).unwrap();
+        assert_eq!(info(0), code.write(&idx_map).unwrap());
+        assert!(check(&code, ClassFileVersion::Jdk6).is_empty());
+
+        // Only zeros are allowed before version 51
+        let code = Code::read(&info(1), &idx_map).unwrap();
+        assert_eq!(info(1), code.write(&idx_map).unwrap());
+        assert_eq!(
+            vec![StructuralViolation::SwitchPadding { pc: 1 }],
+            check(&code, ClassFileVersion::Jdk6)
+        );
+        assert!(check(&code, ClassFileVersion::Jdk7).is_empty());
+    }
+
+    #[test]
+    fn code_must_fit_in_65535_bytes() {
+        let nops = |count: u32| (0..count).map(|pc| (pc, Instruction::Nop)).collect();
+        let mut code = Code {
+            max_stack: 0,
+            max_locals: 0,
+            instructions: nops(u16::MAX as u32),
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        };
+        let idx_map = ConstIdxMap::new();
+        assert!(code.write(&idx_map).is_ok());
+
+        code.exception_table.push(ExceptionHandler {
+            start: Label(0),
+            end: Label(0x1_0001),
+            handler: Label(0),
+            catch_type: None,
+        });
+        let error = code.write(&idx_map).unwrap_err();
+        assert_eq!(&ClassReaderError::CodeTooLarge(0x1_0001), error.kind());
+
+        code.instructions = nops(u16::MAX as u32 + 1);
+        let error = code.write(&idx_map).unwrap_err();
+        assert_eq!(&ClassReaderError::CodeTooLarge(0x1_0000), error.kind());
+    }
+
+    #[test]
+    fn dangling_constants_are_not_written() {
+        let code = Code {
+            max_stack: 1,
+            max_locals: 0,
+            instructions: vec![
+                (0, Instruction::LdcW(ConstItemIdx::from_raw(4))),
+                (3, Instruction::Areturn),
+            ],
+            exception_table: Vec::new(),
+            attributes: Vec::new(),
+        };
+        let error = code.write(&ConstIdxMap::new()).unwrap_err();
+        assert_eq!(&ClassReaderError::InvalidConstantPoolIdx(5), error.kind());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    constants::{ConstItem, ConstItemIdx, Constants},
+    descriptor::{FieldType, MethodDescriptor},
+    java_str::JavaStr,
+};
+
+use super::{Code, Instruction, Label, WideInstruction};
+
+/// Descriptor of the member referenced by a FieldRef, MethodRef, InterfaceMethodRef or
+/// InvokeDynamic constant
+fn member_descriptor(constants: &Constants, idx: ConstItemIdx) -> Option<&JavaStr> {
+    let name_and_type_index = match constants.get(idx)? {
+        ConstItem::FieldRef(item) => item.name_and_type_index,
+        ConstItem::MethodRef(item) => item.name_and_type_index,
+        ConstItem::InterfaceMethodRef(item) => item.name_and_type_index,
+        ConstItem::InvokeDynamic(item) => item.name_and_type_index,
+        _ => return None,
+    };
+    let name_and_type = constants.get(name_and_type_index)?.as_name_and_type()?;
+    let descriptor = constants.get(name_and_type.descriptor_index)?.as_utf8()?;
+    Some(&descriptor.string)
+}
+
+impl Instruction {
+    /// Change in the operand stack size caused by the instruction, in slots.
+    ///
+    /// Returns `None` when a referenced constant or descriptor is malformed.
+    pub fn stack_delta(&self, constants: &Constants) -> Option<i32> {
+        use Instruction::*;
+
+        let field_slots = |idx: &ConstItemIdx| {
+            let field_type = FieldType::parse(member_descriptor(constants, *idx)?).ok()?;
+            Some(field_type.slots())
+        };
+        let method_slots = |idx: &ConstItemIdx| {
+            let descriptor = MethodDescriptor::parse(member_descriptor(constants, *idx)?).ok()?;
+            Some((descriptor.parameter_slots(), descriptor.return_slots()))
+        };
+        Some(match self {
+            Nop | Iinc(..) | Goto(_) | GotoW(_) | Ret(_) | Return | Swap | Ineg | Lneg | Fneg
+            | Dneg | I2f | L2d | F2i | D2l | I2b | I2c | I2s | Newarray(_) | Anewarray(_)
+            | Arraylength | Checkcast(_) | Instanceof(_) | Athrow => 0,
+            AconstNull | IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5
+            | Fconst0 | Fconst1 | Fconst2 | Bipush(_) | Sipush(_) | Ldc(_) | LdcW(_) | Iload(_)
+            | Fload(_) | Aload(_) | Iload0 | Iload1 | Iload2 | Iload3 | Fload0 | Fload1
+            | Fload2 | Fload3 | Aload0 | Aload1 | Aload2 | Aload3 | Dup | DupX1 | DupX2 | I2l
+            | I2d | F2l | F2d | Jsr(_) | JsrW(_) | New(_) => 1,
+            Lconst0 | Lconst1 | Dconst0 | Dconst1 | Ldc2W(_) | Lload(_) | Dload(_) | Lload0
+            | Lload1 | Lload2 | Lload3 | Dload0 | Dload1 | Dload2 | Dload3 | Dup2 | Dup2X1
+            | Dup2X2 => 2,
+            Iaload | Faload | Aaload | Baload | Caload | Saload | Istore(_) | Fstore(_)
+            | Astore(_) | Istore0 | Istore1 | Istore2 | Istore3 | Fstore0 | Fstore1 | Fstore2
+            | Fstore3 | Astore0 | Astore1 | Astore2 | Astore3 | Pop | Iadd | Fadd | Isub | Fsub
+            | Imul | Fmul | Idiv | Fdiv | Irem | Frem | Ishl | Ishr | Iushr | Lshl | Lshr
+            | Lushr | Iand | Ior | Ixor | L2i | L2f | D2i | D2f | Fcmpl | Fcmpg | Ifeq(_)
+            | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) | Ifnull(_) | Ifnonnull(_)
+            | Tableswitch(_) | Lookupswitch(_) | Ireturn | Freturn | Areturn | Monitorenter
+            | Monitorexit => -1,
+            Laload | Daload => 0,
+            Lstore(_) | Dstore(_) | Lstore0 | Lstore1 | Lstore2 | Lstore3 | Dstore0 | Dstore1
+            | Dstore2 | Dstore3 | Pop2 | Ladd | Dadd | Lsub | Dsub | Lmul | Dmul | Ldiv | Ddiv
+            | Lrem | Drem | Land | Lor | Lxor | IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_)
+            | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) | IfAcmpeq(_) | IfAcmpne(_) | Lreturn
+            | Dreturn => -2,
+            Iastore | Fastore | Aastore | Bastore | Castore | Sastore | Lcmp | Dcmpl | Dcmpg => -3,
+            Lastore | Dastore => -4,
+            Getstatic(idx) => field_slots(idx)? as i32,
+            Putstatic(idx) => -(field_slots(idx)? as i32),
+            Getfield(idx) => field_slots(idx)? as i32 - 1,
+            Putfield(idx) => -(field_slots(idx)? as i32) - 1,
+            Invokestatic(idx) | Invokedynamic(idx) => {
+                let (args, ret) = method_slots(idx)?;
+                ret as i32 - args as i32
+            }
+            Invokevirtual(idx) | Invokespecial(idx) | Invokeinterface(idx, _) => {
+                let (args, ret) = method_slots(idx)?;
+                ret as i32 - args as i32 - 1
+            }
+            Multianewarray(_, dimensions) => 1 - *dimensions as i32,
+            Wide(instruction) => match instruction {
+                WideInstruction::Iload(_)
+                | WideInstruction::Fload(_)
+                | WideInstruction::Aload(_) => 1,
+                WideInstruction::Lload(_) | WideInstruction::Dload(_) => 2,
+                WideInstruction::Istore(_)
+                | WideInstruction::Fstore(_)
+                | WideInstruction::Astore(_) => -1,
+                WideInstruction::Lstore(_) | WideInstruction::Dstore(_) => -2,
+                WideInstruction::Ret(_) | WideInstruction::Iinc(..) => 0,
+            },
+        })
+    }
+
+    /// Whether execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Goto(_)
+                | Instruction::GotoW(_)
+                | Instruction::Ret(_)
+                | Instruction::Wide(WideInstruction::Ret(_))
+                | Instruction::Tableswitch(_)
+                | Instruction::Lookupswitch(_)
+                | Instruction::Ireturn
+                | Instruction::Lreturn
+                | Instruction::Freturn
+                | Instruction::Dreturn
+                | Instruction::Areturn
+                | Instruction::Return
+                | Instruction::Athrow
+        )
+    }
+
+    /// Highest local variable slot accessed by the instruction, plus one
+    pub fn locals_used(&self) -> u16 {
+        use Instruction::*;
+
+        match self {
+            Iload(idx)
+            | Fload(idx)
+            | Aload(idx)
+            | Istore(idx)
+            | Fstore(idx)
+            | Astore(idx)
+            | Ret(idx)
+            | Iinc(idx, _) => *idx as u16 + 1,
+            Lload(idx) | Dload(idx) | Lstore(idx) | Dstore(idx) => *idx as u16 + 2,
+            Iload0 | Fload0 | Aload0 | Istore0 | Fstore0 | Astore0 => 1,
+            Iload1 | Fload1 | Aload1 | Istore1 | Fstore1 | Astore1 | Lload0 | Dload0 | Lstore0
+            | Dstore0 => 2,
+            Iload2 | Fload2 | Aload2 | Istore2 | Fstore2 | Astore2 | Lload1 | Dload1 | Lstore1
+            | Dstore1 => 3,
+            Iload3 | Fload3 | Aload3 | Istore3 | Fstore3 | Astore3 | Lload2 | Dload2 | Lstore2
+            | Dstore2 => 4,
+            Lload3 | Dload3 | Lstore3 | Dstore3 => 5,
+            Wide(instruction) => match instruction {
+                WideInstruction::Iload(idx)
+                | WideInstruction::Fload(idx)
+                | WideInstruction::Aload(idx)
+                | WideInstruction::Istore(idx)
+                | WideInstruction::Fstore(idx)
+                | WideInstruction::Astore(idx)
+                | WideInstruction::Ret(idx)
+                | WideInstruction::Iinc(idx, _) => idx.saturating_add(1),
+                WideInstruction::Lload(idx)
+                | WideInstruction::Dload(idx)
+                | WideInstruction::Lstore(idx)
+                | WideInstruction::Dstore(idx) => idx.saturating_add(2),
+            },
+            _ => 0,
+        }
+    }
+}
+
+impl Code {
+    /// Computes the maximum depth of the operand stack, following every path through the code.
+    ///
+    /// Subroutines are assumed to return with the stack they were called with.
+    pub fn compute_max_stack(&self, constants: &Constants) -> u16 {
+        let mut depths: Vec<Option<i32>> = vec![None; self.instructions.len()];
+        let mut pending = Vec::new();
+        let mut enqueue = |label: Label, depth: i32, pending: &mut Vec<(usize, i32)>| {
+            if let Some(idx) = self.position(label.0)
+                && depths[idx].is_none()
+            {
+                depths[idx] = Some(depth);
+                pending.push((idx, depth));
+            }
+        };
+        enqueue(Label(0), 0, &mut pending);
+        for handler in &self.exception_table {
+            enqueue(handler.handler, 1, &mut pending);
+        }
+
+        let mut max = 0;
+        while let Some((idx, depth)) = pending.pop() {
+            let (pc, instruction) = &self.instructions[idx];
+            let after = (depth + instruction.stack_delta(constants).unwrap_or(0)).max(0);
+            max = max.max(depth).max(after);
+            for target in instruction.targets() {
+                enqueue(target, after, &mut pending);
+            }
+            if instruction.falls_through() {
+                let next = pc + instruction.size(*pc);
+                let depth = match instruction {
+                    Instruction::Jsr(_) | Instruction::JsrW(_) => depth,
+                    _ => after,
+                };
+                enqueue(Label(next), depth, &mut pending);
+            }
+        }
+        max.min(u16::MAX as i32) as u16
+    }
+
+    /// Computes the number of local variable slots used by the code, given the slots taken by
+    /// the arguments of the method (including `this`)
+    pub fn compute_max_locals(&self, argument_slots: u16) -> u16 {
+        self.instructions
+            .iter()
+            .map(|(_, instruction)| instruction.locals_used())
+            .fold(argument_slots, u16::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ClassFile;
+
+    #[test]
+    fn computed_sizes_match_javac() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+        for method in &class.methods {
+            let code = method.code(&class.constants, &idx_map).unwrap().unwrap();
+            assert_eq!(code.max_stack, code.compute_max_stack(&class.constants));
+            assert!(code.compute_max_locals(0) <= code.max_locals);
+        }
+    }
+}
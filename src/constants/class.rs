@@ -1,16 +1,39 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstClass {
     pub name_index: ConstItemIdx,
 }
 
 impl Read for ConstClass {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
-            name_index: ConstItemIdx::read(buf, consts_count, empty_const_slots)?,
+            name_index: ConstItemIdx::read_raw(buf)?,
         })
     }
 }
+
+impl Write for ConstClass {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.name_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_class(&self) -> bool {
+        matches!(self, Self::Class(_))
+    }
+
+    pub fn as_class(&self) -> Option<&ConstClass> {
+        if let Self::Class(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
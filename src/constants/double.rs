@@ -1,14 +1,120 @@
-use crate::{Read, Result, buffer::Buffer};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
+
+use super::{ConstIdxMap, ConstItem};
+
+/// A double constant, stored as its raw bits.
+///
+/// Equality and hashing compare bit patterns, so NaN payloads are preserved and `NaN == NaN`.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ConstDouble {
-    pub double: f64,
+    pub bits: u64,
+}
+
+impl ConstDouble {
+    pub fn from_value(double: f64) -> Self {
+        Self {
+            bits: double.to_bits(),
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        f64::from_bits(self.bits)
+    }
+}
+
+/// Shows the value, along with the bits that tell NaNs apart
+impl fmt::Debug for ConstDouble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ConstDouble");
+        debug.field("value", &self.value());
+        if self.value().is_nan() {
+            debug.field("bits", &format_args!("{:#018X}", self.bits));
+        }
+        debug.finish()
+    }
 }
 
 impl Read for ConstDouble {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
-            double: buf.read_f64()?,
+            bits: buf.read_u64()?,
         })
     }
 }
+
+impl Write for ConstDouble {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_u64(self.bits);
+    }
+}
+
+impl ConstItem {
+    pub fn is_double(&self) -> bool {
+        matches!(self, Self::Double(_))
+    }
+
+    pub fn as_double(&self) -> Option<&ConstDouble> {
+        if let Self::Double(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Read, Write,
+        buffer::{Buffer, BufferWriter},
+        constants::{ConstDouble, ConstIdxMap},
+    };
+
+    #[test]
+    fn nan_payloads_are_compared_bitwise() {
+        let signalling = ConstDouble {
+            bits: 0x7FF0_0000_0000_0001,
+        };
+        let quiet = ConstDouble::from_value(f64::NAN);
+
+        assert!(signalling.value().is_nan());
+        assert_eq!(signalling, signalling.clone());
+        assert_ne!(signalling, quiet);
+        assert_ne!(ConstDouble::from_value(0.0), ConstDouble::from_value(-0.0));
+    }
+
+    #[test]
+    fn signalling_nan_survives_a_round_trip() {
+        let signalling = ConstDouble {
+            bits: 0x7FF4_0000_0000_0123,
+        };
+        let mut writer = BufferWriter::new();
+        signalling.write(&mut writer, &ConstIdxMap::new());
+        let bytes = writer.into_bytes();
+
+        let read = ConstDouble::read(&mut Buffer::new(&bytes), &ConstIdxMap::new()).unwrap();
+        assert_eq!(0x7FF4_0000_0000_0123, read.bits);
+    }
+
+    #[test]
+    fn debug_shows_the_value() {
+        assert_eq!(
+            "ConstDouble { value: 1.5 }",
+            format!("{:?}", ConstDouble::from_value(1.5))
+        );
+        assert_eq!(
+            "ConstDouble { value: NaN, bits: 0x7FF4000000000123 }",
+            format!(
+                "{:?}",
+                ConstDouble {
+                    bits: 0x7FF4_0000_0000_0123
+                }
+            )
+        );
+    }
+}
@@ -1,17 +1,20 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstFieldRef {
     pub class_index: ConstItemIdx,
     pub name_and_type_index: ConstItemIdx,
 }
 
 impl Read for ConstFieldRef {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
-        let class_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let name_and_type_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
+        let class_index = ConstItemIdx::read_raw(buf)?;
+        let name_and_type_index = ConstItemIdx::read_raw(buf)?;
 
         Ok(Self {
             class_index,
@@ -19,3 +22,24 @@ impl Read for ConstFieldRef {
         })
     }
 }
+
+impl Write for ConstFieldRef {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.class_index.write(buf, idx_map);
+        self.name_and_type_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_field_ref(&self) -> bool {
+        matches!(self, Self::FieldRef(_))
+    }
+
+    pub fn as_field_ref(&self) -> Option<&ConstFieldRef> {
+        if let Self::FieldRef(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
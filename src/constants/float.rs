@@ -1,14 +1,111 @@
-use crate::{Read, Result, buffer::Buffer};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
+
+use super::{ConstIdxMap, ConstItem};
+
+/// A float constant, stored as its raw bits.
+///
+/// Equality and hashing compare bit patterns, so NaN payloads are preserved and `NaN == NaN`.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ConstFloat {
-    pub float: f32,
+    pub bits: u32,
+}
+
+impl ConstFloat {
+    pub fn from_value(float: f32) -> Self {
+        Self {
+            bits: float.to_bits(),
+        }
+    }
+
+    pub fn value(&self) -> f32 {
+        f32::from_bits(self.bits)
+    }
+}
+
+/// Shows the value, along with the bits that tell NaNs apart
+impl fmt::Debug for ConstFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ConstFloat");
+        debug.field("value", &self.value());
+        if self.value().is_nan() {
+            debug.field("bits", &format_args!("{:#010X}", self.bits));
+        }
+        debug.finish()
+    }
 }
 
 impl Read for ConstFloat {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
-            float: buf.read_f32()?,
+            bits: buf.read_u32()?,
         })
     }
 }
+
+impl Write for ConstFloat {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_u32(self.bits);
+    }
+}
+
+impl ConstItem {
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Float(_))
+    }
+
+    pub fn as_float(&self) -> Option<&ConstFloat> {
+        if let Self::Float(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Read, Write,
+        buffer::{Buffer, BufferWriter},
+        constants::{ConstFloat, ConstIdxMap},
+    };
+
+    #[test]
+    fn nan_payloads_are_compared_bitwise() {
+        let signalling = ConstFloat { bits: 0x7F80_0001 };
+        let quiet = ConstFloat::from_value(f32::NAN);
+
+        assert!(signalling.value().is_nan());
+        assert_eq!(signalling, signalling.clone());
+        assert_ne!(signalling, quiet);
+        assert_ne!(ConstFloat::from_value(0.0), ConstFloat::from_value(-0.0));
+    }
+
+    #[test]
+    fn signalling_nan_survives_a_round_trip() {
+        let signalling = ConstFloat { bits: 0x7FA0_0123 };
+        let mut writer = BufferWriter::new();
+        signalling.write(&mut writer, &ConstIdxMap::new());
+        let bytes = writer.into_bytes();
+
+        let read = ConstFloat::read(&mut Buffer::new(&bytes), &ConstIdxMap::new()).unwrap();
+        assert_eq!(0x7FA0_0123, read.bits);
+    }
+
+    #[test]
+    fn debug_shows_the_value() {
+        assert_eq!(
+            "ConstFloat { value: 1.5 }",
+            format!("{:?}", ConstFloat::from_value(1.5))
+        );
+        assert_eq!(
+            "ConstFloat { value: NaN, bits: 0x7FA00123 }",
+            format!("{:?}", ConstFloat { bits: 0x7FA0_0123 })
+        );
+    }
+}
@@ -1,14 +1,39 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-#[derive(Debug, Clone)]
+use super::{ConstIdxMap, ConstItem};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstInteger {
     pub integer: i32,
 }
 
 impl Read for ConstInteger {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
             integer: buf.read_i32()?,
         })
     }
 }
+
+impl Write for ConstInteger {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_i32(self.integer);
+    }
+}
+
+impl ConstItem {
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Integer(_))
+    }
+
+    pub fn as_integer(&self) -> Option<&ConstInteger> {
+        if let Self::Integer(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::{ConstItem, ConstItemIdx};
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstInvokeDynamic {
     // TODO
     pub bootstrap_method_attr_index: u16,
@@ -10,9 +13,9 @@ pub struct ConstInvokeDynamic {
 }
 
 impl Read for ConstInvokeDynamic {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         let bootstrap_method_attr_index = buf.read_u16()?;
-        let name_and_type_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+        let name_and_type_index = ConstItemIdx::read_raw(buf)?;
 
         Ok(Self {
             bootstrap_method_attr_index,
@@ -21,6 +24,13 @@ impl Read for ConstInvokeDynamic {
     }
 }
 
+impl Write for ConstInvokeDynamic {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u16(self.bootstrap_method_attr_index);
+        self.name_and_type_index.write(buf, idx_map);
+    }
+}
+
 impl ConstItem {
     pub fn is_invoke_dynamic(&self) -> bool {
         matches!(self, Self::InvokeDynamic(_))
@@ -1,14 +1,39 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-#[derive(Debug, Clone)]
+use super::{ConstIdxMap, ConstItem};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstLong {
     pub long: i64,
 }
 
 impl Read for ConstLong {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
             long: buf.read_i64()?,
         })
     }
 }
+
+impl Write for ConstLong {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_i64(self.long);
+    }
+}
+
+impl ConstItem {
+    pub fn is_long(&self) -> bool {
+        matches!(self, Self::Long(_))
+    }
+
+    pub fn as_long(&self) -> Option<&ConstLong> {
+        if let Self::Long(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
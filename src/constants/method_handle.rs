@@ -1,8 +1,12 @@
-use crate::{Read, Result, buffer::Buffer, error::ClassReaderError};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+    error::ClassReaderError,
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CPMethodHandleReferenceKind {
     GetField,
     GetStatic,
@@ -34,40 +38,45 @@ impl TryFrom<u8> for CPMethodHandleReferenceKind {
     }
 }
 
-impl Into<u8> for CPMethodHandleReferenceKind {
-    fn into(self) -> u8 {
-        match self {
-            Self::GetField => 1,
-            Self::GetStatic => 2,
-            Self::PutField => 3,
-            Self::PutStatic => 4,
-            Self::InvokeVirtual => 5,
-            Self::InvokeStatic => 6,
-            Self::InvokeSpecial => 7,
-            Self::NewInvokeSpecial => 8,
-            Self::InvokeInterface => 9,
+impl From<CPMethodHandleReferenceKind> for u8 {
+    fn from(kind: CPMethodHandleReferenceKind) -> u8 {
+        match kind {
+            CPMethodHandleReferenceKind::GetField => 1,
+            CPMethodHandleReferenceKind::GetStatic => 2,
+            CPMethodHandleReferenceKind::PutField => 3,
+            CPMethodHandleReferenceKind::PutStatic => 4,
+            CPMethodHandleReferenceKind::InvokeVirtual => 5,
+            CPMethodHandleReferenceKind::InvokeStatic => 6,
+            CPMethodHandleReferenceKind::InvokeSpecial => 7,
+            CPMethodHandleReferenceKind::NewInvokeSpecial => 8,
+            CPMethodHandleReferenceKind::InvokeInterface => 9,
         }
     }
 }
 
 impl Read for CPMethodHandleReferenceKind {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         let tag = buf.read_u8()?;
         Self::try_from(tag).map_err(|_| ClassReaderError::InvalidMethodHandleReferenceKind(tag))
     }
 }
 
-#[derive(Debug, Clone)]
+impl Write for CPMethodHandleReferenceKind {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_u8(self.clone().into());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstMethodHandle {
     pub reference_kind: CPMethodHandleReferenceKind,
     pub reference_index: ConstItemIdx,
 }
 
 impl Read for ConstMethodHandle {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
-        let reference_kind =
-            CPMethodHandleReferenceKind::read(buf, consts_count, empty_const_slots)?;
-        let reference_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let reference_kind = CPMethodHandleReferenceKind::read(buf, idx_map)?;
+        let reference_index = ConstItemIdx::read_raw(buf)?;
 
         Ok(Self {
             reference_kind,
@@ -75,3 +84,24 @@ impl Read for ConstMethodHandle {
         })
     }
 }
+
+impl Write for ConstMethodHandle {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.reference_kind.write(buf, idx_map);
+        self.reference_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_method_handle(&self) -> bool {
+        matches!(self, Self::MethodHandle(_))
+    }
+
+    pub fn as_method_handle(&self) -> Option<&ConstMethodHandle> {
+        if let Self::MethodHandle(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
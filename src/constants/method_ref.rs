@@ -1,17 +1,20 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstMethodRef {
     pub class_index: ConstItemIdx,
     pub name_and_type_index: ConstItemIdx,
 }
 
 impl Read for ConstMethodRef {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
-        let class_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let name_and_type_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
+        let class_index = ConstItemIdx::read_raw(buf)?;
+        let name_and_type_index = ConstItemIdx::read_raw(buf)?;
 
         Ok(Self {
             class_index,
@@ -19,3 +22,24 @@ impl Read for ConstMethodRef {
         })
     }
 }
+
+impl Write for ConstMethodRef {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.class_index.write(buf, idx_map);
+        self.name_and_type_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_method_ref(&self) -> bool {
+        matches!(self, Self::MethodRef(_))
+    }
+
+    pub fn as_method_ref(&self) -> Option<&ConstMethodRef> {
+        if let Self::MethodRef(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
@@ -1,16 +1,39 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstMethodType {
     pub descriptor_index: ConstItemIdx,
 }
 
 impl Read for ConstMethodType {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
-            descriptor_index: ConstItemIdx::read(buf, consts_count, empty_const_slots)?,
+            descriptor_index: ConstItemIdx::read_raw(buf)?,
         })
     }
 }
+
+impl Write for ConstMethodType {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.descriptor_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_method_type(&self) -> bool {
+        matches!(self, Self::MethodType(_))
+    }
+
+    pub fn as_method_type(&self) -> Option<&ConstMethodType> {
+        if let Self::MethodType(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
@@ -22,74 +22,177 @@ mod method_type;
 pub use method_type::*;
 mod name_and_type;
 pub use name_and_type::*;
+mod pool_builder;
+pub use pool_builder::*;
 mod string;
 pub use string::*;
 mod utf8;
 pub use utf8::*;
+mod validation;
+pub use validation::validate;
 
 use index_vec::{IndexVec, define_index_type};
 
-use crate::{Read, Result, buffer::Buffer, error::ClassReaderError};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+    error::ClassReaderError,
+};
 
 define_index_type! {
     pub struct ConstItemIdx = u16;
 }
 
+impl ConstItemIdx {
+    /// Reads an index exactly as it is stored in the class file, without resolving it.
+    ///
+    /// Entries of the constant pool may point to entries that have not been read yet, so their
+    /// indices are kept raw and fixed up by [ConstItem::resolve_indices] once the pool is known.
+    pub(crate) fn read_raw(buf: &mut Buffer) -> Result<Self> {
+        Ok(ConstItemIdx::from_raw(buf.read_u16()?))
+    }
+}
+
 impl Read for ConstItemIdx {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_consts_slots: &[u16]) -> Result<Self> {
-        let idx = buf.read_u16()?;
-        if idx == 0 {
-            return Err(ClassReaderError::InvalidConstantPoolIdx(idx));
-        }
-        let idx = idx - 1;
-        let mut offset = 0;
-        for slot in empty_consts_slots {
-            if &idx == slot {
-                return Err(ClassReaderError::InvalidConstantPoolIdx(idx));
-            } else if &idx < slot {
-                break;
-            } else {
-                offset += 1;
-            }
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let pos = buf.get_pos();
+        idx_map
+            .resolve(buf.read_u16()?)
+            .map_err(|error| error.at(pos))
+    }
+}
+
+impl Read for Option<ConstItemIdx> {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
+        let pos = buf.get_pos();
+        match buf.read_u16()? {
+            0 => Ok(None),
+            idx => idx_map
+                .resolve(idx)
+                .map(Some)
+                .map_err(|error| error.at(pos)),
         }
-        let idx = idx - offset;
-        if idx >= consts_count {
-            Err(ClassReaderError::InvalidConstantPoolIdx(idx))
-        } else {
-            Ok(ConstItemIdx::from_raw(idx))
+    }
+}
+
+impl Write for ConstItemIdx {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        let raw = idx_map
+            .raw(*self)
+            .expect("constant pool index out of range");
+        buf.write_u16(raw);
+    }
+}
+
+impl Write for Option<ConstItemIdx> {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        match self {
+            Some(idx) => idx.write(buf, idx_map),
+            None => buf.write_u16(0),
         }
     }
 }
 
-impl Read for Option<ConstItemIdx> {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_consts_slots: &[u16]) -> Result<Self> {
-        let idx = buf.read_u16()?;
-        if idx == 0 {
-            return Ok(None);
+/// Translates the 1-based indices used inside a class file into [ConstItemIdx] values.
+///
+/// Long and double constants take two slots in the class file but a single entry in
+/// [Constants], so every slot is precomputed once and lookups are O(1).
+#[derive(Debug, Clone)]
+pub struct ConstIdxMap {
+    /// Compacted index of every class file slot, or [ConstIdxMap::UNUSABLE]
+    slots: Vec<u16>,
+    /// Class file slot of every compacted index
+    raw: Vec<u16>,
+}
+
+impl ConstIdxMap {
+    /// Marks slot 0 and the slots following a long or a double
+    const UNUSABLE: u16 = u16::MAX;
+
+    /// Creates an empty map, with only the reserved slot 0
+    pub fn new() -> Self {
+        Self {
+            slots: vec![Self::UNUSABLE],
+            raw: Vec::new(),
         }
-        let idx = idx - 1;
-        let mut offset = 0;
-        for slot in empty_consts_slots {
-            if &idx == slot {
-                return Err(ClassReaderError::InvalidConstantPoolIdx(idx));
-            } else if &idx < slot {
-                break;
-            } else {
-                offset += 1;
-            }
+    }
+
+    /// Builds the map of an already read constant pool, failing if it takes more slots than a
+    /// class file can hold
+    pub fn from_constants(constants: &Constants) -> Result<Self> {
+        let mut idx_map = Self::new();
+        for item in constants {
+            idx_map.push(item)?;
+        }
+        Ok(idx_map)
+    }
+
+    /// Registers the next item of the constant pool, failing if the pool would then take more
+    /// than the 65535 slots a class file can hold
+    pub fn push(&mut self, item: &ConstItem) -> Result<()> {
+        self.push_slots(item.is_8bit())
+    }
+
+    /// Registers the next item of the constant pool, taking two slots if `wide`
+    pub(crate) fn push_slots(&mut self, wide: bool) -> Result<()> {
+        let slots = self.slots.len() + 1 + wide as usize;
+        if slots > u16::MAX as usize {
+            return Err(ClassReaderError::TooManyConstants(slots));
+        }
+        self.raw.push(self.slots.len() as u16);
+        self.slots.push((self.raw.len() - 1) as u16);
+        if wide {
+            self.slots.push(Self::UNUSABLE);
         }
-        let idx = idx - offset;
-        if idx >= consts_count {
-            Err(ClassReaderError::InvalidConstantPoolIdx(idx))
-        } else {
-            Ok(Some(ConstItemIdx::from_raw(idx)))
+        Ok(())
+    }
+
+    /// Number of entries in the constant pool
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Number of slots used in the class file, i.e. the `constant_pool_count` field
+    pub fn slots_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Resolves a class file index into an index of [Constants]
+    pub fn resolve(&self, idx: u16) -> Result<ConstItemIdx> {
+        match self.slots.get(idx as usize) {
+            Some(&slot) if slot != Self::UNUSABLE => Ok(ConstItemIdx::from_raw(slot)),
+            _ => Err(ClassReaderError::InvalidConstantPoolIdx(idx)),
         }
     }
+
+    /// Converts an index of [Constants] back into the index used in the class file, `None` if
+    /// it lies past the end of the pool
+    pub fn raw(&self, idx: ConstItemIdx) -> Option<u16> {
+        self.raw.get(idx.index()).copied()
+    }
+
+    /// Class file index of an entry, even if it lies past the end of the pool
+    pub(crate) fn raw_past_end(&self, idx: ConstItemIdx) -> u16 {
+        self.raw(idx).unwrap_or_else(|| {
+            let skipped = self.slots_count() - self.len();
+            (idx.index() + skipped).min(u16::MAX as usize) as u16
+        })
+    }
+}
+
+impl Default for ConstIdxMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub type Constants = IndexVec<ConstItemIdx, ConstItem>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConstItem {
     Class(ConstClass),
     FieldRef(ConstFieldRef),
@@ -108,46 +211,236 @@ pub enum ConstItem {
 }
 
 impl ConstItem {
+    /// Name of the kind of this item, as used in the JVMS
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Self::Class(_) => "Class",
+            Self::FieldRef(_) => "FieldRef",
+            Self::MethodRef(_) => "MethodRef",
+            Self::InterfaceMethodRef(_) => "InterfaceMethodRef",
+            Self::String(_) => "String",
+            Self::Integer(_) => "Integer",
+            Self::Float(_) => "Float",
+            Self::Long(_) => "Long",
+            Self::Double(_) => "Double",
+            Self::NameAndType(_) => "NameAndType",
+            Self::Utf8(_) => "Utf8",
+            Self::MethodHandle(_) => "MethodHandle",
+            Self::MethodType(_) => "MethodType",
+            Self::InvokeDynamic(_) => "InvokeDynamic",
+        }
+    }
+
+    /// Tag identifying the kind of this item in the class file
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Utf8(_) => 1,
+            Self::Integer(_) => 3,
+            Self::Float(_) => 4,
+            Self::Long(_) => 5,
+            Self::Double(_) => 6,
+            Self::Class(_) => 7,
+            Self::String(_) => 8,
+            Self::FieldRef(_) => 9,
+            Self::MethodRef(_) => 10,
+            Self::InterfaceMethodRef(_) => 11,
+            Self::NameAndType(_) => 12,
+            Self::MethodHandle(_) => 15,
+            Self::MethodType(_) => 16,
+            Self::InvokeDynamic(_) => 18,
+        }
+    }
+
     pub fn is_8bit(&self) -> bool {
         matches!(self, Self::Double(_) | Self::Long(_))
     }
+
+    /// Resolves the raw class file indices this item points to, see [ConstItemIdx::read_raw]
+    pub(crate) fn resolve_indices(&mut self, idx_map: &ConstIdxMap) -> Result<()> {
+        let resolve = |idx: &mut ConstItemIdx| -> Result<()> {
+            *idx = idx_map.resolve(idx.raw())?;
+            Ok(())
+        };
+        match self {
+            Self::Class(item) => resolve(&mut item.name_index),
+            Self::FieldRef(item) => {
+                resolve(&mut item.class_index)?;
+                resolve(&mut item.name_and_type_index)
+            }
+            Self::MethodRef(item) => {
+                resolve(&mut item.class_index)?;
+                resolve(&mut item.name_and_type_index)
+            }
+            Self::InterfaceMethodRef(item) => {
+                resolve(&mut item.interface_index)?;
+                resolve(&mut item.name_and_type_index)
+            }
+            Self::String(item) => resolve(&mut item.string_index),
+            Self::NameAndType(item) => {
+                resolve(&mut item.name_index)?;
+                resolve(&mut item.descriptor_index)
+            }
+            Self::MethodHandle(item) => resolve(&mut item.reference_index),
+            Self::MethodType(item) => resolve(&mut item.descriptor_index),
+            Self::InvokeDynamic(item) => resolve(&mut item.name_and_type_index),
+            Self::Integer(_) | Self::Float(_) | Self::Long(_) | Self::Double(_) | Self::Utf8(_) => {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Tags of the items [ConstItem] can hold, with the size of what follows them in the class file,
+/// or `None` for Utf8 whose size comes first
+const CONST_ITEM_TAGS: [(u8, Option<usize>); 14] = [
+    (1, None),
+    (3, Some(4)),
+    (4, Some(4)),
+    (5, Some(8)),
+    (6, Some(8)),
+    (7, Some(2)),
+    (8, Some(2)),
+    (9, Some(4)),
+    (10, Some(4)),
+    (11, Some(4)),
+    (12, Some(4)),
+    (15, Some(3)),
+    (16, Some(2)),
+    (18, Some(4)),
+];
+
+/// Size of what follows `tag` in the class file, see [CONST_ITEM_TAGS]
+fn const_item_size(tag: u8) -> Result<Option<usize>> {
+    CONST_ITEM_TAGS
+        .iter()
+        .find(|&&(known, _)| known == tag)
+        .map(|&(_, size)| size)
+        .ok_or(ClassReaderError::InvalidConstItemTag(tag))
+}
+
+/// Skips an entry of the constant pool, accepting the same tags as [ConstItem::read], and returns
+/// whether it takes two slots
+pub(crate) fn skip_const_item(buf: &mut Buffer) -> Result<bool> {
+    let tag = buf.read_u8()?;
+    let size = match const_item_size(tag)? {
+        Some(size) => size,
+        None => buf.read_u16()? as usize,
+    };
+    buf.read_bytes(size)?;
+    Ok(matches!(tag, 5 | 6))
 }
 
 impl Read for ConstItem {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
         let tag = buf.read_u8()?;
+        const_item_size(tag)?;
         Ok(match tag {
-            1 => Self::Utf8(ConstUtf8::read(buf, consts_count, empty_const_slots)?),
-            3 => Self::Integer(ConstInteger::read(buf, consts_count, empty_const_slots)?),
-            4 => Self::Float(ConstFloat::read(buf, consts_count, empty_const_slots)?),
-            5 => Self::Long(ConstLong::read(buf, consts_count, empty_const_slots)?),
-            6 => Self::Double(ConstDouble::read(buf, consts_count, empty_const_slots)?),
-            7 => Self::Class(ConstClass::read(buf, consts_count, empty_const_slots)?),
-            8 => Self::String(ConstString::read(buf, consts_count, empty_const_slots)?),
-            9 => Self::FieldRef(ConstFieldRef::read(buf, consts_count, empty_const_slots)?),
-            10 => Self::MethodRef(ConstMethodRef::read(buf, consts_count, empty_const_slots)?),
-            11 => Self::InterfaceMethodRef(ConstInterfaceMethodRef::read(
-                buf,
-                consts_count,
-                empty_const_slots,
-            )?),
-            12 => Self::NameAndType(ConstNameAndType::read(
-                buf,
-                consts_count,
-                empty_const_slots,
-            )?),
-            15 => Self::MethodHandle(ConstMethodHandle::read(
-                buf,
-                consts_count,
-                empty_const_slots,
-            )?),
-            16 => Self::MethodType(ConstMethodType::read(buf, consts_count, empty_const_slots)?),
-            18 => Self::InvokeDynamic(ConstInvokeDynamic::read(
-                buf,
-                consts_count,
-                empty_const_slots,
-            )?),
-            tag => return Err(ClassReaderError::InvalidConstItemTag(tag)),
+            1 => Self::Utf8(ConstUtf8::read(buf, idx_map)?),
+            3 => Self::Integer(ConstInteger::read(buf, idx_map)?),
+            4 => Self::Float(ConstFloat::read(buf, idx_map)?),
+            5 => Self::Long(ConstLong::read(buf, idx_map)?),
+            6 => Self::Double(ConstDouble::read(buf, idx_map)?),
+            7 => Self::Class(ConstClass::read(buf, idx_map)?),
+            8 => Self::String(ConstString::read(buf, idx_map)?),
+            9 => Self::FieldRef(ConstFieldRef::read(buf, idx_map)?),
+            10 => Self::MethodRef(ConstMethodRef::read(buf, idx_map)?),
+            11 => Self::InterfaceMethodRef(ConstInterfaceMethodRef::read(buf, idx_map)?),
+            12 => Self::NameAndType(ConstNameAndType::read(buf, idx_map)?),
+            15 => Self::MethodHandle(ConstMethodHandle::read(buf, idx_map)?),
+            16 => Self::MethodType(ConstMethodType::read(buf, idx_map)?),
+            18 => Self::InvokeDynamic(ConstInvokeDynamic::read(buf, idx_map)?),
+            tag => unreachable!("constant pool tag {tag} has a size but no item"),
         })
     }
 }
+
+impl Write for ConstItem {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u8(self.tag());
+        match self {
+            Self::Class(item) => item.write(buf, idx_map),
+            Self::FieldRef(item) => item.write(buf, idx_map),
+            Self::MethodRef(item) => item.write(buf, idx_map),
+            Self::InterfaceMethodRef(item) => item.write(buf, idx_map),
+            Self::String(item) => item.write(buf, idx_map),
+            Self::Integer(item) => item.write(buf, idx_map),
+            Self::Float(item) => item.write(buf, idx_map),
+            Self::Long(item) => item.write(buf, idx_map),
+            Self::Double(item) => item.write(buf, idx_map),
+            Self::NameAndType(item) => item.write(buf, idx_map),
+            Self::Utf8(item) => item.write(buf, idx_map),
+            Self::MethodHandle(item) => item.write(buf, idx_map),
+            Self::MethodType(item) => item.write(buf, idx_map),
+            Self::InvokeDynamic(item) => item.write(buf, idx_map),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Read,
+        buffer::Buffer,
+        constants::{
+            ConstIdxMap, ConstInteger, ConstItem, ConstItemIdx, ConstLong, skip_const_item,
+        },
+        error::ClassReaderError,
+    };
+
+    #[test]
+    fn long_constants_take_two_slots() {
+        let mut idx_map = ConstIdxMap::new();
+        idx_map
+            .push(&ConstItem::Integer(ConstInteger { integer: 1 }))
+            .unwrap();
+        idx_map
+            .push(&ConstItem::Long(ConstLong { long: 2 }))
+            .unwrap();
+        idx_map
+            .push(&ConstItem::Integer(ConstInteger { integer: 3 }))
+            .unwrap();
+
+        assert_eq!(3, idx_map.len());
+        assert_eq!(5, idx_map.slots_count());
+        assert_eq!(Ok(ConstItemIdx::from_raw(0)), idx_map.resolve(1));
+        assert_eq!(Ok(ConstItemIdx::from_raw(1)), idx_map.resolve(2));
+        assert_eq!(Ok(ConstItemIdx::from_raw(2)), idx_map.resolve(4));
+        assert_eq!(Some(4), idx_map.raw(ConstItemIdx::from_raw(2)));
+        assert_eq!(None, idx_map.raw(ConstItemIdx::from_raw(3)));
+    }
+
+    #[test]
+    fn unusable_slots_are_rejected() {
+        let mut idx_map = ConstIdxMap::new();
+        idx_map
+            .push(&ConstItem::Long(ConstLong { long: 2 }))
+            .unwrap();
+
+        for idx in [0, 2, 3] {
+            assert_eq!(
+                Err(ClassReaderError::InvalidConstantPoolIdx(idx)),
+                idx_map.resolve(idx)
+            );
+        }
+    }
+
+    #[test]
+    fn skipping_accepts_the_tags_reading_does() {
+        for tag in 0..=u8::MAX {
+            let mut bytes = vec![tag];
+            bytes.extend([0; 8]);
+            let (mut read, mut skipped) = (Buffer::new(&bytes), Buffer::new(&bytes));
+            let item = ConstItem::read(&mut read, &ConstIdxMap::new());
+            let skip = skip_const_item(&mut skipped);
+            assert_eq!(
+                Err(ClassReaderError::InvalidConstItemTag(tag)) == item,
+                skip.is_err(),
+                "{tag}"
+            );
+            if let Ok(item) = item {
+                assert_eq!(Ok(item.is_8bit()), skip);
+                assert_eq!(read.get_pos(), skipped.get_pos());
+            }
+        }
+    }
+}
@@ -1,17 +1,20 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstNameAndType {
     pub name_index: ConstItemIdx,
     pub descriptor_index: ConstItemIdx,
 }
 
 impl Read for ConstNameAndType {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
-        let name_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let descriptor_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
+        let name_index = ConstItemIdx::read_raw(buf)?;
+        let descriptor_index = ConstItemIdx::read_raw(buf)?;
 
         Ok(Self {
             name_index,
@@ -19,3 +22,24 @@ impl Read for ConstNameAndType {
         })
     }
 }
+
+impl Write for ConstNameAndType {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.name_index.write(buf, idx_map);
+        self.descriptor_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_name_and_type(&self) -> bool {
+        matches!(self, Self::NameAndType(_))
+    }
+
+    pub fn as_name_and_type(&self) -> Option<&ConstNameAndType> {
+        if let Self::NameAndType(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::{ClassReaderError, Result},
+    java_str::JavaString,
+};
+
+use super::{
+    CPMethodHandleReferenceKind, ConstClass, ConstDouble, ConstFieldRef, ConstFloat, ConstIdxMap,
+    ConstInteger, ConstInterfaceMethodRef, ConstInvokeDynamic, ConstItem, ConstItemIdx, ConstLong,
+    ConstMethodHandle, ConstMethodRef, ConstMethodType, ConstNameAndType, ConstString, ConstUtf8,
+    Constants,
+};
+
+/// Builds a constant pool, reusing entries that are already present
+#[derive(Debug, Clone, Default)]
+pub struct ConstPoolBuilder {
+    constants: Constants,
+    idx_map: ConstIdxMap,
+    lookup: HashMap<ConstItem, ConstItemIdx>,
+    /// Number of slots the pool would have needed for the first entry that did not fit
+    overflow: Option<usize>,
+}
+
+impl ConstPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from an existing pool, whose entries keep their indices
+    pub fn from_constants(constants: Constants) -> Result<Self> {
+        let mut lookup = HashMap::with_capacity(constants.len());
+        for (idx, item) in constants.iter_enumerated() {
+            lookup.entry(item.clone()).or_insert(idx);
+        }
+        Ok(Self {
+            idx_map: ConstIdxMap::from_constants(&constants)?,
+            constants,
+            lookup,
+            overflow: None,
+        })
+    }
+
+    pub fn constants(&self) -> &Constants {
+        &self.constants
+    }
+
+    /// Map of the class file indices of the entries added so far.
+    ///
+    /// Entries are only ever appended, so the indices it gives stay valid.
+    pub fn idx_map(&self) -> &ConstIdxMap {
+        &self.idx_map
+    }
+
+    pub fn into_constants(self) -> Constants {
+        self.constants
+    }
+
+    /// Fails if an entry did not fit in the pool, see [ConstPoolBuilder::add]
+    pub fn check_size(&self) -> Result<()> {
+        match self.overflow {
+            Some(slots) => Err(ClassReaderError::TooManyConstants(slots)),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds an item to the pool, unless an identical one is already present.
+    ///
+    /// Once the pool is full, new items are not added and the index returned lies past its
+    /// end, which [ConstPoolBuilder::check_size] then reports.
+    pub fn add(&mut self, item: ConstItem) -> ConstItemIdx {
+        if let Some(idx) = self.lookup.get(&item) {
+            return *idx;
+        }
+        if self.overflow.is_none()
+            && let Err(ClassReaderError::TooManyConstants(slots)) = self.idx_map.push(&item)
+        {
+            self.overflow = Some(slots);
+        }
+        if self.overflow.is_some() {
+            return ConstItemIdx::from_usize(self.constants.len());
+        }
+        let idx = self.constants.push(item.clone());
+        self.lookup.insert(item, idx);
+        idx
+    }
+
+    pub fn utf8(&mut self, string: impl Into<JavaString>) -> ConstItemIdx {
+        self.add(ConstItem::Utf8(ConstUtf8 {
+            string: string.into(),
+        }))
+    }
+
+    /// Adds a class from its internal name, e.g. `java/lang/Object`
+    pub fn class(&mut self, name: impl Into<JavaString>) -> ConstItemIdx {
+        let name_index = self.utf8(name);
+        self.add(ConstItem::Class(ConstClass { name_index }))
+    }
+
+    pub fn string(&mut self, string: impl Into<JavaString>) -> ConstItemIdx {
+        let string_index = self.utf8(string);
+        self.add(ConstItem::String(ConstString { string_index }))
+    }
+
+    pub fn integer(&mut self, integer: i32) -> ConstItemIdx {
+        self.add(ConstItem::Integer(ConstInteger { integer }))
+    }
+
+    pub fn float(&mut self, float: f32) -> ConstItemIdx {
+        self.add(ConstItem::Float(ConstFloat::from_value(float)))
+    }
+
+    pub fn long(&mut self, long: i64) -> ConstItemIdx {
+        self.add(ConstItem::Long(ConstLong { long }))
+    }
+
+    pub fn double(&mut self, double: f64) -> ConstItemIdx {
+        self.add(ConstItem::Double(ConstDouble::from_value(double)))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> ConstItemIdx {
+        let name_index = self.utf8(name);
+        let descriptor_index = self.utf8(descriptor);
+        self.add(ConstItem::NameAndType(ConstNameAndType {
+            name_index,
+            descriptor_index,
+        }))
+    }
+
+    pub fn field_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> ConstItemIdx {
+        let class_index = self.class(owner);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstItem::FieldRef(ConstFieldRef {
+            class_index,
+            name_and_type_index,
+        }))
+    }
+
+    pub fn method_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> ConstItemIdx {
+        let class_index = self.class(owner);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstItem::MethodRef(ConstMethodRef {
+            class_index,
+            name_and_type_index,
+        }))
+    }
+
+    pub fn interface_method_ref(
+        &mut self,
+        owner: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> ConstItemIdx {
+        let interface_index = self.class(owner);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstItem::InterfaceMethodRef(ConstInterfaceMethodRef {
+            interface_index,
+            name_and_type_index,
+        }))
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> ConstItemIdx {
+        let descriptor_index = self.utf8(descriptor);
+        self.add(ConstItem::MethodType(ConstMethodType { descriptor_index }))
+    }
+
+    /// Adds a method handle to a FieldRef, MethodRef or InterfaceMethodRef
+    pub fn method_handle(
+        &mut self,
+        reference_kind: CPMethodHandleReferenceKind,
+        reference_index: ConstItemIdx,
+    ) -> ConstItemIdx {
+        self.add(ConstItem::MethodHandle(ConstMethodHandle {
+            reference_kind,
+            reference_index,
+        }))
+    }
+
+    pub fn invoke_dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> ConstItemIdx {
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstItem::InvokeDynamic(ConstInvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::{ConstPoolBuilder, Constants},
+        error::ClassReaderError,
+    };
+
+    #[test]
+    fn identical_entries_are_shared() {
+        let mut pool = ConstPoolBuilder::new();
+        let first = pool.method_ref("java/lang/Object", "<init>", "()V");
+        let second = pool.method_ref("java/lang/Object", "<init>", "()V");
+        let class = pool.class("java/lang/Object");
+
+        assert_eq!(first, second);
+        assert_eq!(6, pool.constants().len());
+        assert_eq!(
+            Some(class),
+            pool.constants()
+                .iter()
+                .position(|i| i.is_class())
+                .map(Into::into)
+        );
+    }
+
+    #[test]
+    fn existing_entries_keep_their_indices() {
+        let mut pool = ConstPoolBuilder::new();
+        pool.integer(1);
+        pool.string("a");
+        let constants: Constants = pool.into_constants();
+
+        let mut pool = ConstPoolBuilder::from_constants(constants.clone()).unwrap();
+        assert_eq!(1, pool.utf8("a").index());
+        assert_eq!(3, pool.long(7).index());
+        assert_eq!(
+            constants.as_raw_slice(),
+            &pool.constants().as_raw_slice()[..3]
+        );
+    }
+
+    #[test]
+    fn full_pools_stop_growing() {
+        let mut pool = ConstPoolBuilder::new();
+        // Slot 0 and 65534 integers fill the 65535 slots
+        for integer in 0..65534 {
+            pool.integer(integer);
+        }
+        assert_eq!(Ok(()), pool.check_size());
+
+        let idx = pool.long(1);
+        assert_eq!(pool.constants().len(), idx.index());
+        assert_eq!(idx, pool.integer(-1));
+        assert_eq!(
+            Err(ClassReaderError::TooManyConstants(65537)),
+            pool.check_size()
+        );
+    }
+}
@@ -1,16 +1,39 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+};
 
-use super::ConstItemIdx;
+use super::{ConstIdxMap, ConstItem, ConstItemIdx};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstString {
     pub string_index: ConstItemIdx,
 }
 
 impl Read for ConstString {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         Ok(Self {
-            string_index: ConstItemIdx::read(buf, consts_count, empty_const_slots)?,
+            string_index: ConstItemIdx::read_raw(buf)?,
         })
     }
 }
+
+impl Write for ConstString {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        self.string_index.write(buf, idx_map);
+    }
+}
+
+impl ConstItem {
+    pub fn is_string(&self) -> bool {
+        matches!(self, Self::String(_))
+    }
+
+    pub fn as_string(&self) -> Option<&ConstString> {
+        if let Self::String(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
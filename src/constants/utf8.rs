@@ -1,15 +1,43 @@
-use crate::{Read, Result, buffer::Buffer};
+use crate::{
+    Read, Result, Write,
+    buffer::{Buffer, BufferWriter},
+    java_str::JavaString,
+};
 
-#[derive(Debug, Clone)]
+use super::{ConstIdxMap, ConstItem};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ConstUtf8 {
-    pub string: String,
+    pub string: JavaString,
 }
 
 impl Read for ConstUtf8 {
-    fn read(buf: &mut Buffer, _consts_count: u16, _empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, _idx_map: &ConstIdxMap) -> Result<Self> {
         let len = buf.read_u16()?;
+        buf.allocate(len as usize)?;
         Ok(Self {
-            string: buf.read_utf8(len as usize)?,
+            string: buf.read_java_str(len as usize)?.to_java_string(),
         })
     }
 }
+
+impl Write for ConstUtf8 {
+    fn write(&self, buf: &mut BufferWriter, _idx_map: &ConstIdxMap) {
+        buf.write_len_u16(self.string.len());
+        buf.write_bytes(self.string.as_bytes());
+    }
+}
+
+impl ConstItem {
+    pub fn is_utf8(&self) -> bool {
+        matches!(self, Self::Utf8(_))
+    }
+
+    pub fn as_utf8(&self) -> Option<&ConstUtf8> {
+        if let Self::Utf8(item) = self {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    descriptor::{FieldType, MethodDescriptor},
+    error::{ClassReaderError, Result},
+    java_str::JavaStr,
+    names::is_valid_binary_name,
+    version::ClassFileVersion,
+};
+
+use super::{
+    CPMethodHandleReferenceKind, ConstIdxMap, ConstItem, ConstItemIdx, ConstNameAndType, ConstUtf8,
+    Constants,
+};
+
+/// Checks that the entries of a constant pool point to entries of the expected kinds, and that
+/// the class names and descriptors they reference are well-formed (JVMS 4.4).
+///
+/// Indices reported in errors are the ones used in the class file.
+pub fn validate(constants: &Constants, version: &ClassFileVersion) -> Result<()> {
+    let validator = Validator {
+        constants,
+        idx_map: ConstIdxMap::from_constants(constants)?,
+        version,
+    };
+    for (idx, item) in constants.iter_enumerated() {
+        validator.check(idx, item)?;
+    }
+    Ok(())
+}
+
+struct Validator<'a> {
+    constants: &'a Constants,
+    idx_map: ConstIdxMap,
+    version: &'a ClassFileVersion,
+}
+
+impl<'a> Validator<'a> {
+    fn check(&self, idx: ConstItemIdx, item: &ConstItem) -> Result<()> {
+        match item {
+            ConstItem::Class(class) => {
+                let name = self.utf8(class.name_index)?;
+                if name.string.as_bytes().starts_with(b"[") {
+                    if !is_field_descriptor(&name.string) {
+                        return Err(self.invalid_descriptor(class.name_index, name));
+                    }
+                } else if !is_valid_binary_name(&name.string.to_str()) {
+                    return Err(ClassReaderError::InvalidClassName(
+                        self.raw(class.name_index),
+                        name.string.to_str().into_owned(),
+                    ));
+                }
+            }
+            ConstItem::FieldRef(field_ref) => {
+                self.class(field_ref.class_index)?;
+                self.member_type(field_ref.name_and_type_index, is_field_descriptor)?;
+            }
+            ConstItem::MethodRef(method_ref) => {
+                self.class(method_ref.class_index)?;
+                self.member_type(method_ref.name_and_type_index, is_method_descriptor)?;
+            }
+            ConstItem::InterfaceMethodRef(method_ref) => {
+                self.class(method_ref.interface_index)?;
+                self.member_type(method_ref.name_and_type_index, is_method_descriptor)?;
+            }
+            ConstItem::String(string) => {
+                self.utf8(string.string_index)?;
+            }
+            ConstItem::NameAndType(name_and_type) => {
+                self.utf8(name_and_type.name_index)?;
+                let descriptor = self.utf8(name_and_type.descriptor_index)?;
+                if !is_field_descriptor(&descriptor.string)
+                    && !is_method_descriptor(&descriptor.string)
+                {
+                    return Err(self.invalid_descriptor(name_and_type.descriptor_index, descriptor));
+                }
+            }
+            ConstItem::MethodHandle(method_handle) => {
+                self.requires(idx, item, ClassFileVersion::Jdk7)?;
+                self.method_handle(
+                    idx,
+                    &method_handle.reference_kind,
+                    method_handle.reference_index,
+                )?;
+            }
+            ConstItem::MethodType(method_type) => {
+                self.requires(idx, item, ClassFileVersion::Jdk7)?;
+                let descriptor = self.utf8(method_type.descriptor_index)?;
+                if !is_method_descriptor(&descriptor.string) {
+                    return Err(self.invalid_descriptor(method_type.descriptor_index, descriptor));
+                }
+            }
+            ConstItem::InvokeDynamic(invoke_dynamic) => {
+                self.requires(idx, item, ClassFileVersion::Jdk7)?;
+                self.member_type(invoke_dynamic.name_and_type_index, is_method_descriptor)?;
+            }
+            ConstItem::Integer(_)
+            | ConstItem::Float(_)
+            | ConstItem::Long(_)
+            | ConstItem::Double(_)
+            | ConstItem::Utf8(_) => {}
+        }
+        Ok(())
+    }
+
+    fn raw(&self, idx: ConstItemIdx) -> u16 {
+        self.idx_map.raw_past_end(idx)
+    }
+
+    fn get(&self, idx: ConstItemIdx) -> Result<&'a ConstItem> {
+        self.constants
+            .get(idx)
+            .ok_or(ClassReaderError::InvalidConstantPoolIdx(self.raw(idx)))
+    }
+
+    fn expect<T>(
+        &self,
+        idx: ConstItemIdx,
+        expected: &'static str,
+        select: impl Fn(&'a ConstItem) -> Option<&'a T>,
+    ) -> Result<&'a T> {
+        let item = self.get(idx)?;
+        select(item).ok_or(ClassReaderError::UnexpectedConstItem(
+            self.raw(idx),
+            item.kind_name(),
+            expected,
+        ))
+    }
+
+    fn utf8(&self, idx: ConstItemIdx) -> Result<&'a ConstUtf8> {
+        self.expect(idx, "Utf8", ConstItem::as_utf8)
+    }
+
+    fn class(&self, idx: ConstItemIdx) -> Result<()> {
+        self.expect(idx, "Class", ConstItem::as_class).map(|_| ())
+    }
+
+    /// Checks the NameAndType of a member reference, returning the name of the member
+    fn member_type(
+        &self,
+        idx: ConstItemIdx,
+        is_valid_descriptor: fn(&JavaStr) -> bool,
+    ) -> Result<&'a ConstUtf8> {
+        let name_and_type: &ConstNameAndType =
+            self.expect(idx, "NameAndType", ConstItem::as_name_and_type)?;
+        let name = self.utf8(name_and_type.name_index)?;
+        let descriptor = self.utf8(name_and_type.descriptor_index)?;
+        if !is_valid_descriptor(&descriptor.string) {
+            return Err(self.invalid_descriptor(name_and_type.descriptor_index, descriptor));
+        }
+        Ok(name)
+    }
+
+    fn method_handle(
+        &self,
+        idx: ConstItemIdx,
+        kind: &CPMethodHandleReferenceKind,
+        reference_index: ConstItemIdx,
+    ) -> Result<()> {
+        use CPMethodHandleReferenceKind::*;
+
+        let reference = self.get(reference_index)?;
+        let (allowed, expected) = match kind {
+            GetField | GetStatic | PutField | PutStatic => (reference.is_field_ref(), "FieldRef"),
+            InvokeVirtual | NewInvokeSpecial => (reference.is_method_ref(), "MethodRef"),
+            InvokeStatic | InvokeSpecial if *self.version < ClassFileVersion::Jdk8 => {
+                (reference.is_method_ref(), "MethodRef")
+            }
+            InvokeStatic | InvokeSpecial => (
+                reference.is_method_ref() || reference.is_interface_method_ref(),
+                "MethodRef or InterfaceMethodRef",
+            ),
+            InvokeInterface => (reference.is_interface_method_ref(), "InterfaceMethodRef"),
+        };
+        if !allowed {
+            return Err(ClassReaderError::UnexpectedConstItem(
+                self.raw(reference_index),
+                reference.kind_name(),
+                expected,
+            ));
+        }
+
+        let name_and_type_index = match reference {
+            ConstItem::FieldRef(field_ref) => field_ref.name_and_type_index,
+            ConstItem::MethodRef(method_ref) => method_ref.name_and_type_index,
+            ConstItem::InterfaceMethodRef(method_ref) => method_ref.name_and_type_index,
+            _ => unreachable!("reference kinds are checked above"),
+        };
+        let name = self
+            .expect(
+                name_and_type_index,
+                "NameAndType",
+                ConstItem::as_name_and_type,
+            )
+            .and_then(|name_and_type| self.utf8(name_and_type.name_index))?;
+        let valid_name = match kind {
+            NewInvokeSpecial => name.string == "<init>",
+            InvokeVirtual | InvokeStatic | InvokeSpecial | InvokeInterface => {
+                name.string != "<init>" && name.string != "<clinit>"
+            }
+            GetField | GetStatic | PutField | PutStatic => true,
+        };
+        if valid_name {
+            Ok(())
+        } else {
+            Err(ClassReaderError::InvalidMethodHandleReference(
+                self.raw(idx),
+            ))
+        }
+    }
+
+    fn requires(
+        &self,
+        idx: ConstItemIdx,
+        item: &ConstItem,
+        version: ClassFileVersion,
+    ) -> Result<()> {
+        if *self.version < version {
+            Err(ClassReaderError::UnsupportedConstItem(
+                self.raw(idx),
+                item.kind_name(),
+                self.version.clone(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn invalid_descriptor(&self, idx: ConstItemIdx, descriptor: &ConstUtf8) -> ClassReaderError {
+        ClassReaderError::InvalidDescriptor(self.raw(idx), descriptor.string.to_str().into_owned())
+    }
+}
+
+fn is_field_descriptor(descriptor: &JavaStr) -> bool {
+    FieldType::parse(descriptor).is_ok()
+}
+
+fn is_method_descriptor(descriptor: &JavaStr) -> bool {
+    MethodDescriptor::parse(descriptor).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        constants::{
+            CPMethodHandleReferenceKind, ConstClass, ConstFieldRef, ConstInterfaceMethodRef,
+            ConstItem, ConstItemIdx, ConstMethodHandle, ConstMethodRef, ConstNameAndType,
+            ConstString, ConstUtf8, Constants, validate,
+        },
+        error::ClassReaderError,
+        version::ClassFileVersion,
+    };
+
+    fn utf8(string: &str) -> ConstItem {
+        ConstItem::Utf8(ConstUtf8 {
+            string: string.into(),
+        })
+    }
+
+    #[test]
+    fn sample_class_is_valid() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        assert_eq!(Ok(()), class.validate_constants());
+    }
+
+    #[test]
+    fn string_must_point_to_utf8() {
+        let mut constants = Constants::new();
+        constants.push(ConstItem::Class(ConstClass {
+            name_index: ConstItemIdx::from_raw(2),
+        }));
+        constants.push(ConstItem::String(ConstString {
+            string_index: ConstItemIdx::from_raw(0),
+        }));
+        constants.push(utf8("Foo"));
+
+        assert_eq!(
+            Err(ClassReaderError::UnexpectedConstItem(1, "Class", "Utf8")),
+            validate(&constants, &ClassFileVersion::Jdk8)
+        );
+    }
+
+    #[test]
+    fn descriptors_are_checked() {
+        let mut constants = Constants::new();
+        constants.push(ConstItem::NameAndType(ConstNameAndType {
+            name_index: ConstItemIdx::from_raw(1),
+            descriptor_index: ConstItemIdx::from_raw(2),
+        }));
+        constants.push(utf8("foo"));
+        constants.push(utf8("Ljava/lang/String;"));
+        assert_eq!(Ok(()), validate(&constants, &ClassFileVersion::Jdk8));
+
+        constants[ConstItemIdx::from_raw(2)] = utf8("Ljava/lang/String");
+        assert_eq!(
+            Err(ClassReaderError::InvalidDescriptor(
+                3,
+                "Ljava/lang/String".into()
+            )),
+            validate(&constants, &ClassFileVersion::Jdk8)
+        );
+    }
+
+    #[test]
+    fn class_names_are_checked() {
+        for (name, valid) in [
+            ("java/lang/String", true),
+            ("[Ljava/lang/String;", true),
+            ("java.lang.String", false),
+            ("java//String", false),
+            ("java/lang/", false),
+            ("", false),
+            ("Foo;", false),
+        ] {
+            let mut constants = Constants::new();
+            constants.push(ConstItem::Class(ConstClass {
+                name_index: ConstItemIdx::from_raw(1),
+            }));
+            constants.push(utf8(name));
+            assert_eq!(
+                valid,
+                validate(&constants, &ClassFileVersion::Jdk8).is_ok(),
+                "{name}"
+            );
+        }
+    }
+
+    /// A MethodHandle of `kind` to the member `name` of class `Foo`, `reference` building the
+    /// member reference from the indices of the class and of the name and type
+    fn method_handle(
+        kind: CPMethodHandleReferenceKind,
+        reference: fn(ConstItemIdx, ConstItemIdx) -> ConstItem,
+        name: &str,
+    ) -> Constants {
+        let mut constants = Constants::new();
+        constants.push(ConstItem::MethodHandle(ConstMethodHandle {
+            reference_kind: kind,
+            reference_index: ConstItemIdx::from_raw(1),
+        }));
+        let reference = reference(ConstItemIdx::from_raw(2), ConstItemIdx::from_raw(3));
+        let descriptor = if reference.is_field_ref() { "I" } else { "()V" };
+        constants.push(reference);
+        constants.push(ConstItem::Class(ConstClass {
+            name_index: ConstItemIdx::from_raw(4),
+        }));
+        constants.push(ConstItem::NameAndType(ConstNameAndType {
+            name_index: ConstItemIdx::from_raw(5),
+            descriptor_index: ConstItemIdx::from_raw(6),
+        }));
+        constants.push(utf8("Foo"));
+        constants.push(utf8(name));
+        constants.push(utf8(descriptor));
+        constants
+    }
+
+    fn field_ref(class_index: ConstItemIdx, name_and_type_index: ConstItemIdx) -> ConstItem {
+        ConstItem::FieldRef(ConstFieldRef {
+            class_index,
+            name_and_type_index,
+        })
+    }
+
+    fn method_ref(class_index: ConstItemIdx, name_and_type_index: ConstItemIdx) -> ConstItem {
+        ConstItem::MethodRef(ConstMethodRef {
+            class_index,
+            name_and_type_index,
+        })
+    }
+
+    fn interface_method_ref(
+        interface_index: ConstItemIdx,
+        name_and_type_index: ConstItemIdx,
+    ) -> ConstItem {
+        ConstItem::InterfaceMethodRef(ConstInterfaceMethodRef {
+            interface_index,
+            name_and_type_index,
+        })
+    }
+
+    #[test]
+    fn method_handles_must_fit_their_kind() {
+        use CPMethodHandleReferenceKind::*;
+
+        let static_interface = method_handle(InvokeStatic, interface_method_ref, "run");
+        assert_eq!(Ok(()), validate(&static_interface, &ClassFileVersion::Jdk8));
+        for kind in [InvokeStatic, InvokeSpecial] {
+            let constants = method_handle(kind, interface_method_ref, "run");
+            assert_eq!(
+                Err(ClassReaderError::UnexpectedConstItem(
+                    2,
+                    "InterfaceMethodRef",
+                    "MethodRef"
+                )),
+                validate(&constants, &ClassFileVersion::Jdk7)
+            );
+        }
+
+        let constants = method_handle(GetField, method_ref, "run");
+        assert_eq!(
+            Err(ClassReaderError::UnexpectedConstItem(
+                2,
+                "MethodRef",
+                "FieldRef"
+            )),
+            validate(&constants, &ClassFileVersion::Jdk8)
+        );
+        let constants = method_handle(PutStatic, field_ref, "count");
+        assert_eq!(Ok(()), validate(&constants, &ClassFileVersion::Jdk8));
+    }
+
+    #[test]
+    fn method_handles_must_fit_the_method_name() {
+        use CPMethodHandleReferenceKind::*;
+
+        for (kind, name, valid) in [
+            (NewInvokeSpecial, "<init>", true),
+            (NewInvokeSpecial, "run", false),
+            (InvokeVirtual, "run", true),
+            (InvokeVirtual, "<init>", false),
+            (InvokeVirtual, "<clinit>", false),
+        ] {
+            let constants = method_handle(kind, method_ref, name);
+            let expected = if valid {
+                Ok(())
+            } else {
+                Err(ClassReaderError::InvalidMethodHandleReference(1))
+            };
+            assert_eq!(
+                expected,
+                validate(&constants, &ClassFileVersion::Jdk8),
+                "{name}"
+            );
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Field and method descriptors (JVMS 4.3)
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::java_str::{JavaStr, JavaString};
+
+/// Errors related to parsing a descriptor, with the offset of the faulty byte
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DescriptorError {
+    #[error("unexpected end of descriptor")]
+    UnexpectedEnd,
+
+    #[error("unexpected character at offset {0}")]
+    UnexpectedChar(usize),
+
+    #[error("invalid class name at offset {0}")]
+    InvalidClassName(usize),
+
+    #[error("more than 255 array dimensions at offset {0}")]
+    TooManyDimensions(usize),
+
+    #[error("unexpected data after the descriptor at offset {0}")]
+    TrailingData(usize),
+}
+
+type Result<T> = std::result::Result<T, DescriptorError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    pub(crate) fn from_char(c: u8) -> Option<Self> {
+        Some(match c {
+            b'B' => Self::Byte,
+            b'C' => Self::Char,
+            b'D' => Self::Double,
+            b'F' => Self::Float,
+            b'I' => Self::Int,
+            b'J' => Self::Long,
+            b'S' => Self::Short,
+            b'Z' => Self::Boolean,
+            _ => return None,
+        })
+    }
+
+    /// Character of the type in descriptors
+    pub fn descriptor_char(&self) -> char {
+        match self {
+            Self::Byte => 'B',
+            Self::Char => 'C',
+            Self::Double => 'D',
+            Self::Float => 'F',
+            Self::Int => 'I',
+            Self::Long => 'J',
+            Self::Short => 'S',
+            Self::Boolean => 'Z',
+        }
+    }
+
+    /// Keyword of the type in Java source
+    pub fn java_name(&self) -> &'static str {
+        match self {
+            Self::Byte => "byte",
+            Self::Char => "char",
+            Self::Double => "double",
+            Self::Float => "float",
+            Self::Int => "int",
+            Self::Long => "long",
+            Self::Short => "short",
+            Self::Boolean => "boolean",
+        }
+    }
+}
+
+/// The type of a field, a parameter or a return value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Base(BaseType),
+    /// A class or an interface, by its internal name such as `java/lang/String`
+    Object(JavaString),
+    /// An array with its number of dimensions and the type of its elements, which is never
+    /// an array itself
+    Array(u8, Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &JavaStr) -> Result<Self> {
+        let mut parser = Parser::new(descriptor.as_bytes());
+        let field_type = parser.field_type()?;
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    /// Number of local variable or operand stack slots taken by a value of this type
+    pub fn slots(&self) -> u16 {
+        match self {
+            Self::Base(BaseType::Long | BaseType::Double) => 2,
+            _ => 1,
+        }
+    }
+
+    /// Displays the type as in Java source, e.g. `java.lang.String[]`
+    pub fn java(&self) -> impl Display + '_ {
+        JavaSyntax(self)
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = DescriptorError;
+
+    fn from_str(descriptor: &str) -> Result<Self> {
+        Self::parse(&JavaString::from(descriptor))
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Base(base_type) => write!(f, "{}", base_type.descriptor_char()),
+            Self::Object(name) => write!(f, "L{};", name),
+            Self::Array(dimensions, element) => {
+                write!(f, "{}{}", "[".repeat(*dimensions as usize), element)
+            }
+        }
+    }
+}
+
+/// The parameters and return type of a method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    /// `None` for `void`
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &JavaStr) -> Result<Self> {
+        let mut parser = Parser::new(descriptor.as_bytes());
+        parser.expect(b'(')?;
+        let mut parameters = Vec::new();
+        while parser.peek()? != b')' {
+            parameters.push(parser.field_type()?);
+        }
+        parser.expect(b')')?;
+        let return_type = if parser.peek()? == b'V' {
+            parser.pos += 1;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.end()?;
+        Ok(Self {
+            parameters,
+            return_type,
+        })
+    }
+
+    /// Slots taken by the parameters, excluding `this`
+    pub fn parameter_slots(&self) -> u16 {
+        self.parameters.iter().map(FieldType::slots).sum()
+    }
+
+    /// Slots taken by the return value, 0 for `void`
+    pub fn return_slots(&self) -> u16 {
+        self.return_type.as_ref().map_or(0, FieldType::slots)
+    }
+
+    /// Displays the method as declared in Java source, e.g. `void run(int, java.lang.String)`
+    pub fn java<'a>(&'a self, name: &'a str) -> impl Display + 'a {
+        JavaMethod(self, name)
+    }
+}
+
+impl FromStr for MethodDescriptor {
+    type Err = DescriptorError;
+
+    fn from_str(descriptor: &str) -> Result<Self> {
+        Self::parse(&JavaString::from(descriptor))
+    }
+}
+
+impl Display for MethodDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{}", parameter)?;
+        }
+        match &self.return_type {
+            Some(return_type) => write!(f, "){}", return_type),
+            None => write!(f, ")V"),
+        }
+    }
+}
+
+struct JavaSyntax<'a>(&'a FieldType);
+
+impl Display for JavaSyntax<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            FieldType::Base(base_type) => write!(f, "{}", base_type.java_name()),
+            FieldType::Object(name) => write!(f, "{}", name.to_str().replace('/', ".")),
+            FieldType::Array(dimensions, element) => {
+                write!(f, "{}{}", element.java(), "[]".repeat(*dimensions as usize))
+            }
+        }
+    }
+}
+
+struct JavaMethod<'a>(&'a MethodDescriptor, &'a str);
+
+impl Display for JavaMethod<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self(descriptor, name) = self;
+        match &descriptor.return_type {
+            Some(return_type) => write!(f, "{} {}(", return_type.java(), name)?,
+            None => write!(f, "void {}(", name)?,
+        }
+        for (idx, parameter) in descriptor.parameters.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", parameter.java())?;
+        }
+        write!(f, ")")
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or(DescriptorError::UnexpectedEnd)
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if self.peek()? != c {
+            return Err(DescriptorError::UnexpectedChar(self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn end(&self) -> Result<()> {
+        if self.pos < self.bytes.len() {
+            return Err(DescriptorError::TrailingData(self.pos));
+        }
+        Ok(())
+    }
+
+    fn field_type(&mut self) -> Result<FieldType> {
+        let start = self.pos;
+        while self.peek()? == b'[' {
+            self.pos += 1;
+        }
+        let dimensions = u8::try_from(self.pos - start)
+            .map_err(|_| DescriptorError::TooManyDimensions(start))?;
+        let element = self.element_type()?;
+        Ok(match dimensions {
+            0 => element,
+            _ => FieldType::Array(dimensions, Box::new(element)),
+        })
+    }
+
+    fn element_type(&mut self) -> Result<FieldType> {
+        let c = self.peek()?;
+        if let Some(base_type) = BaseType::from_char(c) {
+            self.pos += 1;
+            return Ok(FieldType::Base(base_type));
+        }
+        if c != b'L' {
+            return Err(DescriptorError::UnexpectedChar(self.pos));
+        }
+        let start = self.pos + 1;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|&c| c == b';')
+            .ok_or(DescriptorError::UnexpectedEnd)?;
+        let name = &self.bytes[start..start + len];
+        let valid = !name.split(|&c| c == b'/').any(|part| part.is_empty())
+            && !name.iter().any(|c| matches!(c, b'.' | b'['));
+        let name = JavaStr::from_bytes(name)
+            .ok()
+            .filter(|_| valid)
+            .ok_or(DescriptorError::InvalidClassName(start))?;
+        self.pos = start + len + 1;
+        Ok(FieldType::Object(name.to_java_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BaseType, DescriptorError, FieldType, MethodDescriptor};
+
+    #[test]
+    fn descriptors_are_parsed_and_displayed() {
+        let descriptor: MethodDescriptor = "(IJ[[Ljava/lang/String;)D".parse().unwrap();
+        assert_eq!(
+            vec![
+                FieldType::Base(BaseType::Int),
+                FieldType::Base(BaseType::Long),
+                FieldType::Array(2, Box::new(FieldType::Object("java/lang/String".into()))),
+            ],
+            descriptor.parameters
+        );
+        assert_eq!(
+            (4, 2),
+            (descriptor.parameter_slots(), descriptor.return_slots())
+        );
+        assert_eq!("(IJ[[Ljava/lang/String;)D", descriptor.to_string());
+        assert_eq!(
+            "double run(int, long, java.lang.String[][])",
+            descriptor.java("run").to_string()
+        );
+
+        let void: MethodDescriptor = "()V".parse().unwrap();
+        assert_eq!(None, void.return_type);
+        assert_eq!("void run()", void.java("run").to_string());
+    }
+
+    #[test]
+    fn invalid_descriptors_are_rejected() {
+        let error = |descriptor: &str| descriptor.parse::<FieldType>().unwrap_err();
+        assert_eq!(DescriptorError::UnexpectedEnd, error("Ljava/lang/String"));
+        assert_eq!(DescriptorError::UnexpectedChar(0), error("V"));
+        assert_eq!(
+            DescriptorError::InvalidClassName(1),
+            error("Ljava.lang.String;")
+        );
+        assert_eq!(DescriptorError::InvalidClassName(2), error("[L;"));
+        assert_eq!(DescriptorError::TrailingData(1), error("II"));
+        assert_eq!(
+            DescriptorError::TooManyDimensions(0),
+            error(&format!("{}I", "[".repeat(256)))
+        );
+
+        let error = |descriptor: &str| descriptor.parse::<MethodDescriptor>().unwrap_err();
+        assert_eq!(DescriptorError::UnexpectedChar(1), error("(V)V"));
+        assert_eq!(DescriptorError::UnexpectedEnd, error("()"));
+    }
+}
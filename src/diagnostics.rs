@@ -0,0 +1,417 @@
+//! Reading malformed classes, see [ClassFile::read_with_diagnostics]
+
+use crate::{
+    ClassAccessFlags, ClassFile, Read, Result,
+    attribute::Attribute,
+    buffer::Buffer,
+    code::Code,
+    constants::{ConstIdxMap, ConstItemIdx, Constants},
+    error::{ClassReaderError, member_segment_in},
+    field::{Field, FieldAccessFlags},
+    limits::Limits,
+    method::{Method, MethodAccessFlags},
+};
+
+/// How [ClassFile::read_with_diagnostics] handles malformed data
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Fail on the first error instead of skipping or fixing up the malformed structure
+    pub strict: bool,
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Unusual data that does not prevent reading the class
+    Warning,
+    /// Malformed data, that was skipped or fixed up to go on reading
+    Error,
+}
+
+/// A problem found while reading a class
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: ClassReaderError,
+    /// Bytes of the member or attribute left out of the class because of the error
+    pub skipped: Option<Vec<u8>>,
+}
+
+impl ClassFile {
+    /// Reads a class, going on past the malformed structures that can be skipped or fixed up,
+    /// and reports what was found along the way.
+    ///
+    /// - Access flags with unknown bits are kept as they are.
+    /// - Members and attributes with invalid indices are left out, their bytes being kept in
+    ///   the [Diagnostic].
+    /// - `Code` attributes whose instructions cannot be decoded are kept, as all attributes,
+    ///   as raw bytes.
+    /// - A class cut short, or exceeding [ReadOptions::limits], is returned with the members
+    ///   and attributes read until then.
+    ///
+    /// Errors in the header and the constant pool, which the rest of the class depends on,
+    /// are still returned as errors, as are all errors when [ReadOptions::strict] is set.
+    pub fn read_with_diagnostics(
+        bytes: &[u8],
+        options: &ReadOptions,
+    ) -> Result<(Self, Vec<Diagnostic>)> {
+        let mut recovery = Recovery {
+            bytes,
+            options,
+            diagnostics: Vec::new(),
+        };
+        let mut buf = Buffer::with_limits(bytes, options.limits);
+        let class = recovery.read_class(&mut buf);
+        let class = class.map_err(|error| error.at(buf.get_pos()))?;
+        Ok((class, recovery.diagnostics))
+    }
+}
+
+/// Fields common to fields and methods
+struct Member {
+    flags: u16,
+    name_index: ConstItemIdx,
+    descriptor_index: ConstItemIdx,
+    attributes: Vec<Attribute>,
+    segment: String,
+}
+
+struct Recovery<'a> {
+    bytes: &'a [u8],
+    options: &'a ReadOptions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Recovery<'a> {
+    fn read_class(&mut self, buf: &mut Buffer<'a>) -> Result<ClassFile> {
+        ClassFile::check_magic_number(buf)?;
+        let (version, minor_version) = ClassFile::read_version(buf)?;
+        let (constants, idx_map) = ClassFile::read_constants(buf)?;
+        let flags = buf.read_u16()?;
+        if ClassAccessFlags::from_bits(flags).is_none() {
+            let error = ClassReaderError::InvalidClassAccessFlags(flags).at(buf.get_pos() - 2);
+            self.report(Severity::Error, error, None)?;
+        }
+        let this_class =
+            ConstItemIdx::read(buf, &idx_map).map_err(|error| error.within("this_class"))?;
+
+        let mut class = ClassFile {
+            version,
+            minor_version,
+            constants,
+            access_flag: ClassAccessFlags::from_bits_retain(flags),
+            this_class,
+            super_class: None,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        };
+        match self.read_body(buf, &mut class, &idx_map) {
+            Ok(()) if buf.has_more_data() => {
+                let len = self.bytes.len() - buf.get_pos();
+                let error = ClassReaderError::TrailingData(len).at(buf.get_pos());
+                self.report(Severity::Warning, error, None)?;
+            }
+            Ok(()) => {}
+            // Only truncated data and exceeded limits stop the reading, other errors being
+            // recovered from
+            Err(error) => self.report(Severity::Error, error.at(buf.get_pos()), None)?,
+        }
+        Ok(class)
+    }
+
+    /// Reads what follows `this_class` into `class`, as long as there is data
+    fn read_body(
+        &mut self,
+        buf: &mut Buffer<'a>,
+        class: &mut ClassFile,
+        idx_map: &ConstIdxMap,
+    ) -> Result<()> {
+        let super_class = Option::<ConstItemIdx>::read(buf, idx_map);
+        let super_class = super_class.map_err(|error| error.within("super_class"));
+        class.super_class = self.recover(super_class)?.flatten();
+
+        for idx in 0..buf.read_u16()? {
+            let interface = ConstItemIdx::read(buf, idx_map);
+            let interface = interface.map_err(|error| error.within(format!("interfaces[{idx}]")));
+            class.interfaces.extend(self.recover(interface)?);
+        }
+
+        for idx in 0..buf.read_u16()? {
+            let start = buf.get_pos();
+            let Some(member) = self.read_member(buf, &class.constants, idx_map, "field", idx)?
+            else {
+                continue;
+            };
+            if FieldAccessFlags::from_bits(member.flags).is_none() {
+                let error = ClassReaderError::InvalidFieldAccessFlags(member.flags);
+                self.report(
+                    Severity::Error,
+                    error.at(start).within(member.segment),
+                    None,
+                )?;
+            }
+            buf.allocate(size_of::<Field>())?;
+            class.fields.push(Field {
+                access_flag: FieldAccessFlags::from_bits_retain(member.flags),
+                name_index: member.name_index,
+                descriptor_index: member.descriptor_index,
+                attributes: member.attributes,
+            });
+        }
+
+        for idx in 0..buf.read_u16()? {
+            let start = buf.get_pos();
+            let Some(member) = self.read_member(buf, &class.constants, idx_map, "method", idx)?
+            else {
+                continue;
+            };
+            if MethodAccessFlags::from_bits(member.flags).is_none() {
+                let error = ClassReaderError::InvalidMethodAccessFlags(member.flags);
+                self.report(
+                    Severity::Error,
+                    error.at(start).within(member.segment),
+                    None,
+                )?;
+            }
+            buf.allocate(size_of::<Method>())?;
+            class.methods.push(Method {
+                access_flags: MethodAccessFlags::from_bits_retain(member.flags),
+                name_index: member.name_index,
+                descriptor_index: member.descriptor_index,
+                attributes: member.attributes,
+            });
+        }
+
+        class.attributes = self.read_attributes(buf, &class.constants, idx_map, None)?;
+        Ok(())
+    }
+
+    /// Reads a field or a method, or skips it if its name or descriptor is invalid
+    fn read_member(
+        &mut self,
+        buf: &mut Buffer<'a>,
+        constants: &Constants,
+        idx_map: &ConstIdxMap,
+        kind: &str,
+        idx: u16,
+    ) -> Result<Option<Member>> {
+        let start = buf.get_pos();
+        let flags = buf.read_u16()?;
+        let name_index = stops_reading(ConstItemIdx::read(buf, idx_map))?;
+        let descriptor_index = stops_reading(ConstItemIdx::read(buf, idx_map))?;
+        let segment = member_segment_in(
+            constants,
+            kind,
+            idx,
+            name_index.as_ref().ok().copied(),
+            descriptor_index.as_ref().ok().copied(),
+        );
+        let attributes = self.read_attributes(buf, constants, idx_map, Some(&segment))?;
+
+        match (name_index, descriptor_index) {
+            (Ok(name_index), Ok(descriptor_index)) => Ok(Some(Member {
+                flags,
+                name_index,
+                descriptor_index,
+                attributes,
+                segment,
+            })),
+            (Err(error), _) | (_, Err(error)) => {
+                let skipped = &self.bytes[start..buf.get_pos()];
+                self.report(Severity::Error, error.within(segment), Some(skipped))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads a table of attributes, skipping those whose name is invalid
+    fn read_attributes(
+        &mut self,
+        buf: &mut Buffer<'a>,
+        constants: &Constants,
+        idx_map: &ConstIdxMap,
+        segment: Option<&str>,
+    ) -> Result<Vec<Attribute>> {
+        let within = |error: ClassReaderError, idx: u16| {
+            let error = error.within(format!("[{idx}]")).within("attributes");
+            match segment {
+                Some(segment) => error.within(segment),
+                None => error,
+            }
+        };
+
+        let count = buf.read_u16()?;
+        let capacity = (count as usize).min(buf.remaining());
+        buf.allocate(capacity * size_of::<Attribute>())?;
+        let mut attributes = Vec::with_capacity(capacity);
+        for idx in 0..count {
+            let start = buf.get_pos();
+            let attribute_name_index = stops_reading(ConstItemIdx::read(buf, idx_map))?;
+            let len = Attribute::read_length(buf)?;
+            let info_start = buf.get_pos();
+            let info = buf.read_bytes(len)?;
+            let attribute_name_index = match attribute_name_index {
+                Ok(attribute_name_index) => attribute_name_index,
+                Err(error) => {
+                    let skipped = &self.bytes[start..buf.get_pos()];
+                    self.report(Severity::Error, within(error, idx), Some(skipped))?;
+                    continue;
+                }
+            };
+
+            buf.allocate(len)?;
+            let attribute = Attribute {
+                attribute_name_index,
+                info: info.to_vec(),
+            };
+            if attribute.name(constants).is_some_and(|name| name == "Code")
+                && let Err(error) = Code::read(info, idx_map)
+            {
+                let error = error.relative_to(Some(info_start)).within("attribute Code");
+                let error = match segment {
+                    Some(segment) => error.within(segment),
+                    None => error,
+                };
+                self.report(Severity::Error, error, None)?;
+            }
+            attributes.push(attribute);
+        }
+        Ok(attributes)
+    }
+
+    /// Turns a recoverable error into `None` once reported
+    fn recover<T>(&mut self, result: Result<T>) -> Result<Option<T>> {
+        match stops_reading(result)? {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => {
+                self.report(Severity::Error, error, None)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Records a problem, unless reading is strict, in which case errors are returned
+    fn report(
+        &mut self,
+        severity: Severity,
+        error: ClassReaderError,
+        skipped: Option<&[u8]>,
+    ) -> Result<()> {
+        if self.options.strict && severity == Severity::Error {
+            return Err(error);
+        }
+        self.diagnostics.push(Diagnostic {
+            severity,
+            error,
+            skipped: skipped.map(Vec::from),
+        });
+        Ok(())
+    }
+}
+
+/// Returns truncated data and exceeded limits as errors, other errors being kept to be
+/// recovered from
+fn stops_reading<T>(result: Result<T>) -> Result<Result<T>> {
+    match result {
+        Err(error)
+            if matches!(
+                error.kind(),
+                ClassReaderError::UnexpectedEndOfData | ClassReaderError::LimitExceeded(_)
+            ) =>
+        {
+            Err(error)
+        }
+        result => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        error::ClassReaderError,
+        reader::{ClassReader, skip_member},
+    };
+
+    use super::{ReadOptions, Severity};
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    /// Positions of the access flags and of the first two methods of the sample
+    fn positions() -> (usize, usize, usize) {
+        let reader = ClassReader::new(SAMPLE).unwrap();
+        let mut buf = reader.buffer_at(reader.header_pos() + 6);
+        let interfaces = buf.read_u16().unwrap();
+        buf.read_bytes(2 * interfaces as usize).unwrap();
+        for _ in 0..buf.read_u16().unwrap() {
+            skip_member(&mut buf).unwrap();
+        }
+        buf.read_u16().unwrap();
+        let method = buf.get_pos();
+        skip_member(&mut buf).unwrap();
+        (reader.header_pos(), method, buf.get_pos())
+    }
+
+    #[test]
+    fn malformed_structures_are_skipped() {
+        let (flags, method, next) = positions();
+        let mut bytes = SAMPLE.to_vec();
+        bytes[flags] |= 0x80;
+        bytes[method + 2..method + 4].copy_from_slice(&[0xFF, 0xFF]);
+        bytes.push(0);
+
+        let (class, diagnostics) =
+            ClassFile::read_with_diagnostics(&bytes, &ReadOptions::default()).unwrap();
+        let errors: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.error.kind()))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    Severity::Error,
+                    &ClassReaderError::InvalidClassAccessFlags(0x8021)
+                ),
+                (
+                    Severity::Error,
+                    &ClassReaderError::InvalidConstantPoolIdx(0xFFFF)
+                ),
+                (Severity::Warning, &ClassReaderError::TrailingData(1)),
+            ],
+            errors
+        );
+        assert_eq!(
+            Some(&bytes[method..next]),
+            diagnostics[1].skipped.as_deref()
+        );
+        assert_eq!(0x8021, class.access_flag.bits());
+        assert_eq!(5, class.methods.len());
+
+        let strict = ReadOptions {
+            strict: true,
+            ..ReadOptions::default()
+        };
+        let error = ClassFile::read_with_diagnostics(&bytes, &strict).unwrap_err();
+        assert_eq!(
+            &ClassReaderError::InvalidClassAccessFlags(0x8021),
+            error.kind()
+        );
+        assert_eq!(Some(flags), error.context().unwrap().offset);
+    }
+
+    #[test]
+    fn truncated_classes_are_partially_read() {
+        let (_, _, next) = positions();
+        let (class, diagnostics) =
+            ClassFile::read_with_diagnostics(&SAMPLE[..next + 3], &ReadOptions::default()).unwrap();
+        assert_eq!(6, class.fields.len());
+        assert_eq!(1, class.methods.len());
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(
+            &ClassReaderError::UnexpectedEndOfData,
+            diagnostics[0].error.kind()
+        );
+    }
+}
@@ -1,15 +1,44 @@
 use std::{
     error::Error,
     fmt::{Display, Formatter},
+    io,
 };
 
-use crate::buffer::BufferError;
+use crate::{
+    buffer::BufferError,
+    constants::{ConstItemIdx, Constants},
+    java_str::JavaStr,
+    version::ClassFileVersion,
+};
+
+/// Where an error was found in a class file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Position of the faulty data from the start of the class file, when the class bytes
+    /// were at hand
+    pub offset: Option<usize>,
+    /// Structures being read, from the outermost, e.g. ``method `foo(I)V` `` then
+    /// `attribute Code` then `exception_table[2]`
+    pub path: Vec<String>,
+}
 
 /// Models the possible errors returned when reading a .class file
 #[derive(Debug, PartialEq, Eq)]
 pub enum ClassReaderError {
     InvalidMagicBytes(u32),
     InvalidConstantPoolIdx(u16),
+    /// `constant_pool_count` that is 0, or that a long or a double in the last slot overruns
+    InvalidConstantPoolCount(u16),
+    /// Constant pool index, kind found and kind expected
+    UnexpectedConstItem(u16, &'static str, &'static str),
+    /// Constant pool index of the malformed descriptor
+    InvalidDescriptor(u16, String),
+    /// Constant pool index of a Class whose name is neither a binary name nor an array descriptor
+    InvalidClassName(u16, String),
+    /// Constant pool index of a MethodHandle whose reference does not fit its reference kind
+    InvalidMethodHandleReference(u16),
+    /// Constant pool index of an item that is not allowed in this class file version
+    UnsupportedConstItem(u16, &'static str, ClassFileVersion),
     InvalidMethodHandleReferenceKind(u8),
     InvalidConstItemTag(u8),
     InvalidClassAccessFlags(u16),
@@ -17,7 +46,131 @@ pub enum ClassReaderError {
     InvalidMethodAccessFlags(u16),
     UnexpectedEndOfData,
     InvalidCesu8String,
+    InvalidOpcode(u8),
+    InvalidArrayType(u8),
+    /// pc of a switch whose bounds or number of cases are invalid
+    InvalidSwitch(u32),
+    /// pc of an instruction whose operand does not fit in its encoding
+    OperandOutOfRange(u32),
+    /// pc of an instruction that does not follow the previous one
+    InvalidInstructionOffset(u32),
+    /// Label used in code without being placed
+    UndefinedLabel(u32),
+    /// Code length, or pc used by an exception handler, beyond the 65535 bytes code may have
+    CodeTooLarge(u32),
+    /// Major version of a class with code that needs the `StackMapTable` frames that are not
+    /// computed
+    StackMapTableRequired(u16),
+    /// Number of slots of a constant pool too large for a class file
+    TooManyConstants(usize),
     UnsupportedVersion(u16, u16),
+    /// Tag of an annotation element value
+    InvalidElementValueTag(u8),
+    /// Kind and message of an error raised while loading the class bytes
+    Io(io::ErrorKind, String),
+    /// Number of bytes following the end of the class
+    TrailingData(usize),
+    /// Name of the field of [Limits](crate::limits::Limits) that was exceeded
+    LimitExceeded(&'static str),
+    /// An error along with where it was found, see [ClassReaderError::context]
+    InContext(Box<ClassReaderError>, ErrorContext),
+}
+
+impl ClassReaderError {
+    /// Where the error was found, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::InContext(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context
+    pub fn kind(&self) -> &ClassReaderError {
+        match self {
+            Self::InContext(error, _) => error,
+            error => error,
+        }
+    }
+
+    /// Records the offset of the error, unless a more precise one is already known
+    pub(crate) fn at(self, offset: usize) -> Self {
+        match self {
+            Self::InContext(error, mut context) => {
+                context.offset.get_or_insert(offset);
+                Self::InContext(error, context)
+            }
+            error => Self::InContext(
+                Box::new(error),
+                ErrorContext {
+                    offset: Some(offset),
+                    path: Vec::new(),
+                },
+            ),
+        }
+    }
+
+    /// Adds the structure the error was found in to its path.
+    ///
+    /// Indices such as `[2]` are appended to the name of their table.
+    pub(crate) fn within(self, segment: impl Into<String>) -> Self {
+        let mut segment = segment.into();
+        match self {
+            Self::InContext(error, mut context) => {
+                match context.path.first_mut() {
+                    Some(first) if first.starts_with('[') => {
+                        segment.push_str(first);
+                        *first = segment;
+                    }
+                    _ => context.path.insert(0, segment),
+                }
+                Self::InContext(error, context)
+            }
+            error => Self::InContext(
+                Box::new(error),
+                ErrorContext {
+                    offset: None,
+                    path: vec![segment],
+                },
+            ),
+        }
+    }
+
+    /// Turns the offset of an error found in data starting at `base` into an offset from the
+    /// start of the class file, or forgets it when `base` is unknown
+    pub(crate) fn relative_to(self, base: Option<usize>) -> Self {
+        match self {
+            Self::InContext(error, mut context) => {
+                context.offset = context.offset.zip(base).map(|(offset, base)| offset + base);
+                Self::InContext(error, context)
+            }
+            error => error,
+        }
+    }
+}
+
+/// Segment of an error path naming a field or a method, e.g. ``method `foo(I)V` ``
+pub(crate) fn member_segment(kind: &str, name: &JavaStr, descriptor: &JavaStr) -> String {
+    match kind {
+        "method" => format!("method `{}{}`", name, descriptor),
+        _ => format!("{} `{}`", kind, name),
+    }
+}
+
+/// Segment of an error path naming the `idx`-th field or method of a class, by its name and
+/// descriptor if their indices point to Utf8 constants, or else by its position
+pub(crate) fn member_segment_in(
+    constants: &Constants,
+    kind: &str,
+    idx: u16,
+    name_index: Option<ConstItemIdx>,
+    descriptor_index: Option<ConstItemIdx>,
+) -> String {
+    let utf8 = |idx: Option<ConstItemIdx>| constants.get(idx?)?.as_utf8().map(|item| &*item.string);
+    match (utf8(name_index), utf8(descriptor_index)) {
+        (Some(name), Some(descriptor)) => member_segment(kind, name, descriptor),
+        _ => format!("{kind}s[{idx}]"),
+    }
 }
 
 impl Display for ClassReaderError {
@@ -33,6 +186,44 @@ impl Display for ClassReaderError {
             ClassReaderError::InvalidConstantPoolIdx(idx) => {
                 write!(f, "Invalid ConstantPool index `{}`", idx)
             }
+            ClassReaderError::InvalidConstantPoolCount(count) => {
+                write!(f, "Invalid constant_pool_count `{}`", count)
+            }
+            ClassReaderError::UnexpectedConstItem(idx, found, expected) => {
+                write!(
+                    f,
+                    "ConstantPool entry `{}` is a {} (expected {})",
+                    idx, found, expected
+                )
+            }
+            ClassReaderError::InvalidDescriptor(idx, descriptor) => {
+                write!(
+                    f,
+                    "Invalid descriptor `{}` in ConstantPool entry `{}`",
+                    descriptor, idx
+                )
+            }
+            ClassReaderError::InvalidClassName(idx, name) => {
+                write!(
+                    f,
+                    "Invalid class name `{}` in ConstantPool entry `{}`",
+                    name, idx
+                )
+            }
+            ClassReaderError::InvalidMethodHandleReference(idx) => {
+                write!(
+                    f,
+                    "MethodHandle `{}` references a member not allowed for its reference_kind",
+                    idx
+                )
+            }
+            ClassReaderError::UnsupportedConstItem(idx, kind, version) => {
+                write!(
+                    f,
+                    "ConstantPool entry `{}` is a {}, which is not supported by {}",
+                    idx, kind, version
+                )
+            }
             ClassReaderError::InvalidMethodHandleReferenceKind(val) => {
                 write!(
                     f,
@@ -62,9 +253,75 @@ impl Display for ClassReaderError {
             ClassReaderError::InvalidCesu8String => {
                 write!(f, "Invalid cesu8 string")
             }
+            ClassReaderError::InvalidOpcode(opcode) => {
+                write!(f, "Invalid opcode `0x{:02X}`", opcode)
+            }
+            ClassReaderError::InvalidArrayType(atype) => {
+                write!(
+                    f,
+                    "Invalid value `{}` for newarray atype (not in range 4..=11)",
+                    atype
+                )
+            }
+            ClassReaderError::InvalidSwitch(pc) => {
+                write!(f, "Invalid switch at pc {}", pc)
+            }
+            ClassReaderError::OperandOutOfRange(pc) => {
+                write!(f, "Operand of instruction at pc {} is out of range", pc)
+            }
+            ClassReaderError::InvalidInstructionOffset(pc) => {
+                write!(f, "Instruction at pc {} overlaps the previous one", pc)
+            }
+            ClassReaderError::UndefinedLabel(label) => {
+                write!(f, "Label `{}` is used but never placed", label)
+            }
+            ClassReaderError::CodeTooLarge(pc) => {
+                write!(
+                    f,
+                    "Code reaches pc {} (at most 65535 bytes are allowed)",
+                    pc
+                )
+            }
+            ClassReaderError::StackMapTableRequired(major) => {
+                write!(
+                    f,
+                    "Code with branches or exception handlers needs a StackMapTable in class \
+                     files of version {}, which is not computed",
+                    major
+                )
+            }
+            ClassReaderError::InvalidElementValueTag(tag) => {
+                write!(f, "Invalid annotation element tag `0x{:02X}`", tag)
+            }
+            ClassReaderError::TooManyConstants(slots) => {
+                write!(
+                    f,
+                    "ConstantPool has {} slots (at most 65535 are allowed)",
+                    slots
+                )
+            }
             ClassReaderError::UnsupportedVersion(major, minor) => {
                 write!(f, "Unsupported class file version {major}.{minor}")
             }
+            ClassReaderError::Io(_, message) => {
+                write!(f, "I/O error: {}", message)
+            }
+            ClassReaderError::TrailingData(len) => {
+                write!(f, "Unexpected {} bytes after the end of the class", len)
+            }
+            ClassReaderError::LimitExceeded(limit) => {
+                write!(f, "The `{}` limit is exceeded", limit)
+            }
+            ClassReaderError::InContext(error, context) => {
+                let mut location = Vec::new();
+                if let Some(offset) = context.offset {
+                    location.push(format!("at byte 0x{:X}", offset));
+                }
+                if !context.path.is_empty() {
+                    location.push(format!("in {}", context.path.join(" → ")));
+                }
+                write!(f, "{}: {}", location.join(" "), error)
+            }
         }
     }
 }
@@ -78,6 +335,13 @@ impl From<BufferError> for ClassReaderError {
         match err {
             BufferError::UnexpectedEndOfData => Self::UnexpectedEndOfData,
             BufferError::InvalidCesu8String => Self::InvalidCesu8String,
+            BufferError::LimitExceeded(limit) => Self::LimitExceeded(limit),
         }
     }
 }
+
+impl From<io::Error> for ClassReaderError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err.kind(), err.to_string())
+    }
+}
@@ -1,7 +1,10 @@
 use bitflags::bitflags;
 
 use crate::{
-    Read, Result, attribute::Attribute, buffer::Buffer, constants::ConstItemIdx,
+    Read, Result, Write,
+    attribute::Attribute,
+    buffer::{Buffer, BufferWriter},
+    constants::{ConstIdxMap, ConstItemIdx, Constants},
     error::ClassReaderError,
 };
 
@@ -28,7 +31,7 @@ impl Default for FieldAccessFlags {
 }
 
 impl FieldAccessFlags {
-    fn read(buf: &mut Buffer) -> Result<Self> {
+    pub(crate) fn read(buf: &mut Buffer) -> Result<Self> {
         let num = buf.read_u16()?;
         Self::from_bits(num).ok_or(ClassReaderError::InvalidFieldAccessFlags(num))
     }
@@ -42,12 +45,18 @@ pub struct Field {
     pub attributes: Vec<Attribute>,
 }
 
+impl Field {
+    pub fn find_attribute(&self, constants: &Constants, name: &str) -> Option<&Attribute> {
+        Attribute::find(&self.attributes, constants, name)
+    }
+}
+
 impl Read for Field {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
         let access_flag = FieldAccessFlags::read(buf)?;
-        let name_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let descriptor_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let attributes = Vec::read(buf, consts_count, empty_const_slots)?;
+        let name_index = ConstItemIdx::read(buf, idx_map)?;
+        let descriptor_index = ConstItemIdx::read(buf, idx_map)?;
+        let attributes = Vec::read(buf, idx_map).map_err(|error| error.within("attributes"))?;
 
         Ok(Self {
             access_flag,
@@ -57,3 +66,12 @@ impl Read for Field {
         })
     }
 }
+
+impl Write for Field {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u16(self.access_flag.bits());
+        self.name_index.write(buf, idx_map);
+        self.descriptor_index.write(buf, idx_map);
+        self.attributes.write(buf, idx_map);
+    }
+}
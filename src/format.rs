@@ -0,0 +1,777 @@
+//! Format checking (JVMS 4.8), see [ClassFile::check_format]
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{
+    ClassAccessFlags, ClassFile,
+    attribute::Attribute,
+    constants::{ConstIdxMap, ConstItem, ConstItemIdx, Constants},
+    descriptor::{BaseType, FieldType, MethodDescriptor},
+    error::{ClassReaderError, member_segment},
+    field::{Field, FieldAccessFlags},
+    java_str::JavaStr,
+    method::{Method, MethodAccessFlags},
+    names::{is_valid_binary_name, is_valid_method_name, is_valid_unqualified_name},
+    version::ClassFileVersion,
+};
+
+/// A violation of the static rules of the class file format.
+///
+/// `target` names the structure at fault, e.g. `class`, ``field `count` `` or
+/// ``method `run()V` ``.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// An invalid constant pool, or an index to a constant of the wrong kind
+    #[error(transparent)]
+    Constants(#[from] ClassReaderError),
+
+    #[error("invalid name `{name}` for {target}")]
+    InvalidName { target: String, name: String },
+
+    #[error("invalid descriptor `{descriptor}` for {target}")]
+    InvalidDescriptor { target: String, descriptor: String },
+
+    #[error("illegal access flags 0x{flags:04X} for {target}: {reason}")]
+    IllegalAccessFlags {
+        target: String,
+        flags: u16,
+        reason: &'static str,
+    },
+
+    #[error("invalid super class: {0}")]
+    InvalidSuperClass(&'static str),
+
+    #[error("duplicate interface `{0}`")]
+    DuplicateInterface(String),
+
+    #[error("duplicate {0}")]
+    DuplicateMember(String),
+
+    #[error("invalid {target}: {reason}")]
+    InvalidSpecialMethod {
+        target: String,
+        reason: &'static str,
+    },
+
+    #[error("attribute {attribute} is not allowed in {target}")]
+    MisplacedAttribute { target: String, attribute: String },
+
+    #[error("more than one {attribute} attribute in {target}")]
+    DuplicateAttribute { target: String, attribute: String },
+
+    /// Code is missing from a concrete method, or present in an abstract or native one
+    #[error("{target} {reason}")]
+    InvalidCode {
+        target: String,
+        reason: &'static str,
+    },
+
+    #[error("ConstantValue of {target} is a {found}, which does not match its type")]
+    InvalidConstantValue { target: String, found: &'static str },
+}
+
+type Result<T> = std::result::Result<T, FormatError>;
+
+/// Structures holding attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Class,
+    Field,
+    Method,
+    Code,
+}
+
+/// Where a predefined attribute may appear (JVMS 4.7), and whether it may appear more than
+/// once there. Other attributes are allowed anywhere.
+fn attribute_rules(name: &str) -> Option<(&'static [Location], bool)> {
+    use Location::*;
+
+    const ANYWHERE: &[Location] = &[Class, Field, Method];
+    Some(match name {
+        "ConstantValue" => (&[Field], false),
+        "Code" | "Exceptions" | "AnnotationDefault" | "MethodParameters" => (&[Method], false),
+        "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+            (&[Method], false)
+        }
+        "StackMapTable" => (&[Code], false),
+        "LineNumberTable" | "LocalVariableTable" | "LocalVariableTypeTable" => (&[Code], true),
+        "SourceFile"
+        | "SourceDebugExtension"
+        | "InnerClasses"
+        | "EnclosingMethod"
+        | "BootstrapMethods"
+        | "NestHost"
+        | "NestMembers"
+        | "Module"
+        | "ModulePackages"
+        | "ModuleMainClass"
+        | "Record"
+        | "PermittedSubclasses" => (&[Class], false),
+        "Signature" | "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+            (ANYWHERE, false)
+        }
+        "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+            (&[Class, Field, Method, Code], false)
+        }
+        "Synthetic" | "Deprecated" => (ANYWHERE, true),
+        _ => return None,
+    })
+}
+
+impl ClassFile {
+    /// Checks the static rules of the class file format (JVMS 4.8), returning the first
+    /// violation found.
+    ///
+    /// Besides [ClassFile::validate_constants], this checks that names and descriptors are
+    /// well-formed, that access flags are consistent, that members are unique, that `<init>`
+    /// and `<clinit>` have the right shape and that predefined attributes only appear where
+    /// they are allowed.
+    pub fn check_format(&self) -> Result<()> {
+        self.validate_constants()?;
+        let checker = Checker {
+            class: self,
+            idx_map: self.const_idx_map()?,
+            interface: self.access_flag.contains(ClassAccessFlags::INTERFACE),
+        };
+        checker.check_constants()?;
+        checker.check_header()?;
+        checker.check_attributes(&self.attributes, Location::Class, "class")?;
+
+        let mut fields = HashSet::new();
+        for field in &self.fields {
+            let name = checker.utf8(field.name_index)?;
+            let descriptor = checker.utf8(field.descriptor_index)?;
+            let target = member_segment("field", name, descriptor);
+            if !fields.insert((name, descriptor)) {
+                return Err(FormatError::DuplicateMember(target));
+            }
+            checker.check_field(field, name, descriptor, &target)?;
+        }
+
+        let mut methods = HashSet::new();
+        for method in &self.methods {
+            let name = checker.utf8(method.name_index)?;
+            let descriptor = checker.utf8(method.descriptor_index)?;
+            let target = member_segment("method", name, descriptor);
+            if !methods.insert((name, descriptor)) {
+                return Err(FormatError::DuplicateMember(target));
+            }
+            checker.check_method(method, name, descriptor, &target)?;
+        }
+        Ok(())
+    }
+}
+
+struct Checker<'a> {
+    class: &'a ClassFile,
+    idx_map: ConstIdxMap,
+    interface: bool,
+}
+
+impl<'a> Checker<'a> {
+    fn constants(&self) -> &'a Constants {
+        &self.class.constants
+    }
+
+    fn version(&self) -> &'a ClassFileVersion {
+        &self.class.version
+    }
+
+    fn get(&self, idx: ConstItemIdx) -> Result<&'a ConstItem> {
+        let raw = self.idx_map.raw_past_end(idx);
+        let item = self.constants().get(idx);
+        Ok(item.ok_or(ClassReaderError::InvalidConstantPoolIdx(raw))?)
+    }
+
+    fn expect<T>(
+        &self,
+        idx: ConstItemIdx,
+        expected: &'static str,
+        select: impl Fn(&'a ConstItem) -> Option<&'a T>,
+    ) -> Result<&'a T> {
+        let item = self.get(idx)?;
+        select(item).ok_or_else(|| {
+            let raw = self.idx_map.raw_past_end(idx);
+            ClassReaderError::UnexpectedConstItem(raw, item.kind_name(), expected).into()
+        })
+    }
+
+    fn utf8(&self, idx: ConstItemIdx) -> Result<&'a JavaStr> {
+        self.expect(idx, "Utf8", ConstItem::as_utf8)
+            .map(|utf8| &*utf8.string)
+    }
+
+    fn class_name(&self, idx: ConstItemIdx) -> Result<&'a JavaStr> {
+        let class = self.expect(idx, "Class", ConstItem::as_class)?;
+        self.utf8(class.name_index)
+    }
+
+    /// Checks the names of classes and member references (JVMS 4.4.1, 4.4.2)
+    fn check_constants(&self) -> Result<()> {
+        for (idx, item) in self.constants().iter_enumerated() {
+            let target = || format!("constant pool entry #{}", self.idx_map.raw_past_end(idx));
+            let (name_and_type_index, is_valid_name): (_, fn(&str) -> bool) = match item {
+                ConstItem::Class(class) => {
+                    let name = self.utf8(class.name_index)?.to_str();
+                    // Array descriptors are checked along with the constant pool
+                    if !name.starts_with('[') && !is_valid_binary_name(&name) {
+                        return Err(invalid_name(target(), &name));
+                    }
+                    continue;
+                }
+                ConstItem::FieldRef(field_ref) => {
+                    (field_ref.name_and_type_index, is_valid_unqualified_name)
+                }
+                ConstItem::MethodRef(method_ref) => (method_ref.name_and_type_index, |name| {
+                    is_valid_method_name(name) && name != "<clinit>"
+                }),
+                ConstItem::InterfaceMethodRef(method_ref) => {
+                    (method_ref.name_and_type_index, is_ordinary_method_name)
+                }
+                ConstItem::InvokeDynamic(invoke_dynamic) => {
+                    (invoke_dynamic.name_and_type_index, is_ordinary_method_name)
+                }
+                _ => continue,
+            };
+            let name_and_type = self.expect(
+                name_and_type_index,
+                "NameAndType",
+                ConstItem::as_name_and_type,
+            )?;
+            let name = self.utf8(name_and_type.name_index)?.to_str();
+            if !is_valid_name(&name) {
+                return Err(invalid_name(target(), &name));
+            }
+            let descriptor = self.utf8(name_and_type.descriptor_index)?;
+            if name == "<init>" && !descriptor.as_bytes().ends_with(b")V") {
+                return Err(FormatError::InvalidSpecialMethod {
+                    target: target(),
+                    reason: "<init> must return void",
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the access flags, the name, the super class and the interfaces of the class
+    fn check_header(&self) -> Result<()> {
+        let class = self.class;
+        let flags = &class.access_flag;
+        let illegal = |reason| FormatError::IllegalAccessFlags {
+            target: "class".to_string(),
+            flags: flags.bits(),
+            reason,
+        };
+        if self.interface {
+            if !flags.contains(ClassAccessFlags::ABSTRACT) {
+                return Err(illegal("an interface must be abstract"));
+            }
+            if flags.intersects(
+                ClassAccessFlags::FINAL | ClassAccessFlags::SUPER | ClassAccessFlags::ENUM,
+            ) {
+                return Err(illegal("an interface cannot be final, super or an enum"));
+            }
+        } else {
+            if flags.contains(ClassAccessFlags::ANNOTATION) {
+                return Err(illegal("an annotation must be an interface"));
+            }
+            if flags.contains(ClassAccessFlags::FINAL | ClassAccessFlags::ABSTRACT) {
+                return Err(illegal("a class cannot be both final and abstract"));
+            }
+        }
+
+        let name = self.class_name(class.this_class)?.to_str();
+        if !is_valid_binary_name(&name) {
+            return Err(invalid_name("class".to_string(), &name));
+        }
+        match class.super_class {
+            None if name != "java/lang/Object" => {
+                return Err(FormatError::InvalidSuperClass(
+                    "only java/lang/Object has no super class",
+                ));
+            }
+            Some(super_class) => {
+                let super_name = self.class_name(super_class)?;
+                if super_name.as_bytes().starts_with(b"[") {
+                    return Err(FormatError::InvalidSuperClass(
+                        "an array cannot be extended",
+                    ));
+                }
+                if self.interface && *super_name != "java/lang/Object" {
+                    return Err(FormatError::InvalidSuperClass(
+                        "the super class of an interface must be java/lang/Object",
+                    ));
+                }
+            }
+            None => {}
+        }
+
+        let mut interfaces = HashSet::new();
+        for &interface in &class.interfaces {
+            let interface = self.class_name(interface)?;
+            if !interfaces.insert(interface) {
+                return Err(FormatError::DuplicateInterface(interface.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_field(
+        &self,
+        field: &Field,
+        name: &JavaStr,
+        descriptor: &JavaStr,
+        target: &str,
+    ) -> Result<()> {
+        if !is_valid_unqualified_name(&name.to_str()) {
+            return Err(invalid_name(target.to_string(), &name.to_str()));
+        }
+        let Ok(field_type) = FieldType::parse(descriptor) else {
+            return Err(invalid_descriptor(target, descriptor));
+        };
+
+        let flags = &field.access_flag;
+        let illegal = |reason| FormatError::IllegalAccessFlags {
+            target: target.to_string(),
+            flags: flags.bits(),
+            reason,
+        };
+        let visibility =
+            FieldAccessFlags::PUBLIC | FieldAccessFlags::PRIVATE | FieldAccessFlags::PROTECTED;
+        if flags.clone().intersection(visibility).bits().count_ones() > 1 {
+            return Err(illegal(
+                "at most one of public, private and protected is allowed",
+            ));
+        }
+        if flags.contains(FieldAccessFlags::FINAL | FieldAccessFlags::VOLATILE) {
+            return Err(illegal("a field cannot be both final and volatile"));
+        }
+        if self.interface {
+            let required =
+                FieldAccessFlags::PUBLIC | FieldAccessFlags::STATIC | FieldAccessFlags::FINAL;
+            if !flags.contains(required.clone())
+                || !(required | FieldAccessFlags::SYNTHETIC).contains(flags.clone())
+            {
+                return Err(illegal(
+                    "an interface field must be public, static and final",
+                ));
+            }
+        }
+
+        self.check_attributes(&field.attributes, Location::Field, target)?;
+        if let Some(attribute) = field.find_attribute(self.constants(), "ConstantValue") {
+            self.check_constant_value(attribute, &field_type, target)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the value of a `ConstantValue` attribute has the type of its field
+    fn check_constant_value(
+        &self,
+        attribute: &Attribute,
+        field_type: &FieldType,
+        target: &str,
+    ) -> Result<()> {
+        let raw = match attribute.info[..] {
+            [high, low] => u16::from_be_bytes([high, low]),
+            [_] | [] => return Err(ClassReaderError::UnexpectedEndOfData.into()),
+            _ => return Err(ClassReaderError::TrailingData(attribute.info.len() - 2).into()),
+        };
+        let value = self.get(self.idx_map.resolve(raw)?)?;
+        let matches = match field_type {
+            FieldType::Base(BaseType::Long) => matches!(value, ConstItem::Long(_)),
+            FieldType::Base(BaseType::Float) => matches!(value, ConstItem::Float(_)),
+            FieldType::Base(BaseType::Double) => matches!(value, ConstItem::Double(_)),
+            FieldType::Base(_) => matches!(value, ConstItem::Integer(_)),
+            FieldType::Object(name) if *name == "java/lang/String" => {
+                matches!(value, ConstItem::String(_))
+            }
+            _ => false,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(FormatError::InvalidConstantValue {
+                target: target.to_string(),
+                found: value.kind_name(),
+            })
+        }
+    }
+
+    fn check_method(
+        &self,
+        method: &Method,
+        name: &JavaStr,
+        descriptor: &JavaStr,
+        target: &str,
+    ) -> Result<()> {
+        let name = name.to_str();
+        if !is_valid_method_name(&name) {
+            return Err(invalid_name(target.to_string(), &name));
+        }
+        let Ok(method_descriptor) = MethodDescriptor::parse(descriptor) else {
+            return Err(invalid_descriptor(target, descriptor));
+        };
+
+        let flags = &method.access_flags;
+        let illegal = |reason| FormatError::IllegalAccessFlags {
+            target: target.to_string(),
+            flags: flags.bits(),
+            reason,
+        };
+        let special = |reason| FormatError::InvalidSpecialMethod {
+            target: target.to_string(),
+            reason,
+        };
+        let visibility =
+            MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE | MethodAccessFlags::PROTECTED;
+        if flags
+            .clone()
+            .intersection(visibility.clone())
+            .bits()
+            .count_ones()
+            > 1
+        {
+            return Err(illegal(
+                "at most one of public, private and protected is allowed",
+            ));
+        }
+
+        if name == "<clinit>" {
+            // Other flags of class initialization methods are ignored
+            if *descriptor != "()V" {
+                return Err(special("<clinit> must take no arguments and return void"));
+            }
+            if *self.version() >= ClassFileVersion::Jdk7
+                && !flags.contains(MethodAccessFlags::STATIC)
+            {
+                return Err(illegal("<clinit> must be static"));
+            }
+        } else if name == "<init>" {
+            if self.interface {
+                return Err(special("an interface cannot have an <init> method"));
+            }
+            if method_descriptor.return_type.is_some() {
+                return Err(special("<init> must return void"));
+            }
+            let allowed = visibility
+                | MethodAccessFlags::VARARGS
+                | MethodAccessFlags::STRICT
+                | MethodAccessFlags::SYNTHETIC;
+            if !allowed.contains(flags.clone()) {
+                return Err(illegal(
+                    "<init> may only be varargs, strict or synthetic besides its visibility",
+                ));
+            }
+        } else if self.interface {
+            self.check_interface_method_flags(flags).map_err(illegal)?;
+        }
+
+        if flags.contains(MethodAccessFlags::ABSTRACT) {
+            let mut forbidden = MethodAccessFlags::PRIVATE
+                | MethodAccessFlags::STATIC
+                | MethodAccessFlags::FINAL
+                | MethodAccessFlags::SYNCHRONIZED
+                | MethodAccessFlags::NATIVE;
+            let version = self.version();
+            if *version >= ClassFileVersion::Jdk1_2 && *version < ClassFileVersion::Jdk17 {
+                forbidden |= MethodAccessFlags::STRICT;
+            }
+            if flags.intersects(forbidden) {
+                return Err(illegal(
+                    "an abstract method cannot be private, static, final, synchronized, native \
+                     or strict",
+                ));
+            }
+        }
+
+        self.check_attributes(&method.attributes, Location::Method, target)?;
+        let has_code = method.find_attribute(self.constants(), "Code").is_some();
+        let is_concrete =
+            !flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::NATIVE);
+        match (is_concrete, has_code) {
+            (true, false) => {
+                return Err(FormatError::InvalidCode {
+                    target: target.to_string(),
+                    reason: "has no Code attribute",
+                });
+            }
+            (false, true) => {
+                return Err(FormatError::InvalidCode {
+                    target: target.to_string(),
+                    reason: "is abstract or native, but has a Code attribute",
+                });
+            }
+            _ => {}
+        }
+        if let Some(code) = method.code(self.constants(), &self.idx_map)? {
+            let target = format!("attribute Code of {target}");
+            self.check_attributes(&code.attributes, Location::Code, &target)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the flags of a method of an interface, other than `<clinit>`
+    fn check_interface_method_flags(
+        &self,
+        flags: &MethodAccessFlags,
+    ) -> std::result::Result<(), &'static str> {
+        if *self.version() < ClassFileVersion::Jdk8 {
+            let required = MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT;
+            let allowed = required.clone()
+                | MethodAccessFlags::VARARGS
+                | MethodAccessFlags::BRIDGE
+                | MethodAccessFlags::SYNTHETIC;
+            if !flags.contains(required) || !allowed.contains(flags.clone()) {
+                return Err("an interface method must be public and abstract before Java 8");
+            }
+        } else {
+            if flags.intersects(
+                MethodAccessFlags::PROTECTED
+                    | MethodAccessFlags::FINAL
+                    | MethodAccessFlags::SYNCHRONIZED
+                    | MethodAccessFlags::NATIVE,
+            ) {
+                return Err(
+                    "an interface method cannot be protected, final, synchronized or \
+                            native",
+                );
+            }
+            let visibility = flags
+                .clone()
+                .intersection(MethodAccessFlags::PUBLIC | MethodAccessFlags::PRIVATE);
+            if visibility.bits().count_ones() != 1 {
+                return Err("an interface method must be either public or private");
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the predefined attributes in `attributes` are allowed at `location`, and
+    /// are not repeated unless they may be
+    fn check_attributes(
+        &self,
+        attributes: &[Attribute],
+        location: Location,
+        target: &str,
+    ) -> Result<()> {
+        let mut seen = HashSet::new();
+        for attribute in attributes {
+            let name = self.utf8(attribute.attribute_name_index)?;
+            let Some((locations, repeatable)) = attribute_rules(&name.to_str()) else {
+                continue;
+            };
+            if !locations.contains(&location) {
+                return Err(FormatError::MisplacedAttribute {
+                    target: target.to_string(),
+                    attribute: name.to_string(),
+                });
+            }
+            if !repeatable && !seen.insert(name) {
+                return Err(FormatError::DuplicateAttribute {
+                    target: target.to_string(),
+                    attribute: name.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` is a valid name for a method other than `<init>` and `<clinit>`
+fn is_ordinary_method_name(name: &str) -> bool {
+    is_valid_method_name(name) && !name.starts_with('<')
+}
+
+fn invalid_name(target: String, name: &str) -> FormatError {
+    FormatError::InvalidName {
+        target,
+        name: name.to_string(),
+    }
+}
+
+fn invalid_descriptor(target: &str, descriptor: &JavaStr) -> FormatError {
+    FormatError::InvalidDescriptor {
+        target: target.to_string(),
+        descriptor: descriptor.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassAccessFlags, ClassFile, builder::ClassBuilder, code::Instruction,
+        field::FieldAccessFlags, format::FormatError, method::MethodAccessFlags,
+        version::ClassFileVersion,
+    };
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn compiled_classes_are_well_formed() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        assert_eq!(Ok(()), class.check_format());
+    }
+
+    #[test]
+    fn violations_are_reported() {
+        let check = |build: &dyn Fn(&mut ClassBuilder)| {
+            let mut builder = ClassBuilder::new("Test");
+            build(&mut builder);
+            builder.build().unwrap().check_format().unwrap_err()
+        };
+
+        let error = check(&|class| {
+            class.access_flags(ClassAccessFlags::FINAL | ClassAccessFlags::ABSTRACT);
+        });
+        assert!(matches!(error, FormatError::IllegalAccessFlags { .. }));
+
+        let error = check(&|class| {
+            class
+                .field("count", "I", FieldAccessFlags::PRIVATE, |_| {})
+                .field("count", "I", FieldAccessFlags::PUBLIC, |_| {});
+        });
+        assert_eq!(
+            FormatError::DuplicateMember("field `count`".to_string()),
+            error
+        );
+
+        let error = check(&|class| {
+            class.method("<init>", "()I", MethodAccessFlags::PUBLIC, |method| {
+                method.code(|code| {
+                    code.instruction(Instruction::Iconst0)
+                        .instruction(Instruction::Ireturn);
+                });
+            });
+        });
+        assert_eq!(
+            "invalid method `<init>()I`: <init> must return void",
+            error.to_string()
+        );
+
+        let error = check(&|class| {
+            class.method("run", "()V", MethodAccessFlags::PUBLIC, |_| {});
+        });
+        assert!(matches!(error, FormatError::InvalidCode { .. }));
+
+        let error = check(&|class| {
+            class.field("NAME", "J", FieldAccessFlags::STATIC, |field| {
+                field.attribute("Code", Vec::new());
+            });
+        });
+        assert_eq!(
+            "attribute Code is not allowed in field `NAME`",
+            error.to_string()
+        );
+    }
+
+    /// Checks an interface of `version` with a method `name` with `flags`, which has code
+    /// unless it is abstract, returning the reason its flags are rejected
+    fn check_interface_method(
+        version: ClassFileVersion,
+        name: &str,
+        flags: MethodAccessFlags,
+    ) -> Result<(), &'static str> {
+        let mut builder = ClassBuilder::new("Api");
+        builder
+            .version(version, 0)
+            .access_flags(ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT);
+        let has_code = !flags.contains(MethodAccessFlags::ABSTRACT);
+        builder.method(name, "()V", flags, |method| {
+            if has_code {
+                method.code(|code| {
+                    code.instruction(Instruction::Return);
+                });
+            }
+        });
+        builder
+            .build()
+            .unwrap()
+            .check_format()
+            .map_err(|error| match error {
+                FormatError::IllegalAccessFlags { reason, .. } => reason,
+                error => panic!("unexpected error: {error}"),
+            })
+    }
+
+    #[test]
+    fn interface_methods_are_public_and_abstract_before_java_8() {
+        use MethodAccessFlags as F;
+
+        let reason = Err("an interface method must be public and abstract before Java 8");
+        for (flags, expected) in [
+            (F::PUBLIC | F::ABSTRACT, Ok(())),
+            (F::PUBLIC | F::ABSTRACT | F::VARARGS, Ok(())),
+            (F::PUBLIC, reason),
+            (F::PRIVATE, reason),
+            (F::PUBLIC | F::STATIC, reason),
+        ] {
+            assert_eq!(
+                expected,
+                check_interface_method(ClassFileVersion::Jdk7, "run", flags.clone()),
+                "{flags:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn interface_methods_may_have_code_from_java_8() {
+        use MethodAccessFlags as F;
+
+        for (flags, expected) in [
+            (F::PUBLIC | F::ABSTRACT, Ok(())),
+            (F::PUBLIC, Ok(())),
+            (F::PRIVATE, Ok(())),
+            (F::PUBLIC | F::STATIC, Ok(())),
+            (F::PRIVATE | F::STATIC, Ok(())),
+            (
+                F::PUBLIC | F::FINAL,
+                Err("an interface method cannot be protected, final, synchronized or native"),
+            ),
+            (
+                F::PROTECTED | F::ABSTRACT,
+                Err("an interface method cannot be protected, final, synchronized or native"),
+            ),
+            (
+                F::ABSTRACT,
+                Err("an interface method must be either public or private"),
+            ),
+        ] {
+            assert_eq!(
+                expected,
+                check_interface_method(ClassFileVersion::Jdk8, "run", flags.clone()),
+                "{flags:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn interface_initializers_follow_the_class_rules() {
+        let static_flags = MethodAccessFlags::STATIC;
+        for version in [ClassFileVersion::Jdk6, ClassFileVersion::Jdk8] {
+            assert_eq!(
+                Ok(()),
+                check_interface_method(version, "<clinit>", static_flags.clone())
+            );
+        }
+        // Before version 51, the flags of <clinit> are ignored
+        assert_eq!(
+            Ok(()),
+            check_interface_method(
+                ClassFileVersion::Jdk6,
+                "<clinit>",
+                MethodAccessFlags::empty()
+            )
+        );
+        assert_eq!(
+            Err("<clinit> must be static"),
+            check_interface_method(
+                ClassFileVersion::Jdk7,
+                "<clinit>",
+                MethodAccessFlags::empty()
+            )
+        );
+    }
+}
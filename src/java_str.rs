@@ -0,0 +1,289 @@
+use std::{
+    borrow::{Borrow, Cow},
+    fmt::{Debug, Display, Formatter},
+    ops::Deref,
+};
+
+use cesu8::to_java_cesu8;
+
+use crate::buffer::BufferError;
+
+/// A string in the modified UTF-8 encoding used by class files (JVMS 4.4.7).
+///
+/// The raw bytes are kept untouched, so strings that are not valid Unicode (e.g. containing
+/// unpaired surrogates) can still be read, compared and written back byte for byte.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct JavaStr([u8]);
+
+/// The owned counterpart of [JavaStr]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JavaString(Vec<u8>);
+
+/// Length of the well-formed modified UTF-8 sequence at the start of `bytes`
+fn sequence_len(bytes: &[u8]) -> Option<usize> {
+    let is_continuation = |idx: usize| bytes.get(idx).is_some_and(|b| b & 0xC0 == 0x80);
+    match bytes[0] {
+        0x01..=0x7F => Some(1),
+        // NUL is the only character encoded on more bytes than needed
+        0xC0 if bytes.get(1) == Some(&0x80) => Some(2),
+        0xC2..=0xDF if is_continuation(1) => Some(2),
+        0xE0..=0xEF if is_continuation(1) && is_continuation(2) => Some(3),
+        _ => None,
+    }
+}
+
+impl JavaStr {
+    /// Wraps bytes that are already known to be well-formed modified UTF-8
+    fn from_bytes_unchecked(bytes: &[u8]) -> &JavaStr {
+        // SAFETY: JavaStr is a transparent wrapper around [u8]
+        unsafe { &*(bytes as *const [u8] as *const JavaStr) }
+    }
+
+    /// Checks that `bytes` is well-formed modified UTF-8.
+    ///
+    /// Surrogates do not have to be paired, as the JVM does not require it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<&JavaStr, BufferError> {
+        let mut pos = 0;
+        while pos < bytes.len() {
+            pos += sequence_len(&bytes[pos..]).ok_or(BufferError::InvalidCesu8String)?;
+        }
+        Ok(Self::from_bytes_unchecked(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the string without copying it, if it is also valid UTF-8.
+    ///
+    /// This is the case for every string without NUL characters nor characters outside of the
+    /// Basic Multilingual Plane, which covers the vast majority of names and descriptors.
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// Decodes the string into UTF-16 code units, the way the JVM sees it
+    pub fn to_utf16(&self) -> Vec<u16> {
+        let mut units = Vec::with_capacity(self.0.len());
+        let mut pos = 0;
+        while pos < self.0.len() {
+            let bytes = &self.0[pos..];
+            let len = sequence_len(bytes).expect("JavaStr is always well-formed");
+            let unit = match len {
+                1 => bytes[0] as u16,
+                2 => (bytes[0] as u16 & 0x1F) << 6 | (bytes[1] as u16 & 0x3F),
+                _ => {
+                    (bytes[0] as u16 & 0x0F) << 12
+                        | (bytes[1] as u16 & 0x3F) << 6
+                        | (bytes[2] as u16 & 0x3F)
+                }
+            };
+            units.push(unit);
+            pos += len;
+        }
+        units
+    }
+
+    /// Converts the string into a Rust string, replacing unpaired surrogates with
+    /// `U+FFFD REPLACEMENT CHARACTER`
+    pub fn to_str(&self) -> Cow<'_, str> {
+        match self.as_str() {
+            Some(str) => Cow::Borrowed(str),
+            None => Cow::Owned(String::from_utf16_lossy(&self.to_utf16())),
+        }
+    }
+
+    pub fn to_java_string(&self) -> JavaString {
+        JavaString(self.0.to_vec())
+    }
+}
+
+impl JavaString {
+    /// Checks that `bytes` is well-formed modified UTF-8, see [JavaStr::from_bytes]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<JavaString, BufferError> {
+        JavaStr::from_bytes(&bytes)?;
+        Ok(JavaString(bytes))
+    }
+
+    /// Encodes UTF-16 code units, which may contain unpaired surrogates
+    pub fn from_utf16(units: &[u16]) -> JavaString {
+        let mut bytes = Vec::with_capacity(units.len());
+        for &unit in units {
+            match unit {
+                0x0001..=0x007F => bytes.push(unit as u8),
+                0x0000 | 0x0080..=0x07FF => {
+                    bytes.push(0xC0 | (unit >> 6) as u8);
+                    bytes.push(0x80 | (unit & 0x3F) as u8);
+                }
+                _ => {
+                    bytes.push(0xE0 | (unit >> 12) as u8);
+                    bytes.push(0x80 | (unit >> 6 & 0x3F) as u8);
+                    bytes.push(0x80 | (unit & 0x3F) as u8);
+                }
+            }
+        }
+        JavaString(bytes)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for JavaString {
+    type Target = JavaStr;
+
+    fn deref(&self) -> &JavaStr {
+        JavaStr::from_bytes_unchecked(&self.0)
+    }
+}
+
+impl Borrow<JavaStr> for JavaString {
+    fn borrow(&self) -> &JavaStr {
+        self
+    }
+}
+
+impl ToOwned for JavaStr {
+    type Owned = JavaString;
+
+    fn to_owned(&self) -> JavaString {
+        self.to_java_string()
+    }
+}
+
+impl From<&str> for JavaString {
+    fn from(string: &str) -> JavaString {
+        JavaString(to_java_cesu8(string).into_owned())
+    }
+}
+
+impl From<String> for JavaString {
+    fn from(string: String) -> JavaString {
+        match to_java_cesu8(&string) {
+            Cow::Borrowed(_) => JavaString(string.into_bytes()),
+            Cow::Owned(bytes) => JavaString(bytes),
+        }
+    }
+}
+
+impl From<&JavaStr> for JavaString {
+    fn from(string: &JavaStr) -> JavaString {
+        string.to_java_string()
+    }
+}
+
+impl PartialEq<str> for JavaStr {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == *to_java_cesu8(other)
+    }
+}
+
+impl PartialEq<&str> for JavaStr {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<str> for JavaString {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<&str> for JavaString {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+impl PartialEq<JavaString> for &str {
+    fn eq(&self, other: &JavaString) -> bool {
+        other == self
+    }
+}
+
+impl Display for JavaStr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.to_str(), f)
+    }
+}
+
+impl Debug for JavaStr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.to_str(), f)
+    }
+}
+
+impl Display for JavaString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl Debug for JavaString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        buffer::BufferError,
+        java_str::{JavaStr, JavaString},
+    };
+
+    #[test]
+    fn nul_and_supplementary_characters_use_modified_encoding() {
+        let string = JavaString::from("a\0😀");
+
+        assert_eq!(
+            &[0x61, 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80],
+            string.as_bytes()
+        );
+        assert_eq!(None, string.as_str());
+        assert_eq!("a\0😀", string.to_str());
+        assert_eq!(string, "a\0😀");
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_kept() {
+        let bytes = [0x78, 0xED, 0xA0, 0x80];
+        let string = JavaStr::from_bytes(&bytes).unwrap();
+
+        assert_eq!(vec![0x78, 0xD800], string.to_utf16());
+        assert_eq!("x\u{FFFD}", string.to_str());
+        assert_eq!(
+            &bytes,
+            JavaString::from_utf16(&string.to_utf16()).as_bytes()
+        );
+    }
+
+    #[test]
+    fn malformed_sequences_are_rejected() {
+        for bytes in [
+            &[0x00][..],
+            &[0xF0, 0x9F, 0x98, 0x80],
+            &[0xC3],
+            &[0xC1, 0x81],
+            &[0xC0, 0x81],
+            &[0xE0, 0x80, 0x41],
+        ] {
+            assert_eq!(
+                Err(BufferError::InvalidCesu8String),
+                JavaStr::from_bytes(bytes)
+            );
+        }
+    }
+}
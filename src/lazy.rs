@@ -0,0 +1,219 @@
+use std::cell::OnceCell;
+
+use crate::{
+    ClassAccessFlags, ClassFile, Read, Result,
+    attribute::Attribute,
+    buffer::Buffer,
+    constants::{ConstItemIdx, Constants},
+    error::ClassReaderError,
+    field::Field,
+    java_str::JavaStr,
+    method::Method,
+    reader::{ClassReader, skip_member},
+};
+
+/// A class whose members and attributes are decoded when they are first accessed.
+///
+/// Reading only locates the constant pool entries and the start of every field and method,
+/// and decodes the header. Errors in the rest of the class are reported by the accessor that
+/// decodes the faulty part, which then keeps failing with the same error.
+///
+/// ```
+/// use classfile::lazy::LazyClassFile;
+///
+/// # let bytes = classfile::builder::ClassBuilder::new("Empty").build().unwrap().write();
+/// let class = LazyClassFile::read(&bytes)?;
+/// if let Some(method) = class.find_method("<init>", "()V")? {
+///     let constants = class.constants()?;
+///     let code = method.code(constants, class.reader().idx_map())?;
+/// }
+/// # Ok::<(), classfile::error::ClassReaderError>(())
+/// ```
+#[derive(Debug)]
+pub struct LazyClassFile<'a> {
+    reader: ClassReader<'a>,
+    pub access_flag: ClassAccessFlags,
+    pub this_class: ConstItemIdx,
+    pub super_class: Option<ConstItemIdx>,
+    pub interfaces: Vec<ConstItemIdx>,
+    constants: OnceCell<Constants>,
+    fields: Vec<Lazy<Field>>,
+    methods: Vec<Lazy<Method>>,
+    attributes: Lazy<Vec<Attribute>>,
+}
+
+/// A part of the class, decoded from `offset` on first access
+#[derive(Debug)]
+struct Lazy<T> {
+    offset: usize,
+    value: OnceCell<T>,
+}
+
+impl<T: Read> Lazy<T> {
+    fn new(offset: usize) -> Self {
+        Self {
+            offset,
+            value: OnceCell::new(),
+        }
+    }
+
+    fn get(&self, reader: &ClassReader) -> Result<&T> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = T::read(&mut reader.buffer_at(self.offset), reader.idx_map())?;
+        Ok(self.value.get_or_init(|| value))
+    }
+}
+
+impl<'a> LazyClassFile<'a> {
+    pub fn read(bytes: &'a [u8]) -> Result<Self> {
+        let reader = ClassReader::new(bytes)?;
+        let mut buf = reader.buffer_at(reader.header_pos());
+        let flags = buf.read_u16()?;
+        let access_flag = ClassAccessFlags::from_bits(flags)
+            .ok_or(ClassReaderError::InvalidClassAccessFlags(flags))?;
+        let this_class = ConstItemIdx::read(&mut buf, reader.idx_map())?;
+        let super_class = Option::<ConstItemIdx>::read(&mut buf, reader.idx_map())?;
+        let interfaces = Vec::read(&mut buf, reader.idx_map())?;
+        let fields = index_members(&mut buf)?;
+        let methods = index_members(&mut buf)?;
+        let attributes = Lazy::new(buf.get_pos());
+
+        Ok(Self {
+            reader,
+            access_flag,
+            this_class,
+            super_class,
+            interfaces,
+            constants: OnceCell::new(),
+            fields,
+            methods,
+            attributes,
+        })
+    }
+
+    /// The reader locating the constant pool entries, which also gives access to single
+    /// entries without decoding the others
+    pub fn reader(&self) -> &ClassReader<'a> {
+        &self.reader
+    }
+
+    /// Internal name of the class
+    pub fn name(&self) -> Result<&'a JavaStr> {
+        self.reader.class_name(self.this_class)
+    }
+
+    pub fn constants(&self) -> Result<&Constants> {
+        if let Some(constants) = self.constants.get() {
+            return Ok(constants);
+        }
+        let constants = self.reader.constants()?;
+        Ok(self.constants.get_or_init(|| constants))
+    }
+
+    pub fn fields_count(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Decodes the field at `idx`.
+    ///
+    /// Panics if `idx` is not lower than [LazyClassFile::fields_count].
+    pub fn field(&self, idx: usize) -> Result<&Field> {
+        self.fields[idx].get(&self.reader)
+    }
+
+    pub fn methods_count(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// Decodes the method at `idx`.
+    ///
+    /// Panics if `idx` is not lower than [LazyClassFile::methods_count].
+    pub fn method(&self, idx: usize) -> Result<&Method> {
+        self.methods[idx].get(&self.reader)
+    }
+
+    /// Decodes the first method with the given name and descriptor, the other methods only
+    /// having their name and descriptor read
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Result<Option<&Method>> {
+        for (idx, method) in self.methods.iter().enumerate() {
+            let mut buf = self.reader.buffer_at(method.offset + 2);
+            let idx_map = self.reader.idx_map();
+            if self.reader.utf8(ConstItemIdx::read(&mut buf, idx_map)?)? == name
+                && self.reader.utf8(ConstItemIdx::read(&mut buf, idx_map)?)? == descriptor
+            {
+                return self.method(idx).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decodes the attributes of the class
+    pub fn attributes(&self) -> Result<&[Attribute]> {
+        self.attributes.get(&self.reader).map(Vec::as_slice)
+    }
+
+    /// Decodes everything that was not yet, giving the same class as [ClassFile::read]
+    pub fn to_class_file(&self) -> Result<ClassFile> {
+        Ok(ClassFile {
+            version: self.reader.version().clone(),
+            minor_version: self.reader.minor_version(),
+            constants: self.constants()?.clone(),
+            access_flag: self.access_flag.clone(),
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces.clone(),
+            fields: (0..self.fields_count())
+                .map(|idx| self.field(idx).cloned())
+                .collect::<Result<_>>()?,
+            methods: (0..self.methods_count())
+                .map(|idx| self.method(idx).cloned())
+                .collect::<Result<_>>()?,
+            attributes: self.attributes()?.to_vec(),
+        })
+    }
+}
+
+/// Records the start of every member of a table of fields or methods
+fn index_members<T: Read>(buf: &mut Buffer) -> Result<Vec<Lazy<T>>> {
+    let count = buf.read_u16()?;
+    let mut members = Vec::with_capacity((count as usize).min(buf.remaining()));
+    for _ in 0..count {
+        members.push(Lazy::new(buf.get_pos()));
+        skip_member(buf)?;
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ClassFile, lazy::LazyClassFile};
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn lazy_classes_decode_to_the_same_class() {
+        let class = LazyClassFile::read(SAMPLE).unwrap();
+        assert_eq!(class.name().unwrap(), "Sample");
+        assert_eq!(6, class.methods_count());
+        assert_eq!(SAMPLE, class.to_class_file().unwrap().write());
+    }
+
+    #[test]
+    fn errors_are_reported_on_access() {
+        // Make the last attribute of the class run past the end of the bytes
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let mut bytes = SAMPLE.to_vec();
+        let len = bytes.len() - class.attributes.last().unwrap().info.len() - 4;
+        bytes[len..len + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let class = LazyClassFile::read(&bytes).unwrap();
+        let compute = class
+            .find_method("compute", "(IJDLjava/lang/Comparable;)I")
+            .unwrap()
+            .unwrap();
+        assert!(!compute.attributes.is_empty());
+        assert!(class.attributes().is_err());
+    }
+}
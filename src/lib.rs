@@ -1,39 +1,78 @@
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 pub mod attribute;
+pub mod borrowed;
 pub mod buffer;
+pub mod builder;
+pub mod cfg;
+pub mod code;
 pub mod constants;
+pub mod descriptor;
+pub mod diagnostics;
 pub mod error;
 pub mod field;
+pub mod format;
+pub mod java_str;
+pub mod lazy;
+pub mod limits;
 pub mod method;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod names;
 pub mod reader;
+pub mod signature;
+pub mod transform;
+pub mod verifier;
 pub mod version;
+pub mod visitor;
+
+use std::{fs, io, path::Path};
 
 use attribute::Attribute;
 use bitflags::bitflags;
-use buffer::Buffer;
-use constants::{ConstItem, ConstItemIdx, Constants};
-use error::ClassReaderError;
+use buffer::{Buffer, BufferWriter};
+use constants::{ConstIdxMap, ConstItem, ConstItemIdx, Constants};
+use error::{ClassReaderError, member_segment_in};
 use field::Field;
-use index_vec::IndexVec;
 use method::Method;
 
 pub use error::Result;
 use version::ClassFileVersion;
 
+/// Items that can be read from a class file, once its constant pool is known
 pub trait Read: Sized {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self>;
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self>;
 }
 
 impl<T: Read> Read for Vec<T> {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
         let count = buf.read_u16()?;
-        let mut vec = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            vec.push(T::read(buf, consts_count, empty_const_slots)?);
+        // Every item takes at least a byte, which bounds what a forged count can reserve
+        let capacity = (count as usize).min(buf.remaining());
+        buf.allocate(capacity * size_of::<T>())?;
+        let mut vec = Vec::with_capacity(capacity);
+        for idx in 0..count {
+            let item = T::read(buf, idx_map);
+            vec.push(item.map_err(|error| error.at(buf.get_pos()).within(format!("[{idx}]")))?);
         }
         Ok(vec)
     }
 }
 
+/// Items that can be written to a class file, the counterpart of [Read]
+pub trait Write {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap);
+}
+
+impl<T: Write> Write for Vec<T> {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_len_u16(self.len());
+        for item in self {
+            item.write(buf, idx_map);
+        }
+    }
+}
+
 bitflags! {
     /// Class flags
     #[derive(Debug, Clone)]
@@ -58,6 +97,7 @@ impl Default for ClassAccessFlags {
 #[derive(Debug, Clone)]
 pub struct ClassFile {
     pub version: ClassFileVersion,
+    pub minor_version: u16,
     pub constants: Constants,
     pub access_flag: ClassAccessFlags,
     pub this_class: ConstItemIdx,
@@ -66,26 +106,36 @@ pub struct ClassFile {
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     pub attributes: Vec<Attribute>,
-    pub empty_const_slots: Vec<u16>,
 }
 
 impl ClassFile {
+    /// Reads a class from its bytes.
+    ///
+    /// Errors carry the offset of the faulty data and the structures it was found in, see
+    /// [ClassReaderError::context].
     pub fn read(buf: &[u8]) -> Result<Self> {
         let mut buf = Buffer::new(buf);
-        Self::check_magic_number(&mut buf)?;
-        let version = Self::read_version(&mut buf)?;
-        let (constants, empty_const_slots) = Self::read_constants(&mut buf)?;
-        let consts_count = constants.len() as u16;
-        let access_flag = Self::read_access_flags(&mut buf)?;
-        let this_class = ConstItemIdx::read(&mut buf, consts_count, &empty_const_slots)?;
-        let super_class = Option::<ConstItemIdx>::read(&mut buf, consts_count, &empty_const_slots)?;
-        let interfaces = Vec::read(&mut buf, consts_count, &empty_const_slots)?;
-        let fields = Vec::read(&mut buf, consts_count, &empty_const_slots)?;
-        let methods = Vec::read(&mut buf, consts_count, &empty_const_slots)?;
-        let attributes = Vec::read(&mut buf, consts_count, &empty_const_slots)?;
+        let class = Self::read_class(&mut buf);
+        class.map_err(|error| error.at(buf.get_pos()))
+    }
+
+    fn read_class(buf: &mut Buffer) -> Result<Self> {
+        Self::check_magic_number(buf)?;
+        let (version, minor_version) = Self::read_version(buf)?;
+        let (constants, idx_map) = Self::read_constants(buf)?;
+        let access_flag = Self::read_access_flags(buf)?;
+        let this_class =
+            ConstItemIdx::read(buf, &idx_map).map_err(|error| error.within("this_class"))?;
+        let super_class = Option::<ConstItemIdx>::read(buf, &idx_map)
+            .map_err(|error| error.within("super_class"))?;
+        let interfaces = Vec::read(buf, &idx_map).map_err(|error| error.within("interfaces"))?;
+        let fields = Self::read_members(buf, &constants, &idx_map, "field")?;
+        let methods = Self::read_members(buf, &constants, &idx_map, "method")?;
+        let attributes = Vec::read(buf, &idx_map).map_err(|error| error.within("attributes"))?;
 
         Ok(Self {
             version,
+            minor_version,
             constants,
             access_flag,
             this_class,
@@ -94,10 +144,49 @@ impl ClassFile {
             fields,
             methods,
             attributes,
-            empty_const_slots,
         })
     }
 
+    /// Reads a class from `reader` until its end
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::read(&bytes)
+    }
+
+    /// Reads the class file at `path`
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read(&fs::read(path)?)
+    }
+
+    /// Serializes the class back to the class file format.
+    ///
+    /// Writing a class that was just read gives back the exact same bytes.
+    ///
+    /// Panics if an index does not point inside [ClassFile::constants], or if a table is too
+    /// large to be represented in a class file.
+    pub fn write(&self) -> Vec<u8> {
+        let mut buf = BufferWriter::new();
+        let idx_map = self
+            .const_idx_map()
+            .expect("the constant pool is too large");
+        buf.write_u32(0xCAFEBABE);
+        buf.write_u16(self.minor_version);
+        buf.write_u16(self.version.major());
+        buf.write_len_u16(idx_map.slots_count());
+        for item in &self.constants {
+            item.write(&mut buf, &idx_map);
+        }
+        buf.write_u16(self.access_flag.bits());
+        self.this_class.write(&mut buf, &idx_map);
+        self.super_class.write(&mut buf, &idx_map);
+        self.interfaces.write(&mut buf, &idx_map);
+        self.fields.write(&mut buf, &idx_map);
+        self.methods.write(&mut buf, &idx_map);
+        self.attributes.write(&mut buf, &idx_map);
+        buf.into_bytes()
+    }
+
     fn check_magic_number(buf: &mut Buffer) -> Result<()> {
         match buf.read_u32() {
             Ok(0xCAFEBABE) => Ok(()),
@@ -106,35 +195,112 @@ impl ClassFile {
         }
     }
 
-    fn read_version(buf: &mut Buffer) -> Result<ClassFileVersion> {
+    fn read_version(buf: &mut Buffer) -> Result<(ClassFileVersion, u16)> {
         let minor = buf.read_u16()?;
         let major = buf.read_u16()?;
 
-        ClassFileVersion::from(major, minor)
+        Ok((ClassFileVersion::from(major, minor)?, minor))
+    }
+
+    fn read_constants(buf: &mut Buffer) -> Result<(Constants, ConstIdxMap)> {
+        let mut consts = Constants::new();
+        let (idx_map, offsets) =
+            Self::read_constant_pool(buf, size_of::<ConstItem>(), |buf, idx_map| {
+                let item = ConstItem::read(buf, idx_map)?;
+                let wide = item.is_8bit();
+                consts.push(item);
+                Ok(wide)
+            })?;
+        for (idx, item) in consts.iter_mut_enumerated() {
+            item.resolve_indices(&idx_map).map_err(|error| {
+                let entry = idx_map.raw_past_end(idx);
+                let offset = offsets[idx.index()];
+                error
+                    .at(offset)
+                    .within(format!("constant pool entry #{entry}"))
+            })?;
+        }
+        Ok((consts, idx_map))
     }
 
-    fn read_constants(buf: &mut Buffer) -> Result<(Constants, Vec<u16>)> {
-        let consts_count = buf.read_u16()? - 1;
-        let mut empty_const_slots = Vec::new();
-        let mut real_len = 0;
-        let mut i = 0;
-        let pos = buf.get_pos();
-        while i < consts_count {
-            let item = ConstItem::read(buf, consts_count, &[])?;
-            if item.is_8bit() {
-                i += 1;
-                empty_const_slots.push(i);
+    /// Reads `constant_pool_count`, the number of slots of the constant pool plus one, then
+    /// every entry with `read_item`, which tells whether the entry takes two slots.
+    ///
+    /// `item_size` is the memory each slot takes once read, counted against the limits of
+    /// `buf`. Returns the map of the pool with the offset of each entry.
+    pub(crate) fn read_constant_pool<'a>(
+        buf: &mut Buffer<'a>,
+        item_size: usize,
+        mut read_item: impl FnMut(&mut Buffer<'a>, &ConstIdxMap) -> Result<bool>,
+    ) -> Result<(ConstIdxMap, Vec<usize>)> {
+        let count = buf.read_u16()?;
+        if count == 0 {
+            return Err(ClassReaderError::InvalidConstantPoolCount(count));
+        }
+        if count > buf.limits().max_constants {
+            return Err(ClassReaderError::LimitExceeded("max_constants"));
+        }
+        let slots_count = count as usize;
+        buf.allocate(slots_count * (item_size + size_of::<usize>()))?;
+        let mut idx_map = ConstIdxMap::new();
+        let mut offsets = Vec::with_capacity(slots_count);
+        while idx_map.slots_count() < slots_count {
+            let offset = buf.get_pos();
+            let entry = idx_map.slots_count();
+            let in_entry = |error: ClassReaderError| {
+                error
+                    .at(offset)
+                    .within(format!("constant pool entry #{entry}"))
+            };
+            let wide = read_item(buf, &idx_map).map_err(in_entry)?;
+            // A long or a double in the last slot would take one past the declared count
+            if wide && idx_map.slots_count() + 2 > slots_count {
+                return Err(in_entry(ClassReaderError::InvalidConstantPoolCount(count)));
             }
-            real_len += 1;
-            i += 1;
+            idx_map.push_slots(wide).map_err(in_entry)?;
+            offsets.push(offset);
         }
-        buf.set_pos(pos);
-        let mut consts = IndexVec::with_capacity(real_len);
-        for _ in 0..real_len {
-            let item = ConstItem::read(buf, real_len as u16, &empty_const_slots)?;
-            consts.push(item);
+        Ok((idx_map, offsets))
+    }
+
+    /// Reads the fields or the methods of the class, errors naming the faulty member
+    fn read_members<T: Read>(
+        buf: &mut Buffer,
+        constants: &Constants,
+        idx_map: &ConstIdxMap,
+        kind: &str,
+    ) -> Result<Vec<T>> {
+        let count = buf.read_u16()?;
+        let capacity = (count as usize).min(buf.remaining());
+        buf.allocate(capacity * size_of::<T>())?;
+        let mut members = Vec::with_capacity(capacity);
+        for idx in 0..count {
+            let start = buf.get_pos();
+            match T::read(buf, idx_map) {
+                Ok(member) => members.push(member),
+                Err(error) => {
+                    let error = error.at(buf.get_pos());
+                    buf.set_pos(start + 2);
+                    let name_index = ConstItemIdx::read(buf, idx_map).ok();
+                    let descriptor_index = ConstItemIdx::read(buf, idx_map).ok();
+                    let segment =
+                        member_segment_in(constants, kind, idx, name_index, descriptor_index);
+                    return Err(error.within(segment));
+                }
+            }
         }
-        Ok((consts, empty_const_slots))
+        Ok(members)
+    }
+
+    /// Checks that the constant pool is structurally valid, see [constants::validate]
+    pub fn validate_constants(&self) -> Result<()> {
+        constants::validate(&self.constants, &self.version)
+    }
+
+    /// Builds the map from class file indices to indices of [ClassFile::constants], failing
+    /// if they take more slots than a class file can hold
+    pub fn const_idx_map(&self) -> Result<ConstIdxMap> {
+        ConstIdxMap::from_constants(&self.constants)
     }
 
     fn read_access_flags(buf: &mut Buffer) -> Result<ClassAccessFlags> {
@@ -142,3 +308,69 @@ impl ClassFile {
         ClassAccessFlags::from_bits(num).ok_or(ClassReaderError::InvalidClassAccessFlags(num))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use crate::{ClassFile, constants::ConstItem, error::ClassReaderError};
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn can_read_sample_class() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let ConstItem::Class(this_class) = &class.constants[class.this_class] else {
+            panic!("this_class is not a Class constant");
+        };
+        let ConstItem::Utf8(name) = &class.constants[this_class.name_index] else {
+            panic!("Class name is not an Utf8 constant");
+        };
+
+        assert_eq!("Sample", name.string);
+        assert_eq!(6, class.fields.len());
+        assert_eq!(6, class.methods.len());
+    }
+
+    #[test]
+    fn writing_gives_back_the_same_bytes() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        assert_eq!(SAMPLE, class.write());
+    }
+
+    #[test]
+    fn classes_can_be_read_from_files() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/Sample.class");
+        assert_eq!(SAMPLE, ClassFile::from_path(path).unwrap().write());
+        assert_eq!(SAMPLE, ClassFile::from_reader(SAMPLE).unwrap().write());
+
+        let err = ClassFile::from_path("tests/fixtures/Missing.class").unwrap_err();
+        assert!(matches!(err, ClassReaderError::Io(ErrorKind::NotFound, _)));
+    }
+
+    #[test]
+    fn wide_constants_cannot_overrun_the_pool() {
+        // A long in the last slot takes one more than the count allows
+        let bytes = [
+            0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 2, 5, 0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+        let error = ClassFile::read(&bytes).unwrap_err();
+        assert_eq!(&ClassReaderError::InvalidConstantPoolCount(2), error.kind());
+        assert_eq!(Some(10), error.context().unwrap().offset);
+    }
+
+    #[test]
+    fn indices_after_wide_constants_are_compacted() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+
+        let long = class
+            .constants
+            .iter()
+            .position(|item| matches!(item, ConstItem::Long(_)))
+            .unwrap();
+        let after = idx_map.raw((long + 1).into()).unwrap();
+        assert_eq!(idx_map.raw(long.into()).unwrap() + 2, after);
+        assert!(idx_map.resolve(after - 1).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Bounds on the resources used to read a class.
+//!
+//! The class file format already bounds most tables with 2-byte counts, and the reader never
+//! allocates more than the input could fill, but a service reading classes from untrusted
+//! sources may want tighter bounds. Exceeding a limit is reported as
+//! [ClassReaderError::LimitExceeded](crate::error::ClassReaderError::LimitExceeded).
+
+/// Limits enforced while reading a class, see [ReadOptions](crate::diagnostics::ReadOptions)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum `constant_pool_count`, i.e. number of slots of the constant pool plus one
+    pub max_constants: u16,
+    /// Maximum length of an attribute
+    pub max_attribute_length: u32,
+    /// Maximum nesting of annotations and arrays in annotation element values
+    pub max_annotation_depth: usize,
+    /// Maximum number of bytes allocated for the constants, tables and attributes of a class
+    pub max_allocation: usize,
+}
+
+impl Default for Limits {
+    /// Limits that any class produced by a compiler is well within
+    fn default() -> Self {
+        Self {
+            max_constants: u16::MAX,
+            max_attribute_length: u32::MAX,
+            max_annotation_depth: 64,
+            max_allocation: 256 << 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile, borrowed::ClassFileRef, diagnostics::ReadOptions, error::ClassReaderError,
+        reader::ClassReader,
+    };
+
+    use super::Limits;
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn exceeding_a_limit_stops_reading() {
+        let read = |limits| {
+            let options = ReadOptions {
+                strict: true,
+                limits,
+            };
+            ClassFile::read_with_diagnostics(SAMPLE, &options).map(|(class, _)| class)
+        };
+        assert!(read(Limits::default()).is_ok());
+
+        for (limits, limit) in [
+            (
+                Limits {
+                    max_constants: 16,
+                    ..Limits::default()
+                },
+                "max_constants",
+            ),
+            (
+                Limits {
+                    max_attribute_length: 16,
+                    ..Limits::default()
+                },
+                "max_attribute_length",
+            ),
+            (
+                Limits {
+                    max_allocation: 4096,
+                    ..Limits::default()
+                },
+                "max_allocation",
+            ),
+        ] {
+            let error = read(limits).unwrap_err();
+            assert_eq!(&ClassReaderError::LimitExceeded(limit), error.kind());
+        }
+    }
+
+    #[test]
+    fn empty_constant_pools_are_rejected() {
+        let bytes = [
+            0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 0, 0, 0x21, 0, 1, 0, 0, 0, 0,
+        ];
+        let error = ClassReaderError::InvalidConstantPoolCount(0);
+        assert_eq!(&error, ClassFile::read(&bytes).unwrap_err().kind());
+        assert_eq!(&error, ClassFileRef::read(&bytes).unwrap_err().kind());
+        assert_eq!(&error, ClassReader::new(&bytes).unwrap_err().kind());
+    }
+}
@@ -1,8 +1,12 @@
 use bitflags::bitflags;
 
 use crate::{
-    Read, Result, attribute::Attribute, buffer::Buffer, constants::ConstItemIdx,
-    error::ClassReaderError,
+    Read, Result, Write,
+    attribute::Attribute,
+    buffer::{Buffer, BufferWriter},
+    code::Code,
+    constants::{ConstIdxMap, ConstItemIdx, Constants},
+    error::{ClassReaderError, member_segment},
 };
 
 bitflags! {
@@ -31,7 +35,7 @@ impl Default for MethodAccessFlags {
 }
 
 impl MethodAccessFlags {
-    fn read(buf: &mut Buffer) -> Result<Self> {
+    pub(crate) fn read(buf: &mut Buffer) -> Result<Self> {
         let num = buf.read_u16()?;
         Self::from_bits(num).ok_or(ClassReaderError::InvalidMethodAccessFlags(num))
     }
@@ -45,12 +49,40 @@ pub struct Method {
     pub attributes: Vec<Attribute>,
 }
 
+impl Method {
+    pub fn find_attribute(&self, constants: &Constants, name: &str) -> Option<&Attribute> {
+        Attribute::find(&self.attributes, constants, name)
+    }
+
+    /// Decodes the `Code` attribute of the method, if it has one.
+    ///
+    /// Errors name the method, but have no offset as the position of the attribute in the
+    /// class file is not known.
+    pub fn code(&self, constants: &Constants, idx_map: &ConstIdxMap) -> Result<Option<Code>> {
+        self.find_attribute(constants, "Code")
+            .map(|attribute| {
+                Code::read(&attribute.info, idx_map).map_err(|error| {
+                    let error = error.relative_to(None).within("attribute Code");
+                    let utf8 =
+                        |idx: ConstItemIdx| constants.get(idx)?.as_utf8().map(|item| &*item.string);
+                    match (utf8(self.name_index), utf8(self.descriptor_index)) {
+                        (Some(name), Some(descriptor)) => {
+                            error.within(member_segment("method", name, descriptor))
+                        }
+                        _ => error,
+                    }
+                })
+            })
+            .transpose()
+    }
+}
+
 impl Read for Method {
-    fn read(buf: &mut Buffer, consts_count: u16, empty_const_slots: &[u16]) -> Result<Self> {
+    fn read(buf: &mut Buffer, idx_map: &ConstIdxMap) -> Result<Self> {
         let access_flags = MethodAccessFlags::read(buf)?;
-        let name_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let descriptor_index = ConstItemIdx::read(buf, consts_count, empty_const_slots)?;
-        let attributes = Vec::read(buf, consts_count, empty_const_slots)?;
+        let name_index = ConstItemIdx::read(buf, idx_map)?;
+        let descriptor_index = ConstItemIdx::read(buf, idx_map)?;
+        let attributes = Vec::read(buf, idx_map).map_err(|error| error.within("attributes"))?;
 
         Ok(Self {
             access_flags,
@@ -60,3 +92,12 @@ impl Read for Method {
         })
     }
 }
+
+impl Write for Method {
+    fn write(&self, buf: &mut BufferWriter, idx_map: &ConstIdxMap) {
+        buf.write_u16(self.access_flags.bits());
+        self.name_index.write(buf, idx_map);
+        self.descriptor_index.write(buf, idx_map);
+        self.attributes.write(buf, idx_map);
+    }
+}
@@ -0,0 +1,61 @@
+//! Memory-mapped class files, enabled by the `mmap` feature
+
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{ClassFile, Result, borrowed::ClassFileRef, reader::ClassReader};
+
+/// A class file mapped in memory, which can be read without copying its bytes
+#[derive(Debug)]
+pub struct MappedClassFile {
+    map: Mmap,
+}
+
+impl MappedClassFile {
+    /// Maps the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, as the bytes borrowed
+    /// from it would then change under the reader.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: upheld by the caller
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Reads the class, borrowing its strings and attribute bodies from the mapping
+    pub fn class_file_ref(&self) -> Result<ClassFileRef<'_>> {
+        ClassFileRef::read(&self.map)
+    }
+
+    /// Reads the class with a [ClassReader] over the mapping
+    pub fn reader(&self) -> Result<ClassReader<'_>> {
+        ClassReader::new(&self.map)
+    }
+
+    pub fn class_file(&self) -> Result<ClassFile> {
+        ClassFile::read(&self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappedClassFile;
+
+    const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/Sample.class");
+
+    #[test]
+    fn mapped_classes_can_be_read() {
+        // SAFETY: fixtures are not modified by the tests
+        let class = unsafe { MappedClassFile::open(SAMPLE) }.unwrap();
+        let borrowed = class.class_file_ref().unwrap();
+        assert_eq!(class.bytes(), borrowed.into_owned().write());
+    }
+}
@@ -0,0 +1,276 @@
+//! Conversions between the forms of class names (JVMS 4.2).
+//!
+//! Class files use internal names such as `java/util/Map$Entry`, where the binary name
+//! `java.util.Map$Entry` has its dots replaced by slashes. The canonical name used in Java
+//! source, `java.util.Map.Entry`, cannot be derived from the name alone, as `$` is also a
+//! valid character of class names: it takes the [InnerClasses] of the class.
+
+use std::collections::HashMap;
+
+use crate::{
+    ClassFile, Result,
+    attribute::Attribute,
+    buffer::Buffer,
+    constants::{ConstItem, ConstItemIdx},
+    descriptor::FieldType,
+    error::ClassReaderError,
+};
+
+/// Converts an internal name into a binary name, e.g. `java/lang/String` into
+/// `java.lang.String`
+pub fn internal_to_binary(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+/// Converts a binary name into an internal name, e.g. `java.lang.String` into
+/// `java/lang/String`
+pub fn binary_to_internal(binary_name: &str) -> String {
+    binary_name.replace('.', "/")
+}
+
+/// Package of a class in internal form, e.g. `java/util` for `java/util/Map$Entry`, or an
+/// empty string for the default package
+pub fn package_name(internal_name: &str) -> &str {
+    internal_name
+        .rfind('/')
+        .map_or("", |idx| &internal_name[..idx])
+}
+
+/// Name of a class without its package, e.g. `Map$Entry` for `java/util/Map$Entry`
+pub fn class_name(internal_name: &str) -> &str {
+    internal_name
+        .rfind('/')
+        .map_or(internal_name, |idx| &internal_name[idx + 1..])
+}
+
+/// Descriptor of a class, e.g. `Ljava/lang/String;` for `java/lang/String`.
+///
+/// Array classes, whose names already are descriptors, are returned as they are.
+pub fn to_descriptor(internal_name: &str) -> String {
+    if internal_name.starts_with('[') {
+        internal_name.to_string()
+    } else {
+        format!("L{};", internal_name)
+    }
+}
+
+/// Descriptor of an array of `dimensions` dimensions of a class, e.g. `[[Ljava/lang/String;`
+pub fn to_array_descriptor(internal_name: &str, dimensions: u8) -> String {
+    "[".repeat(dimensions as usize) + &to_descriptor(internal_name)
+}
+
+/// Internal name of the class of a descriptor such as `Ljava/lang/String;`, or of an array
+/// descriptor which is its own name
+pub fn from_descriptor(descriptor: &str) -> Option<&str> {
+    if descriptor.starts_with('[') {
+        return Some(descriptor);
+    }
+    descriptor.strip_prefix('L')?.strip_suffix(';')
+}
+
+/// Whether `name` is a valid unqualified name, as used for fields, local variables and formal
+/// parameters (JVMS 4.2.2)
+pub fn is_valid_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+/// Whether `name` is a valid method name, which is an unqualified name without `<` nor `>`
+/// unless it is `<init>` or `<clinit>` (JVMS 4.2.2)
+pub fn is_valid_method_name(name: &str) -> bool {
+    matches!(name, "<init>" | "<clinit>")
+        || (is_valid_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+/// Whether `internal_name` is a valid binary name in internal form, i.e. unqualified names
+/// separated by slashes (JVMS 4.2.1)
+pub fn is_valid_binary_name(internal_name: &str) -> bool {
+    internal_name.split('/').all(is_valid_unqualified_name)
+}
+
+/// What the `InnerClasses` attribute tells about a nested class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnerClass {
+    /// Internal name of the enclosing class, `None` for local and anonymous classes
+    pub outer_class: Option<String>,
+    /// Name of the class in the source, `None` for anonymous classes
+    pub simple_name: Option<String>,
+}
+
+/// Nested classes by internal name, as declared by `InnerClasses` attributes
+#[derive(Debug, Clone, Default)]
+pub struct InnerClasses {
+    classes: HashMap<String, InnerClass>,
+}
+
+impl InnerClasses {
+    /// Reads the `InnerClasses` attribute of `class`, which lists the nested classes it
+    /// declares or refers to, including the classes enclosing it
+    pub fn read(class: &ClassFile) -> Result<Self> {
+        let mut inner_classes = Self::default();
+        let Some(attribute) = Attribute::find(&class.attributes, &class.constants, "InnerClasses")
+        else {
+            return Ok(inner_classes);
+        };
+
+        let idx_map = class.const_idx_map()?;
+        let class_name = |idx: ConstItemIdx| -> Result<String> {
+            match &class.constants[idx] {
+                ConstItem::Class(item) => utf8(class, item.name_index),
+                item => Err(ClassReaderError::UnexpectedConstItem(
+                    idx_map.raw_past_end(idx),
+                    item.kind_name(),
+                    "Class",
+                )),
+            }
+        };
+        let mut buf = Buffer::new(&attribute.info);
+        for _ in 0..buf.read_u16()? {
+            let inner_class = class_name(idx_map.resolve(buf.read_u16()?)?)?;
+            let outer_class = match buf.read_u16()? {
+                0 => None,
+                idx => Some(class_name(idx_map.resolve(idx)?)?),
+            };
+            let simple_name = match buf.read_u16()? {
+                0 => None,
+                idx => Some(utf8(class, idx_map.resolve(idx)?)?),
+            };
+            buf.read_u16()?;
+            inner_classes.insert(
+                inner_class,
+                InnerClass {
+                    outer_class,
+                    simple_name,
+                },
+            );
+        }
+        Ok(inner_classes)
+    }
+
+    /// Records a nested class, e.g. to merge the attributes of several classes
+    pub fn insert(&mut self, internal_name: impl Into<String>, inner_class: InnerClass) {
+        self.classes.insert(internal_name.into(), inner_class);
+    }
+
+    pub fn get(&self, internal_name: &str) -> Option<&InnerClass> {
+        self.classes.get(internal_name)
+    }
+
+    /// Name of a class in the source, e.g. `Entry` for `java/util/Map$Entry`, or an empty
+    /// string for an anonymous class
+    pub fn simple_name<'a>(&'a self, internal_name: &'a str) -> &'a str {
+        match self.get(internal_name) {
+            Some(inner_class) => inner_class.simple_name.as_deref().unwrap_or(""),
+            None => class_name(internal_name),
+        }
+    }
+
+    /// Canonical name of a class or an array class, e.g. `java.util.Map.Entry[]` for
+    /// `[Ljava/util/Map$Entry;`.
+    ///
+    /// Local and anonymous classes, and classes nested in them, have none (JLS 6.7). Classes
+    /// unknown to these inner classes are assumed to be top-level.
+    pub fn canonical_name(&self, internal_name: &str) -> Option<String> {
+        if internal_name.starts_with('[') {
+            let field_type = internal_name.parse::<FieldType>().ok()?;
+            let FieldType::Array(dimensions, element) = field_type else {
+                return None;
+            };
+            let element = match *element {
+                FieldType::Object(name) => self.canonical_name(&name.to_str())?,
+                element => element.java().to_string(),
+            };
+            return Some(element + &"[]".repeat(dimensions as usize));
+        }
+        self.nested_canonical_name(internal_name, self.classes.len())
+    }
+
+    /// Canonical name of a class nested at most `depth` times, which stops on cycles
+    fn nested_canonical_name(&self, internal_name: &str, depth: usize) -> Option<String> {
+        match self.get(internal_name) {
+            None => Some(internal_to_binary(internal_name)),
+            Some(InnerClass {
+                outer_class: Some(outer_class),
+                simple_name: Some(simple_name),
+            }) if depth > 0 => {
+                Some(self.nested_canonical_name(outer_class, depth - 1)? + "." + simple_name)
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+fn utf8(class: &ClassFile, idx: ConstItemIdx) -> Result<String> {
+    match &class.constants[idx] {
+        ConstItem::Utf8(item) => Ok(item.string.to_str().into_owned()),
+        item => Err(ClassReaderError::UnexpectedConstItem(
+            class.const_idx_map()?.raw_past_end(idx),
+            item.kind_name(),
+            "Utf8",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ClassFile;
+
+    use super::{
+        InnerClass, InnerClasses, class_name, from_descriptor, internal_to_binary,
+        is_valid_binary_name, is_valid_method_name, package_name, to_array_descriptor,
+    };
+
+    #[test]
+    fn names_are_converted() {
+        assert_eq!(
+            "java.util.Map$Entry",
+            internal_to_binary("java/util/Map$Entry")
+        );
+        assert_eq!("java/util", package_name("java/util/Map$Entry"));
+        assert_eq!("", package_name("Sample"));
+        assert_eq!("Map$Entry", class_name("java/util/Map$Entry"));
+        assert_eq!(
+            "[[Ljava/lang/String;",
+            to_array_descriptor("java/lang/String", 2)
+        );
+        assert_eq!(
+            Some("java/lang/String"),
+            from_descriptor("Ljava/lang/String;")
+        );
+        assert_eq!(None, from_descriptor("I"));
+
+        assert!(is_valid_binary_name("java/util/Map$Entry"));
+        assert!(!is_valid_binary_name("java.util.Map"));
+        assert!(!is_valid_binary_name("java//Map"));
+        assert!(is_valid_method_name("<init>"));
+        assert!(!is_valid_method_name("<lambda>"));
+    }
+
+    #[test]
+    fn canonical_names_use_inner_classes() {
+        let class = ClassFile::read(include_bytes!("../tests/fixtures/Sample.class")).unwrap();
+        let mut inner_classes = InnerClasses::read(&class).unwrap();
+        assert_eq!(
+            Some(&InnerClass {
+                outer_class: Some("Sample".into()),
+                simple_name: Some("Inner".into()),
+            }),
+            inner_classes.get("Sample$Inner")
+        );
+        assert_eq!(
+            Some("java.util.Map.Entry[][]".into()),
+            inner_classes.canonical_name("[[Ljava/util/Map$Entry;")
+        );
+        assert_eq!("Entry", inner_classes.simple_name("java/util/Map$Entry"));
+        assert_eq!(Some("a.b$c".into()), inner_classes.canonical_name("a/b$c"));
+
+        inner_classes.insert(
+            "Sample$1",
+            InnerClass {
+                outer_class: None,
+                simple_name: None,
+            },
+        );
+        assert_eq!(None, inner_classes.canonical_name("Sample$1"));
+        assert_eq!("", inner_classes.simple_name("Sample$1"));
+    }
+}
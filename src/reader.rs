@@ -1 +1,1053 @@
+use crate::{
+    ClassAccessFlags, ClassFile, Read, Result,
+    attribute::Attribute,
+    buffer::Buffer,
+    builder::ConstantValue,
+    code::{ExceptionHandler, Instruction, Label, LineNumber, LocalVariable},
+    constants::{ConstIdxMap, ConstItem, ConstItemIdx, Constants, skip_const_item},
+    error::{ClassReaderError, member_segment},
+    field::FieldAccessFlags,
+    java_str::JavaStr,
+    limits::Limits,
+    method::MethodAccessFlags,
+    version::ClassFileVersion,
+    visitor::{
+        AnnotationVisitor, ClassVisitor, CodeVisitor, ElementValue, FieldVisitor, MethodVisitor,
+    },
+};
 
+/// Reads a class by calling a [ClassVisitor], see the [visitor](crate::visitor) module.
+///
+/// Only the positions of the constant pool entries are recorded up front, entries being
+/// decoded when they are needed.
+///
+/// ```
+/// use classfile::{java_str::JavaStr, method::MethodAccessFlags, reader::ClassReader};
+/// use classfile::visitor::{ClassVisitor, MethodVisitor};
+///
+/// #[derive(Default)]
+/// struct MethodNames(Vec<String>);
+///
+/// impl ClassVisitor for MethodNames {
+///     fn visit_method(
+///         &mut self,
+///         _access_flags: MethodAccessFlags,
+///         name: &JavaStr,
+///         _descriptor: &JavaStr,
+///     ) -> Option<Box<dyn MethodVisitor + '_>> {
+///         self.0.push(name.to_string());
+///         None
+///     }
+/// }
+///
+/// # let bytes = classfile::builder::ClassBuilder::new("Empty").build().unwrap().write();
+/// let mut names = MethodNames::default();
+/// ClassReader::new(&bytes).unwrap().accept(&mut names).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClassReader<'a> {
+    bytes: &'a [u8],
+    version: ClassFileVersion,
+    minor_version: u16,
+    idx_map: ConstIdxMap,
+    /// Position of the tag of every constant pool entry
+    offsets: Vec<usize>,
+    /// Position of the access flags, right after the constant pool
+    header: usize,
+    limits: Limits,
+}
+
+impl<'a> ClassReader<'a> {
+    /// Checks the header of the class and locates the entries of its constant pool
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Self::with_limits(bytes, Limits::default())
+    }
+
+    /// Same as [ClassReader::new], `limits` applying to everything the reader decodes
+    pub fn with_limits(bytes: &'a [u8], limits: Limits) -> Result<Self> {
+        let mut buf = Buffer::with_limits(bytes, limits);
+        match buf.read_u32()? {
+            0xCAFEBABE => {}
+            magic => return Err(ClassReaderError::InvalidMagicBytes(magic)),
+        }
+        let minor_version = buf.read_u16()?;
+        let version = ClassFileVersion::from(buf.read_u16()?, minor_version)?;
+
+        let (idx_map, offsets) =
+            ClassFile::read_constant_pool(&mut buf, 0, |buf, _| skip_const_item(buf))?;
+
+        Ok(Self {
+            bytes,
+            version,
+            minor_version,
+            idx_map,
+            offsets,
+            header: buf.get_pos(),
+            limits,
+        })
+    }
+
+    pub fn version(&self) -> &ClassFileVersion {
+        &self.version
+    }
+
+    pub fn minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    pub fn idx_map(&self) -> &ConstIdxMap {
+        &self.idx_map
+    }
+
+    /// Buffer over the class bytes, positioned at `pos`
+    pub(crate) fn buffer_at(&self, pos: usize) -> Buffer<'a> {
+        let mut buf = Buffer::with_limits(self.bytes, self.limits);
+        buf.set_pos(pos);
+        buf
+    }
+
+    /// Buffer over part of the class bytes, such as the content of an attribute
+    fn buffer_of(&self, bytes: &'a [u8]) -> Buffer<'a> {
+        Buffer::with_limits(bytes, self.limits)
+    }
+
+    /// Position of the access flags, right after the constant pool
+    pub(crate) fn header_pos(&self) -> usize {
+        self.header
+    }
+
+    /// Buffer positioned on the tag of an entry of the constant pool.
+    ///
+    /// Panics if `idx` is not an index of the pool, as indexing [Constants] does.
+    fn entry(&self, idx: ConstItemIdx) -> Buffer<'a> {
+        self.buffer_at(self.offsets[idx.index()])
+    }
+
+    /// Decodes an entry of the constant pool
+    pub fn constant(&self, idx: ConstItemIdx) -> Result<ConstItem> {
+        let mut buf = self.entry(idx);
+        let item = ConstItem::read(&mut buf, &self.idx_map).and_then(|mut item| {
+            item.resolve_indices(&self.idx_map)?;
+            Ok(item)
+        });
+        item.map_err(|error| {
+            let entry = self.idx_map.raw_past_end(idx);
+            error
+                .at(self.offsets[idx.index()])
+                .within(format!("constant pool entry #{entry}"))
+        })
+    }
+
+    /// Decodes the whole constant pool, as [ClassFile::read](crate::ClassFile::read) does
+    pub fn constants(&self) -> Result<Constants> {
+        (0..self.offsets.len())
+            .map(|idx| self.constant(idx.into()))
+            .collect()
+    }
+
+    /// Content of an Utf8 entry, borrowed from the class bytes
+    pub fn utf8(&self, idx: ConstItemIdx) -> Result<&'a JavaStr> {
+        let mut buf = self.entry(idx);
+        if buf.read_u8()? != 1 {
+            return Err(self.unexpected(idx, "Utf8"));
+        }
+        let len = buf.read_u16()?;
+        Ok(buf.read_java_str(len as usize)?)
+    }
+
+    /// Internal name of a Class entry, borrowed from the class bytes
+    pub fn class_name(&self, idx: ConstItemIdx) -> Result<&'a JavaStr> {
+        let mut buf = self.entry(idx);
+        if buf.read_u8()? != 7 {
+            return Err(self.unexpected(idx, "Class"));
+        }
+        let name_index = ConstItemIdx::read(&mut buf, &self.idx_map)?;
+        self.utf8(name_index)
+    }
+
+    fn unexpected(&self, idx: ConstItemIdx, expected: &'static str) -> ClassReaderError {
+        match self.constant(idx) {
+            Ok(item) => ClassReaderError::UnexpectedConstItem(
+                self.idx_map.raw_past_end(idx),
+                item.kind_name(),
+                expected,
+            ),
+            Err(err) => err,
+        }
+    }
+
+    fn read_utf8(&self, buf: &mut Buffer) -> Result<&'a JavaStr> {
+        self.utf8(ConstItemIdx::read(buf, &self.idx_map)?)
+    }
+
+    fn read_class_name(&self, buf: &mut Buffer) -> Result<&'a JavaStr> {
+        self.class_name(ConstItemIdx::read(buf, &self.idx_map)?)
+    }
+
+    /// Decodes the part of the class following the constant pool, up to the interfaces
+    pub fn header(&self) -> Result<ClassHeader<'a>> {
+        self.read_header(&mut self.buffer_at(self.header))
+    }
+
+    fn read_header(&self, buf: &mut Buffer) -> Result<ClassHeader<'a>> {
+        let flags = buf.read_u16()?;
+        let access_flags = ClassAccessFlags::from_bits(flags)
+            .ok_or(ClassReaderError::InvalidClassAccessFlags(flags))?;
+        let name = self.read_class_name(buf)?;
+        let super_name = match Option::<ConstItemIdx>::read(buf, &self.idx_map)? {
+            Some(idx) => Some(self.class_name(idx)?),
+            None => None,
+        };
+        let interfaces_count = buf.read_u16()?;
+        let interfaces = (0..interfaces_count)
+            .map(|_| self.read_class_name(buf))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ClassHeader {
+            version: self.version.clone(),
+            minor_version: self.minor_version,
+            access_flags,
+            name,
+            super_name,
+            interfaces,
+        })
+    }
+
+    /// Calls `visitor` for every part of the class
+    pub fn accept(&self, visitor: &mut dyn ClassVisitor) -> Result<()> {
+        let mut buf = self.buffer_at(self.header);
+        let header = self.read_header(&mut buf)?;
+        visitor.visit(
+            &header.version,
+            header.minor_version,
+            header.access_flags,
+            header.name,
+            header.super_name,
+            &header.interfaces,
+        );
+
+        // Class attributes come after the members, but are visited first
+        let fields = buf.get_pos();
+        skip_members(&mut buf)?;
+        let methods = buf.get_pos();
+        skip_members(&mut buf)?;
+        for (name, info) in self.attributes(&mut buf)? {
+            match name.as_str() {
+                Some("SourceFile") => {
+                    visitor.visit_source(self.read_utf8(&mut self.buffer_of(info))?)
+                }
+                Some("Signature") => {
+                    visitor.visit_signature(self.read_utf8(&mut self.buffer_of(info))?)
+                }
+                Some("RuntimeVisibleAnnotations") => self.read_annotations(info, true, visitor)?,
+                Some("RuntimeInvisibleAnnotations") => {
+                    self.read_annotations(info, false, visitor)?
+                }
+                _ => visitor.visit_attribute(name, info),
+            }
+        }
+
+        buf.set_pos(fields);
+        for idx in 0..buf.read_u16()? {
+            let start = buf.get_pos();
+            let result = self.read_field(&mut buf, visitor);
+            result.map_err(|error| self.member_error(error, &buf, start, "field", idx))?;
+        }
+        buf.set_pos(methods);
+        for idx in 0..buf.read_u16()? {
+            let start = buf.get_pos();
+            let result = self.read_method(&mut buf, visitor);
+            result.map_err(|error| self.member_error(error, &buf, start, "method", idx))?;
+        }
+        visitor.visit_end();
+        Ok(())
+    }
+
+    /// Locates an error raised while reading the field or method starting at `start`
+    fn member_error(
+        &self,
+        error: ClassReaderError,
+        buf: &Buffer,
+        start: usize,
+        kind: &str,
+        idx: u16,
+    ) -> ClassReaderError {
+        let mut names = self.buffer_at(start + 2);
+        let name = self.read_utf8(&mut names);
+        let descriptor = self.read_utf8(&mut names);
+        let segment = match (name, descriptor) {
+            (Ok(name), Ok(descriptor)) => member_segment(kind, name, descriptor),
+            _ => format!("{kind}s[{idx}]"),
+        };
+        error.at(buf.get_pos()).within(segment)
+    }
+
+    /// Position of `info`, a slice of the class bytes, from the start of the class
+    fn offset_of(&self, info: &[u8]) -> usize {
+        info.as_ptr() as usize - self.bytes.as_ptr() as usize
+    }
+
+    /// Reads a table of attributes, as pairs of names and contents
+    fn attributes(&self, buf: &mut Buffer<'a>) -> Result<Vec<(&'a JavaStr, &'a [u8])>> {
+        (0..buf.read_u16()?)
+            .map(|_| {
+                let name = self.read_utf8(buf)?;
+                let len = Attribute::read_length(buf)?;
+                Ok((name, buf.read_bytes(len)?))
+            })
+            .collect()
+    }
+
+    fn read_field(&self, buf: &mut Buffer<'a>, visitor: &mut dyn ClassVisitor) -> Result<()> {
+        let flags = buf.read_u16()?;
+        let access_flags = FieldAccessFlags::from_bits(flags)
+            .ok_or(ClassReaderError::InvalidFieldAccessFlags(flags))?;
+        let name = self.read_utf8(buf)?;
+        let descriptor = self.read_utf8(buf)?;
+        let attributes = self.attributes(buf)?;
+        let Some(mut field) = visitor.visit_field(access_flags, name, descriptor) else {
+            return Ok(());
+        };
+
+        for (name, info) in attributes {
+            match name.as_str() {
+                Some("ConstantValue") => {
+                    let idx = ConstItemIdx::read(&mut self.buffer_of(info), &self.idx_map)?;
+                    field.visit_constant_value(self.constant_value(idx)?);
+                }
+                Some("Signature") => {
+                    field.visit_signature(self.read_utf8(&mut self.buffer_of(info))?)
+                }
+                Some("RuntimeVisibleAnnotations") => {
+                    self.read_annotations(info, true, field.as_mut())?
+                }
+                Some("RuntimeInvisibleAnnotations") => {
+                    self.read_annotations(info, false, field.as_mut())?
+                }
+                _ => field.visit_attribute(name, info),
+            }
+        }
+        field.visit_end();
+        Ok(())
+    }
+
+    fn constant_value(&self, idx: ConstItemIdx) -> Result<ConstantValue> {
+        Ok(match self.constant(idx)? {
+            ConstItem::Integer(item) => ConstantValue::Int(item.integer),
+            ConstItem::Long(item) => ConstantValue::Long(item.long),
+            ConstItem::Float(item) => ConstantValue::Float(item.value()),
+            ConstItem::Double(item) => ConstantValue::Double(item.value()),
+            ConstItem::String(item) => ConstantValue::String(self.utf8(item.string_index)?.into()),
+            item => {
+                return Err(ClassReaderError::UnexpectedConstItem(
+                    self.idx_map.raw_past_end(idx),
+                    item.kind_name(),
+                    "Integer, Long, Float, Double or String",
+                ));
+            }
+        })
+    }
+
+    fn read_method(&self, buf: &mut Buffer<'a>, visitor: &mut dyn ClassVisitor) -> Result<()> {
+        let flags = buf.read_u16()?;
+        let access_flags = MethodAccessFlags::from_bits(flags)
+            .ok_or(ClassReaderError::InvalidMethodAccessFlags(flags))?;
+        let name = self.read_utf8(buf)?;
+        let descriptor = self.read_utf8(buf)?;
+        let attributes = self.attributes(buf)?;
+        let Some(mut method) = visitor.visit_method(access_flags, name, descriptor) else {
+            return Ok(());
+        };
+
+        for (name, info) in attributes {
+            match name.as_str() {
+                Some("Code") => {
+                    if let Some(mut code) = method.visit_code() {
+                        self.read_code(info, code.as_mut()).map_err(|error| {
+                            let offset = self.offset_of(info);
+                            error.relative_to(Some(offset)).within("attribute Code")
+                        })?;
+                    }
+                }
+                Some("Exceptions") => {
+                    let mut buf = self.buffer_of(info);
+                    let exceptions = (0..buf.read_u16()?)
+                        .map(|_| self.read_class_name(&mut buf))
+                        .collect::<Result<Vec<_>>>()?;
+                    method.visit_exceptions(&exceptions);
+                }
+                Some("Signature") => {
+                    method.visit_signature(self.read_utf8(&mut self.buffer_of(info))?)
+                }
+                Some("RuntimeVisibleAnnotations") => {
+                    self.read_annotations(info, true, method.as_mut())?
+                }
+                Some("RuntimeInvisibleAnnotations") => {
+                    self.read_annotations(info, false, method.as_mut())?
+                }
+                _ => method.visit_attribute(name, info),
+            }
+        }
+        method.visit_end();
+        Ok(())
+    }
+
+    fn read_code(&self, info: &'a [u8], visitor: &mut dyn CodeVisitor) -> Result<()> {
+        let mut buf = self.buffer_of(info);
+        let max_stack = buf.read_u16()?;
+        let max_locals = buf.read_u16()?;
+        let code_length = buf.read_u32()?;
+        let code_start = buf.get_pos();
+        let code = buf.read_bytes(code_length as usize)?;
+        let exception_table: Vec<ExceptionHandler> =
+            Vec::read(&mut buf, &self.idx_map).map_err(|error| error.within("exception_table"))?;
+        let instruction_error =
+            |error: ClassReaderError, pc: u32| error.at(code_start + pc as usize).within("code");
+
+        let mut line_numbers: Vec<LineNumber> = Vec::new();
+        let mut local_variables: Vec<LocalVariable> = Vec::new();
+        let mut local_variable_types: Vec<LocalVariable> = Vec::new();
+        let mut attributes = Vec::new();
+        for (name, info) in self.attributes(&mut buf)? {
+            let mut buf = self.buffer_of(info);
+            match name.as_str() {
+                // Empty tables stay as they are, the builder only generating non-empty ones
+                _ if info == [0, 0] => attributes.push((name, info)),
+                Some("LineNumberTable") => {
+                    line_numbers.append(&mut Vec::read(&mut buf, &self.idx_map)?)
+                }
+                Some("LocalVariableTable") => {
+                    local_variables.append(&mut Vec::read(&mut buf, &self.idx_map)?)
+                }
+                Some("LocalVariableTypeTable") => {
+                    local_variable_types.append(&mut Vec::read(&mut buf, &self.idx_map)?)
+                }
+                _ => attributes.push((name, info)),
+            }
+        }
+        line_numbers.sort_by_key(|line_number| line_number.start);
+
+        // A first pass finds the pcs that labels are placed on
+        let mut targets = vec![false; code_length as usize + 1];
+        let mut mark = |label: Label| {
+            if let Some(target) = targets.get_mut(label.0 as usize) {
+                *target = true;
+            }
+        };
+        let mut buf = self.buffer_of(code);
+        while buf.has_more_data() {
+            let pc = buf.get_pos() as u32;
+            let instruction = Instruction::read(&mut buf, pc, &self.idx_map)
+                .map_err(|error| instruction_error(error, pc))?;
+            instruction.targets().into_iter().for_each(&mut mark);
+        }
+        for handler in &exception_table {
+            mark(handler.start);
+            mark(handler.end);
+            mark(handler.handler);
+        }
+        for variable in local_variables.iter().chain(&local_variable_types) {
+            mark(variable.start);
+            mark(variable.end);
+        }
+
+        for handler in &exception_table {
+            visitor.visit_try_catch(handler);
+        }
+        for variable in &local_variables {
+            visitor.visit_local_variable(variable);
+        }
+        for variable in &local_variable_types {
+            visitor.visit_local_variable_type(variable);
+        }
+        let mut line_numbers = line_numbers.into_iter().peekable();
+        let mut buf = self.buffer_of(code);
+        while buf.has_more_data() {
+            let pc = buf.get_pos() as u32;
+            if targets[pc as usize] {
+                visitor.visit_label(Label(pc));
+            }
+            while let Some(line_number) = line_numbers.next_if(|entry| entry.start.0 <= pc) {
+                visitor.visit_line_number(line_number.line);
+            }
+            let instruction = Instruction::read(&mut buf, pc, &self.idx_map)
+                .map_err(|error| instruction_error(error, pc))?;
+            visitor.visit_instruction(&instruction);
+        }
+        if targets[code_length as usize] {
+            visitor.visit_label(Label(code_length));
+        }
+        for (name, info) in attributes {
+            visitor.visit_attribute(name, info);
+        }
+        visitor.visit_maxs(max_stack, max_locals);
+        visitor.visit_end();
+        Ok(())
+    }
+
+    /// Reads the content of a `RuntimeVisibleAnnotations` or `RuntimeInvisibleAnnotations`
+    /// attribute, `visit` giving the visitor of each annotation from its descriptor
+    fn read_annotations<T: Annotated + ?Sized>(
+        &self,
+        info: &'a [u8],
+        visible: bool,
+        target: &mut T,
+    ) -> Result<()> {
+        let mut buf = self.buffer_of(info);
+        for _ in 0..buf.read_u16()? {
+            let descriptor = self.read_utf8(&mut buf)?;
+            let mut visitor = target.visit_annotation(descriptor, visible);
+            self.read_annotation(&mut buf, visitor.as_deref_mut(), 0)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the element-value pairs of an annotation, whose type has already been read,
+    /// `depth` being the number of annotations and arrays it is nested in
+    fn read_annotation(
+        &self,
+        buf: &mut Buffer<'a>,
+        mut visitor: Option<&mut (dyn AnnotationVisitor + '_)>,
+        depth: usize,
+    ) -> Result<()> {
+        for _ in 0..buf.read_u16()? {
+            let name = self.read_utf8(buf)?;
+            self.read_element_value(buf, Some(name), visitor.as_deref_mut(), depth + 1)?;
+        }
+        if let Some(visitor) = visitor {
+            visitor.visit_end();
+        }
+        Ok(())
+    }
+
+    /// Reads an element value, `depth` being the number of annotations and arrays enclosing it
+    fn read_element_value(
+        &self,
+        buf: &mut Buffer<'a>,
+        name: Option<&'a JavaStr>,
+        visitor: Option<&mut (dyn AnnotationVisitor + '_)>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > self.limits.max_annotation_depth {
+            return Err(ClassReaderError::LimitExceeded("max_annotation_depth"));
+        }
+        let tag = buf.read_u8()?;
+        let value = match tag {
+            b'e' => {
+                let descriptor = self.read_utf8(buf)?;
+                let value = self.read_utf8(buf)?;
+                if let Some(visitor) = visitor {
+                    visitor.visit_enum(name, descriptor, value);
+                }
+                return Ok(());
+            }
+            b'@' => {
+                let descriptor = self.read_utf8(buf)?;
+                let mut nested =
+                    visitor.and_then(|visitor| visitor.visit_annotation(name, descriptor));
+                return self.read_annotation(buf, nested.as_deref_mut(), depth);
+            }
+            b'[' => {
+                let mut array = visitor.and_then(|visitor| visitor.visit_array(name));
+                for _ in 0..buf.read_u16()? {
+                    self.read_element_value(buf, None, array.as_deref_mut(), depth + 1)?;
+                }
+                if let Some(array) = &mut array {
+                    array.visit_end();
+                }
+                return Ok(());
+            }
+            b's' => ElementValue::String(self.read_utf8(buf)?),
+            b'c' => ElementValue::Class(self.read_utf8(buf)?),
+            _ => {
+                let raw = buf.read_u16()?;
+                let idx = self.idx_map.resolve(raw)?;
+                match (tag, self.constant(idx)?) {
+                    (b'B', ConstItem::Integer(item)) => ElementValue::Byte(item.integer as i8),
+                    (b'C', ConstItem::Integer(item)) => ElementValue::Char(item.integer as u16),
+                    (b'S', ConstItem::Integer(item)) => ElementValue::Short(item.integer as i16),
+                    (b'Z', ConstItem::Integer(item)) => ElementValue::Boolean(item.integer != 0),
+                    (b'I', ConstItem::Integer(item)) => ElementValue::Int(item.integer),
+                    (b'J', ConstItem::Long(item)) => ElementValue::Long(item.long),
+                    (b'F', ConstItem::Float(item)) => ElementValue::Float(item.value()),
+                    (b'D', ConstItem::Double(item)) => ElementValue::Double(item.value()),
+                    (b'B' | b'C' | b'S' | b'Z' | b'I' | b'J' | b'F' | b'D', item) => {
+                        return Err(ClassReaderError::UnexpectedConstItem(
+                            raw,
+                            item.kind_name(),
+                            "Integer, Long, Float or Double",
+                        ));
+                    }
+                    _ => return Err(ClassReaderError::InvalidElementValueTag(tag)),
+                }
+            }
+        };
+        if let Some(visitor) = visitor {
+            visitor.visit(name, value);
+        }
+        Ok(())
+    }
+}
+
+/// Visitors of annotated items
+trait Annotated {
+    fn visit_annotation(
+        &mut self,
+        descriptor: &JavaStr,
+        visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>>;
+}
+
+macro_rules! annotated {
+    ($($visitor:ident),*) => {
+        $(
+            impl Annotated for dyn $visitor + '_ {
+                fn visit_annotation(
+                    &mut self,
+                    descriptor: &JavaStr,
+                    visible: bool,
+                ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+                    $visitor::visit_annotation(self, descriptor, visible)
+                }
+            }
+        )*
+    };
+}
+
+annotated!(ClassVisitor, FieldVisitor, MethodVisitor);
+
+/// The identity of a class and of its direct supertypes, with internal names borrowed from
+/// the class bytes
+#[derive(Debug, Clone)]
+pub struct ClassHeader<'a> {
+    pub version: ClassFileVersion,
+    pub minor_version: u16,
+    pub access_flags: ClassAccessFlags,
+    pub name: &'a JavaStr,
+    /// `None` for `java/lang/Object` only
+    pub super_name: Option<&'a JavaStr>,
+    pub interfaces: Vec<&'a JavaStr>,
+}
+
+impl<'a> ClassHeader<'a> {
+    /// Reads the class up to its interfaces, skipping over the constant pool entries instead
+    /// of decoding them, and ignoring the members and attributes that follow
+    pub fn read(bytes: &'a [u8]) -> Result<Self> {
+        ClassReader::new(bytes)?.header()
+    }
+}
+
+/// Skips a table of fields or methods
+fn skip_members(buf: &mut Buffer) -> Result<()> {
+    for _ in 0..buf.read_u16()? {
+        skip_member(buf)?;
+    }
+    Ok(())
+}
+
+/// Skips a field or a method
+pub(crate) fn skip_member(buf: &mut Buffer) -> Result<()> {
+    buf.read_bytes(6)?;
+    skip_attributes(buf)
+}
+
+/// Skips a table of attributes
+fn skip_attributes(buf: &mut Buffer) -> Result<()> {
+    for _ in 0..buf.read_u16()? {
+        buf.read_bytes(2)?;
+        let len = Attribute::read_length(buf)?;
+        buf.read_bytes(len)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassAccessFlags, ClassFile,
+        builder::ClassBuilder,
+        code::Instruction,
+        error::ClassReaderError,
+        java_str::JavaStr,
+        limits::Limits,
+        method::MethodAccessFlags,
+        reader::{ClassHeader, ClassReader},
+        verifier::Classes,
+        version::ClassFileVersion,
+        visitor::{
+            AnnotationVisitor, ClassVisitor, ClassWriter, CodeVisitor, ElementValue, MethodVisitor,
+        },
+    };
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    /// Forwards everything to a writer, renaming `compute` and recording annotations
+    struct Renamer<'a> {
+        writer: &'a mut ClassWriter,
+        annotations: Vec<String>,
+    }
+
+    impl ClassVisitor for Renamer<'_> {
+        fn visit(
+            &mut self,
+            version: &ClassFileVersion,
+            minor_version: u16,
+            access_flags: ClassAccessFlags,
+            name: &JavaStr,
+            super_name: Option<&JavaStr>,
+            interfaces: &[&JavaStr],
+        ) {
+            self.writer.visit(
+                version,
+                minor_version,
+                access_flags,
+                name,
+                super_name,
+                interfaces,
+            );
+        }
+
+        fn visit_annotation(
+            &mut self,
+            descriptor: &JavaStr,
+            visible: bool,
+        ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+            self.annotations.push(descriptor.to_string());
+            self.writer.visit_annotation(descriptor, visible)
+        }
+
+        fn visit_attribute(&mut self, name: &JavaStr, info: &[u8]) {
+            self.writer.visit_attribute(name, info);
+        }
+
+        fn visit_method(
+            &mut self,
+            access_flags: MethodAccessFlags,
+            name: &JavaStr,
+            descriptor: &JavaStr,
+        ) -> Option<Box<dyn MethodVisitor + '_>> {
+            let name = if name == "compute" {
+                JavaStr::from_bytes(b"calculate").unwrap()
+            } else {
+                name
+            };
+            self.writer.visit_method(access_flags, name, descriptor)
+        }
+
+        fn visit_end(&mut self) {
+            self.writer.visit_end();
+        }
+    }
+
+    #[test]
+    fn readers_can_feed_writers() {
+        let reader = ClassReader::new(SAMPLE).unwrap();
+        let mut writer = ClassWriter::from_reader(&reader).unwrap();
+        let mut renamer = Renamer {
+            writer: &mut writer,
+            annotations: Vec::new(),
+        };
+        reader.accept(&mut renamer).unwrap();
+        assert_eq!(vec!["Ljava/lang/Deprecated;"], renamer.annotations);
+
+        let class = ClassFile::read(&writer.build().unwrap().write()).unwrap();
+        let original = ClassFile::read(SAMPLE).unwrap();
+        // Fields are not forwarded by the renamer
+        assert!(class.fields.is_empty());
+        let names: Vec<_> = class
+            .methods
+            .iter()
+            .map(|method| class.constants[method.name_index].as_utf8().unwrap())
+            .collect();
+        assert!(names.iter().any(|name| name.string == "calculate"));
+        assert!(names.iter().all(|name| name.string != "compute"));
+        for (method, renamed) in original.methods.iter().zip(&class.methods) {
+            let code = method.code(&original.constants, &original.const_idx_map().unwrap());
+            let renamed = renamed.code(&class.constants, &class.const_idx_map().unwrap());
+            assert_eq!(
+                code.unwrap().map(|code| code.instructions),
+                renamed.unwrap().map(|code| code.instructions)
+            );
+        }
+        let annotations = |class: &ClassFile| {
+            class
+                .attributes
+                .iter()
+                .find(|a| a.name(&class.constants).unwrap() == "RuntimeVisibleAnnotations")
+                .unwrap()
+                .info
+                .clone()
+        };
+        assert_eq!(annotations(&original), annotations(&class));
+    }
+
+    /// A class annotated with `@Marker(value = 42)`, the value being nested in `depth` arrays
+    fn annotated(depth: usize) -> Vec<u8> {
+        let mut builder = ClassBuilder::new("Annotated");
+        let value = builder.pool().integer(42);
+        let value = builder.pool().idx_map().raw(value).unwrap();
+        let descriptor = builder.pool().utf8("LMarker;");
+        let descriptor = builder.pool().idx_map().raw(descriptor).unwrap();
+        let name = builder.pool().utf8("value");
+        let name = builder.pool().idx_map().raw(name).unwrap();
+        let mut info = vec![0, 1];
+        info.extend(descriptor.to_be_bytes());
+        info.extend([0, 1]);
+        info.extend(name.to_be_bytes());
+        info.extend([b'[', 0, 1].repeat(depth));
+        info.push(b'I');
+        info.extend(value.to_be_bytes());
+        builder.attribute("RuntimeVisibleAnnotations", info);
+        builder.build().unwrap().write()
+    }
+
+    #[test]
+    fn annotation_values_are_decoded() {
+        struct Values<'a>(&'a mut Vec<String>);
+
+        impl AnnotationVisitor for Values<'_> {
+            fn visit(&mut self, name: Option<&JavaStr>, value: ElementValue) {
+                self.0.push(format!("{}={:?}", name.unwrap(), value));
+            }
+        }
+
+        struct Class(Vec<String>);
+
+        impl ClassVisitor for Class {
+            fn visit_annotation(
+                &mut self,
+                _descriptor: &JavaStr,
+                _visible: bool,
+            ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+                Some(Box::new(Values(&mut self.0)))
+            }
+        }
+
+        let bytes = annotated(0);
+        let mut class = Class(Vec::new());
+        ClassReader::new(&bytes)
+            .unwrap()
+            .accept(&mut class)
+            .unwrap();
+        assert_eq!(vec!["value=Int(42)"], class.0);
+    }
+
+    #[test]
+    fn annotations_nest_up_to_the_limit() {
+        struct Nothing;
+        impl ClassVisitor for Nothing {}
+
+        // value = {{{42}}}
+        let bytes = annotated(3);
+        let read = |max_annotation_depth| {
+            let limits = Limits {
+                max_annotation_depth,
+                ..Limits::default()
+            };
+            ClassReader::with_limits(&bytes, limits)?.accept(&mut Nothing)
+        };
+        assert!(read(4).is_ok());
+        assert_eq!(
+            &ClassReaderError::LimitExceeded("max_annotation_depth"),
+            read(3).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn code_is_read_within_the_limits() {
+        let mut builder = ClassBuilder::new("Handlers");
+        builder.version(ClassFileVersion::Jdk1_4, 0);
+        builder.method("run", "()V", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let (start, end) = (code.new_label(), code.new_label());
+                code.place_label(start)
+                    .instruction(Instruction::Nop)
+                    .place_label(end)
+                    .instruction(Instruction::Return);
+                for _ in 0..64 {
+                    code.try_catch(start, end, end, None);
+                }
+            });
+        });
+        let bytes = builder.build().unwrap().write();
+
+        let read = |max_allocation| {
+            let limits = Limits {
+                max_allocation,
+                ..Limits::default()
+            };
+            ClassReader::with_limits(&bytes, limits)?.accept(&mut ClassWriter::new())
+        };
+        assert!(read(Limits::default().max_allocation).is_ok());
+        assert_eq!(
+            &ClassReaderError::LimitExceeded("max_allocation"),
+            read(512).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn changed_code_gets_new_maxs_and_no_stale_frames() {
+        /// Forwards everything to a writer, pushing and popping two ints at the start of code
+        struct Prepender<'a>(&'a mut ClassWriter);
+
+        impl ClassVisitor for Prepender<'_> {
+            fn visit(
+                &mut self,
+                version: &ClassFileVersion,
+                minor_version: u16,
+                access_flags: ClassAccessFlags,
+                name: &JavaStr,
+                super_name: Option<&JavaStr>,
+                interfaces: &[&JavaStr],
+            ) {
+                self.0.visit(
+                    version,
+                    minor_version,
+                    access_flags,
+                    name,
+                    super_name,
+                    interfaces,
+                );
+            }
+
+            fn visit_method(
+                &mut self,
+                access_flags: MethodAccessFlags,
+                name: &JavaStr,
+                descriptor: &JavaStr,
+            ) -> Option<Box<dyn MethodVisitor + '_>> {
+                let method = self.0.visit_method(access_flags, name, descriptor)?;
+                Some(Box::new(PrependerMethod(method)))
+            }
+
+            fn visit_end(&mut self) {
+                self.0.visit_end();
+            }
+        }
+
+        struct PrependerMethod<'a>(Box<dyn MethodVisitor + 'a>);
+
+        impl MethodVisitor for PrependerMethod<'_> {
+            fn visit_code(&mut self) -> Option<Box<dyn CodeVisitor + '_>> {
+                let mut code = self.0.visit_code()?;
+                for instruction in [
+                    Instruction::Iconst0,
+                    Instruction::Iconst0,
+                    Instruction::Pop2,
+                ] {
+                    code.visit_instruction(&instruction);
+                }
+                Some(code)
+            }
+
+            fn visit_end(&mut self) {
+                self.0.visit_end();
+            }
+        }
+
+        let prepend = |bytes: &[u8]| {
+            let reader = ClassReader::new(bytes).unwrap();
+            let mut writer = ClassWriter::from_reader(&reader).unwrap();
+            reader.accept(&mut Prepender(&mut writer)).unwrap();
+            writer.build()
+        };
+
+        let mut builder = ClassBuilder::new("Abs");
+        builder.version(ClassFileVersion::Jdk1_4, 0);
+        builder.method("abs", "(I)I", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let positive = code.new_label();
+                code.instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ifge(positive))
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ineg)
+                    .instruction(Instruction::Ireturn)
+                    .place_label(positive)
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ireturn);
+            });
+        });
+        let class = prepend(&builder.build().unwrap().write()).unwrap();
+        assert_eq!(Ok(()), class.verify(&Classes::new()));
+
+        // The frames of the branching methods of the sample cannot be carried over
+        assert_eq!(
+            &ClassReaderError::StackMapTableRequired(61),
+            prepend(SAMPLE).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn headers_ignore_what_follows_the_interfaces() {
+        let reader = ClassReader::new(SAMPLE).unwrap();
+        let end = reader.header_pos() + 10;
+        let header = ClassHeader::read(&SAMPLE[..end]).unwrap();
+
+        assert_eq!(header.name, "Sample");
+        assert_eq!(header.super_name.unwrap(), "java/lang/Object");
+        assert_eq!(header.interfaces, ["java/util/function/Supplier"]);
+        assert!(header.access_flags.contains(ClassAccessFlags::PUBLIC));
+    }
+
+    #[test]
+    fn errors_locate_the_faulty_data() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let compute = class
+            .methods
+            .iter()
+            .find(|method| {
+                class.constants[method.name_index].as_utf8().unwrap().string == "compute"
+            })
+            .unwrap();
+        let info = &compute
+            .find_attribute(&class.constants, "Code")
+            .unwrap()
+            .info;
+        let info_pos = SAMPLE
+            .windows(info.len())
+            .position(|w| w == &info[..])
+            .unwrap();
+        let code_length = u32::from_be_bytes(info[4..8].try_into().unwrap()) as usize;
+        // catch_type of the second entry of the exception table
+        let catch_type = info_pos + 8 + code_length + 2 + 8 + 6;
+        let mut bytes = SAMPLE.to_vec();
+        bytes[catch_type..catch_type + 2].copy_from_slice(&[0xFF, 0xFF]);
+
+        let reader = ClassReader::new(&bytes).unwrap();
+        let mut writer = ClassWriter::from_reader(&reader).unwrap();
+        let error = reader.accept(&mut writer).unwrap_err();
+        assert_eq!(
+            &ClassReaderError::InvalidConstantPoolIdx(0xFFFF),
+            error.kind()
+        );
+        assert_eq!(Some(catch_type), error.context().unwrap().offset);
+        assert_eq!(
+            format!(
+                "at byte 0x{:X} in method `compute(IJDLjava/lang/Comparable;)I` → attribute Code \
+                 → exception_table[1]: Invalid ConstantPool index `65535`",
+                catch_type
+            ),
+            error.to_string()
+        );
+
+        // Decoding the attribute of a read class names the method, but cannot tell the offset
+        let class = ClassFile::read(&bytes).unwrap();
+        let method = class
+            .methods
+            .iter()
+            .find(|m| m.name_index == compute.name_index);
+        let error = method
+            .unwrap()
+            .code(&class.constants, &class.const_idx_map().unwrap())
+            .unwrap_err();
+        assert_eq!(None, error.context().unwrap().offset);
+        assert_eq!(
+            vec![
+                "method `compute(IJDLjava/lang/Comparable;)I`",
+                "attribute Code",
+                "exception_table[1]"
+            ],
+            error.context().unwrap().path
+        );
+    }
+}
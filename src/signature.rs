@@ -0,0 +1,682 @@
+//! Generic signatures, as found in `Signature` attributes (JVMS 4.7.9.1)
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{
+    descriptor::BaseType,
+    java_str::{JavaStr, JavaString},
+};
+
+/// Errors related to parsing a signature, with the offset of the faulty byte
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("unexpected end of signature")]
+    UnexpectedEnd,
+
+    #[error("unexpected character at offset {0}")]
+    UnexpectedChar(usize),
+
+    #[error("unexpected data after the signature at offset {0}")]
+    TrailingData(usize),
+
+    #[error("types nested more than {MAX_DEPTH} levels deep at offset {0}")]
+    TooDeep(usize),
+}
+
+/// Maximum nesting of types in arrays and type arguments, well beyond what compilers produce.
+///
+/// Types being parsed, displayed and dropped recursively, this keeps forged signatures from
+/// overflowing the stack.
+pub const MAX_DEPTH: usize = 512;
+
+type Result<T> = std::result::Result<T, SignatureError>;
+
+/// A type used in a signature
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeSignature {
+    Base(BaseType),
+    Class(ClassTypeSignature),
+    /// A type variable, by its name
+    TypeVariable(JavaString),
+    Array(Box<TypeSignature>),
+}
+
+/// A possibly parameterized class type, such as `java/util/Map<TK;TV;>.Entry<TK;TV;>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    /// Package of the class with a trailing `/`, or an empty string for the default package
+    pub package: JavaString,
+    /// The outermost class, followed by the inner classes down to the one designated
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: JavaString,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    /// The unbounded wildcard `?`
+    Any,
+    Exact(TypeSignature),
+    /// `? extends` the type
+    Extends(TypeSignature),
+    /// `? super` the type
+    Super(TypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: JavaString,
+    /// `None` when the parameter is only bounded by interfaces
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+/// The signature of a class, in the `Signature` attribute of a class
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+/// The signature of a method, in the `Signature` attribute of a method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    /// `None` for `void`
+    pub return_type: Option<TypeSignature>,
+    /// Thrown classes and type variables
+    pub throws: Vec<TypeSignature>,
+}
+
+/// The signature of a field, in the `Signature` attribute of a field, or of a local variable
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldSignature {
+    pub field_type: TypeSignature,
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &JavaStr) -> Result<Self> {
+        let mut parser = Parser::new(signature.as_bytes());
+        let type_parameters = parser.type_parameters()?;
+        let super_class = parser.class_type()?;
+        let mut interfaces = Vec::new();
+        while parser.pos < parser.bytes.len() {
+            interfaces.push(parser.class_type()?);
+        }
+        Ok(Self {
+            type_parameters,
+            super_class,
+            interfaces,
+        })
+    }
+
+    /// Displays the signature as in a Java class declaration, e.g.
+    /// `<T> extends java.lang.Object implements java.util.List<T>`.
+    ///
+    /// Interfaces are always introduced by `implements`, as the signature does not tell
+    /// whether the class is an interface.
+    pub fn java(&self) -> impl Display + '_ {
+        Java(self)
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &JavaStr) -> Result<Self> {
+        let mut parser = Parser::new(signature.as_bytes());
+        let type_parameters = parser.type_parameters()?;
+        parser.expect(b'(')?;
+        let mut parameters = Vec::new();
+        while !parser.eat(b')')? {
+            parameters.push(parser.java_type()?);
+        }
+        let return_type = if parser.eat(b'V')? {
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws = Vec::new();
+        while parser.pos < parser.bytes.len() {
+            parser.expect(b'^')?;
+            throws.push(parser.reference_type()?);
+        }
+        Ok(Self {
+            type_parameters,
+            parameters,
+            return_type,
+            throws,
+        })
+    }
+
+    /// Displays the method as declared in Java source, e.g.
+    /// `<T> void run(java.util.List<T>) throws java.io.IOException`
+    pub fn java<'a>(&'a self, name: &'a str) -> impl Display + 'a {
+        JavaMethod(self, name)
+    }
+}
+
+impl FieldSignature {
+    pub fn parse(signature: &JavaStr) -> Result<Self> {
+        let mut parser = Parser::new(signature.as_bytes());
+        let field_type = parser.reference_type()?;
+        parser.end()?;
+        Ok(Self { field_type })
+    }
+
+    /// Displays the type of the field as in Java source, e.g. `java.util.List<? extends T>`
+    pub fn java(&self) -> impl Display + '_ {
+        Java(&self.field_type)
+    }
+}
+
+impl TypeSignature {
+    /// Displays the type as in Java source
+    pub fn java(&self) -> impl Display + '_ {
+        Java(self)
+    }
+}
+
+macro_rules! from_str {
+    ($($signature:ident),*) => {
+        $(
+            impl FromStr for $signature {
+                type Err = SignatureError;
+
+                fn from_str(signature: &str) -> Result<Self> {
+                    Self::parse(&JavaString::from(signature))
+                }
+            }
+        )*
+    };
+}
+
+from_str!(ClassSignature, MethodSignature, FieldSignature);
+
+impl Display for TypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Base(base_type) => write!(f, "{}", base_type.descriptor_char()),
+            Self::Class(class_type) => write!(f, "{}", class_type),
+            Self::TypeVariable(name) => write!(f, "T{};", name),
+            Self::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+impl Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L{}", self.package)?;
+        for (idx, class) in self.classes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class.name)?;
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                for argument in &class.type_arguments {
+                    match argument {
+                        TypeArgument::Any => write!(f, "*")?,
+                        TypeArgument::Exact(bound) => write!(f, "{}", bound)?,
+                        TypeArgument::Extends(bound) => write!(f, "+{}", bound)?,
+                        TypeArgument::Super(bound) => write!(f, "-{}", bound)?,
+                    }
+                }
+                write!(f, ">")?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+
+fn write_type_parameters(
+    f: &mut Formatter<'_>,
+    type_parameters: &[TypeParameter],
+) -> std::fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+    write!(f, "<")?;
+    for parameter in type_parameters {
+        write!(f, "{}:", parameter.name)?;
+        if let Some(bound) = &parameter.class_bound {
+            write!(f, "{}", bound)?;
+        }
+        for bound in &parameter.interface_bounds {
+            write!(f, ":{}", bound)?;
+        }
+    }
+    write!(f, ">")
+}
+
+impl Display for ClassSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.super_class)?;
+        for interface in &self.interfaces {
+            write!(f, "{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MethodSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type)?,
+            None => write!(f, "V")?,
+        }
+        for throws in &self.throws {
+            write!(f, "^{}", throws)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for FieldSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.field_type)
+    }
+}
+
+/// Displays a part of a signature in Java source syntax
+struct Java<'a, T: ?Sized>(&'a T);
+
+impl Display for Java<'_, TypeSignature> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            TypeSignature::Base(base_type) => write!(f, "{}", base_type.java_name()),
+            TypeSignature::Class(class_type) => write!(f, "{}", Java(class_type)),
+            TypeSignature::TypeVariable(name) => write!(f, "{}", name),
+            TypeSignature::Array(component) => write!(f, "{}[]", Java(&**component)),
+        }
+    }
+}
+
+impl Display for Java<'_, ClassTypeSignature> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.package.to_str().replace('/', "."))?;
+        for (idx, class) in self.0.classes.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class.name)?;
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                for (idx, argument) in class.type_arguments.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match argument {
+                        TypeArgument::Any => write!(f, "?")?,
+                        TypeArgument::Exact(bound) => write!(f, "{}", Java(bound))?,
+                        TypeArgument::Extends(bound) => write!(f, "? extends {}", Java(bound))?,
+                        TypeArgument::Super(bound) => write!(f, "? super {}", Java(bound))?,
+                    }
+                }
+                write!(f, ">")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Java<'_, [TypeParameter]> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (idx, parameter) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", parameter.name)?;
+            let bounds = parameter
+                .class_bound
+                .iter()
+                .filter(|bound| !is_object(bound))
+                .chain(&parameter.interface_bounds);
+            for (idx, bound) in bounds.enumerate() {
+                let separator = if idx == 0 { " extends " } else { " & " };
+                write!(f, "{}{}", separator, Java(bound))?;
+            }
+        }
+        write!(f, ">")
+    }
+}
+
+impl Display for Java<'_, ClassSignature> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let signature = self.0;
+        if !signature.type_parameters.is_empty() {
+            write!(f, "{} ", Java(signature.type_parameters.as_slice()))?;
+        }
+        write!(f, "extends {}", Java(&signature.super_class))?;
+        for (idx, interface) in signature.interfaces.iter().enumerate() {
+            let separator = if idx == 0 { " implements " } else { ", " };
+            write!(f, "{}{}", separator, Java(interface))?;
+        }
+        Ok(())
+    }
+}
+
+struct JavaMethod<'a>(&'a MethodSignature, &'a str);
+
+impl Display for JavaMethod<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self(signature, name) = self;
+        if !signature.type_parameters.is_empty() {
+            write!(f, "{} ", Java(signature.type_parameters.as_slice()))?;
+        }
+        match &signature.return_type {
+            Some(return_type) => write!(f, "{} {}(", Java(return_type), name)?,
+            None => write!(f, "void {}(", name)?,
+        }
+        for (idx, parameter) in signature.parameters.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", Java(parameter))?;
+        }
+        write!(f, ")")?;
+        for (idx, throws) in signature.throws.iter().enumerate() {
+            let separator = if idx == 0 { " throws " } else { ", " };
+            write!(f, "{}{}", separator, Java(throws))?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `bound` is `java.lang.Object`, the implicit bound of type parameters
+fn is_object(bound: &TypeSignature) -> bool {
+    matches!(
+        bound,
+        TypeSignature::Class(ClassTypeSignature { package, classes })
+            if package == "java/lang/"
+                && matches!(classes.as_slice(), [class] if class.name == "Object" && class.type_arguments.is_empty())
+    )
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Number of reference types being parsed, each nested in the previous one
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or(SignatureError::UnexpectedEnd)
+    }
+
+    /// Skips `c` if it comes next, telling whether it did
+    fn eat(&mut self, c: u8) -> Result<bool> {
+        let found = self.peek()? == c;
+        if found {
+            self.pos += 1;
+        }
+        Ok(found)
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if !self.eat(c)? {
+            return Err(SignatureError::UnexpectedChar(self.pos));
+        }
+        Ok(())
+    }
+
+    fn end(&self) -> Result<()> {
+        if self.pos < self.bytes.len() {
+            return Err(SignatureError::TrailingData(self.pos));
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<JavaString> {
+        let start = self.pos;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|c| matches!(c, b'.' | b';' | b'[' | b'/' | b'<' | b'>' | b':'))
+            .ok_or(SignatureError::UnexpectedEnd)?;
+        if len == 0 {
+            return Err(SignatureError::UnexpectedChar(start));
+        }
+        self.pos += len;
+        JavaStr::from_bytes(&self.bytes[start..self.pos])
+            .map(JavaStr::to_java_string)
+            .map_err(|_| SignatureError::UnexpectedChar(start))
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>> {
+        let mut type_parameters = Vec::new();
+        if !self.eat(b'<')? {
+            return Ok(type_parameters);
+        }
+        loop {
+            let name = self.identifier()?;
+            self.expect(b':')?;
+            let class_bound = match self.peek()? {
+                b'L' | b'T' | b'[' => Some(self.reference_type()?),
+                _ => None,
+            };
+            let mut interface_bounds = Vec::new();
+            while self.eat(b':')? {
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+            if self.eat(b'>')? {
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature> {
+        match BaseType::from_char(self.peek()?) {
+            Some(base_type) => {
+                self.pos += 1;
+                Ok(TypeSignature::Base(base_type))
+            }
+            None => self.reference_type(),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature> {
+        if self.depth == MAX_DEPTH {
+            return Err(SignatureError::TooDeep(self.pos));
+        }
+        self.depth += 1;
+        let reference_type = self.nested_reference_type();
+        self.depth -= 1;
+        reference_type
+    }
+
+    fn nested_reference_type(&mut self) -> Result<TypeSignature> {
+        match self.peek()? {
+            b'L' => Ok(TypeSignature::Class(self.class_type()?)),
+            b'T' => {
+                self.pos += 1;
+                let name = self.identifier()?;
+                self.expect(b';')?;
+                Ok(TypeSignature::TypeVariable(name))
+            }
+            b'[' => {
+                self.pos += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            _ => Err(SignatureError::UnexpectedChar(self.pos)),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature> {
+        self.expect(b'L')?;
+        let mut package = Vec::new();
+        let mut name = self.identifier()?;
+        while self.eat(b'/')? {
+            package.extend_from_slice(name.as_bytes());
+            package.push(b'/');
+            name = self.identifier()?;
+        }
+        let package = JavaString::from_bytes(package)
+            .map_err(|_| SignatureError::UnexpectedChar(self.pos))?;
+        let mut classes = vec![self.simple_class_type(name)?];
+        while self.eat(b'.')? {
+            let name = self.identifier()?;
+            classes.push(self.simple_class_type(name)?);
+        }
+        self.expect(b';')?;
+        Ok(ClassTypeSignature { package, classes })
+    }
+
+    fn simple_class_type(&mut self, name: JavaString) -> Result<SimpleClassTypeSignature> {
+        let mut type_arguments = Vec::new();
+        if self.eat(b'<')? {
+            loop {
+                type_arguments.push(match self.peek()? {
+                    b'*' => {
+                        self.pos += 1;
+                        TypeArgument::Any
+                    }
+                    b'+' => {
+                        self.pos += 1;
+                        TypeArgument::Extends(self.reference_type()?)
+                    }
+                    b'-' => {
+                        self.pos += 1;
+                        TypeArgument::Super(self.reference_type()?)
+                    }
+                    _ => TypeArgument::Exact(self.reference_type()?),
+                });
+                if self.eat(b'>')? {
+                    break;
+                }
+            }
+        }
+        Ok(SimpleClassTypeSignature {
+            name,
+            type_arguments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ClassFile, attribute::Attribute, java_str::JavaString};
+
+    use super::{
+        ClassSignature, FieldSignature, MAX_DEPTH, MethodSignature, SignatureError, TypeArgument,
+        TypeSignature,
+    };
+
+    const SAMPLE: &[u8] = include_bytes!("../tests/fixtures/Sample.class");
+
+    #[test]
+    fn sample_signatures_round_trip() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let signature = |attributes: &[Attribute]| {
+            let attribute = Attribute::find(attributes, &class.constants, "Signature")?;
+            let idx = u16::from_be_bytes([attribute.info[0], attribute.info[1]]);
+            let idx = class.const_idx_map().unwrap().resolve(idx).unwrap();
+            Some(class.constants[idx].as_utf8().unwrap().string.clone())
+        };
+
+        let class_signature = signature(&class.attributes).unwrap();
+        let parsed = ClassSignature::parse(&class_signature).unwrap();
+        assert_eq!(class_signature, parsed.to_string().as_str());
+        assert_eq!(
+            "<T extends java.lang.Comparable<? super T>> extends java.lang.Object \
+             implements java.util.function.Supplier<java.util.List<T>>",
+            parsed.java().to_string()
+        );
+
+        for method in &class.methods {
+            if let Some(method_signature) = signature(&method.attributes) {
+                let parsed = MethodSignature::parse(&method_signature).unwrap();
+                assert_eq!(method_signature, parsed.to_string().as_str());
+            }
+        }
+        for field in &class.fields {
+            if let Some(field_signature) = signature(&field.attributes) {
+                let parsed = FieldSignature::parse(&field_signature).unwrap();
+                assert_eq!(field_signature, parsed.to_string().as_str());
+            }
+        }
+    }
+
+    #[test]
+    fn methods_and_inner_classes_are_rendered() {
+        let signature: MethodSignature =
+            "<E:Ljava/lang/Exception;>(ILOuter<TE;>.Inner<[J>;)[TE;^TE;^Ljava/io/IOException;"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            "<E extends java.lang.Exception> E[] run(int, Outer<E>.Inner<long[]>) \
+             throws E, java.io.IOException",
+            signature.java("run").to_string()
+        );
+
+        let field: FieldSignature = "Ljava/util/Map<*-TK;>;".parse().unwrap();
+        let TypeSignature::Class(class_type) = &field.field_type else {
+            panic!("not a class type");
+        };
+        assert_eq!(
+            vec![
+                TypeArgument::Any,
+                TypeArgument::Super(TypeSignature::TypeVariable(JavaString::from("K")))
+            ],
+            class_type.classes[0].type_arguments
+        );
+        assert_eq!("java.util.Map<?, ? super K>", field.java().to_string());
+    }
+
+    #[test]
+    fn invalid_signatures_are_rejected() {
+        assert_eq!(
+            Err(SignatureError::UnexpectedEnd),
+            "Ljava/util/List<TT;>".parse::<FieldSignature>()
+        );
+        assert_eq!(
+            Err(SignatureError::UnexpectedChar(0)),
+            "I".parse::<FieldSignature>()
+        );
+        assert_eq!(
+            Err(SignatureError::UnexpectedChar(1)),
+            "<>()V".parse::<MethodSignature>()
+        );
+        let nested = "Ljava/util/List<".repeat(MAX_DEPTH) + "TT;" + &">;".repeat(MAX_DEPTH);
+        assert_eq!(
+            Err(SignatureError::TooDeep(16 * MAX_DEPTH)),
+            nested.parse::<FieldSignature>()
+        );
+    }
+}
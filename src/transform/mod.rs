@@ -0,0 +1,542 @@
+//! Rewriting of classes as streams of elements, in the style of the `java.lang.classfile` API.
+//!
+//! A class is presented to a [ClassTransform] as a sequence of [ClassElement]s, each of which
+//! can be passed on to the [ClassBuilder] with [ClassBuilder::with], replaced, or dropped. The
+//! same goes for methods and code, which are only taken apart when a transform asks for it with
+//! [transform_methods] and [transform_code]. Elements keep referring to the original constant
+//! pool, which is only ever appended to.
+//!
+//! ```
+//! use classfile::{ClassFile, builder::CodeBuilder, code::Instruction, transform::*};
+//!
+//! // Replaces every `iadd` with an `isub`
+//! let mut transform = transform_methods(transform_code(
+//!     |code: &mut CodeBuilder, element: CodeElement| match element {
+//!         CodeElement::Instruction(Instruction::Iadd) => {
+//!             code.instruction(Instruction::Isub);
+//!         }
+//!         element => {
+//!             code.with(element);
+//!         }
+//!     },
+//! ));
+//! # let class = classfile::builder::ClassBuilder::new("Empty").build().unwrap();
+//! let class: ClassFile = class.transform(&mut transform).unwrap();
+//! ```
+
+use crate::{
+    ClassAccessFlags, ClassFile, Result,
+    attribute::Attribute,
+    builder::{ClassBuilder, CodeBuilder, MethodBuilder},
+    code::{Code, ExceptionHandler, Instruction, Label, LineNumber, LocalVariable, read_table},
+    constants::{ConstIdxMap, ConstItemIdx, Constants},
+    field::Field,
+    method::{Method, MethodAccessFlags},
+    version::ClassFileVersion,
+};
+
+/// A part of a class, as seen by a [ClassTransform]
+#[derive(Debug, Clone)]
+pub enum ClassElement {
+    /// The major and minor versions
+    Version(ClassFileVersion, u16),
+    AccessFlags(ClassAccessFlags),
+    Superclass(Option<ConstItemIdx>),
+    Interface(ConstItemIdx),
+    Field(Field),
+    Method(Method),
+    Attribute(Attribute),
+}
+
+/// A part of a method, as seen by a [MethodTransform]
+#[derive(Debug, Clone)]
+pub enum MethodElement {
+    AccessFlags(MethodAccessFlags),
+    /// The `Code` attribute, which [transform_code] takes apart
+    Code(Attribute),
+    Attribute(Attribute),
+}
+
+/// A part of the body of a method, as seen by a [CodeTransform].
+///
+/// The instructions come in order, each preceded by the labels placed on it and by its line
+/// number. Labels of the original code are its pcs, new ones being created with
+/// [CodeBuilder::new_label].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeElement {
+    Instruction(Instruction),
+    Label(Label),
+    LineNumber(u16),
+    ExceptionHandler(ExceptionHandler),
+    LocalVariable(LocalVariable),
+    LocalVariableType(LocalVariable),
+    /// Any attribute other than `StackMapTable` and the non-empty debug tables
+    Attribute(Attribute),
+}
+
+/// Rewrites the elements of a class, see [ClassFile::transform]
+pub trait ClassTransform {
+    /// Handles `element`, passing it to `builder` to keep it
+    fn accept(&mut self, builder: &mut ClassBuilder, element: ClassElement);
+
+    /// Called once every element has been seen, to add new ones
+    fn at_end(&mut self, _builder: &mut ClassBuilder) {}
+
+    /// Feeds the output of this transform to `next`
+    fn and_then<T: ClassTransform>(self, next: T) -> AndThen<Self, T>
+    where
+        Self: Sized,
+    {
+        AndThen(self, next)
+    }
+}
+
+impl<F: FnMut(&mut ClassBuilder, ClassElement)> ClassTransform for F {
+    fn accept(&mut self, builder: &mut ClassBuilder, element: ClassElement) {
+        self(builder, element)
+    }
+}
+
+/// Rewrites the elements of a method, see [ClassBuilder::transform_method]
+pub trait MethodTransform {
+    /// Handles `element`, passing it to `builder` to keep it
+    fn accept(&mut self, builder: &mut MethodBuilder, element: MethodElement);
+
+    /// Called once every element has been seen, to add new ones
+    fn at_end(&mut self, _builder: &mut MethodBuilder) {}
+
+    /// Feeds the output of this transform to `next`
+    fn and_then<T: MethodTransform>(self, next: T) -> AndThen<Self, T>
+    where
+        Self: Sized,
+    {
+        AndThen(self, next)
+    }
+}
+
+impl<F: FnMut(&mut MethodBuilder, MethodElement)> MethodTransform for F {
+    fn accept(&mut self, builder: &mut MethodBuilder, element: MethodElement) {
+        self(builder, element)
+    }
+}
+
+/// Rewrites the elements of the body of a method, see [MethodBuilder::transform_code]
+pub trait CodeTransform {
+    /// Handles `element`, passing it to `builder` to keep it
+    fn accept(&mut self, builder: &mut CodeBuilder, element: CodeElement);
+
+    /// Called once every element has been seen, to add new ones
+    fn at_end(&mut self, _builder: &mut CodeBuilder) {}
+
+    /// Feeds the output of this transform to `next`
+    fn and_then<T: CodeTransform>(self, next: T) -> AndThen<Self, T>
+    where
+        Self: Sized,
+    {
+        AndThen(self, next)
+    }
+}
+
+impl<F: FnMut(&mut CodeBuilder, CodeElement)> CodeTransform for F {
+    fn accept(&mut self, builder: &mut CodeBuilder, element: CodeElement) {
+        self(builder, element)
+    }
+}
+
+/// Two transforms applied one after the other, see [ClassTransform::and_then]
+#[derive(Debug, Clone)]
+pub struct AndThen<A, B>(A, B);
+
+macro_rules! and_then {
+    ($transform:ident, $builder:ident, $element:ident) => {
+        impl<A: $transform, B: $transform> $transform for AndThen<A, B> {
+            fn accept(&mut self, builder: &mut $builder, element: $element) {
+                for element in builder.record(|builder| self.0.accept(builder, element)) {
+                    self.1.accept(builder, element);
+                }
+            }
+
+            fn at_end(&mut self, builder: &mut $builder) {
+                for element in builder.record(|builder| self.0.at_end(builder)) {
+                    self.1.accept(builder, element);
+                }
+                self.1.at_end(builder);
+            }
+        }
+    };
+}
+
+and_then!(ClassTransform, ClassBuilder, ClassElement);
+and_then!(MethodTransform, MethodBuilder, MethodElement);
+and_then!(CodeTransform, CodeBuilder, CodeElement);
+
+/// A class transform applying a method transform to every method
+#[derive(Debug, Clone)]
+pub struct TransformMethods<T>(T);
+
+/// Applies `transform` to every method of a class, keeping the other elements
+pub fn transform_methods<T: MethodTransform>(transform: T) -> TransformMethods<T> {
+    TransformMethods(transform)
+}
+
+impl<T: MethodTransform> ClassTransform for TransformMethods<T> {
+    fn accept(&mut self, builder: &mut ClassBuilder, element: ClassElement) {
+        match element {
+            ClassElement::Method(method) => builder.transform_method(&method, &mut self.0),
+            element => builder.with(element),
+        };
+    }
+}
+
+/// A method transform applying a code transform to the body of the method
+#[derive(Debug, Clone)]
+pub struct TransformCode<T>(T);
+
+/// Applies `transform` to the body of a method, keeping the other elements
+pub fn transform_code<T: CodeTransform>(transform: T) -> TransformCode<T> {
+    TransformCode(transform)
+}
+
+impl<T: CodeTransform> MethodTransform for TransformCode<T> {
+    fn accept(&mut self, builder: &mut MethodBuilder, element: MethodElement) {
+        match element {
+            MethodElement::Code(code) => builder.transform_code(&code, &mut self.0),
+            element => builder.with(element),
+        };
+    }
+}
+
+impl ClassFile {
+    /// The elements of the class, in the order they are presented to a [ClassTransform]
+    pub fn elements(&self) -> Vec<ClassElement> {
+        let mut elements = vec![
+            ClassElement::Version(self.version.clone(), self.minor_version),
+            ClassElement::AccessFlags(self.access_flag.clone()),
+            ClassElement::Superclass(self.super_class),
+        ];
+        elements.extend(self.interfaces.iter().copied().map(ClassElement::Interface));
+        elements.extend(self.fields.iter().cloned().map(ClassElement::Field));
+        elements.extend(self.methods.iter().cloned().map(ClassElement::Method));
+        elements.extend(self.attributes.iter().cloned().map(ClassElement::Attribute));
+        elements
+    }
+
+    /// Builds a new class from the elements of this one passed through `transform`.
+    ///
+    /// The constant pool of the new class starts with the entries of this one, so that
+    /// untouched elements keep their meaning and come out unchanged.
+    pub fn transform(&self, transform: &mut impl ClassTransform) -> Result<ClassFile> {
+        let mut builder = ClassBuilder::from_class(self)?;
+        for element in self.elements() {
+            transform.accept(&mut builder, element);
+        }
+        transform.at_end(&mut builder);
+        builder.build()
+    }
+}
+
+impl Method {
+    /// The elements of the method, in the order they are presented to a [MethodTransform]
+    pub fn elements(&self, constants: &Constants) -> Vec<MethodElement> {
+        let mut elements = vec![MethodElement::AccessFlags(self.access_flags.clone())];
+        for attribute in &self.attributes {
+            let element = if attribute.name(constants).is_some_and(|name| name == "Code") {
+                MethodElement::Code(attribute.clone())
+            } else {
+                MethodElement::Attribute(attribute.clone())
+            };
+            elements.push(element);
+        }
+        elements
+    }
+}
+
+impl Code {
+    /// The elements of the body of the method, in the order they are presented to a
+    /// [CodeTransform]
+    pub fn elements(
+        &self,
+        constants: &Constants,
+        idx_map: &ConstIdxMap,
+    ) -> Result<Vec<CodeElement>> {
+        let mut line_numbers: Vec<LineNumber> = Vec::new();
+        let mut local_variables: Vec<LocalVariable> = Vec::new();
+        let mut local_variable_types: Vec<LocalVariable> = Vec::new();
+        let mut attributes = Vec::new();
+        for attribute in &self.attributes {
+            match attribute.name(constants).and_then(|name| name.as_str()) {
+                Some("StackMapTable") => {}
+                // Empty tables stay as they are, the builder only generating non-empty ones
+                _ if attribute.info == [0, 0] => attributes.push(attribute.clone()),
+                Some("LineNumberTable") => {
+                    line_numbers.append(&mut read_table(&attribute.info, idx_map)?)
+                }
+                Some("LocalVariableTable") => {
+                    local_variables.append(&mut read_table(&attribute.info, idx_map)?)
+                }
+                Some("LocalVariableTypeTable") => {
+                    local_variable_types.append(&mut read_table(&attribute.info, idx_map)?)
+                }
+                _ => attributes.push(attribute.clone()),
+            }
+        }
+        // Entries for the same pc keep their relative order
+        line_numbers.sort_by_key(|line_number| line_number.start);
+
+        let code_length = self.code_length();
+        let mut targets = vec![false; code_length as usize + 1];
+        let mut mark = |label: Label| {
+            if let Some(target) = targets.get_mut(label.0 as usize) {
+                *target = true;
+            }
+        };
+        for (_, instruction) in &self.instructions {
+            instruction.targets().into_iter().for_each(&mut mark);
+        }
+        for handler in &self.exception_table {
+            mark(handler.start);
+            mark(handler.end);
+            mark(handler.handler);
+        }
+        for variable in local_variables.iter().chain(&local_variable_types) {
+            mark(variable.start);
+            mark(variable.end);
+        }
+
+        let mut elements = Vec::with_capacity(self.instructions.len() + line_numbers.len());
+        elements.extend(
+            self.exception_table
+                .iter()
+                .cloned()
+                .map(CodeElement::ExceptionHandler),
+        );
+        elements.extend(local_variables.into_iter().map(CodeElement::LocalVariable));
+        elements.extend(
+            local_variable_types
+                .into_iter()
+                .map(CodeElement::LocalVariableType),
+        );
+        let mut line_numbers = line_numbers.into_iter().peekable();
+        for (pc, instruction) in &self.instructions {
+            if targets[*pc as usize] {
+                elements.push(CodeElement::Label(Label(*pc)));
+            }
+            while let Some(line_number) = line_numbers.next_if(|entry| entry.start.0 <= *pc) {
+                elements.push(CodeElement::LineNumber(line_number.line));
+            }
+            elements.push(CodeElement::Instruction(instruction.clone()));
+        }
+        if targets[code_length as usize] {
+            elements.push(CodeElement::Label(Label(code_length)));
+        }
+        elements.extend(attributes.into_iter().map(CodeElement::Attribute));
+        Ok(elements)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        builder::{ClassBuilder, CodeBuilder, MethodBuilder},
+        code::Instruction,
+        error::ClassReaderError,
+        method::{Method, MethodAccessFlags},
+        transform::{
+            ClassElement, ClassTransform, CodeElement, MethodElement, MethodTransform,
+            transform_code, transform_methods,
+        },
+        verifier::{Classes, verify_method},
+        version::ClassFileVersion,
+    };
+
+    const SAMPLE: &[u8] = include_bytes!("../../tests/fixtures/Sample.class");
+
+    #[test]
+    fn untouched_classes_are_unchanged() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let mut identity = transform_methods(transform_code(
+            |code: &mut CodeBuilder, element: CodeElement| {
+                code.with(element);
+            },
+        ));
+
+        assert_eq!(SAMPLE, class.transform(&mut identity).unwrap().write());
+    }
+
+    #[test]
+    fn transforms_can_drop_and_add_elements() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let constants = &class.constants;
+        let drop_compute = |builder: &mut ClassBuilder, element: ClassElement| match element {
+            ClassElement::Method(method)
+                if constants[method.name_index]
+                    .as_utf8()
+                    .is_some_and(|name| name.string == "compute") => {}
+            element => {
+                builder.with(element);
+            }
+        };
+        let mut transform = drop_compute.and_then(|builder: &mut ClassBuilder, element| {
+            if let ClassElement::Method(method) = &element
+                && let Some(name) = constants[method.name_index].as_utf8()
+            {
+                let name = format!("renamed_{}", name.string);
+                let method = Method {
+                    name_index: builder.pool().utf8(name.as_str()),
+                    ..method.clone()
+                };
+                builder.with(ClassElement::Method(method));
+            } else {
+                builder.with(element);
+            }
+        });
+
+        let transformed = class.transform(&mut transform).unwrap();
+        let names: Vec<_> = transformed
+            .methods
+            .iter()
+            .map(|method| {
+                transformed.constants[method.name_index]
+                    .as_utf8()
+                    .unwrap()
+                    .string
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(class.methods.len() - 1, names.len());
+        assert!(names.iter().all(|name| name.starts_with("renamed_")));
+    }
+
+    #[test]
+    fn instructions_can_be_replaced() {
+        let mut builder = ClassBuilder::new("Math");
+        builder.method("add", "(II)I", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                code.line_number(3)
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Iload1)
+                    .instruction(Instruction::Iadd)
+                    .instruction(Instruction::Ireturn);
+            });
+        });
+        let class = builder.build().unwrap();
+
+        let mut transform = transform_methods(
+            (|method: &mut MethodBuilder, element: MethodElement| {
+                method.with(element);
+            })
+            .and_then(transform_code(
+                |code: &mut CodeBuilder, element: CodeElement| match element {
+                    CodeElement::Instruction(Instruction::Iadd) => {
+                        code.load_int(1000)
+                            .instruction(Instruction::Iadd)
+                            .instruction(Instruction::Iadd);
+                    }
+                    element => {
+                        code.with(element);
+                    }
+                },
+            )),
+        );
+        let transformed = class.transform(&mut transform).unwrap();
+        let code = transformed.methods[0]
+            .code(
+                &transformed.constants,
+                &transformed.const_idx_map().unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+
+        let instructions: Vec<_> = code.instructions.into_iter().map(|(_, i)| i).collect();
+        assert_eq!(
+            vec![
+                Instruction::Iload0,
+                Instruction::Iload1,
+                Instruction::Sipush(1000),
+                Instruction::Iadd,
+                Instruction::Iadd,
+                Instruction::Ireturn
+            ],
+            instructions
+        );
+        assert_eq!(3, code.max_stack);
+        // The line number table is regenerated
+        assert_eq!(1, code.attributes.len());
+    }
+
+    #[test]
+    fn changed_code_with_branches_needs_frames() {
+        let abs = |version| {
+            let mut builder = ClassBuilder::new("Abs");
+            builder.version(ClassFileVersion::Jdk1_4, 0);
+            builder.method("abs", "(I)I", MethodAccessFlags::STATIC, |method| {
+                method.code(|code| {
+                    let positive = code.new_label();
+                    code.instruction(Instruction::Iload0)
+                        .instruction(Instruction::Ifge(positive))
+                        .instruction(Instruction::Iload0)
+                        .instruction(Instruction::Ineg)
+                        .instruction(Instruction::Ireturn)
+                        .place_label(positive)
+                        .instruction(Instruction::Iload0)
+                        .instruction(Instruction::Ireturn);
+                });
+            });
+            let mut class = builder.build().unwrap();
+            class.version = version;
+            class
+        };
+        let mut transform = transform_methods(transform_code(
+            |code: &mut CodeBuilder, element: CodeElement| {
+                if let CodeElement::Instruction(Instruction::Ireturn) = element {
+                    code.instruction(Instruction::Nop);
+                }
+                code.with(element);
+            },
+        ));
+
+        let error = abs(ClassFileVersion::Jdk8)
+            .transform(&mut transform)
+            .unwrap_err();
+        assert_eq!(&ClassReaderError::StackMapTableRequired(52), error.kind());
+        assert!(
+            abs(ClassFileVersion::Jdk1_4)
+                .transform(&mut transform)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn frames_are_kept_when_the_layout_is_unchanged() {
+        let class = ClassFile::read(SAMPLE).unwrap();
+        let mut transform = transform_methods(transform_code(
+            |code: &mut CodeBuilder, element: CodeElement| match element {
+                CodeElement::Instruction(Instruction::Iadd) => {
+                    code.instruction(Instruction::Isub);
+                }
+                element => {
+                    code.with(element);
+                }
+            },
+        ));
+
+        let transformed = class.transform(&mut transform).unwrap();
+        assert_ne!(SAMPLE, transformed.write());
+        let compute = transformed
+            .methods
+            .iter()
+            .find(|method| {
+                transformed.constants[method.name_index]
+                    .as_utf8()
+                    .unwrap()
+                    .string
+                    == "compute"
+            })
+            .unwrap();
+        assert_eq!(
+            Ok(()),
+            verify_method(&transformed, compute, &Classes::new())
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::{ClassAccessFlags, ClassFile};
+
+use super::class_name;
+
+/// What the verifier needs to know about a class to check assignments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassInfo {
+    /// Internal name of the super class, `None` for `java/lang/Object`
+    pub super_class: Option<String>,
+    pub is_interface: bool,
+}
+
+/// Resolves the classes referenced by the code being verified.
+///
+/// The verifier only asks for the classes it has to compare, and treats a class that cannot
+/// be resolved as an error, as the JVM would when loading it.
+pub trait ClassHierarchy {
+    /// Describes the class with the internal name `name`, or returns `None` if it is unknown
+    fn resolve(&self, name: &str) -> Option<ClassInfo>;
+}
+
+/// A [ClassHierarchy] made of known classes.
+///
+/// It starts with the core classes of `java.lang` and `java.io` that compiled code most
+/// commonly relies on, such as `java/lang/Throwable` and its main subclasses.
+#[derive(Debug, Clone)]
+pub struct Classes {
+    classes: HashMap<String, ClassInfo>,
+}
+
+/// Core classes and their super classes
+const CORE_CLASSES: &[(&str, &str)] = &[
+    ("java/lang/String", "java/lang/Object"),
+    ("java/lang/Class", "java/lang/Object"),
+    ("java/lang/Enum", "java/lang/Object"),
+    ("java/lang/Record", "java/lang/Object"),
+    ("java/lang/Number", "java/lang/Object"),
+    ("java/lang/Boolean", "java/lang/Object"),
+    ("java/lang/Character", "java/lang/Object"),
+    ("java/lang/Byte", "java/lang/Number"),
+    ("java/lang/Short", "java/lang/Number"),
+    ("java/lang/Integer", "java/lang/Number"),
+    ("java/lang/Long", "java/lang/Number"),
+    ("java/lang/Float", "java/lang/Number"),
+    ("java/lang/Double", "java/lang/Number"),
+    ("java/lang/AbstractStringBuilder", "java/lang/Object"),
+    ("java/lang/StringBuilder", "java/lang/AbstractStringBuilder"),
+    ("java/lang/StringBuffer", "java/lang/AbstractStringBuilder"),
+    ("java/lang/Thread", "java/lang/Object"),
+    ("java/lang/Throwable", "java/lang/Object"),
+    ("java/lang/Exception", "java/lang/Throwable"),
+    ("java/lang/Error", "java/lang/Throwable"),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    (
+        "java/lang/IllegalArgumentException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalStateException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/NullPointerException",
+        "java/lang/RuntimeException",
+    ),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    (
+        "java/lang/ArithmeticException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IndexOutOfBoundsException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/UnsupportedOperationException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/CloneNotSupportedException",
+        "java/lang/Exception",
+    ),
+    ("java/lang/InterruptedException", "java/lang/Exception"),
+    (
+        "java/lang/ReflectiveOperationException",
+        "java/lang/Exception",
+    ),
+    (
+        "java/lang/ClassNotFoundException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    ("java/lang/AssertionError", "java/lang/Error"),
+    ("java/io/IOException", "java/lang/Exception"),
+    ("java/io/UncheckedIOException", "java/lang/RuntimeException"),
+];
+
+/// Core interfaces
+const CORE_INTERFACES: &[&str] = &[
+    "java/lang/Cloneable",
+    "java/io/Serializable",
+    "java/lang/Comparable",
+    "java/lang/CharSequence",
+    "java/lang/Runnable",
+    "java/lang/Iterable",
+    "java/lang/AutoCloseable",
+    "java/io/Closeable",
+];
+
+impl Classes {
+    /// Starts with `java/lang/Object` and the core classes
+    pub fn new() -> Self {
+        let mut classes = HashMap::new();
+        classes.insert(
+            "java/lang/Object".to_string(),
+            ClassInfo {
+                super_class: None,
+                is_interface: false,
+            },
+        );
+        for (name, super_class) in CORE_CLASSES {
+            let info = ClassInfo {
+                super_class: Some(super_class.to_string()),
+                is_interface: false,
+            };
+            classes.insert(name.to_string(), info);
+        }
+        for name in CORE_INTERFACES {
+            let info = ClassInfo {
+                super_class: Some("java/lang/Object".to_string()),
+                is_interface: true,
+            };
+            classes.insert(name.to_string(), info);
+        }
+        Self { classes }
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, info: ClassInfo) -> &mut Self {
+        self.classes.insert(name.into(), info);
+        self
+    }
+
+    /// Registers `class`, ignored if its name cannot be resolved
+    pub fn add(&mut self, class: &ClassFile) -> &mut Self {
+        let name = |idx| class_name(&class.constants, idx).map(|name| name.to_string());
+        if let Some(this_class) = name(class.this_class) {
+            let info = ClassInfo {
+                super_class: class.super_class.and_then(name),
+                is_interface: class.access_flag.contains(ClassAccessFlags::INTERFACE),
+            };
+            self.insert(this_class, info);
+        }
+        self
+    }
+}
+
+impl Default for Classes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassHierarchy for Classes {
+    fn resolve(&self, name: &str) -> Option<ClassInfo> {
+        self.classes.get(name).cloned()
+    }
+}
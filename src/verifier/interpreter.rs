@@ -0,0 +1,825 @@
+use crate::{
+    ClassFile,
+    code::{ArrayType, Code, Instruction, WideInstruction},
+    constants::{ConstIdxMap, ConstItem, ConstItemIdx, Constants},
+    descriptor::{FieldType, MethodDescriptor},
+    error::ClassReaderError,
+    java_str::JavaStr,
+    method::{Method, MethodAccessFlags},
+};
+
+use super::{ClassHierarchy, Frame, Types, VerificationType, VerifyErrorKind, class_name};
+
+type Result<T> = std::result::Result<T, VerifyErrorKind>;
+
+const OBJECT: &str = "java/lang/Object";
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// The effect of each instruction on a frame (JVMS 4.10.1.9), shared by the verifiers.
+///
+/// Access to protected members is not checked.
+pub(crate) struct Interpreter<'a> {
+    pub(crate) types: Types<'a>,
+    pub(crate) constants: &'a Constants,
+    pub(crate) idx_map: &'a ConstIdxMap,
+    pub(crate) code: &'a Code,
+    /// Major version of the class, which decides the constants `ldc` may load
+    pub(crate) major_version: u16,
+    pub(crate) this_class: &'a str,
+    /// Whether the method is an instance initializer, `<init>`
+    pub(crate) is_init: bool,
+    /// `None` for `void`
+    pub(crate) return_type: Option<VerificationType>,
+}
+
+/// A load or a store of a local variable
+struct LocalAccess {
+    index: u16,
+    store: bool,
+    /// `None` for references
+    value: Option<VerificationType>,
+}
+
+/// The class, name and descriptor of a referenced field or method
+struct MemberRef<'a> {
+    /// `None` for InvokeDynamic constants
+    class: Option<&'a JavaStr>,
+    name: &'a JavaStr,
+    descriptor: &'a JavaStr,
+    descriptor_index: ConstItemIdx,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Prepares the interpretation of the code of `method`, returning the types of its
+    /// arguments with a single entry for longs and doubles
+    pub(crate) fn new(
+        class: &'a ClassFile,
+        method: &Method,
+        this_class: &'a str,
+        code: &'a Code,
+        idx_map: &'a ConstIdxMap,
+        hierarchy: &'a dyn ClassHierarchy,
+    ) -> Result<(Self, Vec<VerificationType>)> {
+        let utf8 = |idx: ConstItemIdx| {
+            let item = class.constants.get(idx).and_then(ConstItem::as_utf8);
+            let raw = idx_map.raw_past_end(idx);
+            item.map(|item| &item.string)
+                .ok_or(ClassReaderError::InvalidConstantPoolIdx(raw))
+        };
+        let name = utf8(method.name_index)?;
+        let descriptor = utf8(method.descriptor_index)?;
+        let descriptor = MethodDescriptor::parse(descriptor).map_err(|_| {
+            let raw = idx_map.raw_past_end(method.descriptor_index);
+            ClassReaderError::InvalidDescriptor(raw, descriptor.to_string())
+        })?;
+        let is_init = *name == "<init>";
+
+        let mut locals = Vec::new();
+        if !method.access_flags.contains(MethodAccessFlags::STATIC) {
+            locals.push(if is_init && this_class != OBJECT {
+                VerificationType::UninitializedThis
+            } else {
+                VerificationType::object(this_class)
+            });
+        }
+        locals.extend(
+            descriptor
+                .parameters
+                .iter()
+                .map(VerificationType::from_field_type),
+        );
+        let interpreter = Self {
+            types: Types { hierarchy },
+            constants: &class.constants,
+            idx_map,
+            code,
+            major_version: class.version.major(),
+            this_class,
+            is_init,
+            return_type: descriptor
+                .return_type
+                .as_ref()
+                .map(VerificationType::from_field_type),
+        };
+        Ok((interpreter, locals))
+    }
+
+    /// The frame the code starts with, given the arguments returned by [Interpreter::new]
+    pub(crate) fn initial_frame(&self, arguments: &[VerificationType]) -> Result<Frame> {
+        Frame::expand(arguments, Vec::new(), self.code.max_locals).ok_or_else(|| {
+            let slots = arguments.iter().map(VerificationType::size).sum::<u16>();
+            VerifyErrorKind::LocalOutOfRange(slots - 1)
+        })
+    }
+
+    /// Applies `instruction`, found at `pc`, to `frame`.
+    ///
+    /// `jsr` and `ret` are left to the verifier, as their effect depends on the subroutine
+    /// being executed.
+    pub(crate) fn execute(
+        &self,
+        pc: u32,
+        instruction: &Instruction,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        use Instruction::*;
+        use VerificationType::{Double, Float, Integer, Long, Null};
+
+        if let Some(access) = local_access(instruction) {
+            return self.access_local(frame, access);
+        }
+        match instruction {
+            Nop | Goto(_) | GotoW(_) => {}
+            AconstNull => self.push(frame, Null)?,
+            IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush(_)
+            | Sipush(_) => self.push(frame, Integer)?,
+            Lconst0 | Lconst1 => self.push(frame, Long)?,
+            Fconst0 | Fconst1 | Fconst2 => self.push(frame, Float)?,
+            Dconst0 | Dconst1 => self.push(frame, Double)?,
+            Ldc(idx) | LdcW(idx) => {
+                let value = self.constant(*idx, false)?;
+                self.push(frame, value)?;
+            }
+            Ldc2W(idx) => {
+                let value = self.constant(*idx, true)?;
+                self.push(frame, value)?;
+            }
+            Iaload => self.array_load(frame, &["[I"], Integer)?,
+            Laload => self.array_load(frame, &["[J"], Long)?,
+            Faload => self.array_load(frame, &["[F"], Float)?,
+            Daload => self.array_load(frame, &["[D"], Double)?,
+            Baload => self.array_load(frame, &["[B", "[Z"], Integer)?,
+            Caload => self.array_load(frame, &["[C"], Integer)?,
+            Saload => self.array_load(frame, &["[S"], Integer)?,
+            Aaload => {
+                self.pop_expect(frame, &Integer)?;
+                let array = self.pop_reference_array(frame)?;
+                let component = array.component().unwrap_or(Null);
+                self.push(frame, component)?;
+            }
+            Iastore => self.array_store(frame, &["[I"], Integer)?,
+            Lastore => self.array_store(frame, &["[J"], Long)?,
+            Fastore => self.array_store(frame, &["[F"], Float)?,
+            Dastore => self.array_store(frame, &["[D"], Double)?,
+            Bastore => self.array_store(frame, &["[B", "[Z"], Integer)?,
+            Castore => self.array_store(frame, &["[C"], Integer)?,
+            Sastore => self.array_store(frame, &["[S"], Integer)?,
+            Aastore => {
+                // Whether the value fits the component type is only known at run time
+                self.pop_expect(frame, &VerificationType::object(OBJECT))?;
+                self.pop_expect(frame, &Integer)?;
+                self.pop_reference_array(frame)?;
+            }
+            Pop => {
+                self.pop_slots(frame, 1)?;
+            }
+            Pop2 => {
+                self.pop_slots(frame, 2)?;
+            }
+            Dup => {
+                let value = self.pop_slots(frame, 1)?;
+                self.push_all(frame, [&value, &value])?;
+            }
+            DupX1 => {
+                let value1 = self.pop_slots(frame, 1)?;
+                let value2 = self.pop_slots(frame, 1)?;
+                self.push_all(frame, [&value1, &value2, &value1])?;
+            }
+            DupX2 => {
+                let value1 = self.pop_slots(frame, 1)?;
+                let value2 = self.pop_slots(frame, 2)?;
+                self.push_all(frame, [&value1, &value2, &value1])?;
+            }
+            Dup2 => {
+                let value = self.pop_slots(frame, 2)?;
+                self.push_all(frame, [&value, &value])?;
+            }
+            Dup2X1 => {
+                let value1 = self.pop_slots(frame, 2)?;
+                let value2 = self.pop_slots(frame, 1)?;
+                self.push_all(frame, [&value1, &value2, &value1])?;
+            }
+            Dup2X2 => {
+                let value1 = self.pop_slots(frame, 2)?;
+                let value2 = self.pop_slots(frame, 2)?;
+                self.push_all(frame, [&value1, &value2, &value1])?;
+            }
+            Swap => {
+                let value1 = self.pop_slots(frame, 1)?;
+                let value2 = self.pop_slots(frame, 1)?;
+                self.push_all(frame, [&value1, &value2])?;
+            }
+            Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor => {
+                self.operation(frame, &[Integer, Integer], Some(Integer))?
+            }
+            Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor => {
+                self.operation(frame, &[Long, Long], Some(Long))?
+            }
+            Lshl | Lshr | Lushr => self.operation(frame, &[Long, Integer], Some(Long))?,
+            Fadd | Fsub | Fmul | Fdiv | Frem => {
+                self.operation(frame, &[Float, Float], Some(Float))?
+            }
+            Dadd | Dsub | Dmul | Ddiv | Drem => {
+                self.operation(frame, &[Double, Double], Some(Double))?
+            }
+            Ineg | I2b | I2c | I2s => self.operation(frame, &[Integer], Some(Integer))?,
+            Lneg => self.operation(frame, &[Long], Some(Long))?,
+            Fneg => self.operation(frame, &[Float], Some(Float))?,
+            Dneg => self.operation(frame, &[Double], Some(Double))?,
+            Iinc(idx, _) => self.local(frame, *idx as u16, Some(&Integer)).map(drop)?,
+            Wide(WideInstruction::Iinc(idx, _)) => {
+                self.local(frame, *idx, Some(&Integer)).map(drop)?
+            }
+            I2l => self.operation(frame, &[Integer], Some(Long))?,
+            I2f => self.operation(frame, &[Integer], Some(Float))?,
+            I2d => self.operation(frame, &[Integer], Some(Double))?,
+            L2i => self.operation(frame, &[Long], Some(Integer))?,
+            L2f => self.operation(frame, &[Long], Some(Float))?,
+            L2d => self.operation(frame, &[Long], Some(Double))?,
+            F2i => self.operation(frame, &[Float], Some(Integer))?,
+            F2l => self.operation(frame, &[Float], Some(Long))?,
+            F2d => self.operation(frame, &[Float], Some(Double))?,
+            D2i => self.operation(frame, &[Double], Some(Integer))?,
+            D2l => self.operation(frame, &[Double], Some(Long))?,
+            D2f => self.operation(frame, &[Double], Some(Float))?,
+            Lcmp => self.operation(frame, &[Long, Long], Some(Integer))?,
+            Fcmpl | Fcmpg => self.operation(frame, &[Float, Float], Some(Integer))?,
+            Dcmpl | Dcmpg => self.operation(frame, &[Double, Double], Some(Integer))?,
+            Ifeq(_) | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) | Tableswitch(_)
+            | Lookupswitch(_) => self.operation(frame, &[Integer], None)?,
+            IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_) | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) => {
+                self.operation(frame, &[Integer, Integer], None)?
+            }
+            IfAcmpeq(_) | IfAcmpne(_) => {
+                self.pop_reference(frame)?;
+                self.pop_reference(frame)?;
+            }
+            Ifnull(_) | Ifnonnull(_) => {
+                self.pop_reference(frame)?;
+            }
+            Jsr(_) | JsrW(_) | Ret(_) | Wide(WideInstruction::Ret(_)) => {
+                return Err(VerifyErrorKind::UnsupportedInstruction(
+                    instruction.mnemonic(),
+                ));
+            }
+            Ireturn => self.return_value(frame, instruction, &Integer)?,
+            Lreturn => self.return_value(frame, instruction, &Long)?,
+            Freturn => self.return_value(frame, instruction, &Float)?,
+            Dreturn => self.return_value(frame, instruction, &Double)?,
+            Areturn => match &self.return_type {
+                Some(return_type) if return_type.is_reference() => {
+                    self.pop_expect(frame, return_type)?;
+                }
+                _ => return Err(VerifyErrorKind::InvalidReturn(instruction.mnemonic())),
+            },
+            Return => {
+                if self.return_type.is_some() {
+                    return Err(VerifyErrorKind::InvalidReturn(instruction.mnemonic()));
+                }
+                if self.is_init && frame.this_uninit {
+                    return Err(VerifyErrorKind::UninitializedReturn);
+                }
+            }
+            Getstatic(idx) => {
+                let field = self.field_type(*idx)?;
+                self.push(frame, field)?;
+            }
+            Putstatic(idx) => {
+                let field = self.field_type(*idx)?;
+                self.pop_expect(frame, &field)?;
+            }
+            Getfield(idx) => {
+                let field = self.field_type(*idx)?;
+                let class = self.member_class(*idx)?;
+                self.pop_expect(frame, &class)?;
+                self.push(frame, field)?;
+            }
+            Putfield(idx) => {
+                let field = self.field_type(*idx)?;
+                let class = self.member_class(*idx)?;
+                self.pop_expect(frame, &field)?;
+                // Constructors may set the fields of their own class before calling super()
+                let object = self.pop(frame)?;
+                let own_field =
+                    matches!(&class, VerificationType::Object(name) if name == self.this_class);
+                if !(object == VerificationType::UninitializedThis && own_field) {
+                    self.expect(object, &class)?;
+                }
+            }
+            Invokevirtual(idx)
+            | Invokespecial(idx)
+            | Invokestatic(idx)
+            | Invokeinterface(idx, _)
+            | Invokedynamic(idx) => self.invoke(instruction, *idx, frame)?,
+            New(idx) => {
+                self.class_constant(*idx)?;
+                let object = VerificationType::Uninitialized(pc);
+                if frame.stack.contains(&object) {
+                    let reason = "the object created by this `new` is already on the stack";
+                    return Err(VerifyErrorKind::InvalidInitialization(reason));
+                }
+                frame.replace(&object, &VerificationType::Top);
+                self.push(frame, object)?;
+            }
+            Newarray(array_type) => {
+                self.pop_expect(frame, &Integer)?;
+                self.push(
+                    frame,
+                    VerificationType::object(array_descriptor(array_type)),
+                )?;
+            }
+            Anewarray(idx) => {
+                let component = self.class_constant(*idx)?;
+                self.pop_expect(frame, &Integer)?;
+                let array = match component.strip_prefix('[') {
+                    Some(_) => format!("[{}", component),
+                    None => format!("[L{};", component),
+                };
+                self.push(frame, VerificationType::object(array))?;
+            }
+            Multianewarray(idx, dimensions) => {
+                let array = self.class_constant(*idx)?;
+                let depth = array.bytes().take_while(|&c| c == b'[').count();
+                if *dimensions == 0 || depth < *dimensions as usize {
+                    return Err(VerifyErrorKind::InvalidConstant(
+                        self.idx_map.raw_past_end(*idx),
+                    ));
+                }
+                for _ in 0..*dimensions {
+                    self.pop_expect(frame, &Integer)?;
+                }
+                self.push(frame, VerificationType::object(array))?;
+            }
+            Arraylength => {
+                let array = self.pop(frame)?;
+                if !array.is_array() {
+                    return Err(incompatible("array", array));
+                }
+                self.push(frame, Integer)?;
+            }
+            Athrow => {
+                self.pop_expect(frame, &VerificationType::object(THROWABLE))?;
+            }
+            Checkcast(idx) => {
+                let class = self.class_constant(*idx)?;
+                self.pop_expect(frame, &VerificationType::object(OBJECT))?;
+                self.push(frame, VerificationType::object(class))?;
+            }
+            Instanceof(idx) => {
+                self.class_constant(*idx)?;
+                self.pop_expect(frame, &VerificationType::object(OBJECT))?;
+                self.push(frame, Integer)?;
+            }
+            Monitorenter | Monitorexit => {
+                self.pop_expect(frame, &VerificationType::object(OBJECT))?;
+            }
+            // Loads and stores were handled by `access_local`
+            _ => unreachable!("{} accesses a local variable", instruction.mnemonic()),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn pop(&self, frame: &mut Frame) -> Result<VerificationType> {
+        frame.stack.pop().ok_or(VerifyErrorKind::StackUnderflow)
+    }
+
+    /// Pops a value that must be assignable to `expected`
+    fn pop_expect(
+        &self,
+        frame: &mut Frame,
+        expected: &VerificationType,
+    ) -> Result<VerificationType> {
+        let value = self.pop(frame)?;
+        self.expect(value, expected)
+    }
+
+    fn expect(
+        &self,
+        value: VerificationType,
+        expected: &VerificationType,
+    ) -> Result<VerificationType> {
+        if self.types.is_assignable(&value, expected)? {
+            Ok(value)
+        } else {
+            Err(incompatible(expected, value))
+        }
+    }
+
+    /// Pops a reference, which may be uninitialized
+    fn pop_reference(&self, frame: &mut Frame) -> Result<VerificationType> {
+        let value = self.pop(frame)?;
+        if value.is_reference() {
+            Ok(value)
+        } else {
+            Err(incompatible("reference", value))
+        }
+    }
+
+    /// Pops an array of references, or `null`
+    fn pop_reference_array(&self, frame: &mut Frame) -> Result<VerificationType> {
+        let array = self.pop(frame)?;
+        match &array {
+            VerificationType::Null => Ok(array),
+            VerificationType::Object(name) if name.starts_with("[L") || name.starts_with("[[") => {
+                Ok(array)
+            }
+            _ => Err(incompatible("array of references", array)),
+        }
+    }
+
+    /// Pops values taking exactly `slots` slots, which must not split a long or a double.
+    ///
+    /// The values are returned from the bottom of the stack.
+    fn pop_slots(&self, frame: &mut Frame, slots: u16) -> Result<Vec<VerificationType>> {
+        let mut values = Vec::new();
+        let mut popped = 0;
+        while popped < slots {
+            let value = self.pop(frame)?;
+            popped += value.size();
+            if popped > slots {
+                return Err(incompatible("category 1 value", value));
+            }
+            values.insert(0, value);
+        }
+        Ok(values)
+    }
+
+    pub(crate) fn push(&self, frame: &mut Frame, value: VerificationType) -> Result<()> {
+        frame.stack.push(value);
+        if frame.stack_size() > self.code.max_stack {
+            return Err(VerifyErrorKind::StackOverflow);
+        }
+        Ok(())
+    }
+
+    fn push_all<const N: usize>(
+        &self,
+        frame: &mut Frame,
+        groups: [&Vec<VerificationType>; N],
+    ) -> Result<()> {
+        for value in groups.into_iter().flatten() {
+            self.push(frame, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Pops `operands`, the last one being on top of the stack, and pushes `result`
+    fn operation(
+        &self,
+        frame: &mut Frame,
+        operands: &[VerificationType],
+        result: Option<VerificationType>,
+    ) -> Result<()> {
+        for operand in operands.iter().rev() {
+            self.pop_expect(frame, operand)?;
+        }
+        if let Some(result) = result {
+            self.push(frame, result)?;
+        }
+        Ok(())
+    }
+
+    fn array_load(
+        &self,
+        frame: &mut Frame,
+        arrays: &[&str],
+        value: VerificationType,
+    ) -> Result<()> {
+        self.pop_expect(frame, &VerificationType::Integer)?;
+        self.pop_primitive_array(frame, arrays)?;
+        self.push(frame, value)
+    }
+
+    fn array_store(
+        &self,
+        frame: &mut Frame,
+        arrays: &[&str],
+        value: VerificationType,
+    ) -> Result<()> {
+        self.pop_expect(frame, &value)?;
+        self.pop_expect(frame, &VerificationType::Integer)?;
+        self.pop_primitive_array(frame, arrays)
+    }
+
+    /// Pops `null` or an array with one of the descriptors `arrays`
+    fn pop_primitive_array(&self, frame: &mut Frame, arrays: &[&str]) -> Result<()> {
+        match self.pop(frame)? {
+            VerificationType::Null => Ok(()),
+            VerificationType::Object(name) if arrays.contains(&name.as_str()) => Ok(()),
+            array => Err(incompatible(arrays.join(" or "), array)),
+        }
+    }
+
+    /// Type of the local variable `index`, which must be assignable to `expected`, or be a
+    /// reference if `expected` is `None`
+    fn local(
+        &self,
+        frame: &Frame,
+        index: u16,
+        expected: Option<&VerificationType>,
+    ) -> Result<VerificationType> {
+        let size = expected.map_or(1, VerificationType::size);
+        if index as usize + size as usize > frame.locals.len() {
+            return Err(VerifyErrorKind::LocalOutOfRange(index));
+        }
+        let value = frame.locals[index as usize].clone();
+        match expected {
+            Some(expected) => self.expect(value, expected),
+            None if value.is_reference() => Ok(value),
+            None => Err(incompatible("reference", value)),
+        }
+    }
+
+    /// Stores `value` in the local variable `index`, invalidating the long or double it
+    /// overwrites half of
+    pub(crate) fn store(
+        &self,
+        frame: &mut Frame,
+        index: u16,
+        value: VerificationType,
+    ) -> Result<()> {
+        let index = index as usize;
+        let size = value.size() as usize;
+        if index + size > frame.locals.len() {
+            return Err(VerifyErrorKind::LocalOutOfRange(index as u16));
+        }
+        if index > 0 && frame.locals[index - 1].size() == 2 {
+            frame.locals[index - 1] = VerificationType::Top;
+        }
+        frame.locals[index] = value;
+        if size == 2 {
+            frame.locals[index + 1] = VerificationType::Top;
+        }
+        Ok(())
+    }
+
+    fn access_local(&self, frame: &mut Frame, access: LocalAccess) -> Result<()> {
+        if !access.store {
+            let value = self.local(frame, access.index, access.value.as_ref())?;
+            return self.push(frame, value);
+        }
+        let value = match &access.value {
+            Some(expected) => self.pop_expect(frame, expected)?,
+            None => {
+                // `astore` also saves the return address of a subroutine
+                let value = self.pop(frame)?;
+                if !value.is_reference() && !matches!(value, VerificationType::ReturnAddress(_)) {
+                    return Err(incompatible("reference", value));
+                }
+                value
+            }
+        };
+        self.store(frame, access.index, value)
+    }
+
+    fn return_value(
+        &self,
+        frame: &mut Frame,
+        instruction: &Instruction,
+        value: &VerificationType,
+    ) -> Result<()> {
+        if self.return_type.as_ref() != Some(value) {
+            return Err(VerifyErrorKind::InvalidReturn(instruction.mnemonic()));
+        }
+        self.pop_expect(frame, value).map(drop)
+    }
+
+    fn invoke(
+        &self,
+        instruction: &Instruction,
+        idx: ConstItemIdx,
+        frame: &mut Frame,
+    ) -> Result<()> {
+        let member = self.member_ref(idx)?;
+        let descriptor = MethodDescriptor::parse(member.descriptor)
+            .map_err(|_| self.invalid_descriptor(&member))?;
+        let is_init = member.name == "<init>";
+        if member.name == "<clinit>"
+            || (is_init && !matches!(instruction, Instruction::Invokespecial(_)))
+        {
+            return Err(VerifyErrorKind::InvalidConstant(
+                self.idx_map.raw_past_end(idx),
+            ));
+        }
+        for parameter in descriptor.parameters.iter().rev() {
+            self.pop_expect(frame, &VerificationType::from_field_type(parameter))?;
+        }
+        let class = member.class.map(|class| class.to_string());
+        match (instruction, class) {
+            (Instruction::Invokespecial(_), Some(class)) if is_init => {
+                let receiver = self.pop(frame)?;
+                let initialized = match &receiver {
+                    VerificationType::UninitializedThis => {
+                        let super_class = self.types.resolve(self.this_class)?.super_class;
+                        if class != self.this_class && Some(&class) != super_class.as_ref() {
+                            let reason = "`this` must be initialized by a constructor of this class or of its super class";
+                            return Err(VerifyErrorKind::InvalidInitialization(reason));
+                        }
+                        frame.this_uninit = false;
+                        self.this_class.to_string()
+                    }
+                    VerificationType::Uninitialized(new_pc) => {
+                        let created = match self.code.instruction_at(*new_pc) {
+                            Some(Instruction::New(idx)) => class_name(self.constants, *idx),
+                            _ => None,
+                        };
+                        if created.is_none_or(|created| *created != class.as_str()) {
+                            let reason = "the constructor is not one of the class created by `new`";
+                            return Err(VerifyErrorKind::InvalidInitialization(reason));
+                        }
+                        class
+                    }
+                    _ => return Err(incompatible("uninitialized object", receiver)),
+                };
+                frame.replace(&receiver, &VerificationType::object(initialized));
+            }
+            (Instruction::Invokespecial(_), Some(_)) => {
+                self.pop_expect(frame, &VerificationType::object(self.this_class))?;
+            }
+            (Instruction::Invokevirtual(_) | Instruction::Invokeinterface(..), Some(class)) => {
+                self.pop_expect(frame, &VerificationType::object(class))?;
+            }
+            _ => {}
+        }
+        if let Some(return_type) = &descriptor.return_type {
+            self.push(frame, VerificationType::from_field_type(return_type))?;
+        }
+        Ok(())
+    }
+
+    /// Type of the value loaded by `ldc`, `ldc_w` or, if `wide`, by `ldc2_w`
+    fn constant(&self, idx: ConstItemIdx, wide: bool) -> Result<VerificationType> {
+        let value = match (self.constants.get(idx), wide) {
+            (Some(ConstItem::Integer(_)), false) => Some(VerificationType::Integer),
+            (Some(ConstItem::Float(_)), false) => Some(VerificationType::Float),
+            (Some(ConstItem::String(_)), false) => {
+                Some(VerificationType::object("java/lang/String"))
+            }
+            (Some(ConstItem::Class(_)), false) if self.major_version >= 49 => {
+                Some(VerificationType::object("java/lang/Class"))
+            }
+            (Some(ConstItem::MethodType(_)), false) if self.major_version >= 51 => {
+                Some(VerificationType::object("java/lang/invoke/MethodType"))
+            }
+            (Some(ConstItem::MethodHandle(_)), false) if self.major_version >= 51 => {
+                Some(VerificationType::object("java/lang/invoke/MethodHandle"))
+            }
+            (Some(ConstItem::Long(_)), true) => Some(VerificationType::Long),
+            (Some(ConstItem::Double(_)), true) => Some(VerificationType::Double),
+            _ => None,
+        };
+        value.ok_or_else(|| VerifyErrorKind::InvalidConstant(self.idx_map.raw_past_end(idx)))
+    }
+
+    /// Internal name of the class referenced by the Class constant at `idx`
+    fn class_constant(&self, idx: ConstItemIdx) -> Result<String> {
+        class_name(self.constants, idx)
+            .map(|name| name.to_string())
+            .ok_or_else(|| VerifyErrorKind::InvalidConstant(self.idx_map.raw_past_end(idx)))
+    }
+
+    fn member_ref(&self, idx: ConstItemIdx) -> Result<MemberRef<'_>> {
+        let invalid = || VerifyErrorKind::InvalidConstant(self.idx_map.raw_past_end(idx));
+        let (class_index, name_and_type_index) = match self.constants.get(idx) {
+            Some(ConstItem::FieldRef(item)) => (Some(item.class_index), item.name_and_type_index),
+            Some(ConstItem::MethodRef(item)) => (Some(item.class_index), item.name_and_type_index),
+            Some(ConstItem::InterfaceMethodRef(item)) => {
+                (Some(item.interface_index), item.name_and_type_index)
+            }
+            Some(ConstItem::InvokeDynamic(item)) => (None, item.name_and_type_index),
+            _ => return Err(invalid()),
+        };
+        let class = match class_index {
+            Some(class_index) => Some(class_name(self.constants, class_index).ok_or_else(invalid)?),
+            None => None,
+        };
+        let name_and_type = self
+            .constants
+            .get(name_and_type_index)
+            .and_then(ConstItem::as_name_and_type)
+            .ok_or_else(invalid)?;
+        let utf8 = |idx| {
+            let item = self.constants.get(idx).and_then(ConstItem::as_utf8);
+            item.map(|item| &*item.string).ok_or_else(invalid)
+        };
+        Ok(MemberRef {
+            class,
+            name: utf8(name_and_type.name_index)?,
+            descriptor: utf8(name_and_type.descriptor_index)?,
+            descriptor_index: name_and_type.descriptor_index,
+        })
+    }
+
+    fn field_type(&self, idx: ConstItemIdx) -> Result<VerificationType> {
+        let member = self.member_ref(idx)?;
+        let field_type =
+            FieldType::parse(member.descriptor).map_err(|_| self.invalid_descriptor(&member))?;
+        Ok(VerificationType::from_field_type(&field_type))
+    }
+
+    /// Class declaring the field or method referenced at `idx`
+    fn member_class(&self, idx: ConstItemIdx) -> Result<VerificationType> {
+        let member = self.member_ref(idx)?;
+        let class = member
+            .class
+            .ok_or_else(|| VerifyErrorKind::InvalidConstant(self.idx_map.raw_past_end(idx)))?;
+        Ok(VerificationType::object(class.to_string()))
+    }
+
+    fn invalid_descriptor(&self, member: &MemberRef) -> VerifyErrorKind {
+        let raw = self.idx_map.raw_past_end(member.descriptor_index);
+        ClassReaderError::InvalidDescriptor(raw, member.descriptor.to_string()).into()
+    }
+}
+
+fn incompatible(expected: impl ToString, found: VerificationType) -> VerifyErrorKind {
+    VerifyErrorKind::IncompatibleType {
+        expected: expected.to_string(),
+        found,
+    }
+}
+
+fn array_descriptor(array_type: &ArrayType) -> &'static str {
+    match array_type {
+        ArrayType::Boolean => "[Z",
+        ArrayType::Char => "[C",
+        ArrayType::Float => "[F",
+        ArrayType::Double => "[D",
+        ArrayType::Byte => "[B",
+        ArrayType::Short => "[S",
+        ArrayType::Int => "[I",
+        ArrayType::Long => "[J",
+    }
+}
+
+/// The local variable an `xload` or `xstore` instruction accesses
+fn local_access(instruction: &Instruction) -> Option<LocalAccess> {
+    use Instruction::*;
+    use VerificationType::{Double, Float, Integer, Long};
+
+    let (index, store, value) = match instruction {
+        Iload(idx) => (*idx as u16, false, Some(Integer)),
+        Lload(idx) => (*idx as u16, false, Some(Long)),
+        Fload(idx) => (*idx as u16, false, Some(Float)),
+        Dload(idx) => (*idx as u16, false, Some(Double)),
+        Aload(idx) => (*idx as u16, false, None),
+        Istore(idx) => (*idx as u16, true, Some(Integer)),
+        Lstore(idx) => (*idx as u16, true, Some(Long)),
+        Fstore(idx) => (*idx as u16, true, Some(Float)),
+        Dstore(idx) => (*idx as u16, true, Some(Double)),
+        Astore(idx) => (*idx as u16, true, None),
+        Iload0 | Iload1 | Iload2 | Iload3 => (implicit_index(instruction), false, Some(Integer)),
+        Lload0 | Lload1 | Lload2 | Lload3 => (implicit_index(instruction), false, Some(Long)),
+        Fload0 | Fload1 | Fload2 | Fload3 => (implicit_index(instruction), false, Some(Float)),
+        Dload0 | Dload1 | Dload2 | Dload3 => (implicit_index(instruction), false, Some(Double)),
+        Aload0 | Aload1 | Aload2 | Aload3 => (implicit_index(instruction), false, None),
+        Istore0 | Istore1 | Istore2 | Istore3 => (implicit_index(instruction), true, Some(Integer)),
+        Lstore0 | Lstore1 | Lstore2 | Lstore3 => (implicit_index(instruction), true, Some(Long)),
+        Fstore0 | Fstore1 | Fstore2 | Fstore3 => (implicit_index(instruction), true, Some(Float)),
+        Dstore0 | Dstore1 | Dstore2 | Dstore3 => (implicit_index(instruction), true, Some(Double)),
+        Astore0 | Astore1 | Astore2 | Astore3 => (implicit_index(instruction), true, None),
+        Wide(instruction) => match instruction {
+            WideInstruction::Iload(idx) => (*idx, false, Some(Integer)),
+            WideInstruction::Lload(idx) => (*idx, false, Some(Long)),
+            WideInstruction::Fload(idx) => (*idx, false, Some(Float)),
+            WideInstruction::Dload(idx) => (*idx, false, Some(Double)),
+            WideInstruction::Aload(idx) => (*idx, false, None),
+            WideInstruction::Istore(idx) => (*idx, true, Some(Integer)),
+            WideInstruction::Lstore(idx) => (*idx, true, Some(Long)),
+            WideInstruction::Fstore(idx) => (*idx, true, Some(Float)),
+            WideInstruction::Dstore(idx) => (*idx, true, Some(Double)),
+            WideInstruction::Astore(idx) => (*idx, true, None),
+            WideInstruction::Ret(_) | WideInstruction::Iinc(..) => return None,
+        },
+        _ => return None,
+    };
+    Some(LocalAccess {
+        index,
+        store,
+        value,
+    })
+}
+
+/// First local variable slot accessed by the instruction, and the number of slots accessed
+pub(crate) fn local_slots(instruction: &Instruction) -> Option<(u16, u16)> {
+    match instruction {
+        Instruction::Iinc(idx, _) | Instruction::Ret(idx) => Some((*idx as u16, 1)),
+        Instruction::Wide(WideInstruction::Iinc(idx, _) | WideInstruction::Ret(idx)) => {
+            Some((*idx, 1))
+        }
+        _ => local_access(instruction).map(|access| {
+            let size = access.value.as_ref().map_or(1, VerificationType::size);
+            (access.index, size)
+        }),
+    }
+}
+
+/// Index of the local variable accessed by `xload_<n>` and `xstore_<n>` instructions, which
+/// are laid out in groups of four consecutive opcodes
+fn implicit_index(instruction: &Instruction) -> u16 {
+    let base = match instruction.opcode() {
+        opcode @ 0x1a..=0x2d => opcode - 0x1a,
+        opcode => opcode - 0x3b,
+    };
+    (base % 4) as u16
+}
@@ -0,0 +1,423 @@
+//! Bytecode verification (JVMS 4.10), see [ClassFile::verify]
+
+mod hierarchy;
+pub use hierarchy::*;
+mod interpreter;
+mod stack_map;
+mod structure;
+pub use structure::*;
+mod type_checker;
+mod type_inference;
+mod types;
+pub use types::*;
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use thiserror::Error;
+
+use crate::{
+    ClassAccessFlags, ClassFile,
+    constants::{ConstItemIdx, Constants},
+    error::ClassReaderError,
+    java_str::JavaStr,
+    method::Method,
+};
+
+/// Why the code of a method was rejected
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    #[error("operand stack underflow")]
+    StackUnderflow,
+
+    #[error("operand stack exceeds max_stack")]
+    StackOverflow,
+
+    #[error("local variable {0} is out of max_locals")]
+    LocalOutOfRange(u16),
+
+    /// `expected` describes the accepted types, e.g. `int` or `array of references`
+    #[error("expected {expected}, found {found}")]
+    IncompatibleType {
+        expected: String,
+        found: VerificationType,
+    },
+
+    #[error("`{0}` does not match the return type of the method")]
+    InvalidReturn(&'static str),
+
+    #[error("the frame is not assignable to the stack map frame")]
+    IncompatibleFrame,
+
+    #[error("the operand stacks of merging paths do not match")]
+    StackMismatch,
+
+    #[error("missing stack map frame")]
+    MissingStackMapFrame,
+
+    #[error("invalid StackMapTable: {0}")]
+    InvalidStackMapTable(String),
+
+    #[error("execution falls off the end of the code")]
+    FallsOffEnd,
+
+    #[error("branch target {0} is not the start of an instruction")]
+    InvalidBranchTarget(u32),
+
+    #[error("`{0}` is not allowed by this verifier")]
+    UnsupportedInstruction(&'static str),
+
+    #[error("returning from a constructor before `this` is initialized")]
+    UninitializedReturn,
+
+    #[error("invalid constant #{0} for this instruction")]
+    InvalidConstant(u16),
+
+    #[error("unknown class `{0}`")]
+    UnknownClass(String),
+
+    #[error("invalid object initialization: {0}")]
+    InvalidInitialization(&'static str),
+
+    /// The code breaks a constraint checked before interpreting it, see [check_structure]
+    #[error(transparent)]
+    Structure(#[from] StructuralViolation),
+
+    /// The method or its code could not be decoded
+    #[error(transparent)]
+    Malformed(#[from] ClassReaderError),
+}
+
+/// A method that failed verification.
+///
+/// `expected` and `found` are set when the frame computed for the instruction at `pc` does
+/// not match the frame the code declares, and `found` alone holds the frame the failing
+/// instruction was executed with.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    /// Name and descriptor of the method, e.g. `run(I)V`
+    pub method: String,
+    /// pc of the failing instruction, `None` for errors about the method as a whole
+    pub pc: Option<u32>,
+    pub kind: VerifyErrorKind,
+    pub expected: Option<Box<Frame>>,
+    pub found: Option<Box<Frame>>,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "method `{}`", self.method)?;
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {}", pc)?;
+        }
+        write!(f, ": {}", self.kind)?;
+        if let Some(expected) = &self.expected {
+            write!(f, "; expected {}", expected)?;
+        }
+        if let Some(found) = &self.found {
+            write!(f, "; found {}", found)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for VerifyError {}
+
+impl VerifyError {
+    /// An error without frames, whose `method` is filled by [verify_method]
+    pub(crate) fn new(pc: Option<u32>, kind: VerifyErrorKind) -> Self {
+        Self {
+            method: String::new(),
+            pc,
+            kind,
+            expected: None,
+            found: None,
+        }
+    }
+}
+
+/// Internal name of the class referenced by the Class constant at `idx`
+pub(crate) fn class_name(constants: &Constants, idx: ConstItemIdx) -> Option<&JavaStr> {
+    let class = constants.get(idx)?.as_class()?;
+    Some(&constants.get(class.name_index)?.as_utf8()?.string)
+}
+
+/// How the code of a method is verified (JVMS 4.10)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationMode {
+    /// Checking the code against its `StackMapTable`, as required from version 50
+    TypeChecking,
+    /// Inferring the types by data-flow analysis, as done before version 50. Handles the
+    /// `jsr` and `ret` instructions.
+    TypeInference,
+}
+
+impl ClassFile {
+    /// Verifies the code of every method, returning the first failure.
+    ///
+    /// Classes from version 50 are verified by type checking, those before by type
+    /// inference, see [verify_method]. Classes other than this one are resolved through
+    /// `hierarchy`.
+    pub fn verify(&self, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+        for method in &self.methods {
+            verify_method(self, method, hierarchy)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the code of `method`, which belongs to `class`. Methods without code pass.
+///
+/// The mode depends on the version of the class. As the JVM does, code of version 50 that
+/// fails type checking gets a second chance by type inference.
+pub fn verify_method(
+    class: &ClassFile,
+    method: &Method,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<(), VerifyError> {
+    match class.version.major() {
+        ..50 => verify_method_with(class, method, hierarchy, VerificationMode::TypeInference),
+        50 => verify_method_with(class, method, hierarchy, VerificationMode::TypeChecking).or_else(
+            |_| verify_method_with(class, method, hierarchy, VerificationMode::TypeInference),
+        ),
+        _ => verify_method_with(class, method, hierarchy, VerificationMode::TypeChecking),
+    }
+}
+
+/// Verifies the code of `method`, which belongs to `class`, in the given mode, after checking
+/// its structure with [check_structure]
+pub fn verify_method_with(
+    class: &ClassFile,
+    method: &Method,
+    hierarchy: &dyn ClassHierarchy,
+    mode: VerificationMode,
+) -> Result<(), VerifyError> {
+    let utf8 = |idx: ConstItemIdx| {
+        class
+            .constants
+            .get(idx)
+            .and_then(|item| item.as_utf8())
+            .map(|item| item.string.to_string())
+            .unwrap_or_default()
+    };
+    let method_name = format!(
+        "{}{}",
+        utf8(method.name_index),
+        utf8(method.descriptor_index)
+    );
+    let fail = |kind: VerifyErrorKind| VerifyError {
+        method: method_name.clone(),
+        ..VerifyError::new(None, kind)
+    };
+    let idx_map = class.const_idx_map().map_err(|error| fail(error.into()))?;
+    let Some(code) = method
+        .code(&class.constants, &idx_map)
+        .map_err(|error| fail(error.into()))?
+    else {
+        return Ok(());
+    };
+    if let Some(violation) = check_structure(&code, &class.constants, &class.version)
+        .into_iter()
+        .next()
+    {
+        return Err(VerifyError {
+            pc: violation.pc(),
+            ..fail(violation.into())
+        });
+    }
+    let this_class = class_name(&class.constants, class.this_class).ok_or_else(|| {
+        let raw = idx_map.raw_past_end(class.this_class);
+        fail(ClassReaderError::InvalidConstantPoolIdx(raw).into())
+    })?;
+    let this_class = this_class.to_string();
+    let hierarchy = WithClass {
+        name: &this_class,
+        info: ClassInfo {
+            super_class: class
+                .super_class
+                .and_then(|idx| class_name(&class.constants, idx))
+                .map(|name| name.to_string()),
+            is_interface: class.access_flag.contains(ClassAccessFlags::INTERFACE),
+        },
+        hierarchy,
+    };
+    let verify = match mode {
+        VerificationMode::TypeChecking => type_checker::check,
+        VerificationMode::TypeInference => type_inference::infer,
+    };
+    verify(class, method, &this_class, &code, &idx_map, &hierarchy).map_err(|error| VerifyError {
+        method: method_name.clone(),
+        ..error
+    })
+}
+
+/// Resolves the class being verified from its class file, and other classes through the
+/// user's hierarchy
+struct WithClass<'a> {
+    name: &'a str,
+    info: ClassInfo,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl ClassHierarchy for WithClass<'_> {
+    fn resolve(&self, name: &str) -> Option<ClassInfo> {
+        if name == self.name {
+            Some(self.info.clone())
+        } else {
+            self.hierarchy.resolve(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        builder::ClassBuilder,
+        code::Instruction,
+        method::MethodAccessFlags,
+        verifier::{ClassInfo, Classes, Frame, VerificationType, VerifyErrorKind},
+        version::ClassFileVersion,
+    };
+
+    fn class_info(super_class: &str) -> ClassInfo {
+        ClassInfo {
+            super_class: Some(super_class.to_string()),
+            is_interface: false,
+        }
+    }
+
+    fn interface() -> ClassInfo {
+        ClassInfo {
+            is_interface: true,
+            ..class_info("java/lang/Object")
+        }
+    }
+
+    #[test]
+    fn compiled_classes_verify() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let mut classes = Classes::new();
+        classes
+            .insert("java/util/List", interface())
+            .insert("java/util/ArrayList", class_info("java/util/AbstractList"));
+        assert_eq!(Ok(()), class.verify(&classes));
+    }
+
+    #[test]
+    fn errors_point_at_the_failing_instruction() {
+        let mut builder = ClassBuilder::new("Broken");
+        builder.method(
+            "get",
+            "()Ljava/lang/Object;",
+            MethodAccessFlags::STATIC,
+            |method| {
+                method.code(|code| {
+                    code.instruction(Instruction::Nop)
+                        .instruction(Instruction::Iconst1)
+                        .instruction(Instruction::Areturn);
+                });
+            },
+        );
+        let error = builder
+            .build()
+            .unwrap()
+            .verify(&Classes::new())
+            .unwrap_err();
+
+        assert_eq!("get()Ljava/lang/Object;", error.method);
+        assert_eq!(Some(2), error.pc);
+        assert_eq!(
+            VerifyErrorKind::IncompatibleType {
+                expected: "java/lang/Object".to_string(),
+                found: VerificationType::Integer,
+            },
+            error.kind
+        );
+        let found = Frame {
+            locals: Vec::new(),
+            stack: vec![VerificationType::Integer],
+            this_uninit: false,
+        };
+        assert_eq!(Some(Box::new(found)), error.found);
+    }
+
+    #[test]
+    fn branch_targets_need_a_stack_map_frame() {
+        let mut builder = ClassBuilder::new("Abs");
+        builder.version(ClassFileVersion::Jdk1_4, 0);
+        builder.method("abs", "(I)I", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let positive = code.new_label();
+                code.instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ifge(positive))
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ineg)
+                    .instruction(Instruction::Ireturn)
+                    .place_label(positive)
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ireturn);
+            });
+        });
+        let mut class = builder.build().unwrap();
+        class.version = ClassFileVersion::Jdk8;
+        let error = class.verify(&Classes::new()).unwrap_err();
+
+        assert_eq!(Some(1), error.pc);
+        assert_eq!(VerifyErrorKind::MissingStackMapFrame, error.kind);
+    }
+
+    /// `static int twice(int x) { try { x++; } finally { <finally> } return x; }` as compiled
+    /// for Java 1.4, the `finally` block being a subroutine
+    fn legacy_finally(finally: &[Instruction]) -> ClassFile {
+        let mut builder = ClassBuilder::new("Legacy");
+        builder.version(ClassFileVersion::Jdk1_4, 0);
+        builder.method("twice", "(I)I", MethodAccessFlags::STATIC, |method| {
+            method.code(|code| {
+                let (start, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+                let subroutine = code.new_label();
+                code.place_label(start)
+                    .instruction(Instruction::Iinc(0, 1))
+                    .place_label(end)
+                    .instruction(Instruction::Jsr(subroutine))
+                    .instruction(Instruction::Iload0)
+                    .instruction(Instruction::Ireturn)
+                    .place_label(handler)
+                    .instruction(Instruction::Astore1)
+                    .instruction(Instruction::Jsr(subroutine))
+                    .instruction(Instruction::Aload1)
+                    .instruction(Instruction::Athrow)
+                    .place_label(subroutine)
+                    .instruction(Instruction::Astore2);
+                for instruction in finally {
+                    code.instruction(instruction.clone());
+                }
+                code.instruction(Instruction::Ret(2))
+                    .try_catch(start, end, handler, None);
+            });
+        });
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn legacy_classes_are_verified_by_type_inference() {
+        use Instruction::*;
+
+        let class = legacy_finally(&[Iload0, Iconst2, Imul, Istore0]);
+        assert_eq!(Ok(()), class.verify(&Classes::new()));
+
+        // The subroutine leaves a float where the caller expects its int
+        let class = legacy_finally(&[Fconst1, Fstore0]);
+        let error = class.verify(&Classes::new()).unwrap_err();
+        assert_eq!(Some(6), error.pc);
+        assert_eq!(
+            VerifyErrorKind::IncompatibleType {
+                expected: "int".to_string(),
+                found: VerificationType::Float,
+            },
+            error.kind
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    buffer::Buffer,
+    constants::{ConstIdxMap, Constants},
+    error::ClassReaderError,
+};
+
+use super::{Frame, VerificationType, VerifyErrorKind, class_name};
+
+/// Decodes a `StackMapTable` attribute (JVMS 4.7.4) into the full frame at each pc it
+/// describes.
+///
+/// `initial_locals` are the types of the arguments, with a single entry for longs and
+/// doubles, and frames have their locals padded with [VerificationType::Top] up to
+/// `max_locals`.
+pub(crate) fn decode(
+    info: &[u8],
+    constants: &Constants,
+    idx_map: &ConstIdxMap,
+    initial_locals: &[VerificationType],
+    max_locals: u16,
+) -> Result<BTreeMap<u32, Frame>, VerifyErrorKind> {
+    let mut decoder = Decoder {
+        buf: Buffer::new(info),
+        constants,
+        idx_map,
+    };
+    decoder
+        .frames(initial_locals, max_locals)
+        .map_err(|error| match error {
+            DecodeError::Read(error) => VerifyErrorKind::InvalidStackMapTable(error.to_string()),
+            DecodeError::Invalid(reason) => VerifyErrorKind::InvalidStackMapTable(reason),
+        })
+}
+
+enum DecodeError {
+    Read(ClassReaderError),
+    Invalid(String),
+}
+
+impl<T: Into<ClassReaderError>> From<T> for DecodeError {
+    fn from(error: T) -> Self {
+        Self::Read(error.into())
+    }
+}
+
+struct Decoder<'a> {
+    buf: Buffer<'a>,
+    constants: &'a Constants,
+    idx_map: &'a ConstIdxMap,
+}
+
+impl Decoder<'_> {
+    fn frames(
+        &mut self,
+        initial_locals: &[VerificationType],
+        max_locals: u16,
+    ) -> Result<BTreeMap<u32, Frame>, DecodeError> {
+        let mut frames = BTreeMap::new();
+        // Locals with a single entry for longs and doubles, as frames describe them
+        let mut locals = initial_locals.to_vec();
+        let mut pc: Option<u32> = None;
+        for _ in 0..self.buf.read_u16()? {
+            let frame_type = self.buf.read_u8()?;
+            let (offset_delta, stack) = match frame_type {
+                0..=63 => (frame_type as u16, Vec::new()),
+                64..=127 => (frame_type as u16 - 64, vec![self.verification_type()?]),
+                128..=246 => {
+                    let reason = format!("reserved frame type {}", frame_type);
+                    return Err(DecodeError::Invalid(reason));
+                }
+                247 => {
+                    let offset_delta = self.buf.read_u16()?;
+                    (offset_delta, vec![self.verification_type()?])
+                }
+                248..=250 => {
+                    let offset_delta = self.buf.read_u16()?;
+                    let chopped = (251 - frame_type) as usize;
+                    if chopped > locals.len() {
+                        let reason = format!("cannot chop {} locals", chopped);
+                        return Err(DecodeError::Invalid(reason));
+                    }
+                    locals.truncate(locals.len() - chopped);
+                    (offset_delta, Vec::new())
+                }
+                251 => (self.buf.read_u16()?, Vec::new()),
+                252..=254 => {
+                    let offset_delta = self.buf.read_u16()?;
+                    for _ in 0..frame_type - 251 {
+                        locals.push(self.verification_type()?);
+                    }
+                    (offset_delta, Vec::new())
+                }
+                255 => {
+                    let offset_delta = self.buf.read_u16()?;
+                    locals = self.verification_types()?;
+                    (offset_delta, self.verification_types()?)
+                }
+            };
+            let frame_pc = match pc {
+                None => offset_delta as u32,
+                Some(pc) => pc + offset_delta as u32 + 1,
+            };
+            pc = Some(frame_pc);
+            let frame = Frame::expand(&locals, stack, max_locals).ok_or_else(|| {
+                let reason = format!(
+                    "the frame at pc {} has more than max_locals locals",
+                    frame_pc
+                );
+                DecodeError::Invalid(reason)
+            })?;
+            frames.insert(frame_pc, frame);
+        }
+        if self.buf.remaining() > 0 {
+            let reason = format!("{} unexpected bytes", self.buf.remaining());
+            return Err(DecodeError::Invalid(reason));
+        }
+        Ok(frames)
+    }
+
+    fn verification_types(&mut self) -> Result<Vec<VerificationType>, DecodeError> {
+        let count = self.buf.read_u16()?;
+        (0..count).map(|_| self.verification_type()).collect()
+    }
+
+    fn verification_type(&mut self) -> Result<VerificationType, DecodeError> {
+        Ok(match self.buf.read_u8()? {
+            0 => VerificationType::Top,
+            1 => VerificationType::Integer,
+            2 => VerificationType::Float,
+            3 => VerificationType::Double,
+            4 => VerificationType::Long,
+            5 => VerificationType::Null,
+            6 => VerificationType::UninitializedThis,
+            7 => {
+                let idx = self.idx_map.resolve(self.buf.read_u16()?)?;
+                let name = class_name(self.constants, idx).ok_or_else(|| {
+                    let reason = format!("entry {} is not a Class", self.idx_map.raw_past_end(idx));
+                    DecodeError::Invalid(reason)
+                })?;
+                VerificationType::Object(name.to_string())
+            }
+            8 => VerificationType::Uninitialized(self.buf.read_u16()? as u32),
+            tag => {
+                let reason = format!("invalid verification type tag {}", tag);
+                return Err(DecodeError::Invalid(reason));
+            }
+        })
+    }
+}
@@ -0,0 +1,256 @@
+use thiserror::Error;
+
+use crate::{
+    code::{Code, Instruction},
+    constants::{ConstItem, Constants},
+    version::ClassFileVersion,
+};
+
+/// A violation of the static constraints on the code of a method (JVMS 4.9.1), found
+/// without interpreting the instructions
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StructuralViolation {
+    #[error("code length {0} is not between 1 and 65535")]
+    CodeLength(u32),
+
+    #[error("pc {pc}: branch target {target} is not the start of an instruction")]
+    InvalidBranchTarget { pc: u32, target: u32 },
+
+    #[error("pc {pc}: local variable {index} is out of max_locals {max_locals}")]
+    LocalOutOfRange {
+        pc: u32,
+        index: u16,
+        max_locals: u16,
+    },
+
+    /// `major_version` is the version of the class
+    #[error("pc {pc}: `{mnemonic}` is not allowed in class files of version {major_version}")]
+    InstructionNotAllowed {
+        pc: u32,
+        mnemonic: &'static str,
+        major_version: u16,
+    },
+
+    #[error("pc {pc}: switch padding is not zero, as required before version 51")]
+    SwitchPadding { pc: u32 },
+
+    /// The range of the `index`-th entry of the exception table is empty or does not start
+    /// and end at instruction boundaries
+    #[error("exception_table[{index}]: invalid range {start}..{end}")]
+    InvalidExceptionRange { index: usize, start: u32, end: u32 },
+
+    #[error("exception_table[{index}]: handler {handler} is not the start of an instruction")]
+    InvalidHandler { index: usize, handler: u32 },
+}
+
+impl StructuralViolation {
+    /// pc of the faulty instruction, or the start of the faulty range or handler
+    pub fn pc(&self) -> Option<u32> {
+        match self {
+            Self::CodeLength(_) => None,
+            Self::InvalidBranchTarget { pc, .. }
+            | Self::LocalOutOfRange { pc, .. }
+            | Self::InstructionNotAllowed { pc, .. }
+            | Self::SwitchPadding { pc } => Some(*pc),
+            Self::InvalidExceptionRange { start, .. } => Some(*start),
+            Self::InvalidHandler { handler, .. } => Some(*handler),
+        }
+    }
+}
+
+/// Checks the structural constraints of `code` that need no type information: code length,
+/// branch targets, exception table entries, local variable indices, and the instructions and
+/// switch padding allowed at `version`.
+///
+/// Returns every violation found, in the order of the code then of the exception table.
+pub fn check_structure(
+    code: &Code,
+    constants: &Constants,
+    version: &ClassFileVersion,
+) -> Vec<StructuralViolation> {
+    let mut violations = Vec::new();
+    let code_length = code.code_length();
+    if code_length == 0 || code_length > u16::MAX as u32 {
+        violations.push(StructuralViolation::CodeLength(code_length));
+    }
+    let is_instruction = |pc: u32| code.position(pc).is_some();
+    let major_version = version.major();
+    for (pc, instruction) in &code.instructions {
+        let pc = *pc;
+        for target in instruction.targets() {
+            if !is_instruction(target.0) {
+                violations.push(StructuralViolation::InvalidBranchTarget {
+                    pc,
+                    target: target.0,
+                });
+            }
+        }
+        let locals_used = instruction.locals_used();
+        if locals_used > code.max_locals {
+            violations.push(StructuralViolation::LocalOutOfRange {
+                pc,
+                index: locals_used - 1,
+                max_locals: code.max_locals,
+            });
+        }
+        if !is_allowed(instruction, constants, major_version) {
+            violations.push(StructuralViolation::InstructionNotAllowed {
+                pc,
+                mnemonic: instruction.mnemonic(),
+                major_version,
+            });
+        }
+        if major_version < 51 && instruction.has_nonzero_padding(pc) {
+            violations.push(StructuralViolation::SwitchPadding { pc });
+        }
+    }
+    for (index, handler) in code.exception_table.iter().enumerate() {
+        let (start, end) = (handler.start.0, handler.end.0);
+        if start >= end || !is_instruction(start) || !(is_instruction(end) || end == code_length) {
+            violations.push(StructuralViolation::InvalidExceptionRange { index, start, end });
+        }
+        if !is_instruction(handler.handler.0) {
+            let handler = handler.handler.0;
+            violations.push(StructuralViolation::InvalidHandler { index, handler });
+        }
+    }
+    violations
+}
+
+/// Whether `instruction` may appear in a class of version `major_version`
+fn is_allowed(instruction: &Instruction, constants: &Constants, major_version: u16) -> bool {
+    match instruction {
+        Instruction::Jsr(_) | Instruction::JsrW(_) => major_version < 51,
+        Instruction::Invokedynamic(_) => major_version >= 51,
+        // Static and private interface methods came with Java 8
+        Instruction::Invokestatic(idx) | Instruction::Invokespecial(idx) => {
+            major_version >= 52
+                || !matches!(constants.get(*idx), Some(ConstItem::InterfaceMethodRef(_)))
+        }
+        // Loadable constants other than numbers and strings came with Java 5 and 7
+        Instruction::Ldc(idx) | Instruction::LdcW(idx) => match constants.get(*idx) {
+            Some(ConstItem::Class(_)) => major_version >= 49,
+            Some(ConstItem::MethodType(_) | ConstItem::MethodHandle(_)) => major_version >= 51,
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ClassFile,
+        code::{Code, ExceptionHandler, Instruction, Label},
+        constants::{ConstClass, ConstItem, ConstItemIdx, ConstMethodType, ConstUtf8, Constants},
+        verifier::{StructuralViolation, check_structure},
+        version::ClassFileVersion,
+    };
+
+    #[test]
+    fn compiled_code_is_well_structured() {
+        let class = ClassFile::read(include_bytes!("../../tests/fixtures/Sample.class")).unwrap();
+        let idx_map = class.const_idx_map().unwrap();
+        for method in &class.methods {
+            let code = method.code(&class.constants, &idx_map).unwrap().unwrap();
+            assert_eq!(
+                Vec::<StructuralViolation>::new(),
+                check_structure(&code, &class.constants, &class.version)
+            );
+        }
+    }
+
+    #[test]
+    fn violations_point_at_the_pc() {
+        let code = Code {
+            max_stack: 1,
+            max_locals: 1,
+            instructions: vec![
+                (0, Instruction::Jsr(Label(6))),
+                (3, Instruction::Goto(Label(4))),
+                (6, Instruction::Iload(1)),
+                (8, Instruction::Return),
+            ],
+            exception_table: vec![ExceptionHandler {
+                start: Label(3),
+                end: Label(7),
+                handler: Label(9),
+                catch_type: None,
+            }],
+            attributes: Vec::new(),
+        };
+
+        assert_eq!(
+            vec![
+                StructuralViolation::InstructionNotAllowed {
+                    pc: 0,
+                    mnemonic: "jsr",
+                    major_version: 52,
+                },
+                StructuralViolation::InvalidBranchTarget { pc: 3, target: 4 },
+                StructuralViolation::LocalOutOfRange {
+                    pc: 6,
+                    index: 1,
+                    max_locals: 1,
+                },
+                StructuralViolation::InvalidExceptionRange {
+                    index: 0,
+                    start: 3,
+                    end: 7,
+                },
+                StructuralViolation::InvalidHandler {
+                    index: 0,
+                    handler: 9,
+                },
+            ],
+            check_structure(&code, &Constants::new(), &ClassFileVersion::Jdk8)
+        );
+    }
+
+    #[test]
+    fn loadable_constants_depend_on_the_version() {
+        let mut constants = Constants::new();
+        constants.push(ConstItem::Class(ConstClass {
+            name_index: ConstItemIdx::from_raw(3),
+        }));
+        constants.push(ConstItem::MethodType(ConstMethodType {
+            descriptor_index: ConstItemIdx::from_raw(4),
+        }));
+        constants.push(ConstItem::Utf8(ConstUtf8 {
+            string: "java/lang/String".into(),
+        }));
+        constants.push(ConstItem::Utf8(ConstUtf8 {
+            string: "()V".into(),
+        }));
+        let check = |instruction, version| {
+            let code = Code {
+                max_stack: 1,
+                max_locals: 0,
+                instructions: vec![(0, instruction), (3, Instruction::Areturn)],
+                exception_table: Vec::new(),
+                attributes: Vec::new(),
+            };
+            check_structure(&code, &constants, &version)
+        };
+        let not_allowed = |major_version| {
+            vec![StructuralViolation::InstructionNotAllowed {
+                pc: 0,
+                mnemonic: "ldc_w",
+                major_version,
+            }]
+        };
+
+        let class = Instruction::LdcW(ConstItemIdx::new(0));
+        assert_eq!(
+            not_allowed(48),
+            check(class.clone(), ClassFileVersion::Jdk1_4)
+        );
+        assert!(check(class, ClassFileVersion::Jdk1_5).is_empty());
+        let method_type = Instruction::LdcW(ConstItemIdx::new(1));
+        assert_eq!(
+            not_allowed(50),
+            check(method_type.clone(), ClassFileVersion::Jdk6)
+        );
+        assert!(check(method_type, ClassFileVersion::Jdk7).is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ClassFile,
+    attribute::Attribute,
+    code::{Code, Label},
+    constants::ConstIdxMap,
+    method::Method,
+};
+
+use super::{
+    ClassHierarchy, Frame, VerificationType, VerifyError, VerifyErrorKind, class_name,
+    interpreter::Interpreter, stack_map,
+};
+
+type Result<T> = std::result::Result<T, VerifyError>;
+
+/// Verification by type checking (JVMS 4.10.1): the code is checked in a single pass, the
+/// frames declared by its `StackMapTable` standing in wherever control flow merges.
+///
+/// The `method` of the returned errors is left empty for the caller to fill.
+pub(crate) fn check(
+    class: &ClassFile,
+    method: &Method,
+    this_class: &str,
+    code: &Code,
+    idx_map: &ConstIdxMap,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<()> {
+    let (interpreter, initial_locals) =
+        Interpreter::new(class, method, this_class, code, idx_map, hierarchy)
+            .map_err(|kind| VerifyError::new(None, kind))?;
+    let initial = interpreter
+        .initial_frame(&initial_locals)
+        .map_err(|kind| VerifyError::new(None, kind))?;
+    let frames = match Attribute::find(&code.attributes, &class.constants, "StackMapTable") {
+        Some(attribute) => stack_map::decode(
+            &attribute.info,
+            &class.constants,
+            idx_map,
+            &initial_locals,
+            code.max_locals,
+        )
+        .map_err(|kind| VerifyError::new(None, kind))?,
+        None => BTreeMap::new(),
+    };
+    if let Some(&pc) = frames.keys().find(|&&pc| code.position(pc).is_none()) {
+        let reason = format!(
+            "the frame at pc {} is not at the start of an instruction",
+            pc
+        );
+        return Err(VerifyError::new(
+            None,
+            VerifyErrorKind::InvalidStackMapTable(reason),
+        ));
+    }
+    TypeChecker {
+        interpreter,
+        frames,
+    }
+    .run(initial)
+}
+
+struct TypeChecker<'a> {
+    interpreter: Interpreter<'a>,
+    /// Frames declared by the `StackMapTable`, by pc
+    frames: BTreeMap<u32, Frame>,
+}
+
+impl TypeChecker<'_> {
+    fn run(&self, initial: Frame) -> Result<()> {
+        let code = self.interpreter.code;
+        let mut current = Some(initial);
+        for (pc, instruction) in &code.instructions {
+            let pc = *pc;
+            if let Some(declared) = self.frames.get(&pc) {
+                if let Some(frame) = &current {
+                    self.check_assignable(pc, frame, declared)?;
+                }
+                current = Some(declared.clone());
+            }
+            // Code following an unconditional branch is only reachable through a declared frame
+            let Some(mut frame) = current.take() else {
+                return Err(VerifyError::new(
+                    Some(pc),
+                    VerifyErrorKind::MissingStackMapFrame,
+                ));
+            };
+            self.check_handlers(pc, &frame)?;
+
+            let before = frame.clone();
+            let fail = |kind| VerifyError {
+                found: Some(Box::new(before.clone())),
+                ..VerifyError::new(Some(pc), kind)
+            };
+            self.interpreter
+                .execute(pc, instruction, &mut frame)
+                .map_err(fail)?;
+            for target in instruction.targets() {
+                self.check_target(pc, &frame, target)?;
+            }
+            if instruction.falls_through() {
+                current = Some(frame);
+            }
+        }
+        match (current, code.instructions.last()) {
+            (Some(frame), Some((pc, _))) => Err(VerifyError {
+                found: Some(Box::new(frame)),
+                ..VerifyError::new(Some(*pc), VerifyErrorKind::FallsOffEnd)
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the frame a branch at `pc` goes to `target` with matches the frame declared
+    /// there
+    fn check_target(&self, pc: u32, frame: &Frame, target: Label) -> Result<()> {
+        if self.interpreter.code.position(target.0).is_none() {
+            return Err(VerifyError::new(
+                Some(pc),
+                VerifyErrorKind::InvalidBranchTarget(target.0),
+            ));
+        }
+        let Some(declared) = self.frames.get(&target.0) else {
+            return Err(VerifyError {
+                found: Some(Box::new(frame.clone())),
+                ..VerifyError::new(Some(pc), VerifyErrorKind::MissingStackMapFrame)
+            });
+        };
+        self.check_assignable(pc, frame, declared)
+    }
+
+    /// Checks the frames of the exception handlers covering the instruction at `pc`, which
+    /// start with the locals the instruction is executed with and the caught exception
+    fn check_handlers(&self, pc: u32, frame: &Frame) -> Result<()> {
+        let code = self.interpreter.code;
+        for handler in &code.exception_table {
+            if !(handler.start.0..handler.end.0).contains(&pc) {
+                continue;
+            }
+            let exception = match handler.catch_type {
+                Some(idx) => class_name(self.interpreter.constants, idx)
+                    .map(|name| name.to_string())
+                    .ok_or_else(|| {
+                        let raw = self.interpreter.idx_map.raw_past_end(idx);
+                        VerifyError::new(Some(pc), VerifyErrorKind::InvalidConstant(raw))
+                    })?,
+                None => "java/lang/Throwable".to_string(),
+            };
+            let handler_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VerificationType::object(exception)],
+                this_uninit: frame.this_uninit,
+            };
+            self.check_target(pc, &handler_frame, handler.handler)?;
+        }
+        Ok(())
+    }
+
+    fn check_assignable(&self, pc: u32, frame: &Frame, declared: &Frame) -> Result<()> {
+        let assignable = self
+            .is_frame_assignable(frame, declared)
+            .map_err(|kind| VerifyError::new(Some(pc), kind))?;
+        if assignable {
+            return Ok(());
+        }
+        Err(VerifyError {
+            expected: Some(Box::new(declared.clone())),
+            found: Some(Box::new(frame.clone())),
+            ..VerifyError::new(Some(pc), VerifyErrorKind::IncompatibleFrame)
+        })
+    }
+
+    /// Whether every local and stack entry of `from` is assignable to the one of `to`
+    /// (JVMS 4.10.1.4)
+    fn is_frame_assignable(
+        &self,
+        from: &Frame,
+        to: &Frame,
+    ) -> std::result::Result<bool, VerifyErrorKind> {
+        if from.locals.len() != to.locals.len()
+            || from.stack.len() != to.stack.len()
+            || (from.this_uninit && !to.this_uninit)
+        {
+            return Ok(false);
+        }
+        let types = &self.interpreter.types;
+        for (from, to) in from
+            .locals
+            .iter()
+            .zip(&to.locals)
+            .chain(from.stack.iter().zip(&to.stack))
+        {
+            if !types.is_assignable(from, to)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    ClassFile,
+    code::{Code, Instruction, Label, WideInstruction},
+    constants::ConstIdxMap,
+    method::Method,
+};
+
+use super::{
+    ClassHierarchy, Frame, VerificationType, VerifyError, VerifyErrorKind, class_name,
+    interpreter::{Interpreter, local_slots},
+};
+
+type Result<T> = std::result::Result<T, VerifyError>;
+
+/// Verification by type inference (JVMS 4.10.2): the frame of each instruction is computed
+/// by data-flow analysis, merging the frames of the paths that reach it until nothing
+/// changes.
+///
+/// A subroutine called by `jsr` returns with its own types for the local variables it
+/// accesses, and with the types at each call for the others.
+///
+/// The `method` of the returned errors is left empty for the caller to fill.
+pub(crate) fn infer(
+    class: &ClassFile,
+    method: &Method,
+    this_class: &str,
+    code: &Code,
+    idx_map: &ConstIdxMap,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<()> {
+    let (interpreter, arguments) =
+        Interpreter::new(class, method, this_class, code, idx_map, hierarchy)
+            .map_err(|kind| VerifyError::new(None, kind))?;
+    let initial = interpreter
+        .initial_frame(&arguments)
+        .map_err(|kind| VerifyError::new(None, kind))?;
+    if code.instructions.is_empty() {
+        return Err(VerifyError::new(None, VerifyErrorKind::FallsOffEnd));
+    }
+
+    let mut callers: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (position, (_, instruction)) in code.instructions.iter().enumerate() {
+        if let Instruction::Jsr(target) | Instruction::JsrW(target) = instruction {
+            callers.entry(target.0).or_default().push(position);
+        }
+    }
+    let mut inference = TypeInference {
+        interpreter,
+        frames: vec![None; code.instructions.len()],
+        pending: BTreeSet::new(),
+        callers,
+        rets: HashMap::new(),
+        subroutine_locals: HashMap::new(),
+    };
+    inference.frames[0] = Some(initial);
+    inference.pending.insert(0);
+    inference.run()
+}
+
+struct TypeInference<'a> {
+    interpreter: Interpreter<'a>,
+    /// Frame each instruction is executed with, by position in the code, once reached
+    frames: Vec<Option<Frame>>,
+    /// Positions of the instructions whose frame changed since they were last executed
+    pending: BTreeSet<usize>,
+    /// Positions of the `jsr` calling each subroutine, by pc of the subroutine
+    callers: HashMap<u32, Vec<usize>>,
+    /// Positions of the `ret` found returning from each subroutine
+    rets: HashMap<u32, BTreeSet<usize>>,
+    /// Local variable slots accessed by each subroutine, including by the subroutines it calls
+    subroutine_locals: HashMap<u32, Vec<bool>>,
+}
+
+impl TypeInference<'_> {
+    fn run(&mut self) -> Result<()> {
+        let code = self.interpreter.code;
+        while let Some(position) = self.pending.pop_first() {
+            let (pc, instruction) = &code.instructions[position];
+            let pc = *pc;
+            let Some(frame) = self.frames[position].clone() else {
+                continue;
+            };
+            self.merge_handlers(pc, &frame)?;
+
+            let fail = |kind| VerifyError {
+                found: Some(Box::new(frame.clone())),
+                ..VerifyError::new(Some(pc), kind)
+            };
+            match instruction {
+                Instruction::Jsr(target) | Instruction::JsrW(target) => {
+                    let mut after = frame.clone();
+                    let address = VerificationType::ReturnAddress(target.0);
+                    self.interpreter.push(&mut after, address).map_err(fail)?;
+                    self.merge(pc, *target, after)?;
+                    // Returning from the subroutine depends on the frame at each call
+                    if let Some(rets) = self.rets.get(&target.0) {
+                        self.pending.extend(rets);
+                    }
+                }
+                Instruction::Ret(index) => self.ret(position, *index as u16, &frame)?,
+                Instruction::Wide(WideInstruction::Ret(index)) => {
+                    self.ret(position, *index, &frame)?
+                }
+                _ => {
+                    let mut after = frame.clone();
+                    self.interpreter
+                        .execute(pc, instruction, &mut after)
+                        .map_err(fail)?;
+                    for target in instruction.targets() {
+                        self.merge(pc, target, after.clone())?;
+                    }
+                    if instruction.falls_through() {
+                        self.merge_next(position, after)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges `frame` into the frame of the instruction following the one at `position`
+    fn merge_next(&mut self, position: usize, frame: Frame) -> Result<()> {
+        let (pc, instruction) = &self.interpreter.code.instructions[position];
+        if position + 1 == self.interpreter.code.instructions.len() {
+            return Err(VerifyError {
+                found: Some(Box::new(frame)),
+                ..VerifyError::new(Some(*pc), VerifyErrorKind::FallsOffEnd)
+            });
+        }
+        self.merge(*pc, Label(pc + instruction.size(*pc)), frame)
+    }
+
+    /// Merges the frames the exception handlers covering the instruction at `pc` start with:
+    /// the locals the instruction is executed with and the caught exception
+    fn merge_handlers(&mut self, pc: u32, frame: &Frame) -> Result<()> {
+        let code = self.interpreter.code;
+        for handler in &code.exception_table {
+            if !(handler.start.0..handler.end.0).contains(&pc) {
+                continue;
+            }
+            let exception = match handler.catch_type {
+                Some(idx) => class_name(self.interpreter.constants, idx)
+                    .map(|name| name.to_string())
+                    .ok_or_else(|| {
+                        let raw = self.interpreter.idx_map.raw_past_end(idx);
+                        VerifyError::new(Some(pc), VerifyErrorKind::InvalidConstant(raw))
+                    })?,
+                None => "java/lang/Throwable".to_string(),
+            };
+            let handler_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VerificationType::object(exception)],
+                this_uninit: frame.this_uninit,
+            };
+            self.merge(pc, handler.handler, handler_frame)?;
+        }
+        Ok(())
+    }
+
+    /// Returns from a subroutine to the instruction following each of its calls
+    fn ret(&mut self, position: usize, index: u16, frame: &Frame) -> Result<()> {
+        let pc = self.interpreter.code.instructions[position].0;
+        let fail = |kind| VerifyError {
+            found: Some(Box::new(frame.clone())),
+            ..VerifyError::new(Some(pc), kind)
+        };
+        let subroutine = match frame.locals.get(index as usize) {
+            Some(VerificationType::ReturnAddress(subroutine)) => *subroutine,
+            Some(found) => {
+                return Err(fail(VerifyErrorKind::IncompatibleType {
+                    expected: "returnAddress".to_string(),
+                    found: found.clone(),
+                }));
+            }
+            None => return Err(fail(VerifyErrorKind::LocalOutOfRange(index))),
+        };
+        self.rets.entry(subroutine).or_default().insert(position);
+
+        let accessed = self.subroutine_locals(subroutine);
+        let callers = self.callers.get(&subroutine).cloned().unwrap_or_default();
+        for caller in callers {
+            let Some(caller_frame) = &self.frames[caller] else {
+                continue;
+            };
+            let locals = frame
+                .locals
+                .iter()
+                .zip(&caller_frame.locals)
+                .zip(&accessed)
+                .map(|((own, caller), &accessed)| if accessed { own } else { caller })
+                .cloned()
+                .collect();
+            let after = Frame {
+                locals,
+                stack: frame.stack.clone(),
+                this_uninit: frame.this_uninit,
+            };
+            self.merge_next(caller, after)?;
+        }
+        Ok(())
+    }
+
+    /// Local variable slots accessed by the instructions reachable from the start of the
+    /// subroutine at `pc` without returning from it
+    fn subroutine_locals(&mut self, pc: u32) -> Vec<bool> {
+        if let Some(accessed) = self.subroutine_locals.get(&pc) {
+            return accessed.clone();
+        }
+        let code = self.interpreter.code;
+        let mut accessed = vec![false; code.max_locals as usize];
+        let mut visited = vec![false; code.instructions.len()];
+        let mut pending: Vec<_> = code.position(pc).into_iter().collect();
+        while let Some(position) = pending.pop() {
+            if std::mem::replace(&mut visited[position], true) {
+                continue;
+            }
+            let (pc, instruction) = &code.instructions[position];
+            if let Some((index, size)) = local_slots(instruction) {
+                let end = (index as usize + size as usize).min(accessed.len());
+                for slot in accessed.iter_mut().take(end).skip(index as usize) {
+                    *slot = true;
+                }
+            }
+            if matches!(
+                instruction,
+                Instruction::Ret(_) | Instruction::Wide(WideInstruction::Ret(_))
+            ) {
+                continue;
+            }
+            let handlers = code
+                .exception_table
+                .iter()
+                .filter(|handler| (handler.start.0..handler.end.0).contains(pc))
+                .map(|handler| handler.handler);
+            let next = instruction
+                .falls_through()
+                .then(|| Label(pc + instruction.size(*pc)));
+            for target in instruction
+                .targets()
+                .into_iter()
+                .chain(handlers)
+                .chain(next)
+            {
+                pending.extend(code.position(target.0));
+            }
+        }
+        self.subroutine_locals.insert(pc, accessed.clone());
+        accessed
+    }
+
+    /// Merges `frame`, coming from the instruction at `pc`, into the frame of `target`
+    fn merge(&mut self, pc: u32, target: Label, frame: Frame) -> Result<()> {
+        let Some(position) = self.interpreter.code.position(target.0) else {
+            let kind = VerifyErrorKind::InvalidBranchTarget(target.0);
+            return Err(VerifyError::new(Some(pc), kind));
+        };
+        let Some(existing) = &self.frames[position] else {
+            self.frames[position] = Some(frame);
+            self.pending.insert(position);
+            return Ok(());
+        };
+        let merged = self
+            .merge_frames(existing, &frame)
+            .map_err(|kind| VerifyError {
+                expected: Some(Box::new(existing.clone())),
+                found: Some(Box::new(frame.clone())),
+                ..VerifyError::new(Some(pc), kind)
+            })?;
+        if merged != *existing {
+            self.frames[position] = Some(merged);
+            self.pending.insert(position);
+        }
+        Ok(())
+    }
+
+    /// Merges two frames: locals with nothing in common become unusable, while the operand
+    /// stacks must have the same shape
+    fn merge_frames(&self, a: &Frame, b: &Frame) -> std::result::Result<Frame, VerifyErrorKind> {
+        let types = &self.interpreter.types;
+        if a.stack.len() != b.stack.len() {
+            return Err(VerifyErrorKind::StackMismatch);
+        }
+        let mut stack = Vec::with_capacity(a.stack.len());
+        for (a, b) in a.stack.iter().zip(&b.stack) {
+            let merged = types.merge(a, b)?;
+            if merged == VerificationType::Top {
+                return Err(VerifyErrorKind::StackMismatch);
+            }
+            stack.push(merged);
+        }
+        let locals = a
+            .locals
+            .iter()
+            .zip(&b.locals)
+            .map(|(a, b)| types.merge(a, b))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Frame {
+            locals,
+            stack,
+            this_uninit: a.this_uninit || b.this_uninit,
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use std::fmt::{Display, Formatter};
+
+use crate::descriptor::{BaseType, FieldType};
+
+use super::{ClassHierarchy, VerifyErrorKind};
+
+/// The type of a local variable or of an operand stack entry, as seen by the verifier
+/// (JVMS 4.10.1.2)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VerificationType {
+    /// An unusable slot, such as the second slot of a long
+    Top,
+    /// Also stands for booleans, bytes, chars and shorts
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor, before the constructor of the super class is called
+    UninitializedThis,
+    /// An object created by the `new` instruction at this pc, before its constructor is called
+    Uninitialized(u32),
+    /// A class or an interface by its internal name, or an array by its descriptor
+    Object(String),
+    /// The address pushed by a `jsr` to the subroutine at this pc, only found in code
+    /// verified by type inference
+    ReturnAddress(u32),
+}
+
+impl VerificationType {
+    pub fn from_field_type(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Base(BaseType::Long) => Self::Long,
+            FieldType::Base(BaseType::Float) => Self::Float,
+            FieldType::Base(BaseType::Double) => Self::Double,
+            FieldType::Base(_) => Self::Integer,
+            FieldType::Object(name) => Self::Object(name.to_string()),
+            FieldType::Array(..) => Self::Object(field_type.to_string()),
+        }
+    }
+
+    /// A class, or an array if `name` is an array descriptor
+    pub fn object(name: impl Into<String>) -> Self {
+        Self::Object(name.into())
+    }
+
+    /// Number of slots taken by a value of this type
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::Long | Self::Double => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Null | Self::UninitializedThis | Self::Uninitialized(_) | Self::Object(_)
+        )
+    }
+
+    /// Whether this is an array, or `null` which is usable as any array
+    pub fn is_array(&self) -> bool {
+        match self {
+            Self::Null => true,
+            Self::Object(name) => name.starts_with('['),
+            _ => false,
+        }
+    }
+
+    /// Type of the elements of an array, `null` for the `null` array
+    pub(crate) fn component(&self) -> Option<Self> {
+        let component = match self {
+            Self::Null => return Some(Self::Null),
+            Self::Object(name) => name.strip_prefix('[')?,
+            _ => return None,
+        };
+        Some(match component.as_bytes().first()? {
+            b'L' => Self::Object(component[1..component.len() - 1].to_string()),
+            b'[' => Self::Object(component.to_string()),
+            &c => Self::from_field_type(&FieldType::Base(BaseType::from_char(c)?)),
+        })
+    }
+}
+
+impl Display for VerificationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Top => write!(f, "top"),
+            Self::Integer => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Long => write!(f, "long"),
+            Self::Double => write!(f, "double"),
+            Self::Null => write!(f, "null"),
+            Self::UninitializedThis => write!(f, "uninitializedThis"),
+            Self::Uninitialized(pc) => write!(f, "uninitialized({})", pc),
+            Self::Object(name) => write!(f, "{}", name),
+            Self::ReturnAddress(pc) => write!(f, "returnAddress({})", pc),
+        }
+    }
+}
+
+/// The types of the local variables and of the operand stack at some point of a method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// One entry per slot, longs and doubles being followed by [VerificationType::Top]
+    pub locals: Vec<VerificationType>,
+    /// One entry per value, from the bottom of the stack
+    pub stack: Vec<VerificationType>,
+    /// Whether `this` is not initialized yet, which forbids returning from a constructor
+    pub this_uninit: bool,
+}
+
+impl Frame {
+    /// Builds a frame from locals with a single entry for longs and doubles, as the class
+    /// file describes them, or returns `None` if they take more than `max_locals` slots
+    pub(crate) fn expand(
+        locals: &[VerificationType],
+        stack: Vec<VerificationType>,
+        max_locals: u16,
+    ) -> Option<Self> {
+        let mut expanded = Vec::with_capacity(max_locals as usize);
+        for local in locals {
+            expanded.push(local.clone());
+            if local.size() == 2 {
+                expanded.push(VerificationType::Top);
+            }
+        }
+        if expanded.len() > max_locals as usize {
+            return None;
+        }
+        expanded.resize(max_locals as usize, VerificationType::Top);
+        let this_uninit = expanded.contains(&VerificationType::UninitializedThis);
+        Some(Self {
+            locals: expanded,
+            stack,
+            this_uninit,
+        })
+    }
+
+    /// Number of slots taken by the operand stack
+    pub fn stack_size(&self) -> u16 {
+        self.stack.iter().map(VerificationType::size).sum()
+    }
+
+    /// Replaces every occurrence of `from`, such as an uninitialized object that was just
+    /// initialized
+    pub(crate) fn replace(&mut self, from: &VerificationType, to: &VerificationType) {
+        for entry in self.locals.iter_mut().chain(&mut self.stack) {
+            if entry == from {
+                *entry = to.clone();
+            }
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let list = |types: &[VerificationType]| {
+            types
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "locals [{}], stack [{}]",
+            list(&self.locals),
+            list(&self.stack)
+        )
+    }
+}
+
+const OBJECT: &str = "java/lang/Object";
+
+/// Subtyping between verification types, resolving classes through a [ClassHierarchy]
+pub(crate) struct Types<'a> {
+    pub(crate) hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl Types<'_> {
+    /// Whether a value of type `from` can be used where `to` is expected (JVMS 4.10.1.2)
+    pub(crate) fn is_assignable(
+        &self,
+        from: &VerificationType,
+        to: &VerificationType,
+    ) -> Result<bool, VerifyErrorKind> {
+        use VerificationType::*;
+
+        Ok(match (from, to) {
+            _ if from == to => true,
+            (_, Top) => true,
+            (Null, Object(_)) => true,
+            (Object(from), Object(to)) => self.is_class_assignable(from, to)?,
+            _ => false,
+        })
+    }
+
+    /// Whether the class or array `from` can be used where `to` is expected, interfaces
+    /// being treated as `java/lang/Object` as the JVMS does
+    pub(crate) fn is_class_assignable(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<bool, VerifyErrorKind> {
+        if from == to || to == OBJECT {
+            return Ok(true);
+        }
+        if let Some(to_component) = to.strip_prefix('[') {
+            let Some(from_component) = from.strip_prefix('[') else {
+                return Ok(false);
+            };
+            return match (class_of(from_component), class_of(to_component)) {
+                (Some(from), Some(to)) => self.is_class_assignable(from, to),
+                _ => Ok(from_component == to_component),
+            };
+        }
+        if from.starts_with('[') {
+            return Ok(matches!(to, "java/lang/Cloneable" | "java/io/Serializable"));
+        }
+        if self.resolve(to)?.is_interface {
+            return Ok(true);
+        }
+        let mut class = from.to_string();
+        loop {
+            match self.resolve(&class)?.super_class {
+                Some(super_class) if super_class == to => return Ok(true),
+                Some(super_class) => class = super_class,
+                None => return Ok(false),
+            }
+        }
+    }
+
+    /// The most specific type both `a` and `b` can be assigned to, [VerificationType::Top] if
+    /// there is none (JVMS 4.10.2.2)
+    pub(crate) fn merge(
+        &self,
+        a: &VerificationType,
+        b: &VerificationType,
+    ) -> Result<VerificationType, VerifyErrorKind> {
+        use VerificationType::*;
+
+        Ok(match (a, b) {
+            _ if a == b => a.clone(),
+            (Null, Object(_)) => b.clone(),
+            (Object(_), Null) => a.clone(),
+            (Object(a), Object(b)) => Object(self.merge_classes(a, b)?),
+            _ => Top,
+        })
+    }
+
+    /// The first common super class of two classes or arrays, interfaces being treated as
+    /// `java/lang/Object`
+    fn merge_classes(&self, a: &str, b: &str) -> Result<String, VerifyErrorKind> {
+        if a == b {
+            return Ok(a.to_string());
+        }
+        if a == OBJECT || b == OBJECT {
+            return Ok(OBJECT.to_string());
+        }
+        match (a.strip_prefix('['), b.strip_prefix('[')) {
+            (Some(a), Some(b)) => match (class_of(a), class_of(b)) {
+                (Some(a), Some(b)) => {
+                    let component = self.merge_classes(a, b)?;
+                    Ok(match component.starts_with('[') {
+                        true => format!("[{}", component),
+                        false => format!("[L{};", component),
+                    })
+                }
+                _ => Ok(OBJECT.to_string()),
+            },
+            (None, None) => {
+                if self.resolve(a)?.is_interface || self.resolve(b)?.is_interface {
+                    return Ok(OBJECT.to_string());
+                }
+                let mut ancestors = vec![a.to_string()];
+                while let Some(super_class) = self.resolve(ancestors.last().unwrap())?.super_class {
+                    ancestors.push(super_class);
+                }
+                let mut class = b.to_string();
+                while !ancestors.contains(&class) {
+                    match self.resolve(&class)?.super_class {
+                        Some(super_class) => class = super_class,
+                        None => return Ok(OBJECT.to_string()),
+                    }
+                }
+                Ok(class)
+            }
+            _ => Ok(OBJECT.to_string()),
+        }
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Result<super::ClassInfo, VerifyErrorKind> {
+        self.hierarchy
+            .resolve(name)
+            .ok_or_else(|| VerifyErrorKind::UnknownClass(name.to_string()))
+    }
+}
+
+/// The class or array named by an array component descriptor, `None` for primitive types
+fn class_of(component: &str) -> Option<&str> {
+    if component.starts_with('[') {
+        Some(component)
+    } else {
+        component.strip_prefix('L')?.strip_suffix(';')
+    }
+}
@@ -0,0 +1,194 @@
+//! Event-based reading and writing of classes, in the style of ASM.
+//!
+//! A [ClassReader](crate::reader::ClassReader) walks the bytes of a class and calls a
+//! [ClassVisitor] for each of its parts, without building a [ClassFile](crate::ClassFile). Names
+//! and descriptors are borrowed from the class bytes. A visitor can ask for the members it is
+//! interested in by returning a nested visitor, and skip the others by returning `None`.
+//!
+//! Visitors can forward events to another visitor, and a [ClassWriter] at the end of such a
+//! chain turns them back into a class. Constant operands of instructions are indices into the
+//! pool of the class being read, so the writer must be created with
+//! [ClassWriter::from_reader]; constants needed by new instructions can be added to its
+//! [pool](ClassWriter::pool) before reading.
+//!
+//! Every visitor must be ended with its `visit_end` method, which is where writers store what
+//! they were given.
+
+mod writer;
+pub use writer::*;
+
+use crate::{
+    ClassAccessFlags,
+    builder::ConstantValue,
+    code::{ExceptionHandler, Instruction, Label, LocalVariable},
+    field::FieldAccessFlags,
+    java_str::JavaStr,
+    method::MethodAccessFlags,
+    version::ClassFileVersion,
+};
+
+/// Receives the parts of a class, in the order of the methods below
+pub trait ClassVisitor {
+    /// Visits the header of the class, with internal names
+    fn visit(
+        &mut self,
+        _version: &ClassFileVersion,
+        _minor_version: u16,
+        _access_flags: ClassAccessFlags,
+        _name: &JavaStr,
+        _super_name: Option<&JavaStr>,
+        _interfaces: &[&JavaStr],
+    ) {
+    }
+
+    /// Visits the `SourceFile` attribute
+    fn visit_source(&mut self, _source: &JavaStr) {}
+
+    /// Visits the generic signature of the class
+    fn visit_signature(&mut self, _signature: &JavaStr) {}
+
+    /// Visits an annotation of the class, `visible` telling whether it is kept at runtime
+    fn visit_annotation(
+        &mut self,
+        _descriptor: &JavaStr,
+        _visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        None
+    }
+
+    /// Visits an attribute without a dedicated method, whose content refers to constants by
+    /// their class file index
+    fn visit_attribute(&mut self, _name: &JavaStr, _info: &[u8]) {}
+
+    fn visit_field(
+        &mut self,
+        _access_flags: FieldAccessFlags,
+        _name: &JavaStr,
+        _descriptor: &JavaStr,
+    ) -> Option<Box<dyn FieldVisitor + '_>> {
+        None
+    }
+
+    fn visit_method(
+        &mut self,
+        _access_flags: MethodAccessFlags,
+        _name: &JavaStr,
+        _descriptor: &JavaStr,
+    ) -> Option<Box<dyn MethodVisitor + '_>> {
+        None
+    }
+
+    fn visit_end(&mut self) {}
+}
+
+/// Receives the parts of a field, see [ClassVisitor::visit_field]
+pub trait FieldVisitor {
+    /// Visits the `ConstantValue` attribute
+    fn visit_constant_value(&mut self, _value: ConstantValue) {}
+
+    fn visit_signature(&mut self, _signature: &JavaStr) {}
+
+    fn visit_annotation(
+        &mut self,
+        _descriptor: &JavaStr,
+        _visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        None
+    }
+
+    fn visit_attribute(&mut self, _name: &JavaStr, _info: &[u8]) {}
+
+    fn visit_end(&mut self) {}
+}
+
+/// Receives the parts of a method, see [ClassVisitor::visit_method]
+pub trait MethodVisitor {
+    fn visit_signature(&mut self, _signature: &JavaStr) {}
+
+    /// Visits the checked exceptions declared by the method, by their internal names
+    fn visit_exceptions(&mut self, _exceptions: &[&JavaStr]) {}
+
+    fn visit_annotation(
+        &mut self,
+        _descriptor: &JavaStr,
+        _visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        None
+    }
+
+    fn visit_attribute(&mut self, _name: &JavaStr, _info: &[u8]) {}
+
+    /// Visits the body of the method
+    fn visit_code(&mut self) -> Option<Box<dyn CodeVisitor + '_>> {
+        None
+    }
+
+    fn visit_end(&mut self) {}
+}
+
+/// Receives the body of a method, see [MethodVisitor::visit_code].
+///
+/// Handlers and local variables come first, then the instructions, each preceded by the labels
+/// placed on it and by its line number. Labels are the pcs of the original code, so labels
+/// above `u16::MAX` are free for new branch targets.
+pub trait CodeVisitor {
+    fn visit_try_catch(&mut self, _handler: &ExceptionHandler) {}
+
+    fn visit_local_variable(&mut self, _variable: &LocalVariable) {}
+
+    /// Visits an entry of the `LocalVariableTypeTable`, which holds a signature instead of a
+    /// descriptor
+    fn visit_local_variable_type(&mut self, _variable: &LocalVariable) {}
+
+    fn visit_label(&mut self, _label: Label) {}
+
+    fn visit_line_number(&mut self, _line: u16) {}
+
+    fn visit_instruction(&mut self, _instruction: &Instruction) {}
+
+    /// Visits an attribute other than the non-empty debug tables, `StackMapTable` included
+    fn visit_attribute(&mut self, _name: &JavaStr, _info: &[u8]) {}
+
+    fn visit_maxs(&mut self, _max_stack: u16, _max_locals: u16) {}
+
+    fn visit_end(&mut self) {}
+}
+
+/// A constant element of an annotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElementValue<'a> {
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(&'a JavaStr),
+    /// A class literal, as a return descriptor such as `Ljava/lang/String;` or `V`
+    Class(&'a JavaStr),
+}
+
+/// Receives the elements of an annotation, `name` being `None` for the values of an array
+pub trait AnnotationVisitor {
+    fn visit(&mut self, _name: Option<&JavaStr>, _value: ElementValue) {}
+
+    fn visit_enum(&mut self, _name: Option<&JavaStr>, _descriptor: &JavaStr, _value: &JavaStr) {}
+
+    /// Visits a nested annotation
+    fn visit_annotation(
+        &mut self,
+        _name: Option<&JavaStr>,
+        _descriptor: &JavaStr,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        None
+    }
+
+    /// Visits an array, whose values are visited without names
+    fn visit_array(&mut self, _name: Option<&JavaStr>) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        None
+    }
+
+    fn visit_end(&mut self) {}
+}
@@ -0,0 +1,508 @@
+use crate::{
+    ClassAccessFlags, ClassFile, Result,
+    attribute::Attribute,
+    builder::{ClassBuilder, ConstantValue, FieldBuilder, MethodBuilder},
+    code::{ExceptionHandler, Instruction, Label, LocalVariable},
+    constants::{ConstItemIdx, ConstPoolBuilder},
+    error::ClassReaderError,
+    field::FieldAccessFlags,
+    java_str::{JavaStr, JavaString},
+    method::MethodAccessFlags,
+    reader::ClassReader,
+    transform::{ClassElement, CodeElement},
+    version::ClassFileVersion,
+};
+
+use super::{
+    AnnotationVisitor, ClassVisitor, CodeVisitor, ElementValue, FieldVisitor, MethodVisitor,
+};
+
+/// A [ClassVisitor] building a [ClassFile] from the events it receives
+#[derive(Debug, Default)]
+pub struct ClassWriter {
+    pool: ConstPoolBuilder,
+    this_class: Option<ConstItemIdx>,
+    elements: Vec<ClassElement>,
+    annotations: Annotations,
+    error: Option<ClassReaderError>,
+}
+
+impl ClassWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts with the constant pool of the class read by `reader`, so that the constant
+    /// operands and raw attributes it reports stay valid
+    pub fn from_reader(reader: &ClassReader) -> Result<Self> {
+        Ok(Self {
+            pool: ConstPoolBuilder::from_constants(reader.constants()?)?,
+            ..Self::default()
+        })
+    }
+
+    pub fn pool(&mut self) -> &mut ConstPoolBuilder {
+        &mut self.pool
+    }
+
+    /// Builds the class, failing if the code of a method could not be assembled.
+    ///
+    /// Panics if [ClassVisitor::visit] was never called.
+    pub fn build(self) -> Result<ClassFile> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let this_class = self.this_class.expect("the class header was not visited");
+        let mut builder = ClassBuilder::from_pool(self.pool, this_class);
+        for element in self.elements {
+            builder.with(element);
+        }
+        builder.build()
+    }
+
+    fn attribute(&mut self, name: impl Into<JavaString>, info: Vec<u8>) {
+        let attribute_name_index = self.pool.utf8(name);
+        self.elements.push(ClassElement::Attribute(Attribute {
+            attribute_name_index,
+            info,
+        }));
+    }
+
+    fn utf8_attribute(&mut self, name: &str, value: &JavaStr) {
+        let idx = self.pool.utf8(value);
+        let info = self.pool.idx_map().raw_past_end(idx).to_be_bytes().to_vec();
+        self.attribute(name, info);
+    }
+}
+
+impl ClassVisitor for ClassWriter {
+    fn visit(
+        &mut self,
+        version: &ClassFileVersion,
+        minor_version: u16,
+        access_flags: ClassAccessFlags,
+        name: &JavaStr,
+        super_name: Option<&JavaStr>,
+        interfaces: &[&JavaStr],
+    ) {
+        self.this_class = Some(self.pool.class(name));
+        let super_class = super_name.map(|name| self.pool.class(name));
+        self.elements.extend([
+            ClassElement::Version(version.clone(), minor_version),
+            ClassElement::AccessFlags(access_flags),
+            ClassElement::Superclass(super_class),
+        ]);
+        for interface in interfaces {
+            let idx = self.pool.class(*interface);
+            self.elements.push(ClassElement::Interface(idx));
+        }
+    }
+
+    fn visit_source(&mut self, source: &JavaStr) {
+        self.utf8_attribute("SourceFile", source);
+    }
+
+    fn visit_signature(&mut self, signature: &JavaStr) {
+        self.utf8_attribute("Signature", signature);
+    }
+
+    fn visit_annotation(
+        &mut self,
+        descriptor: &JavaStr,
+        visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        Some(Box::new(self.annotations.start(
+            &mut self.pool,
+            descriptor,
+            visible,
+        )))
+    }
+
+    fn visit_attribute(&mut self, name: &JavaStr, info: &[u8]) {
+        self.attribute(name, info.to_vec());
+    }
+
+    fn visit_field(
+        &mut self,
+        access_flags: FieldAccessFlags,
+        name: &JavaStr,
+        descriptor: &JavaStr,
+    ) -> Option<Box<dyn FieldVisitor + '_>> {
+        Some(Box::new(FieldWriter {
+            builder: Some(FieldBuilder::new(
+                &mut self.pool,
+                name,
+                descriptor,
+                access_flags,
+            )),
+            elements: &mut self.elements,
+            annotations: Annotations::default(),
+        }))
+    }
+
+    fn visit_method(
+        &mut self,
+        access_flags: MethodAccessFlags,
+        name: &JavaStr,
+        descriptor: &JavaStr,
+    ) -> Option<Box<dyn MethodVisitor + '_>> {
+        Some(Box::new(MethodWriter {
+            builder: Some(MethodBuilder::new(
+                &mut self.pool,
+                name,
+                descriptor,
+                access_flags,
+            )),
+            elements: &mut self.elements,
+            error: &mut self.error,
+            annotations: Annotations::default(),
+        }))
+    }
+
+    fn visit_end(&mut self) {
+        for (name, info) in std::mem::take(&mut self.annotations).into_attributes() {
+            self.attribute(name, info);
+        }
+    }
+}
+
+/// Writes a field into the elements of its [ClassWriter]
+struct FieldWriter<'a> {
+    /// Taken once the field is complete
+    builder: Option<FieldBuilder<'a>>,
+    elements: &'a mut Vec<ClassElement>,
+    annotations: Annotations,
+}
+
+impl<'a> FieldWriter<'a> {
+    fn builder(&mut self) -> &mut FieldBuilder<'a> {
+        self.builder
+            .as_mut()
+            .expect("field visited after visit_end")
+    }
+}
+
+impl FieldVisitor for FieldWriter<'_> {
+    fn visit_constant_value(&mut self, value: ConstantValue) {
+        self.builder().constant_value(value);
+    }
+
+    fn visit_signature(&mut self, signature: &JavaStr) {
+        self.builder().signature(signature);
+    }
+
+    fn visit_annotation(
+        &mut self,
+        descriptor: &JavaStr,
+        visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        let pool = self
+            .builder
+            .as_mut()
+            .expect("field visited after visit_end")
+            .pool();
+        Some(Box::new(self.annotations.start(pool, descriptor, visible)))
+    }
+
+    fn visit_attribute(&mut self, name: &JavaStr, info: &[u8]) {
+        self.builder().attribute(name, info.to_vec());
+    }
+
+    fn visit_end(&mut self) {
+        let Some(mut builder) = self.builder.take() else {
+            return;
+        };
+        for (name, info) in std::mem::take(&mut self.annotations).into_attributes() {
+            builder.attribute(name, info);
+        }
+        self.elements
+            .push(ClassElement::Field(builder.into_field()));
+    }
+}
+
+/// Writes a method into the elements of its [ClassWriter]
+struct MethodWriter<'a> {
+    /// Taken once the method is complete
+    builder: Option<MethodBuilder<'a>>,
+    elements: &'a mut Vec<ClassElement>,
+    error: &'a mut Option<ClassReaderError>,
+    annotations: Annotations,
+}
+
+impl<'a> MethodWriter<'a> {
+    fn builder(&mut self) -> &mut MethodBuilder<'a> {
+        self.builder
+            .as_mut()
+            .expect("method visited after visit_end")
+    }
+}
+
+impl MethodVisitor for MethodWriter<'_> {
+    fn visit_signature(&mut self, signature: &JavaStr) {
+        self.builder().signature(signature);
+    }
+
+    fn visit_exceptions(&mut self, exceptions: &[&JavaStr]) {
+        self.builder().exceptions(exceptions.iter().copied());
+    }
+
+    fn visit_annotation(
+        &mut self,
+        descriptor: &JavaStr,
+        visible: bool,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        let pool = self
+            .builder
+            .as_mut()
+            .expect("method visited after visit_end")
+            .pool();
+        Some(Box::new(self.annotations.start(pool, descriptor, visible)))
+    }
+
+    fn visit_attribute(&mut self, name: &JavaStr, info: &[u8]) {
+        self.builder().attribute(name, info.to_vec());
+    }
+
+    fn visit_code(&mut self) -> Option<Box<dyn CodeVisitor + '_>> {
+        Some(Box::new(CodeWriter {
+            method: self.builder(),
+            elements: Vec::new(),
+            maxs: None,
+        }))
+    }
+
+    fn visit_end(&mut self) {
+        let Some(mut builder) = self.builder.take() else {
+            return;
+        };
+        for (name, info) in std::mem::take(&mut self.annotations).into_attributes() {
+            builder.attribute(name, info);
+        }
+        match builder.into_method() {
+            Ok(method) => self.elements.push(ClassElement::Method(method)),
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
+    }
+}
+
+/// Collects the body of a method, which is assembled at [CodeVisitor::visit_end]
+struct CodeWriter<'m, 'a> {
+    method: &'m mut MethodBuilder<'a>,
+    elements: Vec<CodeElement>,
+    maxs: Option<(u16, u16)>,
+}
+
+impl CodeVisitor for CodeWriter<'_, '_> {
+    fn visit_try_catch(&mut self, handler: &ExceptionHandler) {
+        self.elements
+            .push(CodeElement::ExceptionHandler(handler.clone()));
+    }
+
+    fn visit_local_variable(&mut self, variable: &LocalVariable) {
+        self.elements
+            .push(CodeElement::LocalVariable(variable.clone()));
+    }
+
+    fn visit_local_variable_type(&mut self, variable: &LocalVariable) {
+        self.elements
+            .push(CodeElement::LocalVariableType(variable.clone()));
+    }
+
+    fn visit_label(&mut self, label: Label) {
+        self.elements.push(CodeElement::Label(label));
+    }
+
+    fn visit_line_number(&mut self, line: u16) {
+        self.elements.push(CodeElement::LineNumber(line));
+    }
+
+    fn visit_instruction(&mut self, instruction: &Instruction) {
+        self.elements
+            .push(CodeElement::Instruction(instruction.clone()));
+    }
+
+    fn visit_attribute(&mut self, name: &JavaStr, info: &[u8]) {
+        let attribute_name_index = self.method.pool().utf8(name);
+        self.elements.push(CodeElement::Attribute(Attribute {
+            attribute_name_index,
+            info: info.to_vec(),
+        }));
+    }
+
+    fn visit_maxs(&mut self, max_stack: u16, max_locals: u16) {
+        self.maxs = Some((max_stack, max_locals));
+    }
+
+    /// Assembles the code, keeping the visited `StackMapTable` and maxs only if every label of
+    /// the original code is still placed at its pc, as they would be stale otherwise
+    fn visit_end(&mut self) {
+        let mut elements = std::mem::take(&mut self.elements);
+        let mut maxs = self.maxs;
+        if !keeps_layout(&elements) {
+            let constants = self.method.pool().constants();
+            elements.retain(|element| match element {
+                CodeElement::Attribute(attribute) => attribute
+                    .name(constants)
+                    .is_none_or(|name| name != "StackMapTable"),
+                _ => true,
+            });
+            maxs = None;
+        }
+        self.method.code(|code| {
+            for element in elements {
+                code.with(element);
+            }
+            if let Some((max_stack, max_locals)) = maxs {
+                code.max_stack(max_stack).max_locals(max_locals);
+            }
+        });
+    }
+}
+
+/// Whether the labels of the original code, which are its pcs, are placed at the same pcs in
+/// the code laid out from `elements`
+fn keeps_layout(elements: &[CodeElement]) -> bool {
+    let mut pc = 0;
+    for element in elements {
+        match element {
+            CodeElement::Label(label) if label.0 <= u16::MAX as u32 && label.0 != pc => {
+                return false;
+            }
+            CodeElement::Instruction(instruction) => pc += instruction.size(pc),
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Content of the `RuntimeVisibleAnnotations` and `RuntimeInvisibleAnnotations` attributes
+/// of a class, field or method, as counts and encoded annotations
+#[derive(Debug, Default)]
+struct Annotations {
+    visible: (u16, Vec<u8>),
+    invisible: (u16, Vec<u8>),
+}
+
+impl Annotations {
+    fn start<'a>(
+        &'a mut self,
+        pool: &'a mut ConstPoolBuilder,
+        descriptor: &JavaStr,
+        visible: bool,
+    ) -> AnnotationWriter<'a> {
+        let (count, bytes) = if visible {
+            &mut self.visible
+        } else {
+            &mut self.invisible
+        };
+        *count += 1;
+        let idx = pool.utf8(descriptor);
+        bytes.extend(pool.idx_map().raw_past_end(idx).to_be_bytes());
+        AnnotationWriter::new(pool, bytes, true)
+    }
+
+    fn into_attributes(self) -> impl Iterator<Item = (&'static str, Vec<u8>)> {
+        [
+            ("RuntimeVisibleAnnotations", self.visible),
+            ("RuntimeInvisibleAnnotations", self.invisible),
+        ]
+        .into_iter()
+        .filter(|(_, (count, _))| *count > 0)
+        .map(|(name, (count, bytes))| {
+            let mut info = count.to_be_bytes().to_vec();
+            info.extend(bytes);
+            (name, info)
+        })
+    }
+}
+
+/// Encodes the elements of an annotation or of an array, whose count is written at
+/// [AnnotationVisitor::visit_end]
+struct AnnotationWriter<'a> {
+    pool: &'a mut ConstPoolBuilder,
+    bytes: &'a mut Vec<u8>,
+    count_pos: usize,
+    count: u16,
+    /// Whether elements are preceded by their name, which is not the case in arrays
+    named: bool,
+}
+
+impl<'a> AnnotationWriter<'a> {
+    fn new(pool: &'a mut ConstPoolBuilder, bytes: &'a mut Vec<u8>, named: bool) -> Self {
+        let count_pos = bytes.len();
+        bytes.extend([0, 0]);
+        Self {
+            pool,
+            bytes,
+            count_pos,
+            count: 0,
+            named,
+        }
+    }
+
+    fn start_element(&mut self, name: Option<&JavaStr>, tag: u8) {
+        self.count += 1;
+        if self.named {
+            self.write_utf8(name.map_or_else(|| JavaString::from(""), JavaString::from));
+        }
+        self.bytes.push(tag);
+    }
+
+    fn write_utf8(&mut self, value: impl Into<JavaString>) {
+        let idx = self.pool.utf8(value);
+        self.write_idx(idx);
+    }
+
+    fn write_idx(&mut self, idx: ConstItemIdx) {
+        let raw = self.pool.idx_map().raw_past_end(idx);
+        self.bytes.extend(raw.to_be_bytes());
+    }
+}
+
+impl AnnotationVisitor for AnnotationWriter<'_> {
+    fn visit(&mut self, name: Option<&JavaStr>, value: ElementValue) {
+        let (tag, idx) = match value {
+            ElementValue::Byte(value) => (b'B', self.pool.integer(value as i32)),
+            ElementValue::Char(value) => (b'C', self.pool.integer(value as i32)),
+            ElementValue::Short(value) => (b'S', self.pool.integer(value as i32)),
+            ElementValue::Boolean(value) => (b'Z', self.pool.integer(value as i32)),
+            ElementValue::Int(value) => (b'I', self.pool.integer(value)),
+            ElementValue::Long(value) => (b'J', self.pool.long(value)),
+            ElementValue::Float(value) => (b'F', self.pool.float(value)),
+            ElementValue::Double(value) => (b'D', self.pool.double(value)),
+            ElementValue::String(value) => (b's', self.pool.utf8(value)),
+            ElementValue::Class(value) => (b'c', self.pool.utf8(value)),
+        };
+        self.start_element(name, tag);
+        self.write_idx(idx);
+    }
+
+    fn visit_enum(&mut self, name: Option<&JavaStr>, descriptor: &JavaStr, value: &JavaStr) {
+        self.start_element(name, b'e');
+        self.write_utf8(descriptor);
+        self.write_utf8(value);
+    }
+
+    fn visit_annotation(
+        &mut self,
+        name: Option<&JavaStr>,
+        descriptor: &JavaStr,
+    ) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        self.start_element(name, b'@');
+        self.write_utf8(descriptor);
+        Some(Box::new(AnnotationWriter::new(self.pool, self.bytes, true)))
+    }
+
+    fn visit_array(&mut self, name: Option<&JavaStr>) -> Option<Box<dyn AnnotationVisitor + '_>> {
+        self.start_element(name, b'[');
+        Some(Box::new(AnnotationWriter::new(
+            self.pool, self.bytes, false,
+        )))
+    }
+
+    fn visit_end(&mut self) {
+        self.bytes[self.count_pos..self.count_pos + 2].copy_from_slice(&self.count.to_be_bytes());
+    }
+}
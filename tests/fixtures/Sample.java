@@ -0,0 +1,62 @@
+import java.io.IOException;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.Map;
+import java.util.function.Supplier;
+
+@Deprecated
+public class Sample<T extends Comparable<? super T>> implements Supplier<List<T>> {
+    public static final long BIG = 0x1234_5678_9ABC_DEF0L;
+    public static final double PI = 3.141592653589793;
+    public static final float NAN = Float.NaN;
+    public static final String GREETING = "héllo \0 wörld 😀";
+
+    private final List<T> items = new ArrayList<>();
+    protected Map.Entry<String, ? extends Number> entry;
+
+    public class Inner<U> {
+        U value;
+    }
+
+    @Override
+    public List<T> get() {
+        return items;
+    }
+
+    public <E extends Exception> int compute(int a, long b, double c, T t) throws IOException, E {
+        int sum = 0;
+        for (int i = 0; i < a; i++) {
+            sum += (int) (b * i + c);
+        }
+        switch (sum) {
+            case 1: sum += 10; break;
+            case 2: sum += 20; break;
+            case 3: sum += 30; break;
+            default: sum = -1;
+        }
+        switch (sum) {
+            case 100: return 1;
+            case 10000: return 2;
+            default:
+        }
+        try {
+            if (t == null) {
+                throw new IOException("null");
+            }
+        } catch (IllegalStateException | IllegalArgumentException e) {
+            sum = 0;
+        } finally {
+            sum++;
+        }
+        Runnable r = () -> items.clear();
+        r.run();
+        return sum + (int) BIG;
+    }
+
+    synchronized long wide(long x) {
+        long a0 = 0, a1 = 1, a2 = 2, a3 = 3, a4 = 4, a5 = 5, a6 = 6, a7 = 7, a8 = 8, a9 = 9;
+        double[][] grid = new double[3][4];
+        Object[] objs = new String[2];
+        return x + a9 + grid.length + objs.length;
+    }
+}